@@ -0,0 +1,289 @@
+#![no_std]
+#![no_main]
+
+use arrayvec::ArrayString;
+use core::{fmt::Write, panic::PanicInfo};
+use cortex_m_rt::{entry};
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    mono_font::{MonoTextStyle, ascii::FONT_6X10}, text::Text
+};
+use embedded_hal::{spi, spi::FullDuplex, digital::v2::OutputPin};
+use embedded_sdmmc::{Controller, SdMmcSpi, TimeSource, Timestamp, VolumeIdx, Mode};
+use pcf8563::{PCF8563, ClkoutFreq, Control};
+use rtc_time::drift::{
+    write_calibration, write_ppm, write_seconds_per_day, DriftEstimator,
+    CALIBRATION_FILE_NAME,
+};
+use sh1106::{prelude::*, Builder, interface::DisplayInterface};
+use stm32f4xx_hal::{prelude::*, pac, gpio::NoPin, i2c::I2c, rcc::BusTimerClock};
+
+/// Turn on onboard LED in case of panic
+#[inline(never)]
+#[panic_handler]
+fn on_panic(_info: &PanicInfo) -> ! {
+    let dp = unsafe { pac::Peripherals::steal() };
+    let gpioc = dp.GPIOC.split();
+    let mut led = gpioc.pc13.into_push_pull_output();
+    let _ = led.set_low();
+    loop { }
+}
+
+/// Calibration file is rewritten after each this number of measured periods
+const SAVE_PERIODS: u32 = 60;
+
+/// Capture status register CC1IF flag
+const CAPTURE_FLAG: u32 = 0b10;
+
+#[entry]
+fn main() -> ! {
+    if let (Some(dp), Some(cp)) = (
+        pac::Peripherals::take(),
+        cortex_m::peripheral::Peripherals::take(),
+    ) {
+        run(dp, cp).unwrap();
+        loop {}
+    } else {
+        loop {}
+    }
+}
+
+fn run(
+    dp: pac::Peripherals,
+    _cp: cortex_m::Peripherals,
+) -> Result<(), ()> {
+    let rcc = dp.RCC.constrain();
+
+    // Timer clock is derived from the HSE crystal, which is the reference
+    // for the measurement
+    let clocks = rcc.cfgr.use_hse(25.MHz()).sysclk(100.MHz()).hclk(25.MHz()).freeze();
+
+    let gpioa = dp.GPIOA.split();
+    let gpiob = dp.GPIOB.split();
+
+    let dc = gpiob.pb6.into_push_pull_output();
+
+    let spi = dp.SPI2.spi(
+        (gpiob.pb13, NoPin, gpiob.pb15),
+        spi::MODE_0,
+        4000.kHz(),
+        &clocks,
+    );
+
+    let mut display_reset = gpiob.pb14.into_push_pull_output();
+    let mut delay = dp.TIM5.delay_us(&clocks);
+
+    let mut display: GraphicsMode<_> = Builder::new()
+        .with_rotation(DisplayRotation::Rotate180)
+        .with_size(DisplaySize::Display128x64)
+        .connect_spi(spi, dc, sh1106::builder::NoOutputPin::new())
+        .into();
+
+    display.reset(&mut display_reset, &mut delay).map_err(|_| ())?;
+    display.init().unwrap();
+
+    display_text(&mut display, "Initializing ...").unwrap();
+
+    let i2c = I2c::new(
+        dp.I2C1,
+        (
+            gpiob.pb8.into_alternate().set_open_drain(),
+            gpiob.pb9.into_alternate().set_open_drain(),
+        ),
+        400.kHz(),
+        &clocks,
+    );
+
+    let mut rtc = PCF8563::new(i2c);
+
+    let clkout_enabled = rtc.set_clkout_frequency(ClkoutFreq::Clkout_1Hz)
+        .and_then(|_| rtc.control_clkout(Control::On));
+
+    if clkout_enabled.is_err() {
+        display_text(&mut display, "RTC not responding")?;
+        return Err(());
+    }
+
+    let sd_spi = dp.SPI1.spi(
+        (gpioa.pa5, gpioa.pa6, gpioa.pa7),
+        spi::MODE_0,
+        400.kHz(),
+        &clocks,
+    );
+
+    let sd_cs = gpiob.pb0.into_push_pull_output();
+    let mut sd_controller = Controller::new(SdMmcSpi::new(sd_spi, sd_cs), Clock {});
+
+    // PCF8563 CLKOUT is an open drain output, connected to TIM2 channel 1
+    let _clkout = gpioa.pa15.into_alternate::<1>().internal_pull_up(true);
+    let timer = dp.TIM2;
+    start_capture_timer(&timer);
+
+    // APB1 timer clock, twice the PCLK1 when APB1 is divided
+    let reference_hz = pac::TIM2::timer_clock(&clocks).raw();
+    let mut estimator = DriftEstimator::new(reference_hz);
+    let mut last_capture: Option<u32> = None;
+    let mut last_activity = timer.cnt.read().bits();
+    let mut save_status = ArrayString::<40>::new();
+    // Rejected periods are not counted, the file is saved once per count
+    let mut saved_periods = 0;
+
+    loop {
+        if let Some(capture) = read_capture(&timer) {
+            if let Some(previous) = last_capture {
+                estimator.add_period(capture.wrapping_sub(previous));
+            }
+
+            last_capture = Some(capture);
+            last_activity = capture;
+
+            let periods = estimator.periods();
+
+            if let (true, Some(drift_ppb)) = (
+                periods != saved_periods && periods.is_multiple_of(SAVE_PERIODS),
+                estimator.drift_ppb(),
+            ) {
+                saved_periods = periods;
+                save_status.clear();
+
+                match save_calibration(&mut sd_controller, drift_ppb) {
+                    Ok(()) => write!(save_status, "Saved {}", CALIBRATION_FILE_NAME),
+                    Err(error) => write!(save_status, "SD: {}", error),
+                }.map_err(|_| ())?;
+            }
+
+            let mut text = ArrayString::<200>::new();
+            render_status(&mut text, &estimator, &save_status).map_err(|_| ())?;
+            display_text(&mut display, &text)?;
+        } else if timer.cnt.read().bits().wrapping_sub(last_activity) > 2*reference_hz {
+            // Measurement continues from the next edge, the gap is not
+            // counted as a period
+            last_capture = None;
+            last_activity = timer.cnt.read().bits();
+            display_text(&mut display, "No CLKOUT signal\non PA15")?;
+        }
+    }
+}
+
+/// Run TIM2 as a free running 32 bit counter at the timer clock frequency,
+/// capturing the counter value on each rising edge of the channel 1 input
+fn start_capture_timer(timer: &pac::TIM2) {
+    unsafe {
+        (*pac::RCC::ptr()).apb1enr.modify(|_, w| w.tim2en().set_bit());
+
+        timer.psc.write(|w| w.bits(0));
+        timer.arr.write(|w| w.bits(u32::MAX));
+        // CC1S = 01, channel 1 is an input mapped to TI1, no filter
+        timer.ccmr1_input().write(|w| w.bits(0b01));
+        // CC1E = 1, capture enabled on the rising edge
+        timer.ccer.write(|w| w.bits(0b1));
+        // Load the prescaler value
+        timer.egr.write(|w| w.bits(0b1));
+        timer.cr1.write(|w| w.bits(0b1));
+    }
+}
+
+/// Counter value latched on the last edge, reading the capture register
+/// clears the capture flag
+fn read_capture(timer: &pac::TIM2) -> Option<u32> {
+    if timer.sr.read().bits() & CAPTURE_FLAG != 0 {
+        Some(timer.ccr1.read().bits())
+    } else {
+        None
+    }
+}
+
+fn render_status(
+    output: &mut dyn Write,
+    estimator: &DriftEstimator,
+    save_status: &str,
+) -> core::fmt::Result {
+    writeln!(output, "RTC calibration")?;
+    writeln!(output, "Periods: {} (rej {})", estimator.periods(), estimator.rejected())?;
+
+    match estimator.drift_ppb() {
+        Some(drift_ppb) => {
+            write!(output, "Drift: ")?;
+            write_ppm(output, drift_ppb)?;
+            write!(output, "\n       ")?;
+            write_seconds_per_day(output, drift_ppb)?;
+            writeln!(output)?;
+        },
+        None => writeln!(output, "Measuring ...")?,
+    }
+
+    write!(output, "{}", save_status)
+}
+
+/// Overwrite the calibration file in the card root directory
+fn save_calibration<SPI, CS, T>(
+    controller: &mut Controller<SdMmcSpi<SPI, CS>, T>,
+    drift_ppb: i32,
+) -> Result<(), &'static str>
+where
+    SPI: FullDuplex<u8>,
+    CS: OutputPin,
+    T: TimeSource,
+    <SPI as FullDuplex<u8>>::Error: core::fmt::Debug
+{
+    let mut content = ArrayString::<40>::new();
+    write_calibration(&mut content, drift_ppb).map_err(|_| "Format")?;
+
+    controller.device().init().map_err(|_| "Connect")?;
+
+    let result = (0..4)
+        .find_map(|index| controller.get_volume(VolumeIdx(index)).ok())
+        .ok_or("No volume")
+        .and_then(|mut volume| {
+            let dir = controller.open_root_dir(&volume).map_err(|_| "Root dir")?;
+
+            let written = controller.open_file_in_dir(
+                &mut volume, &dir, CALIBRATION_FILE_NAME, Mode::ReadWriteCreateOrTruncate
+            )
+                .map_err(|_| "Open")
+                .and_then(|mut file| {
+                    let written = controller.write(&mut volume, &mut file, content.as_bytes())
+                        .map(|_| ())
+                        .map_err(|_| "Write");
+
+                    controller.close_file(&volume, file).map_err(|_| "Close")?;
+                    written
+                });
+
+            controller.close_dir(&volume, dir);
+            written
+        });
+
+    controller.device().deinit();
+    result
+}
+
+fn display_text<T>(
+    display: &mut GraphicsMode<T>,
+    message: &str
+) -> Result<(), ()>
+where T: DisplayInterface {
+    display.clear();
+
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let position = Point::new(0, 8);
+    Text::new(&message, position, style).draw(display).map_err(|_| ())?;
+    display.flush().map_err(|_| ())
+}
+
+/// Calibration file time stamp is not important, no time source is used
+struct Clock;
+
+impl TimeSource for Clock {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 0,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
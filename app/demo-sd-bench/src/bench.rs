@@ -0,0 +1,200 @@
+use cortex_m::peripheral::{DCB, DWT};
+use embedded_sdmmc::{BlockDevice, Controller, Directory, Mode, TimeSource, Volume};
+use sd_logger::SdWriteError;
+use stm32f4xx_hal::rcc::Clocks;
+
+/// Size of the file written and read by the sequential tests
+pub const FILE_SIZE: usize = 128*1024;
+
+/// Most operations measured by a single test (512 byte writes)
+const MAX_OPERATIONS: usize = FILE_SIZE/512;
+
+const BENCH_FILE_NAME: &str = "BENCH.DAT";
+const APPEND_FILE_NAME: &str = "APPEND.DAT";
+
+/// Measures durations using the DWT cycle counter running at HCLK
+pub struct Stopwatch {
+    cycles_per_us: u32,
+}
+
+impl Stopwatch {
+    pub fn new(dcb: &mut DCB, dwt: &mut DWT, clocks: &Clocks) -> Self {
+        dcb.enable_trace();
+        dwt.enable_cycle_counter();
+        Self { cycles_per_us: clocks.hclk().raw()/1_000_000 }
+    }
+
+    /// Run `operation` and record its duration into `latencies`
+    fn measure<R>(&self, latencies: &mut Latencies, operation: impl FnOnce() -> R) -> R {
+        let start = DWT::cycle_count();
+        let result = operation();
+        latencies.push(DWT::cycle_count().wrapping_sub(start)/self.cycles_per_us);
+        result
+    }
+}
+
+/// Durations of single operations in microseconds
+struct Latencies {
+    values: [u32; MAX_OPERATIONS],
+    count: usize,
+}
+
+impl Latencies {
+    fn new() -> Self {
+        Self { values: [0; MAX_OPERATIONS], count: 0 }
+    }
+
+    fn push(&mut self, value: u32) {
+        if self.count < MAX_OPERATIONS {
+            self.values[self.count] = value;
+            self.count += 1;
+        }
+    }
+}
+
+/// Result of a test, throughput counts the time spent in measured
+/// operations only
+pub struct Summary {
+    pub bytes: usize,
+    pub total_us: u32,
+    pub p50_us: u32,
+    pub p90_us: u32,
+    pub p99_us: u32,
+    pub max_us: u32,
+}
+
+impl Summary {
+    fn new(bytes: usize, latencies: &mut Latencies) -> Self {
+        let sorted = &mut latencies.values[..latencies.count];
+        sorted.sort_unstable();
+
+        Self {
+            bytes,
+            total_us: sorted.iter().sum(),
+            p50_us: percentile(sorted, 50),
+            p90_us: percentile(sorted, 90),
+            p99_us: percentile(sorted, 99),
+            max_us: sorted.last().copied().unwrap_or(0),
+        }
+    }
+
+    pub fn kb_per_second(&self) -> u32 {
+        match self.total_us {
+            0 => 0,
+            total_us => (self.bytes as u64*1_000_000/1024/total_us as u64) as u32,
+        }
+    }
+}
+
+/// Nearest rank percentile of `sorted` values
+fn percentile(sorted: &[u32], percent: usize) -> u32 {
+    match sorted.len() {
+        0 => 0,
+        length => sorted[((length*percent + 99)/100).max(1) - 1],
+    }
+}
+
+/// Write `FILE_SIZE` bytes to an empty file using writes of `buffer` size
+pub fn sequential_write<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    dir: &Directory,
+    buffer: &[u8],
+    stopwatch: &Stopwatch,
+) -> Result<Summary, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    let mut latencies = Latencies::new();
+
+    let mut file = controller.open_file_in_dir(
+        volume, dir, BENCH_FILE_NAME, Mode::ReadWriteCreateOrTruncate
+    ).map_err(SdWriteError::CannotOpenFile)?;
+
+    let mut result = Ok(());
+
+    for _ in 0..FILE_SIZE/buffer.len() {
+        result = stopwatch.measure(&mut latencies, || controller.write(volume, &mut file, buffer))
+            .map(|_| ())
+            .map_err(SdWriteError::CannotWriteToOpenedFile);
+
+        if result.is_err() {
+            break;
+        }
+    }
+
+    let _ = controller.close_file(volume, file);
+    result.map(|_| Summary::new(FILE_SIZE, &mut latencies))
+}
+
+/// Read the file written by `sequential_write` using reads of `buffer` size
+pub fn sequential_read<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    dir: &Directory,
+    buffer: &mut [u8],
+    stopwatch: &Stopwatch,
+) -> Result<Summary, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    let mut latencies = Latencies::new();
+    let mut bytes = 0;
+
+    let mut file = controller.open_file_in_dir(volume, dir, BENCH_FILE_NAME, Mode::ReadOnly)
+        .map_err(SdWriteError::CannotOpenFile)?;
+
+    let mut result = Ok(());
+
+    while !file.eof() {
+        match stopwatch.measure(&mut latencies, || controller.read(volume, &mut file, buffer)) {
+            Ok(size) => bytes += size,
+            Err(error) => {
+                result = Err(SdWriteError::CannotReadOpenedFile(error));
+                break;
+            },
+        }
+    }
+
+    let _ = controller.close_file(volume, file);
+    result.map(|_| Summary::new(bytes, &mut latencies))
+}
+
+/// Append `count` small `record`s, opening and closing the file for each
+/// of them the same way as a logger without a buffer does
+pub fn small_appends<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    dir: &Directory,
+    record: &[u8],
+    count: usize,
+    stopwatch: &Stopwatch,
+) -> Result<Summary, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    let mut latencies = Latencies::new();
+
+    for _ in 0..count {
+        stopwatch.measure(&mut latencies, || {
+            append_record(controller, volume, dir, APPEND_FILE_NAME, record)
+        })?;
+    }
+
+    Ok(Summary::new(record.len()*count, &mut latencies))
+}
+
+/// Open the file named `file_name`, append `record` and close the file
+pub fn append_record<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    dir: &Directory,
+    file_name: &str,
+    record: &[u8],
+) -> Result<(), SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    let mut file = controller.open_file_in_dir(
+        volume, dir, file_name, Mode::ReadWriteCreateOrAppend
+    ).map_err(SdWriteError::CannotOpenFile)?;
+
+    let result = controller.write(volume, &mut file, record)
+        .map(|_| ())
+        .map_err(SdWriteError::CannotWriteToOpenedFile);
+
+    let _ = controller.close_file(volume, file);
+    result
+}
@@ -0,0 +1,260 @@
+#![no_std]
+#![no_main]
+
+mod bench;
+
+use arrayvec::ArrayString;
+use bench::{append_record, sequential_read, sequential_write, small_appends, Stopwatch, Summary};
+use core::{fmt::Write, panic::PanicInfo};
+use cortex_m_rt::{entry};
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    mono_font::{MonoTextStyle, ascii::FONT_6X10}, text::Text
+};
+use embedded_hal::spi;
+use embedded_sdmmc::{Controller, Directory, SdMmcError, SdMmcSpi, TimeSource, Timestamp, Volume};
+use sd_logger::{
+    card::{CardDevice, ClockedCard, SpiClock},
+    open_volume,
+    replace_file,
+    spi_clock::Spi1Clock,
+    SdWriteError,
+};
+use sh1106::{prelude::*, Builder, interface::DisplayInterface};
+use stm32f4xx_hal::{prelude::*, pac, gpio::NoPin};
+
+/// SPI clock levels tested, PCLK2 (25 MHz) divided by 2, 4, 8, 16 and 64
+const CLOCK_LEVELS: [u8; 5] = [0, 1, 2, 3, 5];
+
+/// Buffer sizes used by the sequential write and read tests
+const BUFFER_SIZES: [usize; 3] = [512, 2048, 8192];
+
+/// Size and count of records appended by the small append test
+const RECORD_SIZE: usize = 64;
+const RECORD_COUNT: usize = 32;
+
+/// Time to read a result on the display
+const RESULT_DISPLAY_MS: u16 = 2000;
+
+const REPORT_FILE_NAME: &str = "BENCH.TXT";
+const REPORT_HEADER: &str = "clock_khz,test,buffer,bytes,kb_per_s,p50_us,p90_us,p99_us,max_us\r\n";
+
+/// Turn on onboard LED in case of panic
+#[inline(never)]
+#[panic_handler]
+fn on_panic(_info: &PanicInfo) -> ! {
+    let dp = unsafe { pac::Peripherals::steal() };
+    let gpioc = dp.GPIOC.split();
+    let mut led = gpioc.pc13.into_push_pull_output();
+    let _ = led.set_low();
+    loop { }
+}
+
+#[entry]
+fn main() -> ! {
+    if let (Some(dp), Some(cp)) = (
+        pac::Peripherals::take(),
+        cortex_m::peripheral::Peripherals::take(),
+    ) {
+        run(dp, cp).unwrap();
+        loop {}
+    } else {
+        loop {}
+    }
+}
+
+fn run(
+    dp: pac::Peripherals,
+    mut cp: cortex_m::Peripherals,
+) -> Result<(), ()> {
+    let rcc = dp.RCC.constrain();
+    let clocks = rcc.cfgr.use_hse(25.MHz()).sysclk(100.MHz()).hclk(25.MHz()).freeze();
+
+    let gpioa = dp.GPIOA.split();
+    let gpiob = dp.GPIOB.split();
+
+    let dc = gpiob.pb6.into_push_pull_output();
+
+    let spi = dp.SPI2.spi(
+        (gpiob.pb13, NoPin, gpiob.pb15),
+        spi::MODE_0,
+        4000.kHz(),
+        &clocks,
+    );
+
+    let mut display_reset = gpiob.pb14.into_push_pull_output();
+    let mut delay = dp.TIM5.delay_us(&clocks);
+
+    let mut display: GraphicsMode<_> = Builder::new()
+        .with_rotation(DisplayRotation::Rotate180)
+        .with_size(DisplaySize::Display128x64)
+        .connect_spi(spi, dc, sh1106::builder::NoOutputPin::new())
+        .into();
+
+    display.reset(&mut display_reset, &mut delay).map_err(|_| ())?;
+    display.init().map_err(|_| ())?;
+
+    display_text(&mut display, "SD card benchmark\nInitializing ...")?;
+
+    let sd_spi = dp.SPI1.spi(
+        (gpioa.pa5, gpioa.pa6, gpioa.pa7),
+        spi::MODE_0,
+        400.kHz(),
+        &clocks,
+    );
+
+    let sd_cs = gpiob.pb0.into_push_pull_output();
+    let sd_card = ClockedCard::new(SdMmcSpi::new(sd_spi, sd_cs), Spi1Clock::new(&clocks));
+    let mut sd_controller = Controller::new(sd_card, Clock {});
+
+    let stopwatch = Stopwatch::new(&mut cp.DCB, &mut cp.DWT, &clocks);
+
+    let mut show = |text: &str| {
+        let _ = display_text(&mut display, text);
+        delay.delay_ms(RESULT_DISPLAY_MS);
+    };
+
+    let mut text = ArrayString::<80>::new();
+
+    match benchmark(&mut sd_controller, &stopwatch, &mut show) {
+        Ok(()) => { let _ = write!(text, "Benchmark done\nReport {}", REPORT_FILE_NAME); },
+        Err(error) => { let _ = write!(text, "Benchmark failed\n{}", error); },
+    }
+
+    display_text(&mut display, &text)
+}
+
+/// Run all tests at all clock speeds
+fn benchmark<D, C, T>(
+    controller: &mut Controller<ClockedCard<D, C>, T>,
+    stopwatch: &Stopwatch,
+    show: &mut dyn FnMut(&str),
+) -> Result<(), SdWriteError<SdMmcError>>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock, T: TimeSource {
+    replace_file(controller, REPORT_FILE_NAME, REPORT_HEADER)?;
+
+    let mut buffer = [0u8; 8192];
+
+    for (index, byte) in buffer.iter_mut().enumerate() {
+        *byte = b'0' + (index % 10) as u8;
+    }
+
+    for level in CLOCK_LEVELS {
+        controller.device().set_fastest_level(level);
+        controller.device().initialize().map_err(SdWriteError::CannotConnect)?;
+
+        let result = benchmark_clock(controller, &mut buffer, stopwatch, show);
+        controller.device().release();
+        result?;
+    }
+
+    Ok(())
+}
+
+/// Run all tests at the clock selected at the card initialization
+fn benchmark_clock<D, C, T>(
+    controller: &mut Controller<ClockedCard<D, C>, T>,
+    buffer: &mut [u8],
+    stopwatch: &Stopwatch,
+    show: &mut dyn FnMut(&str),
+) -> Result<(), SdWriteError<SdMmcError>>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock, T: TimeSource {
+    let mut volume = open_volume(controller)?;
+
+    let dir = controller.open_root_dir(&volume)
+        .map_err(SdWriteError::CannotReadRootDir)?;
+
+    let result = run_tests(controller, &mut volume, &dir, buffer, stopwatch, show);
+    controller.close_dir(&volume, dir);
+    result
+}
+
+fn run_tests<D, C, T>(
+    controller: &mut Controller<ClockedCard<D, C>, T>,
+    volume: &mut Volume,
+    dir: &Directory,
+    buffer: &mut [u8],
+    stopwatch: &Stopwatch,
+    show: &mut dyn FnMut(&str),
+) -> Result<(), SdWriteError<SdMmcError>>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock, T: TimeSource {
+    for size in BUFFER_SIZES {
+        let summary = sequential_write(controller, volume, dir, &buffer[..size], stopwatch)?;
+        report(controller, volume, dir, "write", size, &summary, show)?;
+
+        let summary = sequential_read(controller, volume, dir, &mut buffer[..size], stopwatch)?;
+        report(controller, volume, dir, "read", size, &summary, show)?;
+    }
+
+    let summary = small_appends(
+        controller, volume, dir, &buffer[..RECORD_SIZE], RECORD_COUNT, stopwatch
+    )?;
+
+    report(controller, volume, dir, "append", RECORD_SIZE, &summary, show)
+}
+
+/// Show the test result and append it to the report file
+fn report<D, C, T>(
+    controller: &mut Controller<ClockedCard<D, C>, T>,
+    volume: &mut Volume,
+    dir: &Directory,
+    test: &str,
+    buffer_size: usize,
+    summary: &Summary,
+    show: &mut dyn FnMut(&str),
+) -> Result<(), SdWriteError<SdMmcError>>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock, T: TimeSource {
+    // Clock may have been lowered by errors during the test
+    let clock_khz = controller.device().frequency()/1000;
+
+    let mut text = ArrayString::<120>::new();
+
+    let _ = write!(
+        text,
+        "SPI {} kHz\n{} {} B\n{} kB/s\np50 {} us\np99 {} us\nmax {} us",
+        clock_khz, test, buffer_size, summary.kb_per_second(),
+        summary.p50_us, summary.p99_us, summary.max_us
+    );
+
+    show(&text);
+
+    let mut line = ArrayString::<100>::new();
+
+    let _ = write!(
+        line,
+        "{},{},{},{},{},{},{},{},{}\r\n",
+        clock_khz, test, buffer_size, summary.bytes, summary.kb_per_second(),
+        summary.p50_us, summary.p90_us, summary.p99_us, summary.max_us
+    );
+
+    append_record(controller, volume, dir, REPORT_FILE_NAME, line.as_bytes())
+}
+
+struct Clock;
+
+impl TimeSource for Clock {
+    // Fake time source that just returns 1. 1. 1970 0:00:00
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 0,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+fn display_text<T>(
+    display: &mut GraphicsMode<T>,
+    message: &str
+) -> Result<(), ()>
+where T: DisplayInterface {
+    display.clear();
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let position = Point::new(0, 8);
+    Text::new(&message, position, style).draw(display).map_err(|_| ())?;
+    display.flush().map_err(|_| ())
+}
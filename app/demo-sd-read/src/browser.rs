@@ -0,0 +1,194 @@
+use arrayvec::{ArrayString, ArrayVec};
+use core::fmt::Write;
+use embedded_sdmmc::{BlockDevice, Controller, Directory, DirEntry, TimeSource, Timestamp, Volume};
+use sd_logger::{dir::{open_dir_path, MAX_PATH, SEPARATOR}, SdWriteError};
+use ui::list::ListView;
+
+/// Rows of the entry list on the display
+pub const ROWS: usize = 4;
+
+/// Directories nested deeper than this are not remembered
+const MAX_DEPTH: usize = MAX_PATH/2;
+
+/// Directory entry shown in the list
+#[derive(Clone)]
+pub struct Entry {
+    pub name: ArrayString<12>,
+    pub is_dir: bool,
+    pub size: u32,
+    pub modified: Timestamp,
+}
+
+impl Entry {
+    fn new(entry: &DirEntry) -> Self {
+        let mut name = ArrayString::new();
+        let _ = write!(&mut name, "{}", entry.name);
+
+        Self {
+            name,
+            is_dir: entry.attributes.is_directory(),
+            size: entry.size,
+            modified: entry.mtime,
+        }
+    }
+}
+
+/// Directory listing read from the card one screen at a time, so
+/// directories of any size can be browsed
+pub struct Browser {
+    /// Path of the shown directory, empty for the root directory
+    path: ArrayString<MAX_PATH>,
+    list: ListView,
+    /// Entries of the visible rows
+    entries: ArrayVec<Entry, ROWS>,
+    /// Selected entries of the parent directories
+    parents: ArrayVec<usize, MAX_DEPTH>,
+}
+
+impl Browser {
+    pub fn new() -> Self {
+        Self {
+            path: ArrayString::new(),
+            list: ListView::new(ROWS),
+            entries: ArrayVec::new(),
+            parents: ArrayVec::new(),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn list(&self) -> &ListView {
+        &self.list
+    }
+
+    /// Entries of the visible rows, see `ListView::visible`
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.list.selected().checked_sub(self.list.top())?)
+    }
+
+    /// Path of the selected entry
+    pub fn selected_path(&self) -> Option<ArrayString<MAX_PATH>> {
+        let mut path = ArrayString::new();
+
+        if !self.path.is_empty() {
+            path.try_push_str(&self.path).ok()?;
+            path.try_push(SEPARATOR).ok()?;
+        }
+
+        path.try_push_str(&self.selected()?.name).ok()?;
+        Some(path)
+    }
+
+    /// Move the selection by `delta` entries, returns whether the selection
+    /// changed and the visible entries have to be read again
+    pub fn move_by(&mut self, delta: isize) -> bool {
+        self.list.move_by(delta)
+    }
+
+    /// Show the selected directory, returns `false` when the selected
+    /// entry is not a directory or its path is too long
+    pub fn enter(&mut self) -> bool {
+        match (self.selected().map(|entry| entry.is_dir), self.selected_path()) {
+            (Some(true), Some(path)) if !self.parents.is_full() => {
+                self.parents.push(self.list.selected());
+                self.path = path;
+                self.list = ListView::new(ROWS);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Show the parent directory with the left directory selected,
+    /// returns `false` in the root directory
+    pub fn leave(&mut self) -> bool {
+        if self.path.is_empty() {
+            return false;
+        }
+
+        let parent_length = self.path.rfind(SEPARATOR).unwrap_or(0);
+        self.path.truncate(parent_length);
+
+        // Number of entries is not known before the directory is read,
+        // `load` moves the selection back into the directory
+        self.list = ListView::new(ROWS);
+        self.list.set_len(usize::MAX);
+        self.list.select(self.parents.pop().unwrap_or(0));
+        true
+    }
+
+    /// Read the entries of the visible rows, the number of entries is
+    /// counted on the way and the selection kept in the list
+    pub fn load<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+        volume: &Volume,
+        root: &Directory,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: BlockDevice, T: TimeSource {
+        let top = self.list.top();
+        let len = read_entries(controller, volume, root, &self.path, top, &mut self.entries)?;
+        self.list.set_len(len);
+
+        // Directory changed on the card and the list scrolled back
+        if self.list.top() != top {
+            read_entries(controller, volume, root, &self.path, self.list.top(), &mut self.entries)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Read the entries of the directory at `path` starting at the entry
+/// number `first` into `entries`, returns the number of all entries
+///
+/// The volume label, `.` and `..` entries are left out, a directory is
+/// left by a long press of the select button instead
+fn read_entries<D, T, const N: usize>(
+    controller: &mut Controller<D, T>,
+    volume: &Volume,
+    root: &Directory,
+    path: &str,
+    first: usize,
+    entries: &mut ArrayVec<Entry, N>,
+) -> Result<usize, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    entries.clear();
+    let mut count = 0;
+
+    let mut add_entry = |entry: &DirEntry| {
+        if entry.attributes.is_volume() {
+            return;
+        }
+
+        let entry = Entry::new(entry);
+
+        if entry.name.as_str() == "." || entry.name.as_str() == ".." {
+            return;
+        }
+
+        if count >= first && !entries.is_full() {
+            entries.push(entry);
+        }
+
+        count += 1;
+    };
+
+    match path.is_empty() {
+        true => controller.iterate_dir(volume, root, &mut add_entry),
+        false => {
+            let dir = open_dir_path(controller, volume, root, path)?;
+            let result = controller.iterate_dir(volume, &dir, &mut add_entry);
+            controller.close_dir(volume, dir);
+            result
+        },
+    }.map_err(SdWriteError::CannotOpenDir)?;
+
+    Ok(count)
+}
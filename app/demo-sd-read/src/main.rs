@@ -1,22 +1,58 @@
 #![no_std]
 #![no_main]
 
+mod browser;
+mod viewer;
+
 use arrayvec::{ArrayString, ArrayVec};
+use browser::{Browser, Entry, ROWS};
 use core::{fmt::Write, panic::PanicInfo};
 use cortex_m_rt::{entry};
 use embedded_graphics::{
     pixelcolor::BinaryColor,
     prelude::*,
-    mono_font::{MonoTextStyle, ascii::FONT_6X10}, text::Text
-};
-use embedded_hal::{
-    spi, spi::FullDuplex, digital::v2::OutputPin
+    mono_font::{MonoTextStyle, ascii::FONT_6X10},
+    primitives::Rectangle,
+    text::{Alignment, Baseline, Text, TextStyleBuilder}
 };
+use embedded_hal::spi;
 use embedded_sdmmc::{
-    Controller, SdMmcSpi, TimeSource, Timestamp, VolumeIdx, Volume, Mode, Directory
+    BlockDevice, Controller, SdMmcError, SdMmcSpi, TimeSource, Timestamp, VolumeIdx, Volume,
+    Directory
+};
+use sd_logger::{
+    card::{CardDevice, ClockedCard, SpiClock},
+    dir::{split_path, MAX_PATH},
+    spi_clock::Spi1Clock,
+    SdWriteError
 };
 use sh1106::{prelude::*, Builder, interface::DisplayInterface};
 use stm32f4xx_hal::{prelude::*, pac, gpio::NoPin};
+use ui::{button::{Button, ButtonEvent}, list::draw_list, text::draw_lines};
+use viewer::{Scroll, ViewMode, Viewer};
+
+/// Button sampling period
+const TICK_MS: u32 = 10;
+
+/// Characters of a FONT_6X10 line on the 128 pixels wide display
+const LINE_CHARS: usize = 21;
+
+/// Entry list between the title line and the bottom line
+const LIST_AREA: Rectangle = Rectangle::new(Point::new(0, 12), Size::new(128, 40));
+
+/// File lines below the title line
+const FILE_AREA: Rectangle = Rectangle::new(Point::new(0, 12), Size::new(128, 52));
+
+/// Action of a button press
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Input {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Select,
+    Back,
+}
 
 /// Turn on onboard LED in case of panic
 #[inline(never)]
@@ -74,8 +110,6 @@ fn run(
     display.reset(&mut display_reset, &mut delay).map_err(|_| ())?;
     display.init().unwrap();
 
-    display_text(&mut display, "Initializing ...").unwrap();
-
     let sd_spi = dp.SPI1.spi(
         (gpioa.pa5, gpioa.pa6, gpioa.pa7),
         spi::MODE_0,
@@ -83,134 +117,341 @@ fn run(
         &clocks,
     );
 
+    // Card identification runs at 400 kHz at most, transfers as fast
+    // as the card reads reliably
     let sd_cs = gpiob.pb0.into_push_pull_output();
-    let mut sd_controller = Controller::new(SdMmcSpi::new(sd_spi, sd_cs), Clock {});
-    let mut counter: usize = 0;
+    let sd_card = ClockedCard::new(SdMmcSpi::new(sd_spi, sd_cs), Spi1Clock::new(&clocks));
+    let mut sd_controller = Controller::new(sd_card, Clock {});
+
+    // Buttons connect the inputs to the ground, KEY is the on board button
+    let up = gpioa.pa1.into_pull_up_input();
+    let down = gpioa.pa2.into_pull_up_input();
+    let key = gpioa.pa0.into_pull_up_input();
+    let mut up_button = Button::default();
+    let mut down_button = Button::default();
+    let mut key_button = Button::default();
+
+    let mut tick_timer = dp.TIM2.counter_ms(&clocks);
+    tick_timer.start(TICK_MS.millis()).map_err(|_| ())?;
+    let mut ticks: u32 = 0;
+
+    let mut browser = Browser::new();
+    let mut viewer: Option<Viewer> = None;
+    let mut card = mount(&mut sd_controller, &mut browser, &mut display)?;
 
     loop {
-        let mut debug = ArrayString::<100>::new();
-        let mut volume: Option<Volume> = open_file_volume(&mut sd_controller, &mut debug);
-
-        if let Some(ref mut volume) = volume {
-            let root_dir = match sd_controller.open_root_dir(volume) {
-                Ok(dir) => Some(dir),
-                Err(err) => {
-                    let _ = writeln!(&mut debug, "Root dir read ERR\n{:?}", err);
-                    None
-                }
-            };
+        if tick_timer.wait().is_err() {
+            continue;
+        }
+
+        ticks = ticks.wrapping_add(1);
+        let now_ms = ticks.wrapping_mul(TICK_MS);
+
+        let input = read_input(
+            up_button.update(up.is_low(), now_ms),
+            down_button.update(down.is_low(), now_ms),
+            key_button.update(key.is_low(), now_ms),
+        );
+
+        let input = match input {
+            Some(input) => input,
+            None => continue,
+        };
 
-            if let Some(dir) = root_dir {
-                let mut file_names: ArrayVec<ArrayString::<12>, 2> = ArrayVec::new();
-                read_file_names(&mut sd_controller, volume, &dir, &mut file_names, &mut debug);
-                print_file_contents(&mut sd_controller, volume, &dir, &file_names, &mut debug);
-                sd_controller.close_dir(volume, dir);
+        // Card failed before, any button tries again from the root directory
+        let (volume, root) = match card.as_mut() {
+            Some((volume, root)) => (volume, root),
+            None => {
+                browser = Browser::new();
+                viewer = None;
+                card = mount(&mut sd_controller, &mut browser, &mut display)?;
+                continue;
+            },
+        };
 
-                if file_names.is_empty() {
-                    let _ = writeln!(&mut debug, "No files found");
+        match handle_input(input, &mut browser, &mut viewer, &mut sd_controller, volume, root) {
+            Ok(()) => {},
+            Err(error) => {
+                let mut debug = ArrayString::<100>::new();
+                let _ = writeln!(&mut debug, "SD read failed\n{}\nPress a button", error);
+                display_text(&mut display, &debug)?;
+
+                if let Some((volume, root)) = card.take() {
+                    sd_controller.close_dir(&volume, root);
                 }
-            }
-            sd_controller.device().deinit();
+
+                sd_controller.device().release();
+                viewer = None;
+                continue;
+            },
         }
 
-        display_text(&mut display, &debug).unwrap();
-        render_counter(&mut display, counter).unwrap();
-        delay.delay_ms(1000u16);
-        counter += 1;
+        match &viewer {
+            Some(viewer) => draw_viewer(&mut display, viewer)?,
+            None => draw_browser(&mut display, &browser)?,
+        }
     }
 }
 
-/// Print file names and contents (first 32 bytes represented as ASCII test)
-/// from the `file_names` vector
-fn print_file_contents<SPI, CS, T>(
-    controller: &mut Controller<SdMmcSpi<SPI, CS>, T>,
-    volume: &mut Volume,
-    dir: &Directory,
-    file_names: &[ArrayString::<12>],
-    out: &mut dyn Write
-)
-where
-    SPI: FullDuplex<u8>,
-    CS: OutputPin,
-    T: TimeSource,
-    <SPI as FullDuplex<u8>>::Error: core::fmt::Debug
-{
-    const READ: Mode = Mode::ReadOnly;
-
-    for file_name in file_names {
-        if file_name.len() > 0 {
-            if let Ok(mut file) = controller.open_file_in_dir(volume, dir, file_name, READ) {
-                let mut buffer = [0u8; 32];
-                let read_size = controller.read(&volume, &mut file, &mut buffer).unwrap();
-                let bytes = &buffer[0..read_size];
-                write_file_data(&file_name, bytes, out);
-                let _ = controller.close_file(volume, file);
-            }
+/// Initialize the card and show the root directory, the error is shown
+/// when the card cannot be read
+fn mount<D, C, T, I>(
+    controller: &mut Controller<ClockedCard<D, C>, T>,
+    browser: &mut Browser,
+    display: &mut GraphicsMode<I>,
+) -> Result<Option<(Volume, Directory)>, ()>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock, T: TimeSource, I: DisplayInterface {
+    display_text(display, "Initializing ...")?;
+
+    let mut debug = ArrayString::<160>::new();
+
+    let card = open_file_volume(controller, &mut debug).and_then(|volume| {
+        match controller.open_root_dir(&volume) {
+            Ok(root) => Some((volume, root)),
+            Err(error) => {
+                let _ = writeln!(&mut debug, "Root dir read ERR\n{:?}", error);
+                None
+            },
         }
+    });
+
+    let (volume, root) = match card {
+        Some(card) => card,
+        None => {
+            let _ = writeln!(&mut debug, "Press a button");
+            display_text(display, &debug)?;
+            controller.device().release();
+            return Ok(None);
+        },
+    };
+
+    match browser.load(controller, &volume, &root) {
+        Ok(()) => {
+            draw_browser(display, browser)?;
+            Ok(Some((volume, root)))
+        },
+        Err(error) => {
+            let _ = writeln!(&mut debug, "{}\nPress a button", error);
+            display_text(display, &debug)?;
+            controller.close_dir(&volume, root);
+            controller.device().release();
+            Ok(None)
+        },
     }
 }
 
-fn write_file_data(
-    file_name: &str,
-    bytes: &[u8],
-    out: &mut dyn Write
-) {
-    let _ = writeln!(out, "* {}", file_name);
+/// Input of a button press, a short press of the up and down buttons
+/// moves the selection by one row, a long press by one page
+fn read_input(
+    up: Option<ButtonEvent>,
+    down: Option<ButtonEvent>,
+    key: Option<ButtonEvent>,
+) -> Option<Input> {
+    match (up, down, key) {
+        (Some(ButtonEvent::ShortPress), _, _) => Some(Input::Up),
+        (Some(ButtonEvent::LongPress), _, _) => Some(Input::PageUp),
+        (_, Some(ButtonEvent::ShortPress), _) => Some(Input::Down),
+        (_, Some(ButtonEvent::LongPress), _) => Some(Input::PageDown),
+        (_, _, Some(ButtonEvent::ShortPress)) => Some(Input::Select),
+        (_, _, Some(ButtonEvent::LongPress)) => Some(Input::Back),
+        _ => None,
+    }
+}
+
+/// Move in the directory listing and open the selected entry, or scroll
+/// the opened file and switch between text and hex dump
+fn handle_input<D, T>(
+    input: Input,
+    browser: &mut Browser,
+    viewer: &mut Option<Viewer>,
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    root: &Directory,
+) -> Result<(), SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    if let Some(opened) = viewer {
+        let changed = match input {
+            Input::Up => opened.scroll(controller, volume, root, Scroll::LineUp)?,
+            Input::Down => opened.scroll(controller, volume, root, Scroll::LineDown)?,
+            Input::PageUp => opened.scroll(controller, volume, root, Scroll::PageUp)?,
+            Input::PageDown => opened.scroll(controller, volume, root, Scroll::PageDown)?,
+            Input::Select => {
+                opened.toggle_mode();
+                true
+            },
+            Input::Back => {
+                *viewer = None;
+                return Ok(());
+            },
+        };
+
+        if changed {
+            opened.load(controller, volume, root)?;
+        }
 
-    for byte in bytes {
-        let _ = write!(out, "{}", *byte as char);
+        return Ok(());
     }
 
-    if bytes.len() > 0 {
-        let _ = writeln!(out, "");
+    let page = ROWS as isize;
+
+    let moved = match input {
+        Input::Up => browser.move_by(-1),
+        Input::Down => browser.move_by(1),
+        Input::PageUp => browser.move_by(-page),
+        Input::PageDown => browser.move_by(page),
+        Input::Back => browser.leave(),
+        Input::Select => match (browser.selected(), browser.selected_path()) {
+            (Some(entry), _) if entry.is_dir => browser.enter(),
+            (Some(entry), Some(path)) => {
+                let mut opened = Viewer::new(path, entry.size, LINE_CHARS);
+                opened.load(controller, volume, root)?;
+                *viewer = Some(opened);
+                false
+            },
+            _ => false,
+        },
+    };
+
+    if moved {
+        browser.load(controller, volume, root)?;
     }
+
+    Ok(())
 }
 
-/// Read up to first N file names into `file_names` vector
-fn read_file_names<SPI, CS, T, const N: usize>(
-    controller: &mut Controller<SdMmcSpi<SPI, CS>, T>,
-    volume: &Volume,
-    dir: &Directory,
-    file_names: &mut ArrayVec<ArrayString::<12>, N>,
-    out: &mut dyn Write
-)
-where
-    SPI: FullDuplex<u8>,
-    CS: OutputPin,
-    T: TimeSource,
-    <SPI as FullDuplex<u8>>::Error: core::fmt::Debug
-{
-    if let Err(error) = controller.iterate_dir(
-        volume, &dir, |item| {
-            let mut file_name = ArrayString::<12>::new();
-            let _ = write!(&mut file_name, "{}", item.name);
-            if !file_names.is_full() {
-                file_names.push(file_name);
-            }
-        }
-    ) {
-        let _ = writeln!(out, "Iterate dir error\n{:?}", error);
+/// Directory path and position on the top line, the entries with their
+/// sizes below it and the modification time of the selected entry on
+/// the bottom line
+fn draw_browser<T>(
+    display: &mut GraphicsMode<T>,
+    browser: &Browser,
+) -> Result<(), ()>
+where T: DisplayInterface {
+    display.clear();
+
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let list = browser.list();
+
+    let mut position = ArrayString::<24>::new();
+
+    if !list.is_empty() {
+        let _ = write!(&mut position, "{}/{}", list.selected() + 1, list.len());
+    }
+
+    let mut title = ArrayString::<{ MAX_PATH + 1 }>::new();
+    let _ = write!(&mut title, "/{}", browser.path());
+
+    // Path is shortened from the start, the current directory is kept
+    let room = LINE_CHARS.saturating_sub(position.len() + 1);
+    let title = &title[title.len().saturating_sub(room)..];
+
+    draw_line(display, title, Point::zero(), Alignment::Left)?;
+    draw_line(display, &position, Point::new(127, 0), Alignment::Right)?;
+
+    let rows: ArrayVec<ArrayString<LINE_CHARS>, ROWS> = browser.entries()
+        .iter()
+        .map(format_entry)
+        .collect();
+
+    draw_list(display, &LIST_AREA, &FONT_6X10, list, &rows).map_err(|_| ())?;
+
+    match browser.selected() {
+        Some(entry) => {
+            let time = &entry.modified;
+            let mut modified = ArrayString::<LINE_CHARS>::new();
+
+            let _ = write!(
+                &mut modified,
+                "{}-{:02}-{:02} {:02}:{:02}",
+                time.year_since_1970 as u16 + 1970,
+                time.zero_indexed_month + 1,
+                time.zero_indexed_day + 1,
+                time.hours,
+                time.minutes
+            );
+
+            draw_line(display, &modified, Point::new(0, 54), Alignment::Left)?;
+        },
+        None => {
+            Text::with_baseline("Empty", LIST_AREA.top_left, style, Baseline::Top)
+                .draw(display)
+                .map_err(|_| ())?;
+        },
     }
+
+    display.flush().map_err(|_| ())
+}
+
+/// Name and size or `<DIR>` aligned to columns
+fn format_entry(entry: &Entry) -> ArrayString<LINE_CHARS> {
+    let mut size = ArrayString::<8>::new();
+
+    let _ = match (entry.is_dir, entry.size) {
+        (true, _) => write!(&mut size, "<DIR>"),
+        (false, bytes) if bytes < 100_000 => write!(&mut size, "{}", bytes),
+        (false, bytes) if bytes < 10_000*1024 => write!(&mut size, "{}K", bytes/1024),
+        (false, bytes) => write!(&mut size, "{}M", bytes/(1024*1024)),
+    };
+
+    let mut row = ArrayString::new();
+    let _ = write!(&mut row, "{:<12} {:>5}", entry.name, size);
+    row
+}
+
+/// File name, view mode and position on the top line, the shown page
+/// below it
+fn draw_viewer<T>(
+    display: &mut GraphicsMode<T>,
+    viewer: &Viewer,
+) -> Result<(), ()>
+where T: DisplayInterface {
+    display.clear();
+
+    let mode = match viewer.mode() {
+        ViewMode::Text => "TXT",
+        ViewMode::Hex => "HEX",
+    };
+
+    let mut position = ArrayString::<8>::new();
+    let _ = write!(&mut position, "{} {}%", mode, viewer.position());
+
+    draw_line(display, split_path(viewer.path()).1, Point::zero(), Alignment::Left)?;
+    draw_line(display, &position, Point::new(127, 0), Alignment::Right)?;
+    draw_lines(display, &FILE_AREA, &FONT_6X10, viewer.lines()).map_err(|_| ())?;
+
+    display.flush().map_err(|_| ())
+}
+
+fn draw_line<T>(
+    display: &mut GraphicsMode<T>,
+    text: &str,
+    position: Point,
+    alignment: Alignment,
+) -> Result<(), ()>
+where T: DisplayInterface {
+    let character_style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let text_style = TextStyleBuilder::new().alignment(alignment).baseline(Baseline::Top).build();
+
+    Text::with_text_style(text, position, character_style, text_style)
+        .draw(display)
+        .map(|_| ())
+        .map_err(|_| ())
 }
 
 /// Open first SD card volume and return it
 /// Generate debug output into `out` writable
-fn open_file_volume<SPI, CS, T>(
-    controller: &mut Controller<SdMmcSpi<SPI, CS>, T>,
+fn open_file_volume<D, C, T>(
+    controller: &mut Controller<ClockedCard<D, C>, T>,
     out: &mut dyn Write
 ) -> Option<Volume>
-where
-    SPI: FullDuplex<u8>,
-    CS: OutputPin,
-    T: TimeSource,
-    <SPI as FullDuplex<u8>>::Error: core::fmt::Debug
-{
-    match controller.device().init() {
+where D: CardDevice<Error = SdMmcError>, C: SpiClock, T: TimeSource {
+    match controller.device().initialize() {
         Ok(_) => {
-            match controller.device().card_size_bytes() {
-                Ok(size) => writeln!(out, "SD OK: {} MB", size >> 20).unwrap(),
+            match controller.device().num_blocks() {
+                Ok(blocks) => writeln!(out, "SD OK: {} MB", blocks.0 >> 11).unwrap(),
                 Err(_err) => writeln!(out, "SD Card Connected\nCannot read size").unwrap(),
             }
+            writeln!(out, "SPI {} kHz", controller.device().frequency()/1000).unwrap();
             match controller.get_volume(VolumeIdx(0)) {
                 Ok(volume) => {
                     writeln!(out, "Get FAT Volume 0: OK").unwrap();
@@ -245,19 +486,6 @@ impl TimeSource for Clock {
     }
 }
 
-fn render_counter<T>(
-    display: &mut GraphicsMode<T>,
-    counter: usize
-) -> Result<(), ()>
-where T: DisplayInterface {
-    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
-    let position = Point::new(100, 63);
-    let mut text = ArrayString::<10>::new();
-    let _ = write!(&mut text, "{}", counter);
-    Text::new(&text, position, style).draw(display).map_err(|_| ())?;
-    display.flush().map_err(|_| ())
-}
-
 fn display_text<T>(
     display: &mut GraphicsMode<T>,
     message: &str
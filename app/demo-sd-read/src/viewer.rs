@@ -0,0 +1,127 @@
+use arrayvec::ArrayString;
+use embedded_sdmmc::{BlockDevice, Controller, Directory, TimeSource, Volume};
+use sd_logger::{dir::{read_at_path, MAX_PATH}, SdWriteError};
+use ui::{hex::HexPager, text::{TextPager, MAX_LINE}};
+
+/// Lines of the file shown below the title line
+pub const FILE_LINES: usize = 5;
+
+/// Scroll step of the viewer
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scroll {
+    LineUp,
+    LineDown,
+    PageUp,
+    PageDown,
+}
+
+/// How the file content is shown
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ViewMode {
+    Text,
+    Hex,
+}
+
+/// File opened from the browser, read from the card one page at a time,
+/// so files of any size can be viewed
+pub struct Viewer {
+    path: ArrayString<MAX_PATH>,
+    size: u32,
+    mode: ViewMode,
+    text: TextPager<FILE_LINES>,
+    hex: HexPager<FILE_LINES>,
+}
+
+impl Viewer {
+    /// Viewer of the file at `path` of `size` bytes with lines of `width`
+    /// characters, `load` reads the first page
+    pub fn new(path: ArrayString<MAX_PATH>, size: u32, width: usize) -> Self {
+        Self {
+            path,
+            size,
+            mode: ViewMode::Text,
+            text: TextPager::new(width, size),
+            hex: HexPager::new(width, size),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn mode(&self) -> ViewMode {
+        self.mode
+    }
+
+    /// Lines of the shown page
+    pub fn lines(&self) -> &[ArrayString<MAX_LINE>] {
+        match self.mode {
+            ViewMode::Text => self.text.lines(),
+            ViewMode::Hex => self.hex.lines(),
+        }
+    }
+
+    /// Percentage of the file before the shown page
+    pub fn position(&self) -> u32 {
+        let top = match self.mode {
+            ViewMode::Text => self.text.top(),
+            ViewMode::Hex => self.hex.top(),
+        };
+
+        (top as u64*100/self.size.max(1) as u64) as u32
+    }
+
+    /// Switch between text and hex dump, each keeps its own position,
+    /// `load` reads the page
+    pub fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            ViewMode::Text => ViewMode::Hex,
+            ViewMode::Hex => ViewMode::Text,
+        };
+    }
+
+    /// Scroll by a line or a page, returns whether the page has to be
+    /// read again
+    pub fn scroll<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+        volume: &mut Volume,
+        root: &Directory,
+        scroll: Scroll,
+    ) -> Result<bool, SdWriteError<D::Error>>
+    where D: BlockDevice, T: TimeSource {
+        let path = &self.path;
+        let page = matches!(scroll, Scroll::PageUp | Scroll::PageDown);
+        let mut read = |offset, buffer: &mut [u8]| {
+            read_at_path(controller, volume, root, path, offset, buffer)
+        };
+
+        match (self.mode, scroll) {
+            (ViewMode::Text, Scroll::LineDown | Scroll::PageDown) => Ok(self.text.scroll_down(page)),
+            (ViewMode::Text, Scroll::LineUp | Scroll::PageUp) => self.text.scroll_up(page, &mut read),
+            (ViewMode::Hex, Scroll::LineUp) => Ok(self.hex.scroll(-1)),
+            (ViewMode::Hex, Scroll::LineDown) => Ok(self.hex.scroll(1)),
+            (ViewMode::Hex, Scroll::PageUp) => Ok(self.hex.scroll(-(FILE_LINES as i32))),
+            (ViewMode::Hex, Scroll::PageDown) => Ok(self.hex.scroll(FILE_LINES as i32)),
+        }
+    }
+
+    /// Read the shown page from the card
+    pub fn load<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+        volume: &mut Volume,
+        root: &Directory,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: BlockDevice, T: TimeSource {
+        let path = &self.path;
+        let mut read = |offset, buffer: &mut [u8]| {
+            read_at_path(controller, volume, root, path, offset, buffer)
+        };
+
+        match self.mode {
+            ViewMode::Text => self.text.layout(&mut read),
+            ViewMode::Hex => self.hex.layout(&mut read),
+        }
+    }
+}
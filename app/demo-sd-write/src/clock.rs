@@ -0,0 +1,107 @@
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use pcf8563::PCF8563;
+use rtc_time::{
+    calendar::DateTime,
+    clock::{Clock, ClockError, ClockReading},
+    rtc::{
+        century_flag, read_century, read_voltage_low, to_pcf8563, to_reading, write_century,
+    },
+};
+use stm32f4xx_hal::{pac, rtc::Rtc};
+use ::time::{Date, Month, PrimitiveDateTime, Time};
+
+/// External PCF8563 clock, the driver does not expose the clock integrity
+/// and century flags, they are accessed directly using a second I2C bus proxy
+pub struct Pcf8563Clock<I2C, R> {
+    driver: PCF8563<I2C>,
+    registers: R,
+}
+
+impl<I2C, R> Pcf8563Clock<I2C, R> {
+    pub fn new(driver: PCF8563<I2C>, registers: R) -> Self {
+        Self { driver, registers }
+    }
+
+    /// Driver access for the alarm and timer settings
+    pub fn driver(&mut self) -> &mut PCF8563<I2C> {
+        &mut self.driver
+    }
+}
+
+impl<I2C, E, R, RE> Clock for Pcf8563Clock<I2C, R>
+where
+    I2C: Write<Error = E> + WriteRead<Error = E>,
+    R: Write<Error = RE> + WriteRead<Error = RE>,
+{
+    fn name(&self) -> &'static str {
+        "PCF8563"
+    }
+
+    fn read(&mut self) -> Result<ClockReading, ClockError> {
+        let registers = self.driver.get_datetime()
+            .map_err(|_| ClockError::NotResponding)?;
+        let voltage_low = read_voltage_low(&mut self.registers)
+            .map_err(|_| ClockError::NotResponding)?;
+        let century = read_century(&mut self.registers)
+            .map_err(|_| ClockError::NotResponding)?;
+
+        Ok(to_reading(&registers, century, voltage_low))
+    }
+
+    fn set(&mut self, time: &DateTime) -> Result<(), ClockError> {
+        let registers = to_pcf8563(time).map_err(|_| ClockError::OutOfRange)?;
+        self.driver.set_datetime(&registers).map_err(|_| ClockError::NotResponding)?;
+        write_century(&mut self.registers, century_flag(time))
+            .map_err(|_| ClockError::NotResponding)
+    }
+}
+
+/// STM32 internal RTC running from the LSE crystal in the backup domain,
+/// it keeps the time while VBAT is powered
+pub struct InternalClock {
+    rtc: Rtc,
+}
+
+impl InternalClock {
+    pub fn new(rtc: Rtc) -> Self {
+        Self { rtc }
+    }
+}
+
+impl Clock for InternalClock {
+    fn name(&self) -> &'static str {
+        "internal RTC"
+    }
+
+    fn read(&mut self) -> Result<ClockReading, ClockError> {
+        let time = self.rtc.get_datetime();
+
+        let converted = DateTime::new(
+            time.year() as u16, time.month() as u8, time.day(),
+            time.hour(), time.minute(), time.second()
+        );
+
+        Ok(match converted {
+            Ok(time) => ClockReading { time, valid: is_calendar_initialized() },
+            Err(_) => ClockReading { time: DateTime::UNIX_EPOCH, valid: false },
+        })
+    }
+
+    fn set(&mut self, time: &DateTime) -> Result<(), ClockError> {
+        let month = Month::try_from(time.month()).map_err(|_| ClockError::OutOfRange)?;
+        let date = Date::from_calendar_date(time.year() as i32, month, time.day())
+            .map_err(|_| ClockError::OutOfRange)?;
+        let clock = Time::from_hms(time.hours(), time.minutes(), time.seconds())
+            .map_err(|_| ClockError::OutOfRange)?;
+
+        // Years outside 2000 - 2099 are rejected by the RTC
+        self.rtc.set_datetime(&PrimitiveDateTime::new(date, clock))
+            .map_err(|_| ClockError::OutOfRange)
+    }
+}
+
+/// Calendar is initialized (year is not zero) since the time has been
+/// set, it is reset together with the backup domain
+fn is_calendar_initialized() -> bool {
+    unsafe { (*pac::RTC::ptr()).isr.read().inits().bit_is_set() }
+}
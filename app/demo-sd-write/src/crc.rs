@@ -0,0 +1,34 @@
+use sd_logger::record::Checksum;
+use stm32f4xx_hal::pac;
+
+/// CRC calculation unit computing the log record checksums, 32 bit words
+/// are processed in one AHB cycle each
+pub struct HardwareCrc {
+    crc: pac::CRC,
+}
+
+impl HardwareCrc {
+    pub fn new(crc: pac::CRC) -> Self {
+        // RCC is owned by the clock configuration, only the CRC unit
+        // clock is enabled
+        let rcc = unsafe { &*pac::RCC::ptr() };
+        rcc.ahb1enr.modify(|_, w| w.crcen().set_bit());
+
+        Self { crc }
+    }
+}
+
+impl Checksum for HardwareCrc {
+    fn crc32(&mut self, data: &[u8]) -> u32 {
+        // Reset loads the initial value 0xFFFFFFFF
+        self.crc.cr.write(|w| unsafe { w.bits(1) });
+
+        for chunk in data.chunks(4) {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.crc.dr.write(|w| unsafe { w.bits(u32::from_le_bytes(word)) });
+        }
+
+        self.crc.dr.read().bits()
+    }
+}
@@ -0,0 +1,187 @@
+use arrayvec::{ArrayString, ArrayVec};
+use core::fmt::Write;
+use rtc_time::zone::{write_iso8601, LocalTime};
+use sd_logger::{
+    binary::{
+        BinaryScan, FieldType, RecordEncoder, Sample, Schema, FLAG_TIME_INVALID, FLAG_TIME_JUMP,
+    },
+    record::{write_record, Checksum, RecoveryScan},
+};
+
+/// Format of the log files
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LogFormat {
+    /// Numbered text lines with a CRC, see `sd_logger::record`
+    Text,
+    /// Fixed size binary records, see `sd_logger::binary`, optionally
+    /// with the time encoded as a difference
+    #[allow(dead_code)] // Selected by the `LOG_FORMAT` constant
+    Binary { delta: bool },
+}
+
+impl LogFormat {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            LogFormat::Text => "TXT",
+            LogFormat::Binary { .. } => "BIN",
+        }
+    }
+}
+
+/// Data of one log record
+pub struct RecordData<'a> {
+    pub sequence: u32,
+    pub local: &'a LocalTime,
+    /// Clock integrity is guaranteed
+    pub valid: bool,
+    /// First record after a time jump
+    pub jumped: bool,
+    pub uptime_seconds: u32,
+}
+
+/// Encodes records to the active log file, knows what the file needs
+/// before the next record
+pub enum LogWriter {
+    Text {
+        /// File ends by a cut line
+        line_break: bool,
+    },
+    Binary {
+        encoder: RecordEncoder,
+        /// File has no header
+        header: bool,
+        /// Bytes completing a cut record
+        padding: usize,
+    },
+}
+
+impl LogWriter {
+    /// Writer continuing the active file with the given scan
+    pub fn text(scan: &RecoveryScan) -> Self {
+        LogWriter::Text { line_break: scan.unterminated() }
+    }
+
+    /// Writer continuing the active file with the given scan, a file
+    /// without a valid header is continued by a header
+    pub fn binary(delta: bool, scan: &BinaryScan) -> Self {
+        LogWriter::Binary {
+            encoder: RecordEncoder::new(binary_schema(delta)),
+            header: scan.schema().is_none(),
+            padding: scan.padding(),
+        }
+    }
+
+    /// Length of a record without anything preceding it
+    pub fn record_length(&self, record: &RecordData) -> usize {
+        match self {
+            LogWriter::Text { .. } => {
+                let mut line = ArrayString::<{ sd_logger::record::MAX_RECORD }>::new();
+                let _ = write_text(&mut line, record, &mut NoChecksum);
+                line.len()
+            },
+            LogWriter::Binary { encoder, .. } => encoder.schema().record_size(),
+        }
+    }
+
+    /// Encode the `record` to `output`, preceded by what the file needs,
+    /// `started` is set for a new file, returns the length
+    pub fn encode(
+        &mut self,
+        record: &RecordData,
+        started: bool,
+        crc: &mut dyn Checksum,
+        output: &mut [u8],
+    ) -> Result<usize, ()> {
+        match self {
+            LogWriter::Text { line_break } => {
+                let mut line = ArrayString::<{ sd_logger::record::MAX_RECORD + 1 }>::new();
+
+                if *line_break && !started {
+                    line.push('\n');
+                }
+
+                write_text(&mut line, record, crc).map_err(|_| ())?;
+                *line_break = false;
+
+                let output = output.get_mut(..line.len()).ok_or(())?;
+                output.copy_from_slice(line.as_bytes());
+                Ok(line.len())
+            },
+            LogWriter::Binary { encoder, header, padding } => {
+                let mut length = 0;
+
+                if started || *header {
+                    encoder.restart();
+                    length = encoder.schema().write_header(output, crc).map_err(|_| ())?;
+                } else if *padding > 0 {
+                    output.get_mut(..*padding).ok_or(())?.fill(0xff);
+                    length = *padding;
+                }
+
+                *header = false;
+                *padding = 0;
+
+                let sample = binary_sample(record);
+                let output = output.get_mut(length..).ok_or(())?;
+                Ok(length + encoder.encode(&sample, output, crc).map_err(|_| ())?)
+            },
+        }
+    }
+}
+
+/// Binary record fields, UTC offset of the local time and the uptime
+fn binary_schema(delta: bool) -> Schema {
+    let mut schema = Schema::new(delta);
+    let _ = schema.add_field("offset_min", FieldType::I16);
+    let _ = schema.add_field("uptime_s", FieldType::U32);
+    schema
+}
+
+fn binary_sample(record: &RecordData) -> Sample {
+    let utc_seconds = record.local.time.to_unix_seconds() - record.local.offset_minutes as i64*60;
+
+    let mut flags = 0;
+
+    if !record.valid {
+        flags |= FLAG_TIME_INVALID;
+    }
+
+    if record.jumped {
+        flags |= FLAG_TIME_JUMP;
+    }
+
+    let mut values = ArrayVec::new();
+    values.push(record.local.offset_minutes as i64);
+    values.push(record.uptime_seconds as i64);
+
+    Sample { sequence: record.sequence, time: utc_seconds as u32, flags, values }
+}
+
+/// Write the text record, the content is the ISO 8601 local time with
+/// the UTC offset, then `A` for valid time or `V` when the RTC clock
+/// integrity is not guaranteed, optionally followed by `J` for the first
+/// record after an RTC time jump
+fn write_text(
+    output: &mut dyn Write,
+    record: &RecordData,
+    crc: &mut dyn Checksum,
+) -> core::fmt::Result {
+    let mut content = ArrayString::<40>::new();
+    write_iso8601(&mut content, record.local)?;
+    write!(content, " {}", if record.valid { 'A' } else { 'V' })?;
+
+    if record.jumped {
+        write!(content, " J")?;
+    }
+
+    write_record(output, record.sequence, &content, crc)
+}
+
+/// Checksum placeholder used to measure the record length
+struct NoChecksum;
+
+impl Checksum for NoChecksum {
+    fn crc32(&mut self, _data: &[u8]) -> u32 {
+        0
+    }
+}
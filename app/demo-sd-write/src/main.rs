@@ -1,25 +1,50 @@
 #![no_std]
 #![no_main]
 
+mod clock;
+mod crc;
+mod log_format;
 mod time;
-mod sd_logger;
+mod uptime;
+mod wakeup;
 
 use arrayvec::{ArrayString};
+use clock::{InternalClock, Pcf8563Clock};
+use crc::HardwareCrc;
+use log_format::{LogFormat, LogWriter, RecordData};
 use pcf8563::PCF8563;
-use sd_logger::{append_to_file, SdWriteError};
-use time::{ClockData, ZERO_TIMESTAMP};
-use core::{fmt::Write, panic::PanicInfo};
+use sd_logger::{
+    card::{CardDevice, ClockedCard, SpiClock},
+    binary::{BinaryScan, MAX_HEADER, MAX_RECORD},
+    read_chunks_from_file, read_from_file,
+    record::{Checksum, RecordScan, RecoveryScan},
+    rotation::{save_index, start_record, LogRotation, Rotation, RotationPolicy, INDEX_SIZE},
+    session::LoggerSession,
+    spi_clock::Spi1Clock,
+    SdWriteError
+};
+use crate::time::{ClockData, ClockStatus, FileTime};
+use core::{fmt::Write, panic::PanicInfo, sync::atomic::{AtomicBool, Ordering}};
 use cortex_m_rt::{entry};
-use embedded_hal::{spi::FullDuplex, digital::v2::OutputPin};
 use embedded_graphics::{
     pixelcolor::BinaryColor,
     prelude::*,
     mono_font::{MonoTextStyle, ascii::FONT_6X10}, text::Text
 };
 use embedded_hal::{spi};
-use embedded_sdmmc::{Controller, SdMmcSpi, TimeSource};
+use embedded_sdmmc::{Controller, SdMmcError, SdMmcSpi, TimeSource};
+use rtc_time::{
+    alarm::wakeup_at,
+    calendar::DateTime,
+    clock::{synchronize, Clock},
+    drift::{parse_calibration, write_ppm, DriftCorrection, CALIBRATION_FILE_NAME},
+    jump::{parse_last_time, write_last_time, JumpDetector, LAST_TIME_FILE_NAME},
+    schedule::{Schedule, Scheduler},
+    zone::{LocalTime, TimeZone, CENTRAL_EUROPE},
+};
 use sh1106::{prelude::*, Builder, interface::DisplayInterface};
-use stm32f4xx_hal::{prelude::*, pac, gpio::NoPin, i2c::I2c};
+use stm32f4xx_hal::{prelude::*, pac, pac::interrupt, gpio::{Edge, NoPin}, i2c::I2c, rtc::Rtc};
+use wakeup::program_wakeup;
 
 /// Turn on onboard LED in case of panic
 #[inline(never)]
@@ -32,6 +57,45 @@ fn on_panic(_info: &PanicInfo) -> ! {
     loop { }
 }
 
+/// When a record is written to the SD card, `second minute hour day
+/// month weekday` in the local time
+const RECORD_SCHEDULE: &str = "0 */10 * * * *";
+
+/// RTC runs in UTC, displayed and logged time is converted to this zone
+const TIME_ZONE: TimeZone = CENTRAL_EUROPE;
+
+/// Internal RTC is set from the PCF8563 when their times differ more
+const MAX_CLOCK_DIFFERENCE_S: i64 = 1;
+
+/// Records are kept in RAM until the 512 byte buffer fills up or the oldest
+/// gets this old, so at most this time of records is lost on power failure
+const LOG_FLUSH_INTERVAL_S: u32 = 1800;
+
+/// Text lines or binary records, see doc/sd-card-write.md
+const LOG_FORMAT: LogFormat = LogFormat::Text;
+
+/// New log file every day, a month of files kept in the LOGS directory
+const LOG_ROTATION: RotationPolicy = RotationPolicy {
+    rotation: Rotation::Daily,
+    max_files: 31,
+    max_bytes: Some(64*1024*1024),
+    dir: Some("LOGS"),
+    extension: LOG_FORMAT.extension(),
+};
+
+/// Differences between the RTC and the uptime counter up to this value
+/// are not reported as time jumps
+const JUMP_TOLERANCE_S: i64 = 5;
+
+/// Set by the RTC interrupt handler, cleared by the main loop
+static RTC_WAKEUP: AtomicBool = AtomicBool::new(false);
+
+/// Tasks run by the scheduler
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Task {
+    WriteRecord,
+}
+
 #[entry]
 fn main() -> ! {
     if let (Some(dp), Some(cp)) = (
@@ -47,11 +111,12 @@ fn main() -> ! {
 
 fn run(
     dp: pac::Peripherals,
-    _cp: cortex_m::Peripherals,
+    cp: cortex_m::Peripherals,
 ) -> Result<(), ()> {
     let rcc = dp.RCC.constrain();
 
     let clocks = rcc.cfgr.use_hse(25.MHz()).sysclk(100.MHz()).hclk(25.MHz()).freeze();
+    uptime::start(cp.SYST, &clocks);
 
     let gpioa = dp.GPIOA.split();
     let gpiob = dp.GPIOB.split();
@@ -75,7 +140,17 @@ fn run(
         &clocks,
     );
 
-    let mut rtc_driver = PCF8563::new(i2c);
+    let i2c_bus = shared_bus::BusManagerSimple::new(i2c);
+    let mut external_clock = Pcf8563Clock::new(
+        PCF8563::new(i2c_bus.acquire_i2c()), i2c_bus.acquire_i2c()
+    );
+
+    let mut pwr = dp.PWR;
+    let mut internal_clock = InternalClock::new(Rtc::new(dp.RTC, &mut pwr));
+
+    // External RTC is preferred, the internal one is used only when
+    // the PCF8563 does not respond at start up (or stops responding later)
+    let mut use_external = external_clock.read().is_ok();
     let mut clock = ClockData::default();
 
     let mut display_reset = gpiob.pb14.into_push_pull_output();
@@ -99,66 +174,352 @@ fn run(
         &clocks,
     );
 
+    // Card identification runs at 400 kHz at most, transfers as fast
+    // as the card reads reliably
     let sd_cs = gpiob.pb0.into_push_pull_output();
-    let mut sd_controller = Controller::new(SdMmcSpi::new(sd_spi, sd_cs), ClockData::default());
-    let mut counter: usize = 0;
-    let mut last_write_attempt = ZERO_TIMESTAMP;
+    let sd_card = ClockedCard::new(SdMmcSpi::new(sd_spi, sd_cs), Spi1Clock::new(&clocks));
+    let file_time = FileTime::default();
+    let mut sd_controller = Controller::new(sd_card, &file_time);
+
+    // Drift measured by the calibration app, if the card contains it
+    let mut calibration = [0u8; 32];
+    let drift_ppb = read_from_file(&mut sd_controller, CALIBRATION_FILE_NAME, &mut calibration)
+        .ok()
+        .and_then(|size| parse_calibration(&calibration[..size]));
+
+    // Calibration is measured for the PCF8563 crystal only
+    if let (true, Some(drift_ppb)) = (use_external, drift_ppb) {
+        clock.set_drift_correction(DriftCorrection::new(drift_ppb));
+
+        let mut text = ArrayString::<40>::new();
+        let _ = write!(text, "RTC drift correction\n");
+        let _ = write_ppm(&mut text, drift_ppb);
+        display_text(&mut display, &text)?;
+        delay.delay_ms(2000u16);
+    }
+
+    // Time of the last record written before restart
+    let mut jump_detector = JumpDetector::new(JUMP_TOLERANCE_S);
+    let mut last_time = [0u8; 32];
+
+    if let Some(time) = read_from_file(&mut sd_controller, LAST_TIME_FILE_NAME, &mut last_time)
+        .ok()
+        .and_then(|size| parse_last_time(&last_time[..size]))
+    {
+        jump_detector.restore(time);
+    }
+
+    // Log file rotation state, a new ring of files is started without it
+    let mut rotation = LogRotation::new(LOG_ROTATION);
+    let mut index = [0u8; INDEX_SIZE];
+
+    if let Ok(size) = read_from_file(&mut sd_controller, &rotation.index_file(), &mut index) {
+        rotation.restore(&index[..size]);
+    }
+
+    // Records are numbered on from the last valid record on the card
+    let mut crc = HardwareCrc::new(dp.CRC);
+    let (mut sequence, mut writer) = match LOG_FORMAT {
+        LogFormat::Text => {
+            let (sequence, scan) = recover_sequence::<_, _, RecoveryScan>(
+                &rotation, &mut sd_controller, &mut crc
+            );
+            (sequence, LogWriter::text(&scan))
+        },
+        LogFormat::Binary { delta } => {
+            let (sequence, scan) = recover_sequence::<_, _, BinaryScan>(
+                &rotation, &mut sd_controller, &mut crc
+            );
+            (sequence, LogWriter::binary(delta, &scan))
+        },
+    };
+
+    // PCF8563 INT output (open drain, active low) wakes the MCU up
+    let mut syscfg = dp.SYSCFG.constrain();
+    let mut exti = dp.EXTI;
+    let mut rtc_interrupt = gpioa.pa1.into_pull_up_input();
+    rtc_interrupt.make_interrupt_source(&mut syscfg);
+    rtc_interrupt.trigger_on_edge(&mut exti, Edge::Falling);
+    rtc_interrupt.enable_interrupt(&mut exti);
+    unsafe { pac::NVIC::unmask(rtc_interrupt.interrupt()); }
+
     let mut write_debug = ArrayString::<80>::new();
 
+    if sequence > 0 {
+        let _ = writeln!(&mut write_debug, "Resumed at record {}", sequence);
+    }
+
+    let mut session = LoggerSession::new(LOG_FLUSH_INTERVAL_S);
+    // Time of the last record, saved once the record is written to the card
+    let mut unsaved_time: Option<DateTime> = None;
+    let mut last_jump = None;
+    // Jump is flagged in the first record written after it
+    let mut jump_pending = false;
+
+    let mut scheduler = Scheduler::<Task, 1>::default();
+    let record_schedule = Schedule::parse(RECORD_SCHEDULE).map_err(|_| ())?;
+    scheduler.add(Task::WriteRecord, record_schedule).map_err(|_| ())?;
+
     loop {
         let mut text = ArrayString::<200>::new();
 
-        if let Ok(new_date_time) = rtc_driver.get_datetime() {
-            clock.set_from_pcf8563(new_date_time)
+        let reading = if use_external {
+            match external_clock.read() {
+                Ok(reading) => {
+                    let _ = synchronize(&mut internal_clock, &reading, MAX_CLOCK_DIFFERENCE_S);
+                    Ok(reading)
+                },
+                Err(_) => {
+                    use_external = false;
+                    clock.set_drift_correction(DriftCorrection::default());
+                    internal_clock.read()
+                },
+            }
         } else {
-            clock.reset_to_default();
+            internal_clock.read()
+        };
+
+        match reading {
+            Ok(reading) => clock.set_reading(&reading),
+            Err(_) => clock.reset_to_default(),
         }
 
-        let date_time_str = format_date_time(&clock);
+        file_time.update(&clock);
+
+        if clock.is_present() {
+            if let Some(jump) = jump_detector.check(clock.date_time(), uptime::seconds()) {
+                last_jump = Some(jump);
+                jump_pending = true;
+            }
+        }
+
+        let local = clock.local_time(&TIME_ZONE);
+        let date_time_str = format_date_time(&local);
         writeln!(&mut text, "{}", date_time_str).map_err(|_| ())?;
 
-        if clock.is_present() && clock.seconds() % 10 == 0 {
-            if last_write_attempt != clock.get_timestamp() {
-                last_write_attempt = clock.get_timestamp();
+        match clock.status() {
+            ClockStatus::Missing => writeln!(&mut text, "RTC not responding"),
+            ClockStatus::TimeInvalid => writeln!(&mut text, "TIME INVALID, set RTC"),
+            ClockStatus::Valid => match last_jump {
+                Some(jump) => writeln!(&mut text, "TIME JUMP {:+} s", jump.seconds()),
+                None if !use_external => writeln!(
+                    &mut text, "Using {}", internal_clock.name()
+                ),
+                None => Ok(()),
+            },
+        }.map_err(|_| ())?;
+
+        // Tasks are due when their time passed, whether the loop was woken
+        // up by the RTC or polls the clock, a missed time fires late. Record
+        // values are read now, so missed records are written as one.
+        let mut record_due = false;
+
+        if clock.is_present() {
+            while let Some(fired) = scheduler.poll(&local.time) {
+                match fired.task {
+                    Task::WriteRecord => record_due = true,
+                }
+            }
+        }
+
+        let next_task = scheduler.next_time();
+        write_next_record(&mut text, next_task.as_ref());
+
+        // Next wake-up is set before writing to the card, so that the alarm
+        // cannot be missed when the write takes long. Scheduled times are
+        // local, the RTC alarm compares UTC fields.
+        let wakeup_ready = match next_task {
+            Some(next) if use_external && clock.is_present() => {
+                let offset_seconds = local.offset_minutes as i64*60;
+
+                match next.add_seconds(-offset_seconds) {
+                    Ok(next) => {
+                        let wakeup = wakeup_at(clock.date_time(), &next);
+                        program_wakeup(external_clock.driver(), &wakeup).is_ok()
+                    },
+                    Err(_) => false,
+                }
+            },
+            _ => false,
+        };
+
+        if record_due {
+            let record = RecordData {
+                sequence,
+                local: &local,
+                valid: clock.is_valid(),
+                jumped: jump_pending,
+                uptime_seconds: uptime::seconds(),
+            };
+
+            match write_record_to_sd_card(
+                &record, &mut writer, &mut crc, &mut rotation, &mut session, &mut sd_controller
+            ) {
+                Ok(debug) => {
+                    jump_pending = false;
+                    unsaved_time = Some(*clock.date_time());
+                    write_debug = debug;
+                },
+                Err(debug) => write_debug = debug,
+            }
+
+            // Numbers of failed records are not reused
+            sequence = sequence.wrapping_add(1);
+        } else if let Err(error) = session.poll(&mut sd_controller, uptime::seconds()) {
+            write_debug.clear();
+            let _ = writeln!(&mut write_debug, "SD Write failed\n{}", error);
+        }
 
-                write_debug = write_record_to_sd_card(
-                    &clock, counter, &mut sd_controller
-                );
+        if let (0, Some(time)) = (session.buffered(), unsaved_time) {
+            if save_last_time(&time, &mut session, &mut sd_controller).is_ok() {
+                unsaved_time = None;
             }
         }
 
+        if session.buffered() == 0 && rotation.index_outdated() {
+            let _ = save_index(&mut rotation, &mut session, &mut sd_controller);
+        }
+
         writeln!(&mut text, "{}", write_debug).map_err(|_| ())?;
 
         display_text(&mut display, &text)?;
-        delay.delay_ms(200u16);
-        counter += 1;
+
+        // Without a working RTC wake-up, poll the clock once per second
+        if wakeup_ready {
+            sleep_until_rtc_interrupt();
+        } else {
+            delay.delay_ms(1000u16);
+        }
+    }
+}
+
+#[interrupt]
+fn EXTI1() {
+    // Acknowledge the EXTI line, the RTC keeps its INT output low
+    // until the alarm or timer flag is cleared over I2C
+    unsafe { (*pac::EXTI::ptr()).pr.write(|w| w.pr1().set_bit()) };
+    RTC_WAKEUP.store(true, Ordering::Release);
+}
+
+/// Sleep until the RTC interrupt arrives
+fn sleep_until_rtc_interrupt() {
+    while !RTC_WAKEUP.swap(false, Ordering::AcqRel) {
+        // WFI wakes the core up even with interrupts masked, so an interrupt
+        // arriving between the flag check and WFI cannot be missed
+        cortex_m::interrupt::free(|_| {
+            if !RTC_WAKEUP.load(Ordering::Acquire) {
+                cortex_m::asm::wfi();
+            }
+        });
+    }
+}
+
+fn write_next_record(output: &mut dyn Write, local: Option<&DateTime>) {
+    let _ = match local {
+        Some(time) => writeln!(
+            output, "Next record {}:{:02}:{:02}", time.hours(), time.minutes(), time.seconds()
+        ),
+        None => writeln!(output, "No next record"),
+    };
+}
+
+/// Scan the newest log files for the last valid record, returns the next
+/// sequence number and the scan of the active file
+fn recover_sequence<D, T, S>(
+    rotation: &LogRotation,
+    sd_controller: &mut Controller<D, T>,
+    crc: &mut dyn Checksum,
+) -> (u32, S)
+where D: CardDevice<Error = SdMmcError>, T: TimeSource, S: RecordScan + Default {
+    let mut active = S::default();
+
+    for age in 0..rotation.retained_files() {
+        let file_name = match rotation.retained_file(age) {
+            Some(file_name) => file_name,
+            None => break,
+        };
+
+        let mut scan = S::default();
+
+        if read_chunks_from_file(sd_controller, &file_name, &mut |chunk| scan.feed(chunk, crc))
+            .is_err()
+        {
+            continue;
+        }
+
+        scan.finish(crc);
+
+        let next_sequence = scan.last_sequence().map(|_| scan.next_sequence());
+
+        if age == 0 {
+            active = scan;
+        }
+
+        if let Some(next_sequence) = next_sequence {
+            return (next_sequence, active);
+        }
     }
+
+    (0, active)
 }
 
-fn write_record_to_sd_card<SPI, CS, T>(
-    clock: &ClockData,
-    counter: usize,
-    sd_controller: &mut Controller<SdMmcSpi<SPI, CS>, T>,
-) -> ArrayString::<80>
-where
-    SPI: FullDuplex<u8>,
-    CS: OutputPin,
-    T: TimeSource,
-    <SPI as FullDuplex<u8>>::Error: core::fmt::Debug
-{
+/// Append a record to the log file, returns the text shown on the display
+/// as success or error
+fn write_record_to_sd_card<D, C, T>(
+    record: &RecordData,
+    writer: &mut LogWriter,
+    crc: &mut dyn Checksum,
+    rotation: &mut LogRotation,
+    session: &mut LoggerSession,
+    sd_controller: &mut Controller<ClockedCard<D, C>, T>,
+) -> Result<ArrayString::<80>, ArrayString::<80>>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock, T: TimeSource {
     let mut debug = ArrayString::<80>::new();
 
-    let file_name = format_log_file_name(clock);
+    // Untrusted time does not start a new daily file
+    let day = match record.valid {
+        true => Some(record.local.time.days_since_epoch() as u32),
+        false => None,
+    };
+
+    // Header of a started file or a time sync record may precede the record
+    let record_length = writer.record_length(record);
+    let mut data = [0u8; MAX_HEADER + 2*MAX_RECORD];
+
+    let started = start_record(rotation, session, sd_controller, day, record_length);
 
-    let mut file_line = ArrayString::<100>::new();
-    let _ = write_file_line(&mut file_line, clock, counter);
+    let result = match started.map(|started| writer.encode(record, started, crc, &mut data)) {
+        Ok(Ok(length)) => {
+            rotation.account(length.saturating_sub(record_length));
+
+            session.append_bytes(
+                sd_controller, &rotation.active_file(), &data[..length], uptime::seconds()
+            )
+        },
+        Ok(Err(_)) => {
+            let _ = writeln!(&mut debug, "Record encoding failed\n#{}", record.sequence);
+            return Err(debug);
+        },
+        Err(error) => Err(error),
+    };
 
-    match append_to_file(sd_controller, &file_name, &file_line) {
+    match result {
         Ok(_) => {
-            let _ = writeln!(&mut debug, "Line written\n{}\n{}", file_name, file_line);
+            if session.buffered() == 0 {
+                let speed_khz = sd_controller.device().frequency()/1000;
+                let _ = writeln!(&mut debug, "Record written {}kHz", speed_khz);
+            } else {
+                let _ = writeln!(&mut debug, "Record buffered");
+            }
+
+            let date_time_str = format_date_time(record.local);
+            let _ = writeln!(
+                &mut debug, "{}\n#{} {}", rotation.active_file(), record.sequence, date_time_str
+            );
+            Ok(debug)
         },
         Err(error) => {
-            let date_time_str = format_date_time(&clock);
+            let date_time_str = format_date_time(record.local);
             let _ = writeln!(&mut debug, "SD Write failed\n{}\n{}", date_time_str, error);
 
             if let SdWriteError::CannotWriteToOpenedFile(
@@ -166,42 +527,27 @@ where
             ) = error {
                 let _ = writeln!(&mut debug, "{:?}", device_error);
             }
-        }
-    };
 
-    debug
-}
-
-fn write_file_line(
-    output: &mut dyn Write,
-    time: &ClockData,
-    counter: usize,
-) -> Result<(), ()> {
-    writeln!(
-        output,
-        "{}-{}-{} {}:{:02}:{:02} {} A",
-        time.year(), time.month(), time.day(),
-        time.hours(), time.minutes(), time.seconds(),
-        counter
-    ).map_err(|_| ())
+            Err(debug)
+        }
+    }
 }
 
-fn format_log_file_name(
-    time: &ClockData,
-) -> ArrayString<20> {
-    let mut buffer = ArrayString::<20>::new();
-
-    let _ = write!(
-        buffer,
-        "{}{:02}{:02}.log",
-        time.year(), time.month(), time.day(),
-    );
-
-    buffer
+/// Keep the time of the last record for time jump detection after restart
+fn save_last_time<D, T>(
+    time: &DateTime,
+    session: &mut LoggerSession,
+    sd_controller: &mut Controller<D, T>,
+) -> Result<(), ()>
+where D: CardDevice, T: TimeSource {
+    let mut content = ArrayString::<32>::new();
+    write_last_time(&mut content, time).map_err(|_| ())?;
+    session.replace_file(sd_controller, LAST_TIME_FILE_NAME, &content).map_err(|_| ())
 }
 
-fn format_date_time(time: &ClockData) -> ArrayString<20> {
+fn format_date_time(local: &LocalTime) -> ArrayString<20> {
     let mut buffer = ArrayString::<20>::new();
+    let time = &local.time;
 
     let _ = write!(
         &mut buffer,
@@ -1,87 +1,118 @@
+use core::cell::Cell;
 use embedded_sdmmc::{Timestamp, TimeSource};
-use pcf8563::DateTime;
+use rtc_time::{
+    calendar::DateTime,
+    clock::ClockReading,
+    drift::DriftCorrection,
+    fat::FatTimestamp,
+    zone::{LocalTime, TimeZone, UTC},
+};
 
-pub struct ClockData {
-    timestamp: Timestamp,
-    day_of_week: u8,
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClockStatus {
+    /// RTC does not respond
+    Missing,
+    /// RTC runs, but the time cannot be trusted (clock integrity lost
+    /// or out of range register values), until the time is set again
+    TimeInvalid,
+    Valid,
 }
 
-pub const ZERO_TIMESTAMP: Timestamp = Timestamp {
-    year_since_1970: 0,
-    zero_indexed_month: 0,
-    zero_indexed_day: 0,
-    hours: 0,
-    minutes: 0,
-    seconds: 0,
-};
+pub struct ClockData {
+    time: DateTime,
+    status: ClockStatus,
+    correction: DriftCorrection,
+}
 
 impl Default for ClockData {
     fn default() -> Self {
         Self {
-            timestamp: ZERO_TIMESTAMP,
-            day_of_week: 0,
+            time: DateTime::UNIX_EPOCH,
+            status: ClockStatus::Missing,
+            correction: DriftCorrection::default(),
         }
     }
 }
 
-impl TimeSource for ClockData {
-    fn get_timestamp(&self) -> Timestamp {
-        self.timestamp
+impl ClockData {
+    /// Set time read from the selected clock, drift correction is applied
+    pub fn set_reading(&mut self, reading: &ClockReading) {
+        let corrected = self.correction.correct(reading.time.to_unix_seconds());
+        self.time = DateTime::from_unix_seconds(corrected).unwrap_or(reading.time);
+
+        self.status = if reading.valid {
+            ClockStatus::Valid
+        } else {
+            ClockStatus::TimeInvalid
+        };
     }
-}
 
-impl ClockData {
-    pub fn set_from_pcf8563(&mut self, time: DateTime) {
-        self.timestamp = pcf8563_date_time_to_timestamp(time);
-        self.day_of_week = time.weekday;
+    /// Correct RTC drift measured by the calibration app, drift accumulated
+    /// since the first time read after this call is removed
+    pub fn set_drift_correction(&mut self, correction: DriftCorrection) {
+        self.correction = correction;
     }
 
+    /// Forget the time, drift correction is kept
     pub fn reset_to_default(&mut self) {
-        self.timestamp = ZERO_TIMESTAMP;
-        self.day_of_week = 0;
+        *self = Self { correction: self.correction, ..Self::default() };
     }
 
+    /// RTC responds, but the time may still be invalid
     pub fn is_present(&self) -> bool {
-        self.year() > 0
+        self.status != ClockStatus::Missing
     }
 
-    pub fn year(&self) -> u16 {
-        self.timestamp.year_since_1970 as u16 + 1970
+    pub fn is_valid(&self) -> bool {
+        self.status == ClockStatus::Valid
     }
 
-    pub fn month(&self) -> u8 {
-        self.timestamp.zero_indexed_month + 1
+    pub fn status(&self) -> ClockStatus {
+        self.status
     }
 
-    pub fn day(&self) -> u8 {
-        self.timestamp.zero_indexed_day + 1
+    pub fn date_time(&self) -> &DateTime {
+        &self.time
     }
 
-    #[allow(dead_code)]
-    pub fn week_day(&self) -> u8 {
-        self.day_of_week
+    /// Time converted to the given zone, UTC when the conversion overflows
+    pub fn local_time(&self, zone: &TimeZone) -> LocalTime {
+        zone.to_local(&self.time).unwrap_or(LocalTime {
+            time: self.time,
+            offset_minutes: 0,
+            name: UTC.standard_name,
+        })
     }
+}
 
-    pub fn hours(&self) -> u8 {
-        self.timestamp.hours
-    }
+/// Time stamp of the written files, the SD card controller keeps its time
+/// source for its whole life, so it gets a reference updated from the clock
+pub struct FileTime {
+    timestamp: Cell<FatTimestamp>,
+}
 
-    pub fn minutes(&self) -> u8 {
-        self.timestamp.minutes
+impl Default for FileTime {
+    fn default() -> Self {
+        Self { timestamp: Cell::new(FatTimestamp::EPOCH) }
     }
+}
+
+impl FileTime {
+    /// Take the time of the clock, invalid times and times not representable
+    /// in FAT are stored as the earliest FAT time
+    pub fn update(&self, clock: &ClockData) {
+        let timestamp = match clock.is_valid() {
+            true => FatTimestamp::from_date_time(&clock.time).unwrap_or(FatTimestamp::EPOCH),
+            false => FatTimestamp::EPOCH,
+        };
 
-    pub fn seconds(&self) -> u8 {
-        self.timestamp.seconds
+        self.timestamp.set(timestamp);
     }
 }
 
-fn pcf8563_date_time_to_timestamp(time: DateTime) -> Timestamp {
-    Timestamp {
-        year_since_1970: ((time.year as u32) + 2000 - 1970) as u8,
-        zero_indexed_month: time.month - 1,
-        zero_indexed_day: time.day - 1,
-        hours: time.hours,
-        minutes: time.minutes,
-        seconds: time.seconds,
+impl TimeSource for &FileTime {
+    fn get_timestamp(&self) -> Timestamp {
+        let fat = self.timestamp.get();
+        Timestamp::from_fat(fat.date, fat.time)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,26 @@
+use core::sync::atomic::{AtomicU32, Ordering};
+use cortex_m::peripheral::{syst::SystClkSource, SYST};
+use cortex_m_rt::exception;
+use stm32f4xx_hal::rcc::Clocks;
+
+/// Seconds since start, independent on the RTC
+static UPTIME_S: AtomicU32 = AtomicU32::new(0);
+
+/// Count seconds using the SysTick interrupt, clocked from HCLK/8
+/// (the reload value fits into 24 bits for HCLK up to 134 MHz)
+pub fn start(mut syst: SYST, clocks: &Clocks) {
+    syst.set_clock_source(SystClkSource::External);
+    syst.set_reload(clocks.hclk().raw()/8 - 1);
+    syst.clear_current();
+    syst.enable_interrupt();
+    syst.enable_counter();
+}
+
+pub fn seconds() -> u32 {
+    UPTIME_S.load(Ordering::Relaxed)
+}
+
+#[exception]
+fn SysTick() {
+    UPTIME_S.fetch_add(1, Ordering::Relaxed);
+}
@@ -0,0 +1,85 @@
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use pcf8563::{Control, Error, TimerFreq, PCF8563};
+use rtc_time::alarm::{Alarm, Countdown, Wakeup};
+
+/// Configure the PCF8563 to pull its INT output low at the next wake-up,
+/// pending alarm and timer flags are cleared
+pub fn program_wakeup<I2C, E>(
+    rtc: &mut PCF8563<I2C>,
+    wakeup: &Wakeup,
+) -> Result<(), Error<E>>
+where I2C: Write<Error = E> + WriteRead<Error = E> {
+    acknowledge_wakeup(rtc)?;
+
+    match wakeup {
+        Wakeup::Alarm(alarm) => {
+            rtc.control_timer_interrupt(Control::Off)?;
+            program_alarm(rtc, alarm)?;
+            rtc.control_alarm_interrupt(Control::On)
+        },
+        Wakeup::Countdown(countdown) => {
+            rtc.disable_all_alarms()?;
+            rtc.control_alarm_interrupt(Control::Off)?;
+            program_countdown(rtc, countdown)?;
+            rtc.control_timer_interrupt(Control::On)
+        },
+    }
+}
+
+/// Clear alarm and timer flags, releasing the INT output
+pub fn acknowledge_wakeup<I2C, E>(rtc: &mut PCF8563<I2C>) -> Result<(), Error<E>>
+where I2C: Write<Error = E> + WriteRead<Error = E> {
+    rtc.clear_alarm_flag()?;
+    rtc.clear_timer_flag()
+}
+
+fn program_alarm<I2C, E>(rtc: &mut PCF8563<I2C>, alarm: &Alarm) -> Result<(), Error<E>>
+where I2C: Write<Error = E> + WriteRead<Error = E> {
+    match alarm.minute {
+        Some(minute) => {
+            rtc.set_alarm_minutes(minute)?;
+            rtc.control_alarm_minutes(Control::On)?;
+        },
+        None => rtc.control_alarm_minutes(Control::Off)?,
+    }
+
+    match alarm.hour {
+        Some(hour) => {
+            rtc.set_alarm_hours(hour)?;
+            rtc.control_alarm_hours(Control::On)?;
+        },
+        None => rtc.control_alarm_hours(Control::Off)?,
+    }
+
+    match alarm.day {
+        Some(day) => {
+            rtc.set_alarm_day(day)?;
+            rtc.control_alarm_day(Control::On)?;
+        },
+        None => rtc.control_alarm_day(Control::Off)?,
+    }
+
+    match alarm.weekday {
+        Some(weekday) => {
+            rtc.set_alarm_weekday(weekday)?;
+            rtc.control_alarm_weekday(Control::On)
+        },
+        None => rtc.control_alarm_weekday(Control::Off),
+    }
+}
+
+fn program_countdown<I2C, E>(
+    rtc: &mut PCF8563<I2C>,
+    countdown: &Countdown,
+) -> Result<(), Error<E>>
+where I2C: Write<Error = E> + WriteRead<Error = E> {
+    let (frequency, value) = match *countdown {
+        Countdown::Seconds(value) => (TimerFreq::Timer_1Hz, value),
+        Countdown::Minutes(value) => (TimerFreq::Timer_1_60Hz, value),
+    };
+
+    rtc.control_timer(Control::Off)?;
+    rtc.set_timer_frequency(frequency)?;
+    rtc.set_timer(value)?;
+    rtc.control_timer(Control::On)
+}
@@ -0,0 +1,383 @@
+#![no_std]
+#![no_main]
+
+use arrayvec::ArrayString;
+use core::{fmt::Write, panic::PanicInfo};
+use cortex_m_rt::{entry};
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    mono_font::{MonoTextStyle, ascii::{FONT_5X8, FONT_7X13_BOLD}},
+    text::Text,
+    primitives::{PrimitiveStyle, Rectangle, Circle, Line}
+};
+use embedded_hal::{
+    spi, spi::FullDuplex, digital::v2::OutputPin
+};
+use embedded_sdmmc::{
+    Controller, SdMmcSpi, TimeSource, Timestamp, VolumeIdx, Mode
+};
+use maze_game::{Ball, Cell, Level, Outcome, Tilt, CELL_SIZE, BALL_RADIUS};
+use pcf8563::{PCF8563, DateTime};
+use sh1106::{prelude::*, Builder, interface::DisplayInterface};
+use stm32f4xx_hal::{prelude::*, pac, gpio::NoPin, i2c::I2c};
+use adxl343::{Adxl343, accelerometer::{RawAccelerometer, vector::{I16x3}}, DataFormatFlags};
+
+/// Turn on onboard LED in case of panic
+#[inline(never)]
+#[panic_handler]
+fn on_panic(_info: &PanicInfo) -> ! {
+    let dp = unsafe { pac::Peripherals::steal() };
+    let gpioc = dp.GPIOC.split();
+    let mut led = gpioc.pc13.into_push_pull_output();
+    let _ = led.set_low();
+    loop { }
+}
+
+#[entry]
+fn main() -> ! {
+    if let (Some(dp), Some(cp)) = (
+        pac::Peripherals::take(),
+        cortex_m::peripheral::Peripherals::take(),
+    ) {
+        run(dp, cp).unwrap();
+        loop {}
+    } else {
+        loop {}
+    }
+}
+
+/// Accelerometer raw value corresponding to 1 g (see the accelerometer demo)
+const CONVERT_G: i32 = 16384;
+/// Number of SD card level files tried (`MAZE1.TXT` .. `MAZE9.TXT`)
+const MAX_LEVELS: usize = 9;
+const STATUS_LINE: i32 = 63;
+
+fn run(
+    dp: pac::Peripherals,
+    _cp: cortex_m::Peripherals,
+) -> Result<(), ()> {
+    let rcc = dp.RCC.constrain();
+
+    let clocks = rcc.cfgr.use_hse(25.MHz()).sysclk(100.MHz()).hclk(25.MHz()).freeze();
+
+    let gpioa = dp.GPIOA.split();
+    let gpiob = dp.GPIOB.split();
+
+    let dc = gpiob.pb6.into_push_pull_output();
+
+    let spi = dp.SPI2.spi(
+        (gpiob.pb13, NoPin, gpiob.pb15),
+        spi::MODE_0,
+        4000.kHz(),
+        &clocks,
+    );
+
+    let mut display_reset = gpiob.pb14.into_push_pull_output();
+    let mut delay = dp.TIM5.delay_us(&clocks);
+
+    let mut display: GraphicsMode<_> = Builder::new()
+        .with_rotation(DisplayRotation::Rotate180)
+        .with_size(DisplaySize::Display128x64)
+        .connect_spi(spi, dc, sh1106::builder::NoOutputPin::new())
+        .into();
+
+    display.reset(&mut display_reset, &mut delay).map_err(|_| ())?;
+    display.init().map_err(|_| ())?;
+
+    display_text(&mut display, "Tilt maze\nLoading ...")?;
+
+    let i2c = I2c::new(
+        dp.I2C1,
+        (
+            gpiob.pb8.into_alternate().set_open_drain(),
+            gpiob.pb9.into_alternate().set_open_drain(),
+        ),
+        400.kHz(),
+        &clocks,
+    );
+
+    // Accelerometer and real time clock share the I2C1 bus
+    let i2c_bus = shared_bus::BusManagerSimple::new(i2c);
+
+    let format: DataFormatFlags = DataFormatFlags::RANGE_HI;
+
+    let mut accelerometer = match Adxl343::new_with_data_format(i2c_bus.acquire_i2c(), format) {
+        Ok(device) => device,
+        Err(_error) => stop_on_error(
+            display, "Tilt maze\nAccelerometer\nnot found"
+        )
+    };
+
+    let mut rtc = PCF8563::new(i2c_bus.acquire_i2c());
+
+    let sd_spi = dp.SPI1.spi(
+        (gpioa.pa5, gpioa.pa6, gpioa.pa7),
+        spi::MODE_0,
+        400.kHz(),
+        &clocks,
+    );
+
+    let sd_cs = gpiob.pb0.into_push_pull_output();
+    let mut sd_controller = Controller::new(SdMmcSpi::new(sd_spi, sd_cs), Clock {});
+
+    let mut level_index = 0;
+    let mut level = load_level(&mut sd_controller, level_index).ok_or(())?;
+    let mut ball = Ball::at_start(&level);
+    let mut start_time = rtc.get_datetime().ok();
+
+    loop {
+        let values = match accelerometer.accel_raw() {
+            Ok(raw_values) => raw_values,
+            Err(_error) => I16x3::new(0, 0, 0),
+        };
+
+        let now = rtc.get_datetime().ok();
+        let elapsed = elapsed_seconds(start_time, now);
+
+        match ball.step(&level, tilt_from_raw(values)) {
+            Outcome::Rolling => {},
+            Outcome::FellIntoHole => ball = Ball::at_start(&level),
+            Outcome::ReachedGoal => {
+                let mut text = ArrayString::<60>::new();
+                let _ = writeln!(&mut text, "Level {} done", level_index + 1);
+                let _ = write_elapsed(&mut text, elapsed);
+                display_text(&mut display, &text)?;
+                delay.delay_ms(3000u16);
+
+                (level_index, level) = match load_level(&mut sd_controller, level_index + 1) {
+                    Some(next_level) => (level_index + 1, next_level),
+                    None => (0, load_level(&mut sd_controller, 0).ok_or(())?),
+                };
+
+                ball = Ball::at_start(&level);
+                start_time = rtc.get_datetime().ok();
+                continue;
+            },
+        }
+
+        display.clear();
+        render_level(&mut display, &level)?;
+        render_ball(&mut display, &ball)?;
+        render_status(&mut display, level_index, elapsed)?;
+        display.flush().map_err(|_| ())?;
+    }
+}
+
+/// Map accelerometer axes to display axes, display is rotated by 180 degrees
+fn tilt_from_raw(values: I16x3) -> Tilt {
+    Tilt {
+        x: -(values.x as i32)*1000/CONVERT_G,
+        y: (values.y as i32)*1000/CONVERT_G,
+    }
+}
+
+/// Load level `MAZE<n>.TXT` from the SD card root directory, fall back
+/// to the built in levels when card or file is not available
+fn load_level<SPI, CS, T>(
+    controller: &mut Controller<SdMmcSpi<SPI, CS>, T>,
+    index: usize,
+) -> Option<Level>
+where
+    SPI: FullDuplex<u8>,
+    CS: OutputPin,
+    T: TimeSource,
+    <SPI as FullDuplex<u8>>::Error: core::fmt::Debug
+{
+    if index >= MAX_LEVELS {
+        return None;
+    }
+
+    let mut buffer = [0u8; 256];
+
+    if let Some(size) = read_level_file(controller, index, &mut buffer) {
+        if let Ok(level) = Level::parse(&buffer[..size]) {
+            return Some(level);
+        }
+    }
+
+    Level::builtin(index)
+}
+
+/// Read level file into `buffer` and return the number of bytes read
+fn read_level_file<SPI, CS, T>(
+    controller: &mut Controller<SdMmcSpi<SPI, CS>, T>,
+    index: usize,
+    buffer: &mut [u8],
+) -> Option<usize>
+where
+    SPI: FullDuplex<u8>,
+    CS: OutputPin,
+    T: TimeSource,
+    <SPI as FullDuplex<u8>>::Error: core::fmt::Debug
+{
+    let mut file_name = ArrayString::<12>::new();
+    let _ = write!(&mut file_name, "MAZE{}.TXT", index + 1);
+
+    controller.device().init().ok()?;
+
+    let result = match controller.get_volume(VolumeIdx(0)) {
+        Ok(mut volume) => match controller.open_root_dir(&volume) {
+            Ok(dir) => {
+                let size = match controller.open_file_in_dir(
+                    &mut volume, &dir, &file_name, Mode::ReadOnly
+                ) {
+                    Ok(mut file) => {
+                        let size = controller.read(&volume, &mut file, buffer).ok();
+                        let _ = controller.close_file(&volume, file);
+                        size
+                    },
+                    Err(_) => None,
+                };
+                controller.close_dir(&volume, dir);
+                size
+            },
+            Err(_) => None,
+        },
+        Err(_) => None,
+    };
+
+    controller.device().deinit();
+    result
+}
+
+/// Seconds elapsed between two RTC readings, wrapping around midnight
+fn elapsed_seconds(start: Option<DateTime>, now: Option<DateTime>) -> Option<u32> {
+    match (start, now) {
+        (Some(start), Some(now)) => {
+            const DAY: u32 = 24*3600;
+            Some((seconds_of_day(&now) + DAY - seconds_of_day(&start)) % DAY)
+        },
+        _ => None,
+    }
+}
+
+fn seconds_of_day(time: &DateTime) -> u32 {
+    time.hours as u32*3600 + time.minutes as u32*60 + time.seconds as u32
+}
+
+fn write_elapsed(output: &mut dyn Write, elapsed: Option<u32>) -> core::fmt::Result {
+    match elapsed {
+        Some(seconds) => write!(output, "{:02}:{:02}", seconds/60, seconds%60),
+        None => write!(output, "--:--"),
+    }
+}
+
+fn render_level<T>(
+    display: &mut GraphicsMode<T>,
+    level: &Level,
+) -> Result<(), ()>
+where T: DisplayInterface {
+    let wall_style = PrimitiveStyle::with_fill(BinaryColor::On);
+    let outline_style = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+    let cell_size = Size::new(CELL_SIZE as u32, CELL_SIZE as u32);
+
+    for row in 0..level.height() as i32 {
+        for column in 0..level.width() as i32 {
+            let corner = Point::new(column*CELL_SIZE, row*CELL_SIZE);
+
+            match level.cell(column, row) {
+                Cell::Free => {},
+                Cell::Wall => {
+                    Rectangle::new(corner, cell_size)
+                        .into_styled(wall_style)
+                        .draw(display)
+                        .map_err(|_| ())?;
+                },
+                Cell::Hole => {
+                    Circle::new(corner + Point::new(1, 1), CELL_SIZE as u32 - 2)
+                        .into_styled(outline_style)
+                        .draw(display)
+                        .map_err(|_| ())?;
+                },
+                Cell::Goal => {
+                    let far_corner = corner + Point::new(CELL_SIZE - 1, CELL_SIZE - 1);
+
+                    Rectangle::new(corner, cell_size)
+                        .into_styled(outline_style)
+                        .draw(display)
+                        .map_err(|_| ())?;
+
+                    Line::new(corner, far_corner)
+                        .into_styled(outline_style)
+                        .draw(display)
+                        .map_err(|_| ())?;
+                },
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn render_ball<T>(
+    display: &mut GraphicsMode<T>,
+    ball: &Ball,
+) -> Result<(), ()>
+where T: DisplayInterface {
+    let (x, y) = ball.position();
+    let diameter = 2*BALL_RADIUS as u32 + 1;
+
+    Circle::with_center(Point::new(x, y), diameter)
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)
+        .map_err(|_| ())?;
+
+    Ok(())
+}
+
+fn render_status<T>(
+    display: &mut GraphicsMode<T>,
+    level_index: usize,
+    elapsed: Option<u32>,
+) -> Result<(), ()>
+where T: DisplayInterface {
+    let style = MonoTextStyle::new(&FONT_5X8, BinaryColor::On);
+    let mut text = ArrayString::<30>::new();
+    let _ = write!(&mut text, "Level {}  Time ", level_index + 1);
+    let _ = write_elapsed(&mut text, elapsed);
+    Text::new(&text, Point::new(0, STATUS_LINE), style).draw(display).map_err(|_| ())?;
+    Ok(())
+}
+
+struct Clock;
+
+impl TimeSource for Clock {
+    // Levels are only read, fake time source that returns 1. 1. 1970 0:00:00
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 0,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+fn display_text<T>(
+    display: &mut GraphicsMode<T>,
+    message: &str
+) -> Result<(), ()>
+where T: DisplayInterface {
+    display.clear();
+
+    let style = MonoTextStyle::new(&FONT_7X13_BOLD, BinaryColor::On);
+    let position = Point::new(0, 12);
+    Text::new(&message, position, style).draw(display).map_err(|_| ())?;
+    display.flush().map_err(|_| ())
+}
+
+fn stop_on_error<T>(
+    mut display: GraphicsMode<T>,
+    message: &str
+) -> !
+where T: DisplayInterface {
+    let style = MonoTextStyle::new(&FONT_7X13_BOLD, BinaryColor::On);
+    let position = Point::new(0, 12);
+    display.clear();
+    let _ = Text::new(&message, position, style).draw(&mut display);
+    let _ = display.flush();
+    loop {}
+}
@@ -0,0 +1,89 @@
+use core::cell::RefCell;
+use cortex_m::interrupt::{free, Mutex};
+use stm32f4xx_hal::{
+    pac::{self, interrupt, EXTI, TIM3},
+    gpio::{Edge, ExtiPin, Input, PA8},
+    syscfg::SysCfg,
+};
+
+/// DCF77 receiver modules usually output high level during the carrier
+/// reduction, set to false for modules with an inverted output
+const PULSE_ACTIVE_HIGH: bool = true;
+
+/// TIM3 prescaler giving a 1 kHz count from the 25 MHz timer clock
+const TIMER_PRESCALER: u32 = 25_000 - 1;
+
+struct Receiver {
+    pin: PA8<Input>,
+    /// Last value of the 16 bit hardware counter and its 32 bit extension
+    last_count: u16,
+    milliseconds: u32,
+    pulse_start: Option<u32>,
+}
+
+static DCF_RECEIVER: Mutex<RefCell<Option<Receiver>>> = Mutex::new(RefCell::new(None));
+
+/// Start time and length of the last pulse in milliseconds, not yet taken
+/// by the main loop
+static DCF_PULSE: Mutex<RefCell<Option<(u32, u32)>>> = Mutex::new(RefCell::new(None));
+
+/// Measure pulses on both edges of the receiver output using the EXTI
+/// interrupt and TIM3 counting milliseconds
+pub fn start(mut pin: PA8<Input>, timer: TIM3, syscfg: &mut SysCfg, exti: &mut EXTI) {
+    unsafe {
+        (*pac::RCC::ptr()).apb1enr.modify(|_, w| w.tim3en().set_bit());
+
+        timer.psc.write(|w| w.bits(TIMER_PRESCALER));
+        timer.arr.write(|w| w.bits(0xffff));
+        // Load the prescaler value
+        timer.egr.write(|w| w.bits(0b1));
+        timer.cr1.write(|w| w.bits(0b1));
+    }
+
+    pin.make_interrupt_source(syscfg);
+    pin.trigger_on_edge(exti, Edge::RisingFalling);
+    pin.enable_interrupt(exti);
+    let interrupt = pin.interrupt();
+
+    free(|cs| {
+        DCF_RECEIVER.borrow(cs).replace(Some(Receiver {
+            pin,
+            last_count: 0,
+            milliseconds: 0,
+            pulse_start: None,
+        }));
+    });
+
+    unsafe { pac::NVIC::unmask(interrupt); }
+}
+
+/// Take the last measured pulse as start time and length in milliseconds
+pub fn take_pulse() -> Option<(u32, u32)> {
+    free(|cs| DCF_PULSE.borrow(cs).take())
+}
+
+#[interrupt]
+fn EXTI9_5() {
+    free(|cs| {
+        let mut receiver = DCF_RECEIVER.borrow(cs).borrow_mut();
+
+        if let Some(receiver) = receiver.as_mut() {
+            receiver.pin.clear_interrupt_pending_bit();
+
+            // Edges come at least once per two seconds while receiving,
+            // so the 16 bit counter cannot overflow unnoticed
+            let count = unsafe { (*pac::TIM3::ptr()).cnt.read().bits() } as u16;
+            receiver.milliseconds = receiver.milliseconds
+                .wrapping_add(count.wrapping_sub(receiver.last_count) as u32);
+            receiver.last_count = count;
+
+            let now = receiver.milliseconds;
+
+            if receiver.pin.is_high() == PULSE_ACTIVE_HIGH {
+                receiver.pulse_start = Some(now);
+            } else if let Some(start) = receiver.pulse_start.take() {
+                DCF_PULSE.borrow(cs).replace(Some((start, now.wrapping_sub(start))));
+            }
+        }
+    });
+}
@@ -0,0 +1,222 @@
+use core::{f32::consts::PI, fmt::Write};
+use arrayvec::ArrayString;
+use embedded_graphics::{
+    pixelcolor::BinaryColor,
+    prelude::*,
+    mono_font::{MonoTextStyle, ascii::{FONT_6X10, FONT_10X20}}, text::Text,
+    primitives::{Circle, Line, PrimitiveStyle, Rectangle}
+};
+use micromath::F32Ext;
+use rtc_time::calendar::{DateTime, DAYS};
+
+/// Clock face selected by a short press of the KEY button
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClockFace {
+    Text,
+    Digits,
+    Analog,
+}
+
+impl ClockFace {
+    pub fn next(self) -> Self {
+        match self {
+            ClockFace::Text => ClockFace::Digits,
+            ClockFace::Digits => ClockFace::Analog,
+            ClockFace::Analog => ClockFace::Text,
+        }
+    }
+}
+
+/// Draw the time using the selected face, the target is expected to be
+/// cleared, lost clock integrity replaces the week day by a warning
+pub fn draw_face<D>(
+    target: &mut D,
+    face: ClockFace,
+    datetime: &DateTime,
+    voltage_low: bool,
+) -> Result<(), D::Error>
+where D: DrawTarget<Color = BinaryColor> {
+    match face {
+        ClockFace::Text => draw_text_face(target, datetime, voltage_low),
+        ClockFace::Digits => draw_digits_face(target, datetime, voltage_low),
+        ClockFace::Analog => draw_analog_face(target, datetime, voltage_low),
+    }
+}
+
+fn draw_text_face<D>(
+    target: &mut D,
+    datetime: &DateTime,
+    voltage_low: bool,
+) -> Result<(), D::Error>
+where D: DrawTarget<Color = BinaryColor> {
+    let mut text = ArrayString::<40>::new();
+
+    let _ = write!(
+        &mut text,
+        "{:02}.{:02}.{}\n{:02}:{:02}:{:02}\n{}",
+        datetime.day(),
+        datetime.month(),
+        datetime.year(),
+        datetime.hours(),
+        datetime.minutes(),
+        datetime.seconds(),
+        if voltage_low { "TIME INVALID" } else { DAYS[datetime.weekday() as usize] },
+    );
+
+    let style = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+    Text::new(&text, Point::new(0, 14), style).draw(target)?;
+    Ok(())
+}
+
+const DIGIT_WIDTH: i32 = 22;
+const DIGIT_HEIGHT: i32 = 40;
+const SEGMENT: i32 = 4;
+
+/// Segments a - g (bits 0 - 6) lit for digits 0 - 9
+const DIGIT_SEGMENTS: [u8; 10] = [
+    0x3f, 0x06, 0x5b, 0x4f, 0x66, 0x6d, 0x7d, 0x07, 0x7f, 0x6f
+];
+
+/// Hours and minutes as big seven segment digits, the colon blinks
+/// with seconds, date and seconds are written below
+fn draw_digits_face<D>(
+    target: &mut D,
+    datetime: &DateTime,
+    voltage_low: bool,
+) -> Result<(), D::Error>
+where D: DrawTarget<Color = BinaryColor> {
+    const TOP: i32 = 4;
+
+    let digits = [
+        (8, datetime.hours()/10),
+        (34, datetime.hours() % 10),
+        (70, datetime.minutes()/10),
+        (96, datetime.minutes() % 10),
+    ];
+
+    for (left, digit) in digits {
+        draw_digit(target, Point::new(left, TOP), digit)?;
+    }
+
+    if datetime.seconds() % 2 == 0 {
+        let fill = PrimitiveStyle::with_fill(BinaryColor::On);
+        let dot = Size::new(SEGMENT as u32, SEGMENT as u32);
+        Rectangle::new(Point::new(61, TOP + 10), dot).into_styled(fill).draw(target)?;
+        Rectangle::new(Point::new(61, TOP + 26), dot).into_styled(fill).draw(target)?;
+    }
+
+    let mut text = ArrayString::<30>::new();
+
+    let _ = if voltage_low {
+        write!(&mut text, "TIME INVALID    {:02}", datetime.seconds())
+    } else {
+        write!(
+            &mut text, "{:02}.{:02}.{}      {:02}",
+            datetime.day(), datetime.month(), datetime.year(), datetime.seconds()
+        )
+    };
+
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    Text::new(&text, Point::new(8, 60), style).draw(target)?;
+    Ok(())
+}
+
+fn draw_digit<D>(
+    target: &mut D,
+    origin: Point,
+    digit: u8,
+) -> Result<(), D::Error>
+where D: DrawTarget<Color = BinaryColor> {
+    const HALF: i32 = DIGIT_HEIGHT/2;
+    const VERTICAL: i32 = HALF - SEGMENT - SEGMENT/2;
+    const HORIZONTAL: i32 = DIGIT_WIDTH - 2*SEGMENT;
+
+    // Top left corner and size of segments a - g
+    let segments = [
+        ((SEGMENT, 0), (HORIZONTAL, SEGMENT)),
+        ((DIGIT_WIDTH - SEGMENT, SEGMENT), (SEGMENT, VERTICAL)),
+        ((DIGIT_WIDTH - SEGMENT, HALF + SEGMENT/2), (SEGMENT, VERTICAL)),
+        ((SEGMENT, DIGIT_HEIGHT - SEGMENT), (HORIZONTAL, SEGMENT)),
+        ((0, HALF + SEGMENT/2), (SEGMENT, VERTICAL)),
+        ((0, SEGMENT), (SEGMENT, VERTICAL)),
+        ((SEGMENT, HALF - SEGMENT/2), (HORIZONTAL, SEGMENT)),
+    ];
+
+    let lit = DIGIT_SEGMENTS[digit as usize % DIGIT_SEGMENTS.len()];
+    let fill = PrimitiveStyle::with_fill(BinaryColor::On);
+
+    for (index, ((x, y), (width, height))) in segments.into_iter().enumerate() {
+        if lit & (1 << index) != 0 {
+            Rectangle::new(origin + Point::new(x, y), Size::new(width as u32, height as u32))
+                .into_styled(fill)
+                .draw(target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Analog dial on the left half, date, week day and digital time
+/// on the right half
+fn draw_analog_face<D>(
+    target: &mut D,
+    datetime: &DateTime,
+    voltage_low: bool,
+) -> Result<(), D::Error>
+where D: DrawTarget<Color = BinaryColor> {
+    const RADIUS: i32 = 30;
+    let center = Point::new(31, 32);
+    let thin = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+
+    Circle::with_center(center, (2*RADIUS + 1) as u32).into_styled(thin).draw(target)?;
+
+    for hour in 0..12 {
+        let fraction = hour as f32/12.0;
+        Line::new(
+            dial_point(center, RADIUS - 4, fraction),
+            dial_point(center, RADIUS - 1, fraction),
+        ).into_styled(thin).draw(target)?;
+    }
+
+    let seconds = datetime.seconds() as f32;
+    let minutes = datetime.minutes() as f32 + seconds/60.0;
+    let hours = (datetime.hours() % 12) as f32 + minutes/60.0;
+
+    let hands = [
+        (hours/12.0, 15, 3),
+        (minutes/60.0, 24, 2),
+        (seconds/60.0, 28, 1),
+    ];
+
+    for (fraction, length, width) in hands {
+        Line::new(center, dial_point(center, length, fraction))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, width))
+            .draw(target)?;
+    }
+
+    let mut text = ArrayString::<40>::new();
+
+    let _ = write!(
+        &mut text,
+        "{:02}.{:02}.{}\n\n{}\n\n{:02}:{:02}:{:02}",
+        datetime.day(), datetime.month(), datetime.year(),
+        if voltage_low { "INVALID" } else { DAYS[datetime.weekday() as usize] },
+        datetime.hours(), datetime.minutes(), datetime.seconds(),
+    );
+
+    let style = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    Text::new(&text, Point::new(68, 12), style).draw(target)?;
+    Ok(())
+}
+
+/// Point at `length` pixels from the center in the direction given
+/// by the fraction of the full turn clockwise from 12 o'clock
+fn dial_point(center: Point, length: i32, fraction: f32) -> Point {
+    let angle = 2.0*PI*fraction;
+    let length = length as f32;
+
+    center + Point::new(
+        (length*angle.sin()).round() as i32,
+        -(length*angle.cos()).round() as i32,
+    )
+}
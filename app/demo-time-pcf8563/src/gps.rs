@@ -0,0 +1,59 @@
+use core::cell::RefCell;
+use arrayvec::ArrayString;
+use cortex_m::interrupt::{free, Mutex};
+use embedded_hal::serial::Read;
+use rtc_time::nmea::{SentenceReader, MAX_SENTENCE_LENGTH};
+use stm32f4xx_hal::{pac::{self, interrupt, USART2}, serial::Rx};
+
+pub type Sentence = ArrayString<MAX_SENTENCE_LENGTH>;
+
+/// GPS receiver, owned by the interrupt handler after `start`
+static GPS_RX: Mutex<RefCell<Option<Rx<USART2>>>> = Mutex::new(RefCell::new(None));
+
+/// Sentence being received by the interrupt handler
+static GPS_READER: Mutex<RefCell<Option<SentenceReader>>> = Mutex::new(RefCell::new(None));
+
+/// Last complete sentence not yet taken by the main loop, a newer sentence
+/// replaces it
+static GPS_SENTENCE: Mutex<RefCell<Option<Sentence>>> = Mutex::new(RefCell::new(None));
+
+/// Start receiving sentences in the USART2 interrupt
+pub fn start(mut rx: Rx<USART2>) {
+    rx.listen();
+
+    free(|cs| {
+        GPS_RX.borrow(cs).replace(Some(rx));
+        GPS_READER.borrow(cs).replace(Some(SentenceReader::default()));
+    });
+
+    unsafe { pac::NVIC::unmask(pac::Interrupt::USART2); }
+}
+
+/// Take the last received sentence
+pub fn take_sentence() -> Option<Sentence> {
+    free(|cs| GPS_SENTENCE.borrow(cs).take())
+}
+
+#[interrupt]
+fn USART2() {
+    free(|cs| {
+        let mut rx = GPS_RX.borrow(cs).borrow_mut();
+        let mut reader = GPS_READER.borrow(cs).borrow_mut();
+
+        if let (Some(rx), Some(reader)) = (rx.as_mut(), reader.as_mut()) {
+            loop {
+                match rx.read() {
+                    Ok(byte) => if let Some(text) = reader.feed(byte) {
+                        GPS_SENTENCE.borrow(cs).replace(Sentence::from(text).ok());
+                    },
+                    Err(nb::Error::WouldBlock) => break,
+                    // Overrun or framing error, the sentence is damaged
+                    Err(nb::Error::Other(_)) => {
+                        reader.reset();
+                        break;
+                    },
+                }
+            }
+        }
+    });
+}
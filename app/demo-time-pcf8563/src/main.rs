@@ -1,19 +1,38 @@
 #![no_std]
 #![no_main]
 
+mod dcf;
+mod face;
+mod gps;
+
 use core::{fmt::Write};
 use arrayvec::ArrayString;
 use cortex_m_rt::{entry};
 use embedded_graphics::{
     pixelcolor::BinaryColor,
     prelude::*,
-    mono_font::{MonoTextStyle, ascii::FONT_10X20}, text::Text
+    mono_font::{MonoTextStyle, ascii::FONT_10X20}, text::Text,
+    primitives::{PrimitiveStyle, Rectangle}
 };
-use embedded_hal::spi;
+use embedded_hal::{spi, blocking::i2c::{Write as I2cWrite, WriteRead}};
+use face::{draw_face, ClockFace};
 use panic_halt as _;
-use pcf8563::{PCF8563, DateTime};
+use pcf8563::PCF8563;
+use rtc_time::{
+    calendar::{DateTime, DAYS},
+    console::{parse_command, Command, CommandError, LineBuffer, LineEvent, HELP},
+    dcf77::{ConfirmingDecoder, DecodedMinute},
+    editor::{Field, TimeEditor},
+    nmea::{needs_update, parse_sentence, NmeaError},
+    rtc::{
+        century_flag, from_pcf8563, read_century, read_voltage_low, to_pcf8563, write_century,
+        RtcConvertError,
+    },
+    zone::{TimeZone, CENTRAL_EUROPE},
+};
 use sh1106::{prelude::*, Builder, interface::DisplayInterface};
 use stm32f4xx_hal::{prelude::*, pac, gpio::NoPin, i2c::I2c};
+use ui::button::{Button, ButtonEvent};
 
 #[entry]
 fn main() -> ! {
@@ -36,6 +55,7 @@ fn run(
 
     let clocks = rcc.cfgr.use_hse(25.MHz()).sysclk(100.MHz()).hclk(25.MHz()).freeze();
 
+    let gpioa = dp.GPIOA.split();
     let gpiob = dp.GPIOB.split();
 
     let dc = gpiob.pb6.into_push_pull_output();
@@ -71,68 +91,378 @@ fn run(
 
     display_text(&mut display, "Starting up...").unwrap();
 
-    let mut rtc = PCF8563::new(i2c);
-
-    /*
-    // Set date and time
-    let now = DateTime {
-        year: 22,
-        month: 11,
-        weekday: 5,
-        day: 18,
-        hours: 11,
-        minutes: 28,
-        seconds: 00,
-    };
+    // PCF8563 driver does not expose the clock integrity and century flags,
+    // they are accessed directly using a second I2C bus proxy
+    let i2c_bus = shared_bus::BusManagerSimple::new(i2c);
+    let mut rtc = PCF8563::new(i2c_bus.acquire_i2c());
+    let mut rtc_registers = i2c_bus.acquire_i2c();
+
+    let serial = dp.USART1.serial(
+        (gpioa.pa9.into_alternate(), gpioa.pa10.into_alternate()),
+        115200.bps(),
+        &clocks,
+    ).map_err(|_| ())?;
+
+    let (mut console_tx, mut console_rx) = serial.split();
+    let mut line = LineBuffer::<40>::default();
 
-    rtc.set_datetime(&now).unwrap();
-     */
+    let _ = write!(console_tx, "PCF8563 clock console\r\n{}", HELP);
+
+    // GPS module sends NMEA sentences, only its TX line is connected
+    let gps_rx = dp.USART2.rx(gpioa.pa3.into_alternate(), 9600.bps(), &clocks)
+        .map_err(|_| ())?;
+    gps::start(gps_rx);
+
+    // DCF77 receiver module output, pulses are measured in the EXTI interrupt
+    let mut syscfg = dp.SYSCFG.constrain();
+    let mut exti = dp.EXTI;
+    dcf::start(gpioa.pa8.into_pull_up_input(), dp.TIM3, &mut syscfg, &mut exti);
+    let mut dcf_decoder = ConfirmingDecoder::default();
+
+    // On board KEY button, long press enters time editing mode
+    let key = gpioa.pa0.into_pull_up_input();
+    let mut key_button = Button::default();
+    let mut editor: Option<TimeEditor> = None;
+    let mut face = ClockFace::Text;
+
+    // Serial console is polled continuously, button is sampled each time
+    // the counter elapses, RTC is read every DISPLAY_TICKS and the display
+    // is redrawn only when the shown time changes
+    let mut shown_time: Option<DateTime> = None;
+    let mut tick_timer = dp.TIM2.counter_ms(&clocks);
+    tick_timer.start(TICK_MS.millis()).map_err(|_| ())?;
+    let mut ticks: u32 = 0;
 
     loop {
-        display.clear();
+        if let Ok(byte) = console_rx.read() {
+            match line.push(byte) {
+                LineEvent::Stored(byte) => {
+                    let _ = console_tx.write_char(byte as char);
+                },
+                LineEvent::Erased => {
+                    let _ = console_tx.write_str("\x08 \x08");
+                },
+                LineEvent::Complete => {
+                    let _ = console_tx.write_str("\r\n");
+                    execute_line(line.line(), &mut rtc, &mut rtc_registers, &mut console_tx);
+                    line.clear();
+                },
+                LineEvent::Ignored => {},
+            }
+        }
 
-        let mut text = ArrayString::<40>::new();
+        // Time is not changed under the user editing it
+        if let (Some(sentence), None) = (gps::take_sentence(), &editor) {
+            sync_from_gps(&sentence, &mut rtc, &mut rtc_registers, &mut console_tx);
+        }
 
-        match rtc.get_datetime() {
-            Ok(datetime) => {
-                render_date(&mut text, datetime).unwrap();
+        if let (Some(pulse), None) = (dcf::take_pulse(), &editor) {
+            sync_from_dcf77(
+                pulse, &mut dcf_decoder,
+                &mut rtc, &mut rtc_registers, &mut console_tx
+            );
+        }
+
+        if tick_timer.wait().is_err() {
+            continue;
+        }
+
+        ticks = ticks.wrapping_add(1);
+
+        let key_event = key_button.update(key.is_low(), ticks.wrapping_mul(TICK_MS));
+
+        if let Some(event) = key_event {
+            let message = handle_key(event, &mut editor, &mut face, &mut rtc, &mut rtc_registers);
+
+            if let Some(message) = message {
+                display_text(&mut display, message)?;
+                delay.delay_ms(1000u16);
+            }
+
+            shown_time = None;
+        }
+
+        if key_event.is_none() && ticks % DISPLAY_TICKS != 0 {
+            continue;
+        }
+
+        match editor {
+            Some(ref editor) => if key_event.is_some() {
+                display_editor(&mut display, editor)?
             },
-            Err(error) => {
-                let _ = write!(&mut text, "Error:\n{:?}", error);
+            None => {
+                let mut text = ArrayString::<40>::new();
+
+                let century = read_century(&mut rtc_registers).unwrap_or(false);
+
+                match rtc.get_datetime() {
+                    Ok(registers) => match from_pcf8563(&registers, century) {
+                        Ok(datetime) if shown_time == Some(datetime) => continue,
+                        Ok(datetime) => {
+                            let voltage_low = read_voltage_low(&mut rtc_registers)
+                                .unwrap_or(false);
+
+                            let local = TIME_ZONE.to_local(&datetime)
+                                .map(|local| local.time)
+                                .unwrap_or(datetime);
+
+                            display.clear();
+                            draw_face(&mut display, face, &local, voltage_low)
+                                .map_err(|_| ())?;
+                            display.flush().map_err(|_| ())?;
+                            shown_time = Some(datetime);
+                            continue;
+                        },
+                        Err(error) => {
+                            let _ = write!(&mut text, "Invalid time\n{}", error);
+                        },
+                    },
+                    Err(error) => {
+                        let _ = write!(&mut text, "Error:\n{:?}", error);
+                    }
+                };
+
+                display_text(&mut display, &text)?;
+                shown_time = None;
             }
-        };
+        }
+    }
+}
+
+const TICK_MS: u32 = 10;
+const DISPLAY_TICKS: u32 = 10;
 
-        display_text(&mut display, &text).unwrap();
+/// RTC runs in UTC, shown and entered time is in this zone
+const TIME_ZONE: TimeZone = CENTRAL_EUROPE;
 
-        delay.delay_ms(100u16);
+/// RTC is set from the GPS or DCF77 when their times differ more, GPS
+/// sentences arrive a fraction of a second after the second they describe
+const SYNC_THRESHOLD_S: i64 = 2;
+
+/// Set the RTC from a GPS sentence when the RTC time is invalid or drifted
+/// away, changes are reported to `output`
+fn sync_from_gps<I2C, E, R, RE>(
+    sentence: &str,
+    rtc: &mut PCF8563<I2C>,
+    rtc_registers: &mut R,
+    output: &mut dyn Write,
+)
+where
+    I2C: I2cWrite<Error = E> + WriteRead<Error = E>,
+    E: core::fmt::Debug,
+    R: I2cWrite<Error = RE> + WriteRead<Error = RE>,
+{
+    match parse_sentence(sentence) {
+        Ok(gps) => sync_rtc("GPS", &gps.time, rtc, rtc_registers, output),
+        Err(NmeaError::Unsupported) | Err(NmeaError::NoTime) | Err(NmeaError::NoFix) => {},
+        Err(error) => {
+            let _ = write!(output, "GPS: {}\r\n", error);
+        },
     }
 }
 
-const DAYS: &[&str] = &[
-    "Sunday",
-    "Monday",
-    "Tuesday",
-    "Wednesday",
-    "Thursday",
-    "Friday",
-    "Saturday",
-];
-
-fn render_date<W>(
-    destination: &mut W,
-    datetime: DateTime
-) -> Result<(), ()> where W: Write {
-    write!(
-        destination,
-        "{:02}.{:02}.20{:02}\n{:02}:{:02}:{:02}\n{}",
-        datetime.day,
-        datetime.month,
-        datetime.year,
-        datetime.hours,
-        datetime.minutes,
-        datetime.seconds,
-        DAYS[datetime.weekday as usize]
-    ).map_err(|_| ())
+/// Feed a DCF77 pulse to the decoder and set the RTC from the decoded
+/// minute, if it is confirmed by the previous one
+fn sync_from_dcf77<I2C, E, R, RE>(
+    (start_ms, length_ms): (u32, u32),
+    decoder: &mut ConfirmingDecoder,
+    rtc: &mut PCF8563<I2C>,
+    rtc_registers: &mut R,
+    output: &mut dyn Write,
+)
+where
+    I2C: I2cWrite<Error = E> + WriteRead<Error = E>,
+    E: core::fmt::Debug,
+    R: I2cWrite<Error = RE> + WriteRead<Error = RE>,
+{
+    match decoder.pulse(start_ms, length_ms) {
+        Some(Ok(DecodedMinute { minute, confirmed: true })) => {
+            if let Ok(utc) = minute.to_utc() {
+                sync_rtc("DCF77", &utc, rtc, rtc_registers, output);
+            }
+        },
+        Some(Ok(_)) => {},
+        Some(Err(error)) => {
+            let _ = write!(output, "DCF77: {:?}\r\n", error);
+        },
+        None => {},
+    }
+}
+
+/// Write `utc` time received from `source` into the RTC when the RTC time
+/// is invalid or differs more than `SYNC_THRESHOLD_S`
+fn sync_rtc<I2C, E, R, RE>(
+    source: &str,
+    utc: &DateTime,
+    rtc: &mut PCF8563<I2C>,
+    rtc_registers: &mut R,
+    output: &mut dyn Write,
+)
+where
+    I2C: I2cWrite<Error = E> + WriteRead<Error = E>,
+    E: core::fmt::Debug,
+    R: I2cWrite<Error = RE> + WriteRead<Error = RE>,
+{
+    let century = read_century(rtc_registers).unwrap_or(false);
+    let voltage_low = read_voltage_low(rtc_registers).unwrap_or(true);
+
+    let rtc_time = rtc.get_datetime().ok()
+        .and_then(|registers| from_pcf8563(&registers, century).ok());
+
+    let update = match rtc_time {
+        Some(rtc_time) => voltage_low || needs_update(&rtc_time, utc, SYNC_THRESHOLD_S),
+        None => true,
+    };
+
+    if !update {
+        return;
+    }
+
+    let written = to_pcf8563(utc).ok()
+        .and_then(|registers| rtc.set_datetime(&registers).ok())
+        .and_then(|_| write_century(rtc_registers, century_flag(utc)).ok());
+
+    let _ = match written {
+        Some(()) => write!(
+            output, "{}: RTC set to {}-{:02}-{:02} {:02}:{:02}:{:02} UTC\r\n",
+            source, utc.year(), utc.month(), utc.day(),
+            utc.hours(), utc.minutes(), utc.seconds(),
+        ),
+        None => write!(output, "{}: RTC write failed\r\n", source),
+    };
+}
+
+/// Update the time editing state or switch the clock face after a button
+/// event, returns a message to show when the new time has been written
+fn handle_key<I2C, E, R, RE>(
+    event: ButtonEvent,
+    editor: &mut Option<TimeEditor>,
+    face: &mut ClockFace,
+    rtc: &mut PCF8563<I2C>,
+    rtc_registers: &mut R,
+) -> Option<&'static str>
+where
+    I2C: I2cWrite<Error = E> + WriteRead<Error = E>,
+    E: core::fmt::Debug,
+    R: I2cWrite<Error = RE> + WriteRead<Error = RE>,
+{
+    match (editor.as_mut(), event) {
+        (None, ButtonEvent::LongPress) => {
+            let century = read_century(rtc_registers).unwrap_or(false);
+
+            let current = rtc.get_datetime().ok()
+                .and_then(|datetime| from_pcf8563(&datetime, century).ok())
+                .and_then(|utc| TIME_ZONE.to_local(&utc).ok())
+                .map(|local| local.time)
+                .or_else(|| DateTime::new(2000, 1, 1, 0, 0, 0).ok())?;
+
+            *editor = Some(TimeEditor::new(current));
+            None
+        },
+        (None, ButtonEvent::ShortPress) => {
+            *face = face.next();
+            None
+        },
+        (Some(edited), ButtonEvent::ShortPress) => {
+            edited.increment();
+            None
+        },
+        (Some(edited), ButtonEvent::LongPress) => {
+            let value = edited.advance()?;
+            *editor = None;
+
+            let written = TIME_ZONE.to_utc(&value).ok().and_then(|utc| {
+                to_pcf8563(&utc).ok()
+                    .and_then(|registers| rtc.set_datetime(&registers).ok())
+                    .and_then(|_| write_century(rtc_registers, century_flag(&utc)).ok())
+            });
+
+            match written {
+                Some(()) => Some("Time set"),
+                None => Some("RTC write\nfailed"),
+            }
+        },
+    }
+}
+
+/// Execute single console command line and write the response to `output`
+fn execute_line<I2C, E, R, RE>(
+    line: Option<&str>,
+    rtc: &mut PCF8563<I2C>,
+    rtc_registers: &mut R,
+    output: &mut dyn Write,
+)
+where
+    I2C: I2cWrite<Error = E> + WriteRead<Error = E>,
+    E: core::fmt::Debug,
+    R: I2cWrite<Error = RE> + WriteRead<Error = RE>,
+{
+    let command = match line {
+        Some(line) => parse_command(line),
+        None => {
+            let _ = write!(output, "Line too long\r\n");
+            return;
+        }
+    };
+
+    match command {
+        Ok(Command::Get) => match rtc.get_datetime() {
+            Ok(datetime) => match from_pcf8563(
+                &datetime, read_century(rtc_registers).unwrap_or(false)
+            ).and_then(|utc| TIME_ZONE.to_local(&utc).map_err(RtcConvertError::InvalidValue)) {
+                Ok(local) => {
+                    let datetime = local.time;
+                    let _ = write!(
+                        output,
+                        "{}-{:02}-{:02} {:02}:{:02}:{:02} {} {}\r\n",
+                        datetime.year(), datetime.month(), datetime.day(),
+                        datetime.hours(), datetime.minutes(), datetime.seconds(),
+                        local.name, DAYS[datetime.weekday() as usize]
+                    );
+
+                    if let Ok(true) = read_voltage_low(rtc_registers) {
+                        let _ = write!(output, "Time invalid (RTC voltage low), set the clock\r\n");
+                    }
+                },
+                Err(error) => {
+                    let _ = write!(output, "{}\r\n", error);
+                },
+            },
+            Err(error) => {
+                let _ = write!(output, "RTC read error: {:?}\r\n", error);
+            },
+        },
+        Ok(Command::Set(datetime)) => match TIME_ZONE.to_utc(&datetime)
+            .map_err(RtcConvertError::InvalidValue)
+            .and_then(|utc| to_pcf8563(&utc).map(|registers| (utc, registers)))
+        {
+            Ok((utc, registers)) => match rtc.set_datetime(&registers) {
+                Ok(()) => match write_century(rtc_registers, century_flag(&utc)) {
+                    Ok(()) => {
+                        let _ = write!(
+                            output, "OK, {}\r\n", DAYS[datetime.weekday() as usize]
+                        );
+                    },
+                    Err(_) => {
+                        let _ = write!(output, "RTC century write error\r\n");
+                    },
+                },
+                Err(error) => {
+                    let _ = write!(output, "RTC write error: {:?}\r\n", error);
+                },
+            },
+            Err(error) => {
+                let _ = write!(output, "{}\r\n", error);
+            },
+        },
+        Ok(Command::Help) => {
+            let _ = output.write_str(HELP);
+        },
+        Err(CommandError::Empty) => {},
+        Err(error) => {
+            let _ = write!(output, "Error: {}\r\n", error);
+        },
+    }
 }
 
 fn display_text<T>(
@@ -146,4 +476,52 @@ where T: DisplayInterface {
     let position = Point::new(0, 14);
     Text::new(&message, position, style).draw(display).map_err(|_| ())?;
     display.flush().map_err(|_| ())
-}
\ No newline at end of file
+}
+
+/// Render edited date and time with the current field underlined
+fn display_editor<T>(
+    display: &mut GraphicsMode<T>,
+    editor: &TimeEditor,
+) -> Result<(), ()>
+where T: DisplayInterface {
+    const CHAR_WIDTH: i32 = 10;
+    const LINE_HEIGHT: i32 = 20;
+    const BASELINE: i32 = 14;
+
+    let value = editor.value();
+    let mut text = ArrayString::<40>::new();
+
+    let _ = write!(
+        &mut text,
+        "{:02}.{:02}.{}\n{:02}:{:02}:{:02}\nSet {}",
+        value.day(), value.month(), value.year(),
+        value.hours(), value.minutes(), value.seconds(),
+        editor.field().name()
+    );
+
+    // First character, length and line of the field in the text above
+    let (column, length, line) = match editor.field() {
+        Field::Day => (0, 2, 0),
+        Field::Month => (3, 2, 0),
+        Field::Year => (6, 4, 0),
+        Field::Hours => (0, 2, 1),
+        Field::Minutes => (3, 2, 1),
+        Field::Seconds => (6, 2, 1),
+    };
+
+    display.clear();
+
+    let style = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+    let position = Point::new(0, BASELINE);
+    Text::new(&text, position, style).draw(display).map_err(|_| ())?;
+
+    Rectangle::new(
+        Point::new(column*CHAR_WIDTH, BASELINE + line*LINE_HEIGHT + 3),
+        Size::new((length*CHAR_WIDTH) as u32, 2)
+    )
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(display)
+        .map_err(|_| ())?;
+
+    display.flush().map_err(|_| ())
+}
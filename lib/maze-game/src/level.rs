@@ -0,0 +1,186 @@
+use core::fmt::{Display, Formatter};
+
+/// Maximum number of maze columns (128 px wide display / 8 px cells)
+pub const MAX_COLUMNS: usize = 16;
+/// Maximum number of maze rows, bottom display line is left for status text
+pub const MAX_ROWS: usize = 7;
+/// Size of a single maze cell in display pixels
+pub const CELL_SIZE: i32 = 8;
+
+/// Levels compiled into the firmware, used when the SD card does not
+/// provide a level file
+///
+/// Level format is plain text, one line per maze row:
+///  - `#` wall
+///  - `.` or ` ` free space
+///  - `S` ball start position (exactly one)
+///  - `G` goal (exactly one)
+///  - `O` hole, ball falling into a hole restarts the level
+pub const BUILTIN_LEVELS: &[&str] = &[
+    "################\n\
+     #S.....#.......#\n\
+     #.####.#.#####.#\n\
+     #.#....#.#...#.#\n\
+     #.#.####.#.#.#.#\n\
+     #..........#..G#\n\
+     ################",
+    "################\n\
+     #S#.....O.....G#\n\
+     #.#.###.#.####.#\n\
+     #.#.#O#.#....#.#\n\
+     #.#.#.#.#.##.#.#\n\
+     #...#.....O....#\n\
+     ################",
+    "################\n\
+     #S...O..#....O.#\n\
+     #.##.##.#.##.#.#\n\
+     #..#......#....#\n\
+     ##.##.##.##.##.#\n\
+     #O.....O....#.G#\n\
+     ################",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Cell {
+    Free,
+    Wall,
+    Hole,
+    Goal,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LevelError {
+    Empty,
+    TooManyRows,
+    RowTooLong,
+    UnknownCell(u8),
+    MissingStart,
+    MissingGoal,
+    DuplicateStart,
+    DuplicateGoal,
+}
+
+impl Display for LevelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            LevelError::Empty => write!(f, "Empty"),
+            LevelError::TooManyRows => write!(f, "ManyRows"),
+            LevelError::RowTooLong => write!(f, "LongRow"),
+            LevelError::UnknownCell(byte) => write!(f, "Cell:{}", byte),
+            LevelError::MissingStart => write!(f, "NoStart"),
+            LevelError::MissingGoal => write!(f, "NoGoal"),
+            LevelError::DuplicateStart => write!(f, "DupStart"),
+            LevelError::DuplicateGoal => write!(f, "DupGoal"),
+        }
+    }
+}
+
+/// Maze level, cells outside the parsed area are treated as walls
+#[derive(Clone)]
+pub struct Level {
+    cells: [[Cell; MAX_COLUMNS]; MAX_ROWS],
+    width: usize,
+    height: usize,
+    start: (usize, usize),
+}
+
+impl Level {
+    /// Parse level from its text representation (see `BUILTIN_LEVELS`),
+    /// `\r` characters and trailing empty lines are ignored
+    pub fn parse(data: &[u8]) -> Result<Self, LevelError> {
+        let mut cells = [[Cell::Wall; MAX_COLUMNS]; MAX_ROWS];
+        let mut width = 0;
+        let mut height = 0;
+        let mut start = None;
+        let mut goal_found = false;
+
+        for line in data.split(|byte| *byte == b'\n') {
+            let line = trim_line_end(line);
+
+            if line.is_empty() {
+                continue;
+            }
+
+            if height >= MAX_ROWS {
+                return Err(LevelError::TooManyRows);
+            }
+
+            if line.len() > MAX_COLUMNS {
+                return Err(LevelError::RowTooLong);
+            }
+
+            for (column, byte) in line.iter().enumerate() {
+                cells[height][column] = match *byte {
+                    b'#' => Cell::Wall,
+                    b'.' | b' ' => Cell::Free,
+                    b'O' => Cell::Hole,
+                    b'G' if goal_found => return Err(LevelError::DuplicateGoal),
+                    b'G' => {
+                        goal_found = true;
+                        Cell::Goal
+                    },
+                    b'S' if start.is_some() => return Err(LevelError::DuplicateStart),
+                    b'S' => {
+                        start = Some((column, height));
+                        Cell::Free
+                    },
+                    other => return Err(LevelError::UnknownCell(other)),
+                };
+            }
+
+            width = width.max(line.len());
+            height += 1;
+        }
+
+        if height == 0 {
+            return Err(LevelError::Empty);
+        }
+
+        if !goal_found {
+            return Err(LevelError::MissingGoal);
+        }
+
+        match start {
+            Some(start) => Ok(Self { cells, width, height, start }),
+            None => Err(LevelError::MissingStart),
+        }
+    }
+
+    /// Parse one of the `BUILTIN_LEVELS`, `index` is zero based
+    pub fn builtin(index: usize) -> Option<Self> {
+        BUILTIN_LEVELS.get(index).and_then(|text| Self::parse(text.as_bytes()).ok())
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Start cell as (column, row)
+    pub fn start(&self) -> (usize, usize) {
+        self.start
+    }
+
+    /// Cell at the given column and row, anything outside the level is a wall
+    pub fn cell(&self, column: i32, row: i32) -> Cell {
+        if column < 0 || row < 0 {
+            return Cell::Wall;
+        }
+
+        self.cells
+            .get(row as usize)
+            .and_then(|cells| cells.get(column as usize))
+            .copied()
+            .unwrap_or(Cell::Wall)
+    }
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    match line.iter().rposition(|byte| *byte != b'\r') {
+        Some(last) => &line[..=last],
+        None => &[],
+    }
+}
@@ -0,0 +1,8 @@
+//! Tilt maze game logic, independent on the display and sensor hardware
+#![no_std]
+
+mod level;
+mod physics;
+
+pub use level::{Cell, Level, LevelError, BUILTIN_LEVELS, CELL_SIZE, MAX_COLUMNS, MAX_ROWS};
+pub use physics::{Ball, Outcome, Tilt, BALL_RADIUS, SUBPIXELS};
@@ -0,0 +1,130 @@
+use crate::level::{Cell, Level, CELL_SIZE};
+
+/// Ball position and velocity use fixed point numbers with 8 fractional bits
+pub const SUBPIXELS: i32 = 256;
+/// Ball radius in display pixels
+pub const BALL_RADIUS: i32 = 2;
+
+const CELL: i32 = CELL_SIZE * SUBPIXELS;
+const RADIUS: i32 = BALL_RADIUS * SUBPIXELS;
+/// Velocity change per simulation step for 1 g tilt (subpixels)
+const ACCELERATION: i32 = 48;
+/// Velocity loss per simulation step as a fraction of the current velocity
+const FRICTION_DIVISOR: i32 = 24;
+/// Part of the velocity (in percent) kept after bouncing off a wall
+const BOUNCE_PERCENT: i32 = 40;
+/// Speed limit per step, must stay below the cell size so that the ball
+/// can never tunnel through a wall
+const MAX_SPEED: i32 = 3 * SUBPIXELS;
+
+/// Tilt of the board in milli-g along the display axes
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Tilt {
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Result of a single simulation step
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Outcome {
+    Rolling,
+    FellIntoHole,
+    ReachedGoal,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Ball {
+    x: i32,
+    y: i32,
+    velocity_x: i32,
+    velocity_y: i32,
+}
+
+impl Ball {
+    /// Create a ball resting in the center of the level start cell
+    pub fn at_start(level: &Level) -> Self {
+        let (column, row) = level.start();
+
+        Self {
+            x: column as i32*CELL + CELL/2,
+            y: row as i32*CELL + CELL/2,
+            velocity_x: 0,
+            velocity_y: 0,
+        }
+    }
+
+    /// Ball center in display pixels
+    pub fn position(&self) -> (i32, i32) {
+        (self.x/SUBPIXELS, self.y/SUBPIXELS)
+    }
+
+    /// Ball velocity in subpixels per step
+    pub fn velocity(&self) -> (i32, i32) {
+        (self.velocity_x, self.velocity_y)
+    }
+
+    /// Advance the simulation by one step, accelerating the ball by `tilt`,
+    /// slowing it down by friction and bouncing it off the level walls
+    pub fn step(&mut self, level: &Level, tilt: Tilt) -> Outcome {
+        self.velocity_x = update_velocity(self.velocity_x, tilt.x);
+        self.velocity_y = update_velocity(self.velocity_y, tilt.y);
+
+        let moved_x = self.x + self.velocity_x;
+
+        if collides(level, moved_x, self.y) {
+            self.x = wall_contact(self.x, self.velocity_x);
+            self.velocity_x = bounce(self.velocity_x);
+        } else {
+            self.x = moved_x;
+        }
+
+        let moved_y = self.y + self.velocity_y;
+
+        if collides(level, self.x, moved_y) {
+            self.y = wall_contact(self.y, self.velocity_y);
+            self.velocity_y = bounce(self.velocity_y);
+        } else {
+            self.y = moved_y;
+        }
+
+        match level.cell(self.x.div_euclid(CELL), self.y.div_euclid(CELL)) {
+            Cell::Hole => Outcome::FellIntoHole,
+            Cell::Goal => Outcome::ReachedGoal,
+            Cell::Free | Cell::Wall => Outcome::Rolling,
+        }
+    }
+}
+
+fn update_velocity(velocity: i32, tilt: i32) -> i32 {
+    let accelerated = velocity + tilt*ACCELERATION/1000;
+    let slowed = accelerated - accelerated/FRICTION_DIVISOR;
+    slowed.clamp(-MAX_SPEED, MAX_SPEED)
+}
+
+fn bounce(velocity: i32) -> i32 {
+    -velocity*BOUNCE_PERCENT/100
+}
+
+/// Check whether the ball bounding box centered at `x`, `y` overlaps a wall
+fn collides(level: &Level, x: i32, y: i32) -> bool {
+    let left = (x - RADIUS).div_euclid(CELL);
+    let right = (x + RADIUS - 1).div_euclid(CELL);
+    let top = (y - RADIUS).div_euclid(CELL);
+    let bottom = (y + RADIUS - 1).div_euclid(CELL);
+
+    (top..=bottom).any(|row| {
+        (left..=right).any(|column| level.cell(column, row) == Cell::Wall)
+    })
+}
+
+/// Coordinate at which the ball touches the wall it was moving into,
+/// `position` is the last coordinate without collision
+fn wall_contact(position: i32, velocity: i32) -> i32 {
+    if velocity > 0 {
+        let wall_start = (position + RADIUS - 1).div_euclid(CELL)*CELL + CELL;
+        wall_start - RADIUS
+    } else {
+        let wall_end = (position - RADIUS).div_euclid(CELL)*CELL;
+        wall_end + RADIUS
+    }
+}
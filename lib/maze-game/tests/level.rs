@@ -0,0 +1,69 @@
+//! Level parsing and validation, run on the host using
+//! `cargo test -p maze-game --target x86_64-unknown-linux-gnu`
+
+use maze_game::{Cell, Level, LevelError, BUILTIN_LEVELS, MAX_COLUMNS, MAX_ROWS};
+
+#[test]
+fn builtin_levels_are_valid() {
+    for index in 0..BUILTIN_LEVELS.len() {
+        let level = Level::builtin(index).unwrap();
+        assert_eq!((level.width(), level.height()), (MAX_COLUMNS, MAX_ROWS));
+    }
+
+    assert!(Level::builtin(BUILTIN_LEVELS.len()).is_none());
+}
+
+#[test]
+fn cells_are_parsed() {
+    let level = Level::parse(b"#####\r\n#S.O#\r\n# .G#\r\n#####\r\n\r\n").unwrap();
+
+    assert_eq!((level.width(), level.height()), (5, 4));
+    assert_eq!(level.start(), (1, 1));
+    assert_eq!(level.cell(0, 0), Cell::Wall);
+    assert_eq!(level.cell(1, 1), Cell::Free);
+    assert_eq!(level.cell(2, 1), Cell::Free);
+    assert_eq!(level.cell(3, 1), Cell::Hole);
+    assert_eq!(level.cell(1, 2), Cell::Free);
+    assert_eq!(level.cell(3, 2), Cell::Goal);
+}
+
+#[test]
+fn cells_outside_level_are_walls() {
+    let level = Level::parse(b"SG\n..").unwrap();
+
+    assert_eq!(level.cell(1, 1), Cell::Free);
+    assert_eq!(level.cell(-1, 0), Cell::Wall);
+    assert_eq!(level.cell(0, -1), Cell::Wall);
+    assert_eq!(level.cell(2, 0), Cell::Wall);
+    assert_eq!(level.cell(0, 2), Cell::Wall);
+    assert_eq!(level.cell(MAX_COLUMNS as i32, MAX_ROWS as i32), Cell::Wall);
+}
+
+#[test]
+fn invalid_levels_are_rejected() {
+    let too_many_rows = "SG\n".to_string() + &"..\n".repeat(MAX_ROWS);
+    let too_long_row = format!("S{}G", ".".repeat(MAX_COLUMNS - 1));
+
+    for (text, error) in [
+        ("", LevelError::Empty),
+        ("\r\n\n", LevelError::Empty),
+        (too_many_rows.as_str(), LevelError::TooManyRows),
+        (too_long_row.as_str(), LevelError::RowTooLong),
+        ("#S.G\n#x.#", LevelError::UnknownCell(b'x')),
+        ("#..G#", LevelError::MissingStart),
+        ("#S..#", LevelError::MissingGoal),
+        ("#S.S.G#", LevelError::DuplicateStart),
+        ("#S.G\n#G.#", LevelError::DuplicateGoal),
+    ] {
+        assert_eq!(Level::parse(text.as_bytes()).err(), Some(error), "{:?}", text);
+    }
+}
+
+#[test]
+fn largest_level_fits() {
+    let row = "#".repeat(MAX_COLUMNS) + "\n";
+    let text = format!("S{}G\n", ".".repeat(MAX_COLUMNS - 2)) + &row.repeat(MAX_ROWS - 1);
+
+    let level = Level::parse(text.as_bytes()).unwrap();
+    assert_eq!((level.width(), level.height()), (MAX_COLUMNS, MAX_ROWS));
+}
@@ -0,0 +1,157 @@
+//! Ball movement, collisions and level outcomes, run on the host using
+//! `cargo test -p maze-game --target x86_64-unknown-linux-gnu`
+
+use maze_game::{Ball, Level, Outcome, Tilt, BALL_RADIUS, CELL_SIZE, SUBPIXELS};
+
+/// Speed limit per step keeping the ball from passing through walls
+const MAX_SPEED: i32 = 3*SUBPIXELS;
+
+/// Open room with a long corridor, the ball starts at its left end
+const ROOM: &str = "\
+########
+#S.....#
+#......#
+#......#
+#.....G#
+########";
+
+fn level(text: &str) -> Level {
+    Level::parse(text.as_bytes()).unwrap()
+}
+
+/// Run `steps` simulation steps, returns the first outcome other than
+/// rolling
+fn roll(ball: &mut Ball, level: &Level, tilt: Tilt, steps: usize) -> Outcome {
+    for _ in 0..steps {
+        match ball.step(level, tilt) {
+            Outcome::Rolling => {},
+            outcome => return outcome,
+        }
+    }
+
+    Outcome::Rolling
+}
+
+#[test]
+fn ball_starts_resting_in_start_cell() {
+    let level = level(ROOM);
+    let ball = Ball::at_start(&level);
+
+    assert_eq!(ball.position(), (CELL_SIZE + CELL_SIZE/2, CELL_SIZE + CELL_SIZE/2));
+    assert_eq!(ball.velocity(), (0, 0));
+}
+
+#[test]
+fn flat_board_keeps_ball_resting() {
+    let level = level(ROOM);
+    let mut ball = Ball::at_start(&level);
+
+    assert_eq!(roll(&mut ball, &level, Tilt::default(), 100), Outcome::Rolling);
+    assert_eq!(ball.position(), Ball::at_start(&level).position());
+}
+
+#[test]
+fn speed_is_clamped() {
+    let level = level(ROOM);
+
+    for tilt in [1000, 16000, i32::MAX/1000] {
+        let mut ball = Ball::at_start(&level);
+
+        for _ in 0..50 {
+            ball.step(&level, Tilt { x: tilt, y: tilt });
+            let (x, y) = ball.velocity();
+            assert!(x.abs() <= MAX_SPEED && y.abs() <= MAX_SPEED, "{:?}", ball.velocity());
+        }
+    }
+}
+
+#[test]
+fn friction_slows_ball_down() {
+    let level = level(ROOM);
+    let mut ball = Ball::at_start(&level);
+
+    ball.step(&level, Tilt { x: 1000, y: 0 });
+    let speed = ball.velocity().0;
+    ball.step(&level, Tilt::default());
+
+    assert!(speed > 0);
+    assert!(ball.velocity().0 < speed);
+}
+
+#[test]
+fn ball_stops_at_wall() {
+    let level = level(ROOM);
+    let mut ball = Ball::at_start(&level);
+
+    roll(&mut ball, &level, Tilt { x: -1000, y: -1000 }, 200);
+
+    // Touching the top left corner of the room
+    let corner = CELL_SIZE + BALL_RADIUS;
+    assert_eq!(ball.position(), (corner, corner));
+}
+
+#[test]
+fn ball_never_enters_wall() {
+    let level = level(ROOM);
+    let mut ball = Ball::at_start(&level);
+    let (min, max) = (CELL_SIZE + BALL_RADIUS, 7*CELL_SIZE - BALL_RADIUS);
+
+    for tilt in [(16000, 0), (0, 16000), (-16000, 0), (0, -16000), (16000, -16000)] {
+        for _ in 0..200 {
+            ball.step(&level, Tilt { x: tilt.0, y: tilt.1 });
+            let (x, y) = ball.position();
+            assert!((min..=max).contains(&x) && (min..=max).contains(&y), "{:?}", (x, y));
+        }
+    }
+}
+
+#[test]
+fn ball_slides_along_wall() {
+    let level = level(ROOM);
+    let mut ball = Ball::at_start(&level);
+
+    // Pressed against the top wall while rolling right
+    roll(&mut ball, &level, Tilt { x: 0, y: -1000 }, 50);
+    let (start_x, top) = ball.position();
+    roll(&mut ball, &level, Tilt { x: 300, y: -1000 }, 20);
+    let (x, y) = ball.position();
+
+    assert_eq!(top, CELL_SIZE + BALL_RADIUS);
+    assert_eq!(y, top);
+    assert!(x > start_x);
+}
+
+#[test]
+fn wall_bounce_reverses_velocity() {
+    let level = level(ROOM);
+    let mut ball = Ball::at_start(&level);
+    let mut bounced = false;
+
+    for _ in 0..100 {
+        let before = ball.velocity().0;
+        ball.step(&level, Tilt { x: 1000, y: 0 });
+
+        if before > 0 && ball.velocity().0 < 0 {
+            bounced = true;
+            break;
+        }
+    }
+
+    assert!(bounced);
+}
+
+#[test]
+fn goal_is_reached() {
+    let level = level(ROOM);
+    let mut ball = Ball::at_start(&level);
+
+    assert_eq!(roll(&mut ball, &level, Tilt { x: 1000, y: 1000 }, 500), Outcome::ReachedGoal);
+}
+
+#[test]
+fn ball_falls_into_hole() {
+    let level = level("#####\n#S.O#\n#..G#\n#####");
+    let mut ball = Ball::at_start(&level);
+
+    assert_eq!(roll(&mut ball, &level, Tilt { x: 1000, y: 0 }, 500), Outcome::FellIntoHole);
+}
@@ -0,0 +1,75 @@
+//! PCF8563 alarm and countdown timer settings and wake-up scheduling
+
+use crate::calendar::DateTime;
+
+/// Alarm matching date and time fields, fields set to `None` are not
+/// compared (their alarm enable bit is cleared in the RTC)
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Alarm {
+    pub minute: Option<u8>,
+    pub hour: Option<u8>,
+    pub day: Option<u8>,
+    /// Week day, 0 = Sunday
+    pub weekday: Option<u8>,
+}
+
+impl Alarm {
+    /// Alarm firing once per hour at the given minute
+    pub fn at_minute(minute: u8) -> Self {
+        Self { minute: Some(minute), ..Self::default() }
+    }
+
+    /// Check whether the alarm fires at the given time, the RTC compares
+    /// alarm fields once per minute, so seconds are not taken into account
+    pub fn matches(&self, time: &DateTime) -> bool {
+        let field_matches = |alarm: Option<u8>, value: u8| match alarm {
+            Some(alarm) => alarm == value,
+            None => true,
+        };
+
+        let any_enabled = self.minute.is_some()
+            || self.hour.is_some()
+            || self.day.is_some()
+            || self.weekday.is_some();
+
+        any_enabled
+            && field_matches(self.minute, time.minutes())
+            && field_matches(self.hour, time.hours())
+            && field_matches(self.day, time.day())
+            && field_matches(self.weekday, time.weekday())
+    }
+}
+
+/// Countdown timer period, counter value 1 - 255 with a 1 Hz or 1/60 Hz
+/// source clock, the timer reloads automatically after each period
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Countdown {
+    Seconds(u8),
+    Minutes(u8),
+}
+
+/// RTC setting producing the next wake-up interrupt
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Wakeup {
+    Alarm(Alarm),
+    Countdown(Countdown),
+}
+
+/// RTC setting waking the application up at `next`, a time after `now`.
+/// Near times use the 1 Hz countdown timer, later ones the alarm, which
+/// fires at the start of the minute of `next` (a countdown for the rest
+/// of the minute should be programmed then).
+pub fn wakeup_at(now: &DateTime, next: &DateTime) -> Wakeup {
+    let seconds = next.to_unix_seconds() - now.to_unix_seconds();
+
+    if seconds <= u8::MAX as i64 {
+        Wakeup::Countdown(Countdown::Seconds(seconds.max(1) as u8))
+    } else {
+        Wakeup::Alarm(Alarm {
+            minute: Some(next.minutes()),
+            hour: Some(next.hours()),
+            day: Some(next.day()),
+            weekday: None,
+        })
+    }
+}
@@ -0,0 +1,213 @@
+use core::fmt::{Display, Formatter};
+
+/// Week day names indexed by `DateTime::weekday`
+pub const DAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CalendarError {
+    InvalidYear,
+    InvalidMonth,
+    InvalidDay,
+    InvalidTime,
+}
+
+impl Display for CalendarError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CalendarError::InvalidYear => write!(f, "Invalid year"),
+            CalendarError::InvalidMonth => write!(f, "Invalid month"),
+            CalendarError::InvalidDay => write!(f, "Invalid day"),
+            CalendarError::InvalidTime => write!(f, "Invalid time"),
+        }
+    }
+}
+
+/// Number of seconds in one day
+pub const SECONDS_PER_DAY: i64 = 24*3600;
+
+/// Gregorian calendar date and time, always holding a valid value,
+/// ordering of values is chronological
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct DateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+}
+
+impl DateTime {
+    /// 1. 1. 1970 0:00:00, start of the Unix time
+    pub const UNIX_EPOCH: DateTime = DateTime {
+        year: 1970, month: 1, day: 1, hours: 0, minutes: 0, seconds: 0,
+    };
+
+    /// Create date and time, `month` and `day` are one based, years
+    /// start at 1 A.D.
+    pub fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+    ) -> Result<Self, CalendarError> {
+        if year == 0 {
+            return Err(CalendarError::InvalidYear);
+        }
+
+        if !(1..=12).contains(&month) {
+            return Err(CalendarError::InvalidMonth);
+        }
+
+        if !(1..=days_in_month(year, month)).contains(&day) {
+            return Err(CalendarError::InvalidDay);
+        }
+
+        if hours > 23 || minutes > 59 || seconds > 59 {
+            return Err(CalendarError::InvalidTime);
+        }
+
+        Ok(Self { year, month, day, hours, minutes, seconds })
+    }
+
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn hours(&self) -> u8 {
+        self.hours
+    }
+
+    pub fn minutes(&self) -> u8 {
+        self.minutes
+    }
+
+    pub fn seconds(&self) -> u8 {
+        self.seconds
+    }
+
+    /// Day of week computed from the date, 0 = Sunday, 6 = Saturday
+    pub fn weekday(&self) -> u8 {
+        weekday(self.year, self.month, self.day)
+    }
+
+    /// Number of days since 1. 1. 1970 (negative for earlier dates)
+    pub fn days_since_epoch(&self) -> i64 {
+        days_from_civil(self.year as i64, self.month as i64, self.day as i64)
+    }
+
+    /// Seconds since 1. 1. 1970 0:00:00 (negative for earlier dates)
+    pub fn to_unix_seconds(&self) -> i64 {
+        self.days_since_epoch()*SECONDS_PER_DAY
+            + self.hours as i64*3600
+            + self.minutes as i64*60
+            + self.seconds as i64
+    }
+
+    /// Create date and time from seconds since 1. 1. 1970 0:00:00,
+    /// fails for years that cannot be represented
+    pub fn from_unix_seconds(seconds: i64) -> Result<Self, CalendarError> {
+        let days = seconds.div_euclid(SECONDS_PER_DAY);
+        let time = seconds.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+
+        if !(1..=u16::MAX as i64).contains(&year) {
+            return Err(CalendarError::InvalidYear);
+        }
+
+        Self::new(
+            year as u16, month as u8, day as u8,
+            (time/3600) as u8, (time/60 % 60) as u8, (time % 60) as u8
+        )
+    }
+
+    /// Shift date and time by the given number of seconds
+    pub fn add_seconds(&self, seconds: i64) -> Result<Self, CalendarError> {
+        let shifted = self.to_unix_seconds()
+            .checked_add(seconds)
+            .ok_or(CalendarError::InvalidYear)?;
+
+        Self::from_unix_seconds(shifted)
+    }
+
+    /// Shift date by the given number of days, time of day is kept
+    pub fn add_days(&self, days: i64) -> Result<Self, CalendarError> {
+        let seconds = days
+            .checked_mul(SECONDS_PER_DAY)
+            .ok_or(CalendarError::InvalidYear)?;
+
+        self.add_seconds(seconds)
+    }
+
+    /// Whole days from `self` to `other` (negative if `other` is earlier)
+    pub fn days_until(&self, other: &DateTime) -> i64 {
+        other.days_since_epoch() - self.days_since_epoch()
+    }
+}
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+/// Number of days in the given month (one based), zero for invalid months
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// Day of week (0 = Sunday) using the Sakamoto method
+fn weekday(year: u16, month: u8, day: u8) -> u8 {
+    const OFFSETS: [u32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    let year = if month < 3 { year as u32 - 1 } else { year as u32 };
+    let sum = year + year/4 - year/100 + year/400 + OFFSETS[month as usize - 1] + day as u32;
+    (sum % 7) as u8
+}
+
+/// Days since 1. 1. 1970 for a proleptic Gregorian date
+/// (algorithm by Howard Hinnant, valid for any year)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153*month_from_march + 2)/5 + day - 1;
+    let day_of_era = year_of_era*365 + year_of_era/4 - year_of_era/100 + day_of_year;
+    era*146097 + day_of_era - 719468
+}
+
+/// Inverse of `days_from_civil`, returns (year, month, day)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era/1460 + day_of_era/36524 - day_of_era/146096)/365;
+    let day_of_year = day_of_era - (365*year_of_era + year_of_era/4 - year_of_era/100);
+    let month_from_march = (5*day_of_year + 2)/153;
+    let day = day_of_year - (153*month_from_march + 2)/5 + 1;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = year_of_era + era*400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
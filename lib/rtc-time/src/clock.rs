@@ -0,0 +1,60 @@
+//! Common interface of the real time clocks, so that an application can
+//! switch between an external and the microcontroller internal RTC
+
+use crate::calendar::DateTime;
+
+/// Time read from a clock together with its validity, time is invalid
+/// when the clock lost power or was never set
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ClockReading {
+    pub time: DateTime,
+    pub valid: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ClockError {
+    /// Clock does not respond
+    NotResponding,
+    /// Time cannot be stored in the clock, for example the year is out
+    /// of the clock range
+    OutOfRange,
+}
+
+pub trait Clock {
+    /// Short clock name to show to the user
+    fn name(&self) -> &'static str;
+
+    /// Read current time, fails when the clock does not respond
+    fn read(&mut self) -> Result<ClockReading, ClockError>;
+
+    /// Set the time, the time becomes valid after that
+    fn set(&mut self, time: &DateTime) -> Result<(), ClockError>;
+}
+
+/// Set `clock` from a valid `reference` reading when the clock time is
+/// invalid or differs by more than `tolerance_seconds`, returns true when
+/// the clock has been set
+pub fn synchronize<C>(
+    clock: &mut C,
+    reference: &ClockReading,
+    tolerance_seconds: i64,
+) -> Result<bool, ClockError>
+where C: Clock {
+    if !reference.valid {
+        return Ok(false);
+    }
+
+    let in_sync = match clock.read() {
+        Ok(reading) => {
+            let difference = reading.time.to_unix_seconds() - reference.time.to_unix_seconds();
+            reading.valid && difference.abs() <= tolerance_seconds
+        },
+        Err(_) => false,
+    };
+
+    if in_sync {
+        Ok(false)
+    } else {
+        clock.set(&reference.time).map(|_| true)
+    }
+}
@@ -0,0 +1,161 @@
+//! Serial console commands for reading and setting the real time clock
+//!
+//! Supported commands:
+//!  - `get` - print current date and time
+//!  - `set YYYY-MM-DD HH:MM:SS` - set date and time, week day is computed
+//!  - `help` - print the command list
+
+use core::fmt::{Display, Formatter};
+use crate::calendar::{CalendarError, DateTime};
+
+pub const HELP: &str = "Commands:\r\n  get\r\n  set YYYY-MM-DD HH:MM:SS\r\n  help\r\n";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    Get,
+    Set(DateTime),
+    Help,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CommandError {
+    Empty,
+    UnknownCommand,
+    InvalidFormat,
+    InvalidDateTime(CalendarError),
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "Empty command"),
+            CommandError::UnknownCommand => write!(f, "Unknown command, type help"),
+            CommandError::InvalidFormat => write!(f, "Expected set YYYY-MM-DD HH:MM:SS"),
+            CommandError::InvalidDateTime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Parse a single command line (without the line terminator)
+pub fn parse_command(line: &str) -> Result<Command, CommandError> {
+    let mut words = line.split_whitespace();
+
+    let command = match words.next() {
+        Some("get") => Command::Get,
+        Some("help") => Command::Help,
+        Some("set") => {
+            let date = words.next().ok_or(CommandError::InvalidFormat)?;
+            let time = words.next().ok_or(CommandError::InvalidFormat)?;
+            Command::Set(parse_date_time(date, time)?)
+        },
+        Some(_) => return Err(CommandError::UnknownCommand),
+        None => return Err(CommandError::Empty),
+    };
+
+    match words.next() {
+        Some(_) => Err(CommandError::InvalidFormat),
+        None => Ok(command),
+    }
+}
+
+fn parse_date_time(date: &str, time: &str) -> Result<DateTime, CommandError> {
+    let [year, month, day] = split_numbers::<3>(date, '-', &[4, 2, 2])?;
+    let [hours, minutes, seconds] = split_numbers::<3>(time, ':', &[2, 2, 2])?;
+
+    DateTime::new(
+        year, month as u8, day as u8,
+        hours as u8, minutes as u8, seconds as u8
+    ).map_err(CommandError::InvalidDateTime)
+}
+
+/// Split `text` by `separator` into exactly N decimal numbers having
+/// the given maximal number of digits
+fn split_numbers<const N: usize>(
+    text: &str,
+    separator: char,
+    max_digits: &[usize; N],
+) -> Result<[u16; N], CommandError> {
+    let mut numbers = [0; N];
+    let mut parts = text.split(separator);
+
+    for (number, digits) in numbers.iter_mut().zip(max_digits) {
+        let part = parts.next().ok_or(CommandError::InvalidFormat)?;
+
+        if part.is_empty() || part.len() > *digits || !part.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(CommandError::InvalidFormat);
+        }
+
+        *number = part.parse().map_err(|_| CommandError::InvalidFormat)?;
+    }
+
+    match parts.next() {
+        Some(_) => Err(CommandError::InvalidFormat),
+        None => Ok(numbers),
+    }
+}
+
+/// Collects received characters until a line terminator arrives
+pub struct LineBuffer<const N: usize> {
+    data: [u8; N],
+    length: usize,
+    overflow: bool,
+}
+
+/// Result of feeding a single byte into the `LineBuffer`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LineEvent {
+    /// Printable character was stored and should be echoed back
+    Stored(u8),
+    /// Last character was removed
+    Erased,
+    /// Line terminator received, line can be read using `LineBuffer::line`
+    Complete,
+    /// Byte was ignored (non printable character or full buffer)
+    Ignored,
+}
+
+impl<const N: usize> Default for LineBuffer<N> {
+    fn default() -> Self {
+        Self { data: [0; N], length: 0, overflow: false }
+    }
+}
+
+impl<const N: usize> LineBuffer<N> {
+    /// Push a received byte, a complete line must be consumed using
+    /// `line` and `clear` before pushing more characters
+    pub fn push(&mut self, byte: u8) -> LineEvent {
+        match byte {
+            b'\r' | b'\n' => LineEvent::Complete,
+            // Backspace or delete
+            0x08 | 0x7f if self.length > 0 => {
+                self.length -= 1;
+                LineEvent::Erased
+            },
+            byte if (b' '..=b'~').contains(&byte) => {
+                if self.length < N {
+                    self.data[self.length] = byte;
+                    self.length += 1;
+                    LineEvent::Stored(byte)
+                } else {
+                    self.overflow = true;
+                    LineEvent::Ignored
+                }
+            },
+            _ => LineEvent::Ignored,
+        }
+    }
+
+    /// Received line, `None` if the line did not fit into the buffer
+    pub fn line(&self) -> Option<&str> {
+        if self.overflow {
+            None
+        } else {
+            core::str::from_utf8(&self.data[..self.length]).ok()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.length = 0;
+        self.overflow = false;
+    }
+}
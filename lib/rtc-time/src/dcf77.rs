@@ -0,0 +1,252 @@
+//! Decoder of the DCF77 time signal, fed with lengths of the carrier
+//! reduction pulses as measured by a receiver module
+//!
+//! Each second except the 59th starts with a pulse, 100 ms long for
+//! a zero bit and 200 ms long for a one bit, the missing pulse marks
+//! the end of the minute. Bits 0 - 58 transmitted during a minute carry
+//! the local (CET/CEST) time of the following minute.
+
+use crate::calendar::{CalendarError, DateTime};
+
+/// Pulses shorter than this are noise
+const MIN_PULSE_MS: u32 = 40;
+/// Boundary between zero (100 ms) and one (200 ms) bits
+const BIT_THRESHOLD_MS: u32 = 150;
+const MAX_PULSE_MS: u32 = 280;
+
+/// Pulse starts are one second apart, or two seconds around the minute
+/// marker
+const MIN_SECOND_MS: u32 = 900;
+const MAX_SECOND_MS: u32 = 1100;
+const MIN_MARKER_MS: u32 = 1900;
+const MAX_MARKER_MS: u32 = 2100;
+
+const BITS_PER_MINUTE: u8 = 59;
+const FIRST_YEAR: u16 = 2000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DcfError {
+    /// Pulse length is neither a zero nor a one bit
+    InvalidPulse,
+    /// Pulse did not start one second after the previous one
+    InvalidTiming,
+    /// Minute marker arrived before all bits were received
+    MissingBits,
+    /// Start of time information bit (20) is not set or bit 0 is not zero
+    InvalidFrame,
+    InvalidParity,
+    /// BCD digit out of range or invalid date
+    InvalidValue,
+}
+
+impl From<CalendarError> for DcfError {
+    fn from(_: CalendarError) -> Self {
+        DcfError::InvalidValue
+    }
+}
+
+/// Decoded minute, local German time
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DcfTime {
+    pub time: DateTime,
+    /// Summer time (CEST, UTC+2), otherwise CET (UTC+1)
+    pub summer_time: bool,
+}
+
+impl DcfTime {
+    /// Offset of the transmitted time from UTC in minutes
+    pub fn offset_minutes(&self) -> i16 {
+        if self.summer_time { 120 } else { 60 }
+    }
+
+    pub fn to_utc(&self) -> Result<DateTime, CalendarError> {
+        self.time.add_seconds(-(self.offset_minutes() as i64)*60)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    /// Waiting for the minute marker
+    Searching,
+    /// Receiving bits after the minute marker
+    Receiving,
+}
+
+/// Decoder state machine, feed it with the start time and length of each
+/// pulse in milliseconds (from any wrapping millisecond counter)
+pub struct Dcf77Decoder {
+    state: State,
+    last_start: Option<u32>,
+    bits: u64,
+    count: u8,
+}
+
+impl Default for Dcf77Decoder {
+    fn default() -> Self {
+        Self { state: State::Searching, last_start: None, bits: 0, count: 0 }
+    }
+}
+
+impl Dcf77Decoder {
+    /// Synchronized to the minute marker and receiving bits
+    pub fn is_synchronized(&self) -> bool {
+        self.state == State::Receiving
+    }
+
+    /// Number of bits received in the current minute
+    pub fn bit_count(&self) -> u8 {
+        self.count
+    }
+
+    /// Process a pulse, returns the decoded time when the pulse starts
+    /// a new minute (the decoded time is the time of its start)
+    pub fn pulse(&mut self, start_ms: u32, length_ms: u32) -> Option<Result<DcfTime, DcfError>> {
+        if length_ms < MIN_PULSE_MS {
+            return None;
+        }
+
+        let previous = self.last_start.replace(start_ms);
+
+        if length_ms > MAX_PULSE_MS {
+            self.restart();
+            return Some(Err(DcfError::InvalidPulse));
+        }
+
+        let bit = length_ms >= BIT_THRESHOLD_MS;
+
+        let period = match previous {
+            Some(previous) => start_ms.wrapping_sub(previous),
+            None => return None,
+        };
+
+        if (MIN_MARKER_MS..=MAX_MARKER_MS).contains(&period) {
+            let result = match self.state {
+                State::Receiving if self.count == BITS_PER_MINUTE => Some(decode(self.bits)),
+                State::Receiving => Some(Err(DcfError::MissingBits)),
+                State::Searching => None,
+            };
+
+            self.state = State::Receiving;
+            self.bits = bit as u64;
+            self.count = 1;
+            return result;
+        }
+
+        if !(MIN_SECOND_MS..=MAX_SECOND_MS).contains(&period) {
+            let was_receiving = self.state == State::Receiving;
+            self.restart();
+            return was_receiving.then_some(Err(DcfError::InvalidTiming));
+        }
+
+        if self.state == State::Receiving {
+            if self.count >= BITS_PER_MINUTE {
+                self.restart();
+                return Some(Err(DcfError::InvalidTiming));
+            }
+
+            self.bits |= (bit as u64) << self.count;
+            self.count += 1;
+        }
+
+        None
+    }
+
+    fn restart(&mut self) {
+        self.state = State::Searching;
+        self.bits = 0;
+        self.count = 0;
+    }
+}
+
+/// Decode bits 0 - 58 of a minute
+pub fn decode(bits: u64) -> Result<DcfTime, DcfError> {
+    let bit = |index: u8| (bits >> index) & 1 == 1;
+
+    if bit(0) || !bit(20) {
+        return Err(DcfError::InvalidFrame);
+    }
+
+    // Summer and winter time flags are mutually exclusive
+    if bit(17) == bit(18) {
+        return Err(DcfError::InvalidFrame);
+    }
+
+    if !even_parity(bits, 21, 29) || !even_parity(bits, 29, 36) || !even_parity(bits, 36, 59) {
+        return Err(DcfError::InvalidParity);
+    }
+
+    let minutes = bcd(bits, 21, 7)?;
+    let hours = bcd(bits, 29, 6)?;
+    let day = bcd(bits, 36, 6)?;
+    let month = bcd(bits, 45, 5)?;
+    let year = bcd(bits, 50, 8)?;
+
+    Ok(DcfTime {
+        time: DateTime::new(FIRST_YEAR + year as u16, month, day, hours, minutes, 0)?,
+        summer_time: bit(17),
+    })
+}
+
+/// Bits `first..end` including the parity bit contain an even number of ones
+fn even_parity(bits: u64, first: u8, end: u8) -> bool {
+    let mask = ((1u64 << (end - first)) - 1) << first;
+    (bits & mask).count_ones().is_multiple_of(2)
+}
+
+/// BCD number of `width` bits starting at `first`, least significant
+/// bit first
+fn bcd(bits: u64, first: u8, width: u8) -> Result<u8, DcfError> {
+    let value = ((bits >> first) & ((1 << width) - 1)) as u8;
+    let (tens, units) = (value >> 4, value & 0x0f);
+
+    if units > 9 {
+        return Err(DcfError::InvalidValue);
+    }
+
+    Ok(tens*10 + units)
+}
+
+/// Parity protects single bit errors only, a decoded minute should be
+/// trusted when it follows the previously decoded one
+pub fn confirms(previous: &DcfTime, current: &DcfTime) -> bool {
+    previous.time.add_seconds(60) == Ok(current.time)
+}
+
+/// Minute decoded by the `ConfirmingDecoder`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DecodedMinute {
+    pub minute: DcfTime,
+    /// Minute follows the previously decoded one and can be trusted
+    pub confirmed: bool,
+}
+
+/// Decoder checking each decoded minute against the previous one,
+/// an error forgets the previous minute
+#[derive(Default)]
+pub struct ConfirmingDecoder {
+    decoder: Dcf77Decoder,
+    last_minute: Option<DcfTime>,
+}
+
+impl ConfirmingDecoder {
+    /// Process a pulse, see `Dcf77Decoder::pulse`
+    pub fn pulse(
+        &mut self,
+        start_ms: u32,
+        length_ms: u32,
+    ) -> Option<Result<DecodedMinute, DcfError>> {
+        let result = self.decoder.pulse(start_ms, length_ms)?;
+
+        Some(match result {
+            Ok(minute) => {
+                let confirmed = self.last_minute.is_some_and(|last| confirms(&last, &minute));
+                self.last_minute = Some(minute);
+                Ok(DecodedMinute { minute, confirmed })
+            },
+            Err(error) => {
+                self.last_minute = None;
+                Err(error)
+            },
+        })
+    }
+}
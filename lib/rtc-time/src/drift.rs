@@ -0,0 +1,122 @@
+//! RTC drift measurement against a reference clock and software correction
+//!
+//! Drift is expressed in parts per billion (ppb), positive values mean that
+//! the RTC runs fast (RTC second is shorter than the reference second)
+
+use core::fmt::Write;
+
+const BILLION: i128 = 1_000_000_000;
+
+/// Periods deviating from the nominal length by more than this value are
+/// considered glitches and are not used for the estimate
+const MAX_PERIOD_DEVIATION_PPM: u64 = 1000;
+
+/// Calibration file written by the calibration app and read by the logger
+pub const CALIBRATION_FILE_NAME: &str = "RTCCAL.TXT";
+const CALIBRATION_KEY: &str = "drift_ppb=";
+
+/// Averages lengths of RTC clock output periods (1 Hz) measured in ticks
+/// of a reference timer
+pub struct DriftEstimator {
+    reference_hz: u32,
+    total_ticks: u64,
+    periods: u32,
+    rejected: u32,
+}
+
+impl DriftEstimator {
+    /// `reference_hz` is the reference timer frequency, which is also
+    /// the nominal length of one RTC second in ticks
+    pub fn new(reference_hz: u32) -> Self {
+        Self { reference_hz, total_ticks: 0, periods: 0, rejected: 0 }
+    }
+
+    /// Add a measured RTC second, returns false if the period was rejected
+    pub fn add_period(&mut self, ticks: u32) -> bool {
+        let nominal = self.reference_hz as u64;
+        let deviation = (ticks as u64).abs_diff(nominal);
+
+        if deviation*1_000_000 > nominal*MAX_PERIOD_DEVIATION_PPM {
+            self.rejected += 1;
+            return false;
+        }
+
+        self.total_ticks += ticks as u64;
+        self.periods += 1;
+        true
+    }
+
+    pub fn periods(&self) -> u32 {
+        self.periods
+    }
+
+    pub fn rejected(&self) -> u32 {
+        self.rejected
+    }
+
+    /// Average drift of all accepted periods, `None` before the first one
+    pub fn drift_ppb(&self) -> Option<i32> {
+        if self.periods == 0 {
+            return None;
+        }
+
+        let expected = self.periods as i128*self.reference_hz as i128;
+        let measured = self.total_ticks as i128;
+        Some(((expected - measured)*BILLION/measured) as i32)
+    }
+}
+
+/// Software correction of RTC time for a known drift, accumulated drift
+/// is removed relative to the first corrected time (start of the recording
+/// session), when the clock is assumed to be correct
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct DriftCorrection {
+    drift_ppb: i32,
+    reference: Option<i64>,
+}
+
+impl DriftCorrection {
+    pub fn new(drift_ppb: i32) -> Self {
+        Self { drift_ppb, reference: None }
+    }
+
+    pub fn drift_ppb(&self) -> i32 {
+        self.drift_ppb
+    }
+
+    /// Correct RTC time given in Unix seconds, the first call sets the
+    /// reference time
+    pub fn correct(&mut self, rtc_seconds: i64) -> i64 {
+        let reference = *self.reference.get_or_insert(rtc_seconds);
+        let elapsed = (rtc_seconds - reference) as i128;
+        rtc_seconds - (elapsed*self.drift_ppb as i128/BILLION) as i64
+    }
+}
+
+/// Write drift as the content of the calibration file
+pub fn write_calibration(output: &mut dyn Write, drift_ppb: i32) -> core::fmt::Result {
+    write!(output, "{}{}\r\n", CALIBRATION_KEY, drift_ppb)
+}
+
+/// Read drift from the calibration file content
+pub fn parse_calibration(data: &[u8]) -> Option<i32> {
+    let text = core::str::from_utf8(data).ok()?;
+
+    text.lines()
+        .find_map(|line| line.trim().strip_prefix(CALIBRATION_KEY))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// Write drift in ppm with three decimal places
+pub fn write_ppm(output: &mut dyn Write, drift_ppb: i32) -> core::fmt::Result {
+    let sign = if drift_ppb < 0 { '-' } else { '+' };
+    let value = drift_ppb.unsigned_abs();
+    write!(output, "{}{}.{:03} ppm", sign, value/1000, value % 1000)
+}
+
+/// Write time error accumulated per day in seconds with three decimal places
+pub fn write_seconds_per_day(output: &mut dyn Write, drift_ppb: i32) -> core::fmt::Result {
+    let sign = if drift_ppb < 0 { '-' } else { '+' };
+    let milliseconds = drift_ppb.unsigned_abs() as u64*86_400/1_000_000;
+    write!(output, "{}{}.{:03} s/day", sign, milliseconds/1000, milliseconds % 1000)
+}
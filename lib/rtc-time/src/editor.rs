@@ -0,0 +1,119 @@
+//! Interactive date and time editing using a single button, short press
+//! increments the highlighted field, long press advances to the next field
+
+use crate::calendar::{days_in_month, DateTime};
+use crate::rtc::{PCF8563_FIRST_YEAR, PCF8563_LAST_YEAR};
+
+/// Fields in the order they are edited, date is edited from the year
+/// so that the day range is known when the day is being set
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Field {
+    Year,
+    Month,
+    Day,
+    Hours,
+    Minutes,
+    Seconds,
+}
+
+impl Field {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::Year => "Year",
+            Field::Month => "Month",
+            Field::Day => "Day",
+            Field::Hours => "Hours",
+            Field::Minutes => "Minutes",
+            Field::Seconds => "Seconds",
+        }
+    }
+
+    fn next(&self) -> Option<Field> {
+        match self {
+            Field::Year => Some(Field::Month),
+            Field::Month => Some(Field::Day),
+            Field::Day => Some(Field::Hours),
+            Field::Hours => Some(Field::Minutes),
+            Field::Minutes => Some(Field::Seconds),
+            Field::Seconds => None,
+        }
+    }
+}
+
+/// Edited value is always a valid date and time, day is clamped to the
+/// month length whenever the month or year changes
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimeEditor {
+    value: DateTime,
+    field: Field,
+}
+
+impl TimeEditor {
+    /// Start editing `initial` value, year is clamped to the range
+    /// supported by the PCF8563
+    pub fn new(initial: DateTime) -> Self {
+        let year = initial.year().clamp(PCF8563_FIRST_YEAR, PCF8563_LAST_YEAR);
+
+        Self {
+            value: with_date(&initial, year, initial.month(), initial.day()),
+            field: Field::Year,
+        }
+    }
+
+    pub fn value(&self) -> DateTime {
+        self.value
+    }
+
+    pub fn field(&self) -> Field {
+        self.field
+    }
+
+    /// Increment the current field, wrapping around at the end of its range
+    pub fn increment(&mut self) {
+        let v = self.value;
+
+        self.value = match self.field {
+            Field::Day => with_date(
+                &v, v.year(), v.month(), wrap(v.day(), 1, days_in_month(v.year(), v.month()))
+            ),
+            Field::Month => with_date(&v, v.year(), wrap(v.month(), 1, 12), v.day()),
+            Field::Year => {
+                let year = if v.year() >= PCF8563_LAST_YEAR {
+                    PCF8563_FIRST_YEAR
+                } else {
+                    v.year() + 1
+                };
+                with_date(&v, year, v.month(), v.day())
+            },
+            Field::Hours => with_time(&v, wrap(v.hours(), 0, 23), v.minutes(), v.seconds()),
+            Field::Minutes => with_time(&v, v.hours(), wrap(v.minutes(), 0, 59), v.seconds()),
+            Field::Seconds => with_time(&v, v.hours(), v.minutes(), wrap(v.seconds(), 0, 59)),
+        };
+    }
+
+    /// Move to the next field, returns the final value after the last field
+    pub fn advance(&mut self) -> Option<DateTime> {
+        match self.field.next() {
+            Some(field) => {
+                self.field = field;
+                None
+            },
+            None => Some(self.value),
+        }
+    }
+}
+
+fn wrap(value: u8, min: u8, max: u8) -> u8 {
+    if value >= max { min } else { value + 1 }
+}
+
+fn with_date(time: &DateTime, year: u16, month: u8, day: u8) -> DateTime {
+    let day = day.min(days_in_month(year, month));
+    DateTime::new(year, month, day, time.hours(), time.minutes(), time.seconds())
+        .unwrap_or(*time)
+}
+
+fn with_time(time: &DateTime, hours: u8, minutes: u8, seconds: u8) -> DateTime {
+    DateTime::new(time.year(), time.month(), time.day(), hours, minutes, seconds)
+        .unwrap_or(*time)
+}
@@ -0,0 +1,63 @@
+//! FAT directory entry time stamps, the date is stored as years since 1980
+//! in 7 bits and the time with a two second resolution
+
+use core::fmt::{Display, Formatter};
+use crate::calendar::{CalendarError, DateTime};
+
+pub const FAT_FIRST_YEAR: u16 = 1980;
+pub const FAT_LAST_YEAR: u16 = 2107;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FatTimeError {
+    YearOutOfRange,
+}
+
+impl Display for FatTimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FatTimeError::YearOutOfRange => write!(
+                f, "FAT year must be {}-{}", FAT_FIRST_YEAR, FAT_LAST_YEAR
+            ),
+        }
+    }
+}
+
+/// Date and time packed into the FAT date and time fields
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FatTimestamp {
+    pub date: u16,
+    pub time: u16,
+}
+
+impl FatTimestamp {
+    /// 1. 1. 1980 0:00:00, the earliest FAT time stamp
+    pub const EPOCH: FatTimestamp = FatTimestamp { date: (1 << 5) | 1, time: 0 };
+
+    /// Pack date and time, odd seconds are rounded down
+    pub fn from_date_time(time: &DateTime) -> Result<Self, FatTimeError> {
+        if !(FAT_FIRST_YEAR..=FAT_LAST_YEAR).contains(&time.year()) {
+            return Err(FatTimeError::YearOutOfRange);
+        }
+
+        Ok(Self {
+            date: ((time.year() - FAT_FIRST_YEAR) << 9)
+                | ((time.month() as u16) << 5)
+                | time.day() as u16,
+            time: ((time.hours() as u16) << 11)
+                | ((time.minutes() as u16) << 5)
+                | (time.seconds() as u16 >> 1),
+        })
+    }
+
+    /// Unpack date and time, fails for field values not forming a valid date
+    pub fn to_date_time(&self) -> Result<DateTime, CalendarError> {
+        DateTime::new(
+            FAT_FIRST_YEAR + (self.date >> 9),
+            ((self.date >> 5) & 0b1111) as u8,
+            (self.date & 0b1_1111) as u8,
+            (self.time >> 11) as u8,
+            ((self.time >> 5) & 0b11_1111) as u8,
+            ((self.time & 0b1_1111) << 1) as u8,
+        )
+    }
+}
@@ -0,0 +1,93 @@
+//! Detection of RTC time jumps, caused for example by a dead backup battery
+//! or the time set by hand, comparing the RTC with the time elapsed
+//! according to an independent uptime counter
+
+use core::fmt::Write;
+use crate::calendar::DateTime;
+
+/// File keeping the time of the last record over restarts
+pub const LAST_TIME_FILE_NAME: &str = "LASTTIME.TXT";
+const LAST_TIME_KEY: &str = "last_unix=";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TimeJump {
+    /// RTC went back by the given number of seconds
+    Backwards(i64),
+    /// RTC moved forward by the given number of seconds more than expected
+    Forward(i64),
+}
+
+impl TimeJump {
+    /// Jump size in seconds, negative for backward jumps
+    pub fn seconds(&self) -> i64 {
+        match *self {
+            TimeJump::Backwards(seconds) => -seconds,
+            TimeJump::Forward(seconds) => seconds,
+        }
+    }
+}
+
+/// Compares each RTC reading with the previous one advanced by the uptime
+/// elapsed in between
+pub struct JumpDetector {
+    tolerance_seconds: i64,
+    last_time: Option<DateTime>,
+    /// Uptime of the last reading, unknown for a time restored after restart
+    last_uptime: Option<u32>,
+}
+
+impl JumpDetector {
+    /// Differences within `tolerance_seconds` (uptime counter resolution,
+    /// RTC drift and its correction) are not reported
+    pub fn new(tolerance_seconds: i64) -> Self {
+        Self { tolerance_seconds, last_time: None, last_uptime: None }
+    }
+
+    /// Use the last time known before restart, the time spent powered off
+    /// is unknown, so only backward jumps can be detected for the first
+    /// reading
+    pub fn restore(&mut self, last_time: DateTime) {
+        self.last_time = Some(last_time);
+        self.last_uptime = None;
+    }
+
+    /// Check the RTC time `now` read at `uptime_seconds` of the uptime counter
+    pub fn check(&mut self, now: &DateTime, uptime_seconds: u32) -> Option<TimeJump> {
+        // Time spent powered off is unknown, only a backward jump can be seen
+        let forward_known = self.last_uptime.is_some();
+
+        let expected = self.last_time.map(|last| match self.last_uptime {
+            Some(last_uptime) => {
+                last.to_unix_seconds() + uptime_seconds.wrapping_sub(last_uptime) as i64
+            },
+            None => last.to_unix_seconds(),
+        });
+
+        self.last_time = Some(*now);
+        self.last_uptime = Some(uptime_seconds);
+
+        let difference = now.to_unix_seconds() - expected?;
+
+        if difference < -self.tolerance_seconds {
+            Some(TimeJump::Backwards(-difference))
+        } else if forward_known && difference > self.tolerance_seconds {
+            Some(TimeJump::Forward(difference))
+        } else {
+            None
+        }
+    }
+}
+
+pub fn write_last_time(output: &mut dyn Write, time: &DateTime) -> core::fmt::Result {
+    write!(output, "{}{}\r\n", LAST_TIME_KEY, time.to_unix_seconds())
+}
+
+/// Read the last time from the file content
+pub fn parse_last_time(data: &[u8]) -> Option<DateTime> {
+    let text = core::str::from_utf8(data).ok()?;
+
+    text.lines()
+        .find_map(|line| line.trim().strip_prefix(LAST_TIME_KEY))
+        .and_then(|value| value.trim().parse().ok())
+        .and_then(|seconds| DateTime::from_unix_seconds(seconds).ok())
+}
@@ -0,0 +1,17 @@
+//! Calendar and real time clock utilities shared by the RTC demos,
+//! independent on the microcontroller hardware
+#![no_std]
+
+pub mod alarm;
+pub mod calendar;
+pub mod clock;
+pub mod console;
+pub mod dcf77;
+pub mod drift;
+pub mod editor;
+pub mod fat;
+pub mod jump;
+pub mod nmea;
+pub mod rtc;
+pub mod schedule;
+pub mod zone;
@@ -0,0 +1,225 @@
+//! Parser of the NMEA 0183 sentences carrying date and time (RMC and ZDA)
+//! sent by serial GPS modules
+
+use core::fmt::{Display, Formatter};
+use crate::{calendar::{CalendarError, DateTime}, console::{LineBuffer, LineEvent}};
+
+/// Longest valid sentence including `$` and the checksum, without CR LF
+pub const MAX_SENTENCE_LENGTH: usize = 82;
+
+/// RMC sentences carry only two year digits
+const RMC_FIRST_YEAR: u16 = 2000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NmeaError {
+    /// Sentence does not start with `$`, has no `*` before the checksum
+    /// or is not ASCII
+    InvalidFormat,
+    InvalidChecksum,
+    /// Sentence does not carry time (or is not supported)
+    Unsupported,
+    /// Time or date fields are empty, the receiver does not know the time
+    NoTime,
+    /// Receiver reports the data as void (no fix)
+    NoFix,
+    InvalidField,
+    InvalidDateTime(CalendarError),
+}
+
+impl Display for NmeaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            NmeaError::InvalidFormat => write!(f, "Invalid sentence"),
+            NmeaError::InvalidChecksum => write!(f, "Checksum mismatch"),
+            NmeaError::Unsupported => write!(f, "Unsupported sentence"),
+            NmeaError::NoTime => write!(f, "No time"),
+            NmeaError::NoFix => write!(f, "No fix"),
+            NmeaError::InvalidField => write!(f, "Invalid field"),
+            NmeaError::InvalidDateTime(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SentenceKind {
+    /// Recommended minimum data, date, time and fix status
+    Rmc,
+    /// Date and time
+    Zda,
+}
+
+/// UTC date and time received from the GPS, fractions of seconds
+/// are truncated
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct GpsTime {
+    pub kind: SentenceKind,
+    pub time: DateTime,
+}
+
+/// Collects sentences from the received bytes, a sentence may arrive
+/// in any number of pieces, lines not starting with `$` and lines too long
+/// for a sentence are skipped
+#[derive(Default)]
+pub struct SentenceReader {
+    line: LineBuffer<MAX_SENTENCE_LENGTH>,
+    complete: bool,
+}
+
+impl SentenceReader {
+    /// Feed a received byte, returns the sentence completed by it (without
+    /// line terminators), which is valid until the next call
+    pub fn feed(&mut self, byte: u8) -> Option<&str> {
+        if self.complete {
+            self.line.clear();
+            self.complete = false;
+        }
+
+        match self.line.push(byte) {
+            LineEvent::Complete => {
+                self.complete = true;
+                // Empty lines between CR and LF are skipped as well
+                self.line.line().filter(|text| text.starts_with('$'))
+            },
+            _ => None,
+        }
+    }
+
+    /// Drop the partially received sentence, for example after a receive
+    /// error
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.complete = false;
+    }
+}
+
+/// Parse a single sentence (without line terminators), talker ID is not
+/// checked so that sentences from all satellite systems are accepted
+/// (`$GPRMC`, `$GNRMC`, ...)
+pub fn parse_sentence(sentence: &str) -> Result<GpsTime, NmeaError> {
+    let data = verify_checksum(sentence)?;
+
+    // Sentences are ASCII, fields are sliced by bytes
+    if !data.is_ascii() {
+        return Err(NmeaError::InvalidFormat);
+    }
+
+    let mut fields = data.split(',');
+
+    let address = fields.next().ok_or(NmeaError::InvalidFormat)?;
+
+    if address.len() != 5 {
+        return Err(NmeaError::Unsupported);
+    }
+
+    match &address[2..] {
+        "RMC" => parse_rmc(&mut fields),
+        "ZDA" => parse_zda(&mut fields),
+        _ => Err(NmeaError::Unsupported),
+    }
+}
+
+/// Check the sentence checksum (XOR of all characters between `$` and `*`),
+/// returns the sentence data without `$` and the checksum
+pub fn verify_checksum(sentence: &str) -> Result<&str, NmeaError> {
+    let body = sentence.trim_end().strip_prefix('$').ok_or(NmeaError::InvalidFormat)?;
+    let (data, checksum) = body.split_once('*').ok_or(NmeaError::InvalidFormat)?;
+
+    if checksum.len() != 2 {
+        return Err(NmeaError::InvalidFormat);
+    }
+
+    let expected = u8::from_str_radix(checksum, 16).map_err(|_| NmeaError::InvalidFormat)?;
+    let computed = data.bytes().fold(0, |sum, byte| sum ^ byte);
+
+    if computed == expected {
+        Ok(data)
+    } else {
+        Err(NmeaError::InvalidChecksum)
+    }
+}
+
+/// `hhmmss.ss,A,llll.ll,a,yyyyy.yy,a,x.x,x.x,ddmmyy,...`
+fn parse_rmc<'a>(fields: &mut impl Iterator<Item = &'a str>) -> Result<GpsTime, NmeaError> {
+    let time = fields.next().ok_or(NmeaError::InvalidFormat)?;
+    let status = fields.next().ok_or(NmeaError::InvalidFormat)?;
+    // Latitude, N/S, longitude, E/W, speed and course are not needed
+    let date = fields.nth(6).ok_or(NmeaError::InvalidFormat)?;
+
+    if time.is_empty() || date.is_empty() {
+        return Err(NmeaError::NoTime);
+    }
+
+    if status != "A" {
+        return Err(NmeaError::NoFix);
+    }
+
+    let (hours, minutes, seconds) = parse_time(time)?;
+
+    if date.len() != 6 {
+        return Err(NmeaError::InvalidField);
+    }
+
+    let day = parse_number(&date[0..2])?;
+    let month = parse_number(&date[2..4])?;
+    let year = RMC_FIRST_YEAR + parse_number(&date[4..6])?;
+
+    Ok(GpsTime {
+        kind: SentenceKind::Rmc,
+        time: DateTime::new(year, month as u8, day as u8, hours, minutes, seconds)
+            .map_err(NmeaError::InvalidDateTime)?,
+    })
+}
+
+/// `hhmmss.ss,dd,mm,yyyy,zh,zm`, time is sent by the receiver only when
+/// known, there is no fix status
+fn parse_zda<'a>(fields: &mut impl Iterator<Item = &'a str>) -> Result<GpsTime, NmeaError> {
+    let time = fields.next().ok_or(NmeaError::InvalidFormat)?;
+    let day = fields.next().ok_or(NmeaError::InvalidFormat)?;
+    let month = fields.next().ok_or(NmeaError::InvalidFormat)?;
+    let year = fields.next().ok_or(NmeaError::InvalidFormat)?;
+
+    if time.is_empty() || day.is_empty() || month.is_empty() || year.is_empty() {
+        return Err(NmeaError::NoTime);
+    }
+
+    let (hours, minutes, seconds) = parse_time(time)?;
+
+    Ok(GpsTime {
+        kind: SentenceKind::Zda,
+        time: DateTime::new(
+            parse_number(year)?,
+            parse_number(month)? as u8,
+            parse_number(day)? as u8,
+            hours, minutes, seconds
+        ).map_err(NmeaError::InvalidDateTime)?,
+    })
+}
+
+/// `hhmmss` optionally followed by fractions of seconds
+fn parse_time(field: &str) -> Result<(u8, u8, u8), NmeaError> {
+    let whole = field.split('.').next().unwrap_or("");
+
+    if whole.len() != 6 {
+        return Err(NmeaError::InvalidField);
+    }
+
+    Ok((
+        parse_number(&whole[0..2])? as u8,
+        parse_number(&whole[2..4])? as u8,
+        parse_number(&whole[4..6])? as u8,
+    ))
+}
+
+fn parse_number(field: &str) -> Result<u16, NmeaError> {
+    if !field.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(NmeaError::InvalidField);
+    }
+
+    field.parse().map_err(|_| NmeaError::InvalidField)
+}
+
+/// RTC should be set from the GPS when their times differ by more than
+/// `threshold_seconds`
+pub fn needs_update(rtc: &DateTime, gps: &DateTime, threshold_seconds: i64) -> bool {
+    (rtc.to_unix_seconds() - gps.to_unix_seconds()).abs() > threshold_seconds
+}
@@ -0,0 +1,127 @@
+//! Conversion between calendar values and the PCF8563 driver, and access
+//! to the PCF8563 registers not exposed by the driver
+
+use core::fmt::{Display, Formatter};
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use crate::{calendar::{CalendarError, DateTime}, clock::ClockReading};
+
+/// PCF8563 I2C address
+pub const PCF8563_ADDRESS: u8 = 0x51;
+const REGISTER_VL_SECONDS: u8 = 0x02;
+const VOLTAGE_LOW_FLAG: u8 = 0b1000_0000;
+const REGISTER_CENTURY_MONTHS: u8 = 0x07;
+const CENTURY_FLAG: u8 = 0b1000_0000;
+
+/// PCF8563 stores two year digits and a century flag, which is toggled
+/// when the years overflow from 99 to 00, years are counted from 2000
+/// with the flag cleared and from 2100 with the flag set
+pub const PCF8563_FIRST_YEAR: u16 = 2000;
+pub const PCF8563_LAST_YEAR: u16 = 2199;
+const YEARS_PER_CENTURY: u16 = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RtcConvertError {
+    YearOutOfRange,
+    InvalidValue(CalendarError),
+}
+
+impl Display for RtcConvertError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RtcConvertError::YearOutOfRange => write!(
+                f, "Year must be {}-{}", PCF8563_FIRST_YEAR, PCF8563_LAST_YEAR
+            ),
+            RtcConvertError::InvalidValue(error) => write!(f, "RTC: {}", error),
+        }
+    }
+}
+
+/// Validate date and time read from the PCF8563 together with
+/// the century flag
+pub fn from_pcf8563(
+    time: &pcf8563::DateTime,
+    century: bool,
+) -> Result<DateTime, RtcConvertError> {
+    if time.year as u16 >= YEARS_PER_CENTURY {
+        return Err(RtcConvertError::InvalidValue(CalendarError::InvalidYear));
+    }
+
+    let first_year = if century {
+        PCF8563_FIRST_YEAR + YEARS_PER_CENTURY
+    } else {
+        PCF8563_FIRST_YEAR
+    };
+
+    DateTime::new(
+        first_year + time.year as u16, time.month, time.day,
+        time.hours, time.minutes, time.seconds
+    ).map_err(RtcConvertError::InvalidValue)
+}
+
+/// Clock reading of the PCF8563 registers, the time is invalid when
+/// the clock integrity (VL) flag is set or the registers do not hold
+/// a valid time
+pub fn to_reading(time: &pcf8563::DateTime, century: bool, voltage_low: bool) -> ClockReading {
+    match from_pcf8563(time, century) {
+        Ok(time) => ClockReading { time, valid: !voltage_low },
+        Err(_) => ClockReading { time: DateTime::UNIX_EPOCH, valid: false },
+    }
+}
+
+/// Convert date and time into PCF8563 registers, week day is computed
+/// from the date, the century flag is returned by `century_flag`
+pub fn to_pcf8563(time: &DateTime) -> Result<pcf8563::DateTime, RtcConvertError> {
+    if !(PCF8563_FIRST_YEAR..=PCF8563_LAST_YEAR).contains(&time.year()) {
+        return Err(RtcConvertError::YearOutOfRange);
+    }
+
+    Ok(pcf8563::DateTime {
+        year: ((time.year() - PCF8563_FIRST_YEAR) % YEARS_PER_CENTURY) as u8,
+        month: time.month(),
+        weekday: time.weekday(),
+        day: time.day(),
+        hours: time.hours(),
+        minutes: time.minutes(),
+        seconds: time.seconds(),
+    })
+}
+
+/// Read the PCF8563 clock integrity (VL) flag, the flag is set when
+/// the supply voltage dropped too low (for example a dead backup battery)
+/// and the time can be wrong, it is cleared only by setting the time
+/// (writing the seconds register)
+pub fn read_voltage_low<I2C, E>(i2c: &mut I2C) -> Result<bool, E>
+where I2C: WriteRead<Error = E> {
+    let mut seconds = [0u8];
+    i2c.write_read(PCF8563_ADDRESS, &[REGISTER_VL_SECONDS], &mut seconds)?;
+    Ok(seconds[0] & VOLTAGE_LOW_FLAG != 0)
+}
+
+/// PCF8563 century flag value for the given time
+pub fn century_flag(time: &DateTime) -> bool {
+    time.year() >= PCF8563_FIRST_YEAR + YEARS_PER_CENTURY
+}
+
+/// Read the century flag, the driver masks it out of the month register
+pub fn read_century<I2C, E>(i2c: &mut I2C) -> Result<bool, E>
+where I2C: WriteRead<Error = E> {
+    let mut months = [0u8];
+    i2c.write_read(PCF8563_ADDRESS, &[REGISTER_CENTURY_MONTHS], &mut months)?;
+    Ok(months[0] & CENTURY_FLAG != 0)
+}
+
+/// Write the century flag, the driver clears it when setting the date,
+/// so it needs to be written after the date
+pub fn write_century<I2C, E>(i2c: &mut I2C, century: bool) -> Result<(), E>
+where I2C: Write<Error = E> + WriteRead<Error = E> {
+    let mut months = [0u8];
+    i2c.write_read(PCF8563_ADDRESS, &[REGISTER_CENTURY_MONTHS], &mut months)?;
+
+    let months = if century {
+        months[0] | CENTURY_FLAG
+    } else {
+        months[0] & !CENTURY_FLAG
+    };
+
+    i2c.write(PCF8563_ADDRESS, &[REGISTER_CENTURY_MONTHS, months])
+}
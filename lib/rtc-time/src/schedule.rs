@@ -0,0 +1,329 @@
+//! Cron-like schedules and a scheduler firing registered tasks at matching
+//! times of a real time clock
+
+use core::fmt::{Display, Formatter};
+use crate::calendar::{DateTime, SECONDS_PER_DAY};
+
+/// Search for the next match gives up after this number of steps, which
+/// is enough to find a match within several years for any valid schedule
+const MAX_SEARCH_STEPS: usize = 5000;
+
+/// Clock going backwards by up to this time repeats times, for example
+/// when the daylight saving time ends, a longer change is a correction
+/// of the clock (as in cron)
+pub const MAX_REPEAT_SECONDS: i64 = 3*3600;
+
+/// Matches of a task missed between two polls fire up to this number
+/// of times, the later ones are skipped (for example when the clock jumped
+/// forward by a day)
+pub const MAX_MISSED: u8 = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ScheduleError {
+    /// Schedule text does not have six fields
+    FieldCount,
+    InvalidField,
+    ValueOutOfRange,
+    /// All scheduler slots are used
+    Full,
+}
+
+impl Display for ScheduleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ScheduleError::FieldCount => write!(f, "Expected 6 fields"),
+            ScheduleError::InvalidField => write!(f, "Invalid field"),
+            ScheduleError::ValueOutOfRange => write!(f, "Value out of range"),
+            ScheduleError::Full => write!(f, "Too many tasks"),
+        }
+    }
+}
+
+/// Set of matching second, minute, hour, day, month and week day values
+/// stored as bit masks
+///
+/// Text form has six fields `second minute hour day month weekday`,
+/// each field is `*`, a value, a range `a-b`, a step `*/n` or `a-b/n`
+/// or a comma separated list of those, week days are 0 - 6 starting
+/// with Sunday. As in cron, when both day and week day are restricted,
+/// a day matching either of them matches.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Schedule {
+    seconds: u64,
+    minutes: u64,
+    hours: u32,
+    days: u32,
+    months: u16,
+    weekdays: u8,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Schedule {
+    /// Every `period` seconds, aligned to the start of a minute
+    pub fn every_seconds(period: u8) -> Self {
+        Self { seconds: step_mask(0, 59, period), ..Self::every_second() }
+    }
+
+    /// Every `period` minutes at zero seconds, aligned to the start of an hour
+    pub fn every_minutes(period: u8) -> Self {
+        Self { seconds: 1, minutes: step_mask(0, 59, period), ..Self::every_second() }
+    }
+
+    /// Once per hour at the given minute
+    pub fn hourly_at(minute: u8) -> Self {
+        Self { seconds: 1, minutes: 1 << minute.min(59), ..Self::every_second() }
+    }
+
+    /// Once per day at the given time
+    pub fn daily_at(hour: u8, minute: u8) -> Self {
+        Self { hours: 1 << hour.min(23), ..Self::hourly_at(minute) }
+    }
+
+    fn every_second() -> Self {
+        Self {
+            seconds: step_mask(0, 59, 1),
+            minutes: step_mask(0, 59, 1),
+            hours: step_mask(0, 23, 1) as u32,
+            days: step_mask(1, 31, 1) as u32,
+            months: step_mask(1, 12, 1) as u16,
+            weekdays: step_mask(0, 6, 1) as u8,
+            days_restricted: false,
+            weekdays_restricted: false,
+        }
+    }
+
+    /// Parse the six field text form, for example `0 */10 * * * *`
+    pub fn parse(text: &str) -> Result<Self, ScheduleError> {
+        let mut fields = text.split_whitespace();
+        let mut next_field = || fields.next().ok_or(ScheduleError::FieldCount);
+
+        let seconds = parse_field(next_field()?, 0, 59)?;
+        let minutes = parse_field(next_field()?, 0, 59)?;
+        let hours = parse_field(next_field()?, 0, 23)?;
+        let days_field = next_field()?;
+        let months = parse_field(next_field()?, 1, 12)?;
+        let weekdays_field = next_field()?;
+
+        if fields.next().is_some() {
+            return Err(ScheduleError::FieldCount);
+        }
+
+        Ok(Self {
+            seconds,
+            minutes,
+            hours: hours as u32,
+            days: parse_field(days_field, 1, 31)? as u32,
+            months: months as u16,
+            weekdays: parse_field(weekdays_field, 0, 6)? as u8,
+            days_restricted: days_field != "*",
+            weekdays_restricted: weekdays_field != "*",
+        })
+    }
+
+    /// Schedule matches only at some hours of a day, it is not run again
+    /// at repeated times
+    pub fn has_fixed_hours(&self) -> bool {
+        self.hours != step_mask(0, 23, 1) as u32
+    }
+
+    pub fn matches(&self, time: &DateTime) -> bool {
+        self.date_matches(time)
+            && self.hours & (1 << time.hours()) != 0
+            && self.minutes & (1 << time.minutes()) != 0
+            && self.seconds & (1 << time.seconds()) != 0
+    }
+
+    fn date_matches(&self, time: &DateTime) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday()) != 0;
+
+        let day_matches = match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        };
+
+        day_matches && self.months & (1 << time.month()) != 0
+    }
+
+    /// First matching time after `time`, `None` if there is none within
+    /// the search limit (for example 31st of February) or the calendar range
+    pub fn next_after(&self, time: &DateTime) -> Option<DateTime> {
+        let mut candidate = time.add_seconds(1).ok()?;
+
+        for _ in 0..MAX_SEARCH_STEPS {
+            let (year, month) = (candidate.year(), candidate.month());
+            let midnight = DateTime::new(year, month, candidate.day(), 0, 0, 0).ok()?;
+            let seconds_of_day = candidate.to_unix_seconds() - midnight.to_unix_seconds();
+
+            candidate = if self.months & (1 << month) == 0 {
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                DateTime::new(year, month, 1, 0, 0, 0).ok()?
+            } else if !self.date_matches(&candidate) {
+                midnight.add_seconds(SECONDS_PER_DAY).ok()?
+            } else if self.hours & (1 << candidate.hours()) == 0 {
+                midnight.add_seconds((seconds_of_day/3600 + 1)*3600).ok()?
+            } else if self.minutes & (1 << candidate.minutes()) == 0 {
+                midnight.add_seconds((seconds_of_day/60 + 1)*60).ok()?
+            } else {
+                // Next matching second within the current minute
+                let later_seconds = self.seconds >> candidate.seconds();
+
+                if later_seconds == 0 {
+                    midnight.add_seconds((seconds_of_day/60 + 1)*60).ok()?
+                } else {
+                    return candidate.add_seconds(later_seconds.trailing_zeros() as i64).ok();
+                }
+            };
+        }
+
+        None
+    }
+}
+
+/// Bits `first..=last` set with the given step
+fn step_mask(first: u8, last: u8, step: u8) -> u64 {
+    (first..=last).step_by(step.max(1) as usize).fold(0, |mask, value| mask | (1 << value))
+}
+
+fn parse_field(field: &str, min: u8, max: u8) -> Result<u64, ScheduleError> {
+    field.split(',').try_fold(0, |mask, item| Ok(mask | parse_item(item, min, max)?))
+}
+
+/// `*`, `value`, `first-last`, optionally followed by `/step`
+fn parse_item(item: &str, min: u8, max: u8) -> Result<u64, ScheduleError> {
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, parse_value(step)?),
+        None => (item, 1),
+    };
+
+    if step == 0 {
+        return Err(ScheduleError::ValueOutOfRange);
+    }
+
+    let (first, last) = if range == "*" {
+        (min, max)
+    } else if let Some((first, last)) = range.split_once('-') {
+        (parse_value(first)?, parse_value(last)?)
+    } else {
+        let value = parse_value(range)?;
+        // `5/15` means starting at 5 with the step of 15
+        (value, if item.contains('/') { max } else { value })
+    };
+
+    if first < min || last > max || first > last {
+        return Err(ScheduleError::ValueOutOfRange);
+    }
+
+    Ok(step_mask(first, last, step))
+}
+
+fn parse_value(text: &str) -> Result<u8, ScheduleError> {
+    if text.is_empty() || !text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(ScheduleError::InvalidField);
+    }
+
+    text.parse().map_err(|_| ScheduleError::ValueOutOfRange)
+}
+
+/// Task due at the `scheduled` time
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Fired<T> {
+    pub task: T,
+    pub scheduled: DateTime,
+}
+
+struct Entry<T> {
+    task: T,
+    schedule: Schedule,
+    next: Option<DateTime>,
+    /// Matches fired since the task was last waiting for a future match
+    missed: u8,
+}
+
+/// Fires registered tasks when their schedule matches. Tasks are checked
+/// whenever the application polls the scheduler, each match passed since
+/// the last poll fires exactly once (late), up to `MAX_MISSED` matches
+/// of the same task.
+pub struct Scheduler<T, const N: usize> {
+    entries: [Option<Entry<T>>; N],
+    last_poll: Option<DateTime>,
+}
+
+impl<T, const N: usize> Default for Scheduler<T, N> {
+    fn default() -> Self {
+        Self { entries: core::array::from_fn(|_| None), last_poll: None }
+    }
+}
+
+impl<T: Copy, const N: usize> Scheduler<T, N> {
+    /// Register a task, the first match is searched after the next poll time
+    pub fn add(&mut self, task: T, schedule: Schedule) -> Result<(), ScheduleError> {
+        let free = self.entries.iter_mut()
+            .find(|entry| entry.is_none())
+            .ok_or(ScheduleError::Full)?;
+
+        let next = self.last_poll.and_then(|last| schedule.next_after(&last));
+        *free = Some(Entry { task, schedule, next, missed: 0 });
+        Ok(())
+    }
+
+    /// Return the earliest task due at `now` and plan its next run, call
+    /// repeatedly until `None` to get all due tasks, missed matches
+    /// in the order of their times. Nothing fires on the first poll.
+    ///
+    /// When the clock goes back by up to `MAX_REPEAT_SECONDS`, tasks with
+    /// fixed hours keep their planned runs, so they do not fire twice
+    /// at repeated times, the other tasks are planned again from `now`.
+    /// After a longer change, all tasks are planned again from `now`.
+    pub fn poll(&mut self, now: &DateTime) -> Option<Fired<T>> {
+        match self.last_poll {
+            Some(last) if last <= *now => {},
+            Some(last) if last.to_unix_seconds() - now.to_unix_seconds() <= MAX_REPEAT_SECONDS => {
+                let entries = self.entries.iter_mut().flatten();
+
+                for entry in entries.filter(|entry| !entry.schedule.has_fixed_hours()) {
+                    entry.next = entry.schedule.next_after(now);
+                    entry.missed = 0;
+                }
+            },
+            _ => {
+                for entry in self.entries.iter_mut().flatten() {
+                    entry.next = entry.schedule.next_after(now);
+                    entry.missed = 0;
+                }
+            },
+        }
+
+        self.last_poll = Some(*now);
+
+        let entry = self.entries.iter_mut()
+            .flatten()
+            .filter(|entry| entry.next.is_some_and(|next| next <= *now))
+            .min_by_key(|entry| entry.next)?;
+
+        let scheduled = entry.next?;
+        entry.missed += 1;
+
+        entry.next = match entry.schedule.next_after(&scheduled) {
+            Some(next) if next <= *now && entry.missed >= MAX_MISSED => {
+                entry.schedule.next_after(now)
+            },
+            next => next,
+        };
+
+        if entry.next.is_none_or(|next| next > *now) {
+            entry.missed = 0;
+        }
+
+        Some(Fired { task: entry.task, scheduled })
+    }
+
+    /// Earliest planned run of any task
+    pub fn next_time(&self) -> Option<DateTime> {
+        self.entries.iter()
+            .flatten()
+            .filter_map(|entry| entry.next)
+            .min()
+    }
+}
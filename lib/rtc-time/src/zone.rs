@@ -0,0 +1,170 @@
+//! Conversion between UTC time kept by the RTC and local time using a fixed
+//! offset and a daylight saving rule, and ISO 8601 formatting
+
+use core::fmt::Write;
+use crate::calendar::{days_in_month, CalendarError, DateTime};
+
+/// Daylight saving time rule, daylight time is one hour ahead
+/// of the standard time
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DstRule {
+    None,
+    /// Last Sunday in March 1:00 UTC to last Sunday in October 1:00 UTC
+    Eu,
+    /// Second Sunday in March 2:00 to first Sunday in November 2:00
+    /// local time
+    Us,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TimeZone {
+    /// Standard time offset from UTC in minutes, east is positive
+    pub offset_minutes: i16,
+    pub dst: DstRule,
+    pub standard_name: &'static str,
+    pub daylight_name: &'static str,
+}
+
+pub const UTC: TimeZone = TimeZone {
+    offset_minutes: 0, dst: DstRule::None, standard_name: "UTC", daylight_name: "UTC",
+};
+
+pub const WESTERN_EUROPE: TimeZone = TimeZone {
+    offset_minutes: 0, dst: DstRule::Eu, standard_name: "WET", daylight_name: "WEST",
+};
+
+pub const CENTRAL_EUROPE: TimeZone = TimeZone {
+    offset_minutes: 60, dst: DstRule::Eu, standard_name: "CET", daylight_name: "CEST",
+};
+
+pub const EASTERN_EUROPE: TimeZone = TimeZone {
+    offset_minutes: 120, dst: DstRule::Eu, standard_name: "EET", daylight_name: "EEST",
+};
+
+pub const US_EASTERN: TimeZone = TimeZone {
+    offset_minutes: -300, dst: DstRule::Us, standard_name: "EST", daylight_name: "EDT",
+};
+
+pub const US_CENTRAL: TimeZone = TimeZone {
+    offset_minutes: -360, dst: DstRule::Us, standard_name: "CST", daylight_name: "CDT",
+};
+
+pub const US_MOUNTAIN: TimeZone = TimeZone {
+    offset_minutes: -420, dst: DstRule::Us, standard_name: "MST", daylight_name: "MDT",
+};
+
+pub const US_PACIFIC: TimeZone = TimeZone {
+    offset_minutes: -480, dst: DstRule::Us, standard_name: "PST", daylight_name: "PDT",
+};
+
+const DST_SHIFT_MINUTES: i16 = 60;
+
+/// Local date and time together with the offset used
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LocalTime {
+    pub time: DateTime,
+    pub offset_minutes: i16,
+    pub name: &'static str,
+}
+
+impl TimeZone {
+    /// Daylight saving time is in effect at the given UTC time
+    pub fn is_dst(&self, utc: &DateTime) -> bool {
+        let year = utc.year();
+
+        let (start, end) = match self.dst {
+            DstRule::None => return false,
+            DstRule::Eu => (
+                utc_at(year, 3, last_sunday(year, 3), 60),
+                utc_at(year, 10, last_sunday(year, 10), 60),
+            ),
+            DstRule::Us => (
+                utc_at(year, 3, nth_sunday(year, 3, 2), 120 - self.offset_minutes as i64),
+                utc_at(
+                    year, 11, nth_sunday(year, 11, 1),
+                    120 - (self.offset_minutes + DST_SHIFT_MINUTES) as i64
+                ),
+            ),
+        };
+
+        let now = utc.to_unix_seconds();
+        now >= start && now < end
+    }
+
+    /// Offset from UTC in minutes at the given UTC time
+    pub fn offset_at(&self, utc: &DateTime) -> i16 {
+        if self.is_dst(utc) {
+            self.offset_minutes + DST_SHIFT_MINUTES
+        } else {
+            self.offset_minutes
+        }
+    }
+
+    pub fn to_local(&self, utc: &DateTime) -> Result<LocalTime, CalendarError> {
+        let dst = self.is_dst(utc);
+        let offset_minutes = self.offset_at(utc);
+
+        Ok(LocalTime {
+            time: utc.add_seconds(offset_minutes as i64*60)?,
+            offset_minutes,
+            name: if dst { self.daylight_name } else { self.standard_name },
+        })
+    }
+
+    /// UTC time of the given local time, the hour repeated at the end
+    /// of daylight saving time is taken as daylight time, the hour skipped
+    /// at its start as standard time
+    pub fn to_utc(&self, local: &DateTime) -> Result<DateTime, CalendarError> {
+        let standard = local.add_seconds(-(self.offset_minutes as i64)*60)?;
+        let daylight = standard.add_seconds(-(DST_SHIFT_MINUTES as i64)*60)?;
+
+        if self.is_dst(&daylight) {
+            Ok(daylight)
+        } else {
+            Ok(standard)
+        }
+    }
+}
+
+/// Write local time in the ISO 8601 extended format with the UTC offset,
+/// for example `2026-10-17T14:03:00+02:00`
+pub fn write_iso8601(output: &mut dyn Write, local: &LocalTime) -> core::fmt::Result {
+    let time = &local.time;
+    let sign = if local.offset_minutes < 0 { '-' } else { '+' };
+    let offset = local.offset_minutes.unsigned_abs();
+
+    write!(
+        output,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+        time.year(), time.month(), time.day(),
+        time.hours(), time.minutes(), time.seconds(),
+        sign, offset/60, offset % 60
+    )
+}
+
+/// Unix time of the given UTC date and minutes after midnight
+fn utc_at(year: u16, month: u8, day: u8, minutes: i64) -> i64 {
+    match DateTime::new(year, month, day, 0, 0, 0) {
+        Ok(midnight) => midnight.to_unix_seconds() + minutes*60,
+        Err(_) => i64::MAX,
+    }
+}
+
+/// Day of the `n`-th (one based) Sunday in the month
+fn nth_sunday(year: u16, month: u8, n: u8) -> u8 {
+    let first_weekday = DateTime::new(year, month, 1, 0, 0, 0)
+        .map(|first| first.weekday())
+        .unwrap_or(0);
+
+    1 + (7 - first_weekday) % 7 + 7*(n - 1)
+}
+
+fn last_sunday(year: u16, month: u8) -> u8 {
+    let last_day = days_in_month(year, month);
+
+    let last_weekday = DateTime::new(year, month, last_day, 0, 0, 0)
+        .map(|last| last.weekday())
+        .unwrap_or(0);
+
+    last_day - last_weekday
+}
@@ -0,0 +1,192 @@
+//! Calendar arithmetic, run on the host using
+//! `cargo test -p rtc-time --target x86_64-unknown-linux-gnu`
+
+use rtc_time::calendar::{
+    days_in_month, is_leap_year, CalendarError, DateTime, DAYS, SECONDS_PER_DAY,
+};
+
+fn date(year: u16, month: u8, day: u8) -> DateTime {
+    DateTime::new(year, month, day, 0, 0, 0).unwrap()
+}
+
+/// Following day computed by counting days of months, independent
+/// on the Unix time conversion
+fn next_day(year: u16, month: u8, day: u8) -> (u16, u8, u8) {
+    if day < days_in_month(year, month) {
+        (year, month, day + 1)
+    } else if month < 12 {
+        (year, month + 1, 1)
+    } else {
+        (year + 1, 1, 1)
+    }
+}
+
+#[test]
+fn every_day_round_trips() {
+    let first = date(1, 1, 1);
+    let (mut year, mut month, mut day) = (1, 1, 1);
+    let mut weekday = first.weekday();
+    let mut seconds = first.to_unix_seconds();
+
+    loop {
+        // Different time of each day, covering all hours, minutes and seconds
+        let time_of_day = seconds.rem_euclid(86399);
+        let time = DateTime::from_unix_seconds(seconds + time_of_day).unwrap();
+
+        assert_eq!((time.year(), time.month(), time.day()), (year, month, day));
+        assert_eq!(time.to_unix_seconds(), seconds + time_of_day);
+        assert_eq!(time.weekday(), weekday);
+
+        if (year, month, day) == (u16::MAX, 12, 31) {
+            break;
+        }
+
+        (year, month, day) = next_day(year, month, day);
+        weekday = (weekday + 1) % 7;
+        seconds += SECONDS_PER_DAY;
+    }
+}
+
+#[test]
+fn every_second_of_day_round_trips() {
+    let midnight = date(2026, 10, 17).to_unix_seconds();
+
+    for second in 0..SECONDS_PER_DAY {
+        let time = DateTime::from_unix_seconds(midnight + second).unwrap();
+
+        assert_eq!((time.year(), time.month(), time.day()), (2026, 10, 17));
+        assert_eq!(
+            (time.hours() as i64, time.minutes() as i64, time.seconds() as i64),
+            (second/3600, second/60 % 60, second % 60)
+        );
+        assert_eq!(time.to_unix_seconds(), midnight + second);
+    }
+}
+
+#[test]
+fn unix_epoch_is_zero() {
+    assert_eq!(DateTime::UNIX_EPOCH.to_unix_seconds(), 0);
+    assert_eq!(DateTime::from_unix_seconds(0), Ok(DateTime::UNIX_EPOCH));
+    assert_eq!(
+        DateTime::from_unix_seconds(-1),
+        DateTime::new(1969, 12, 31, 23, 59, 59)
+    );
+}
+
+#[test]
+fn known_unix_times() {
+    for (time, seconds) in [
+        (DateTime::new(2000, 1, 1, 0, 0, 0), 946_684_800),
+        (DateTime::new(2026, 10, 17, 14, 3, 0), 1_792_245_780),
+        (DateTime::new(2038, 1, 19, 3, 14, 7), i32::MAX as i64),
+        (DateTime::new(2106, 2, 7, 6, 28, 15), u32::MAX as i64),
+        (DateTime::new(1900, 1, 1, 0, 0, 0), -2_208_988_800),
+        (DateTime::new(1, 1, 1, 0, 0, 0), -62_135_596_800),
+    ] {
+        let time = time.unwrap();
+        assert_eq!(time.to_unix_seconds(), seconds, "{:?}", time);
+        assert_eq!(DateTime::from_unix_seconds(seconds), Ok(time));
+    }
+}
+
+#[test]
+fn unix_times_outside_calendar_are_rejected() {
+    let first = date(1, 1, 1).to_unix_seconds();
+    let last = DateTime::new(u16::MAX, 12, 31, 23, 59, 59).unwrap().to_unix_seconds();
+
+    assert_eq!(DateTime::from_unix_seconds(first - 1), Err(CalendarError::InvalidYear));
+    assert_eq!(DateTime::from_unix_seconds(last + 1), Err(CalendarError::InvalidYear));
+    assert_eq!(DateTime::from_unix_seconds(i64::MIN), Err(CalendarError::InvalidYear));
+    assert_eq!(DateTime::from_unix_seconds(i64::MAX), Err(CalendarError::InvalidYear));
+}
+
+#[test]
+fn weekdays_of_known_dates() {
+    for ((year, month, day), weekday) in [
+        ((1, 1, 1), "Monday"),
+        ((1900, 1, 1), "Monday"),
+        ((1900, 3, 1), "Thursday"),
+        ((1970, 1, 1), "Thursday"),
+        ((2000, 1, 1), "Saturday"),
+        ((2000, 2, 29), "Tuesday"),
+        ((2024, 2, 29), "Thursday"),
+        ((2026, 10, 17), "Saturday"),
+        ((2038, 1, 19), "Tuesday"),
+        ((2099, 12, 31), "Thursday"),
+        ((2100, 1, 1), "Friday"),
+        ((2100, 3, 1), "Monday"),
+        ((9999, 12, 31), "Friday"),
+    ] {
+        let time = date(year, month, day);
+        assert_eq!(DAYS[time.weekday() as usize], weekday, "{:?}", time);
+    }
+}
+
+#[test]
+fn century_leap_years() {
+    assert!(!is_leap_year(1900));
+    assert!(is_leap_year(2000));
+    assert!(!is_leap_year(2100));
+    assert!(is_leap_year(2400));
+    assert!(is_leap_year(2024));
+    assert!(!is_leap_year(2026));
+
+    assert_eq!(days_in_month(1900, 2), 28);
+    assert_eq!(days_in_month(2000, 2), 29);
+    assert_eq!(days_in_month(2100, 2), 28);
+}
+
+#[test]
+fn february_29_exists_only_in_leap_years() {
+    assert_eq!(DateTime::new(1900, 2, 29, 0, 0, 0), Err(CalendarError::InvalidDay));
+    assert!(DateTime::new(2000, 2, 29, 0, 0, 0).is_ok());
+    assert_eq!(DateTime::new(2100, 2, 29, 0, 0, 0), Err(CalendarError::InvalidDay));
+    assert_eq!(DateTime::new(2000, 2, 30, 0, 0, 0), Err(CalendarError::InvalidDay));
+}
+
+#[test]
+fn february_rolls_over_to_march() {
+    let leap_day = DateTime::new(2000, 2, 29, 23, 59, 59).unwrap();
+    assert_eq!(leap_day.add_seconds(1), DateTime::new(2000, 3, 1, 0, 0, 0));
+    assert_eq!(date(2000, 3, 1).add_days(-1), Ok(date(2000, 2, 29)));
+
+    for year in [1900, 2026, 2100] {
+        let last_day = DateTime::new(year, 2, 28, 23, 59, 59).unwrap();
+        assert_eq!(last_day.add_seconds(1), DateTime::new(year, 3, 1, 0, 0, 0));
+        assert_eq!(date(year, 3, 1).add_days(-1), Ok(date(year, 2, 28)));
+    }
+
+    assert_eq!(date(2000, 2, 28).days_until(&date(2000, 3, 1)), 2);
+    assert_eq!(date(2100, 2, 28).days_until(&date(2100, 3, 1)), 1);
+}
+
+#[test]
+fn day_arithmetic() {
+    let time = DateTime::new(2026, 10, 17, 14, 3, 0).unwrap();
+
+    assert_eq!(time.add_days(76), DateTime::new(2027, 1, 1, 14, 3, 0));
+    assert_eq!(time.add_days(-365), DateTime::new(2025, 10, 17, 14, 3, 0));
+    assert_eq!(time.add_seconds(-14*3600 - 3*60 - 1), DateTime::new(2026, 10, 16, 23, 59, 59));
+    assert_eq!(date(2026, 10, 17).days_until(&date(2024, 10, 17)), -730);
+    assert_eq!(time.add_days(i64::MAX), Err(CalendarError::InvalidYear));
+    assert_eq!(time.add_seconds(i64::MAX), Err(CalendarError::InvalidYear));
+}
+
+#[test]
+fn invalid_values_are_rejected() {
+    assert_eq!(DateTime::new(0, 1, 1, 0, 0, 0), Err(CalendarError::InvalidYear));
+    assert_eq!(DateTime::new(2026, 0, 1, 0, 0, 0), Err(CalendarError::InvalidMonth));
+    assert_eq!(DateTime::new(2026, 13, 1, 0, 0, 0), Err(CalendarError::InvalidMonth));
+    assert_eq!(DateTime::new(2026, 1, 0, 0, 0, 0), Err(CalendarError::InvalidDay));
+    assert_eq!(DateTime::new(2026, 4, 31, 0, 0, 0), Err(CalendarError::InvalidDay));
+    assert_eq!(DateTime::new(2026, 1, 1, 24, 0, 0), Err(CalendarError::InvalidTime));
+    assert_eq!(DateTime::new(2026, 1, 1, 0, 60, 0), Err(CalendarError::InvalidTime));
+    assert_eq!(DateTime::new(2026, 1, 1, 0, 0, 60), Err(CalendarError::InvalidTime));
+}
+
+#[test]
+fn ordering_is_chronological() {
+    assert!(DateTime::new(2026, 1, 31, 23, 59, 59).unwrap() < date(2026, 2, 1));
+    assert!(date(1999, 12, 31) < date(2000, 1, 1));
+    assert!(DateTime::new(2026, 10, 17, 0, 0, 1).unwrap() > date(2026, 10, 17));
+}
@@ -0,0 +1,187 @@
+//! Serial console command parsing, run on the host using
+//! `cargo test -p rtc-time --target x86_64-unknown-linux-gnu`
+
+use rtc_time::{
+    calendar::{CalendarError, DateTime},
+    console::{parse_command, Command, CommandError, LineBuffer, LineEvent},
+};
+
+fn feed<const N: usize>(buffer: &mut LineBuffer<N>, text: &[u8]) -> Vec<LineEvent> {
+    text.iter().map(|byte| buffer.push(*byte)).collect()
+}
+
+#[test]
+fn commands_are_parsed() {
+    let time = DateTime::new(2026, 10, 17, 14, 3, 0).unwrap();
+
+    assert_eq!(parse_command("get"), Ok(Command::Get));
+    assert_eq!(parse_command("help"), Ok(Command::Help));
+    assert_eq!(parse_command("set 2026-10-17 14:03:00"), Ok(Command::Set(time)));
+    assert_eq!(parse_command("  set   2026-10-17\t14:03:00  "), Ok(Command::Set(time)));
+    assert_eq!(parse_command(" get "), Ok(Command::Get));
+}
+
+#[test]
+fn short_fields_are_accepted() {
+    assert_eq!(
+        parse_command("set 2026-1-2 3:4:5"),
+        Ok(Command::Set(DateTime::new(2026, 1, 2, 3, 4, 5).unwrap()))
+    );
+}
+
+#[test]
+fn leap_day_is_accepted() {
+    assert_eq!(
+        parse_command("set 2028-02-29 23:59:59"),
+        Ok(Command::Set(DateTime::new(2028, 2, 29, 23, 59, 59).unwrap()))
+    );
+}
+
+#[test]
+fn fields_out_of_range_are_rejected() {
+    for (line, error) in [
+        ("set 0000-01-01 00:00:00", CalendarError::InvalidYear),
+        ("set 2026-13-01 00:00:00", CalendarError::InvalidMonth),
+        ("set 2026-00-01 00:00:00", CalendarError::InvalidMonth),
+        ("set 2026-02-30 00:00:00", CalendarError::InvalidDay),
+        ("set 2026-02-29 00:00:00", CalendarError::InvalidDay),
+        ("set 2026-04-31 00:00:00", CalendarError::InvalidDay),
+        ("set 2026-10-00 00:00:00", CalendarError::InvalidDay),
+        ("set 2026-10-17 24:00:00", CalendarError::InvalidTime),
+        ("set 2026-10-17 12:60:00", CalendarError::InvalidTime),
+        ("set 2026-10-17 12:00:60", CalendarError::InvalidTime),
+    ] {
+        assert_eq!(parse_command(line), Err(CommandError::InvalidDateTime(error)), "{}", line);
+    }
+}
+
+#[test]
+fn malformed_lines_are_rejected() {
+    for line in [
+        "set",
+        "set 2026-10-17",
+        "set 2026-10-17 14:03:00 extra",
+        "get now",
+        "help me",
+        "set 2026/10/17 14:03:00",
+        "set 2026-10-17 14-03-00",
+        "set 2026-10-17-01 14:03:00",
+        "set 2026-10 14:03:00",
+        "set 2026-10-17 14:03",
+        "set 2026-10-17 14:03:00:00",
+        "set 02026-10-17 14:03:00",
+        "set 2026-010-17 14:03:00",
+        "set 2026-10-17 014:03:00",
+        "set 2026--17 14:03:00",
+        "set +2026-10-17 14:03:00",
+        "set 2026-10-17 14:03:0x",
+    ] {
+        assert_eq!(parse_command(line), Err(CommandError::InvalidFormat), "{}", line);
+    }
+}
+
+#[test]
+fn unknown_and_empty_commands_are_rejected() {
+    assert_eq!(parse_command(""), Err(CommandError::Empty));
+    assert_eq!(parse_command("   \t"), Err(CommandError::Empty));
+    assert_eq!(parse_command("GET"), Err(CommandError::UnknownCommand));
+    assert_eq!(parse_command("reset"), Err(CommandError::UnknownCommand));
+    assert_eq!(parse_command("2026-10-17"), Err(CommandError::UnknownCommand));
+}
+
+#[test]
+fn line_is_completed_by_cr_or_lf() {
+    for terminator in [b'\r', b'\n'] {
+        let mut buffer = LineBuffer::<16>::default();
+
+        assert_eq!(feed(&mut buffer, b"get"), [b'g', b'e', b't'].map(LineEvent::Stored));
+        assert_eq!(buffer.push(terminator), LineEvent::Complete);
+        assert_eq!(buffer.line(), Some("get"));
+    }
+}
+
+#[test]
+fn crlf_completes_an_empty_line_after_clear() {
+    let mut buffer = LineBuffer::<16>::default();
+
+    feed(&mut buffer, b"get");
+    assert_eq!(buffer.push(b'\r'), LineEvent::Complete);
+    assert_eq!(buffer.line(), Some("get"));
+    buffer.clear();
+
+    // LF of the CR LF pair completes an empty line, ignored by the parser
+    assert_eq!(buffer.push(b'\n'), LineEvent::Complete);
+    assert_eq!(buffer.line(), Some(""));
+    assert_eq!(parse_command(buffer.line().unwrap()), Err(CommandError::Empty));
+}
+
+#[test]
+fn backspace_erases_last_character() {
+    for erase in [0x08, 0x7f] {
+        let mut buffer = LineBuffer::<16>::default();
+
+        feed(&mut buffer, b"gex");
+        assert_eq!(buffer.push(erase), LineEvent::Erased);
+        feed(&mut buffer, b"t");
+        assert_eq!(buffer.line(), Some("get"));
+    }
+}
+
+#[test]
+fn backspace_on_empty_line_is_ignored() {
+    let mut buffer = LineBuffer::<16>::default();
+
+    assert_eq!(buffer.push(0x08), LineEvent::Ignored);
+    assert_eq!(buffer.push(0x7f), LineEvent::Ignored);
+    feed(&mut buffer, b"a");
+    assert_eq!(feed(&mut buffer, &[0x08, 0x08]), [LineEvent::Erased, LineEvent::Ignored]);
+    assert_eq!(buffer.line(), Some(""));
+}
+
+#[test]
+fn control_characters_are_ignored() {
+    let mut buffer = LineBuffer::<16>::default();
+
+    assert_eq!(
+        feed(&mut buffer, &[b'g', 0x1b, b'e', b'\t', 0x00, b't', 0xc3, 0xa9]),
+        [
+            LineEvent::Stored(b'g'),
+            LineEvent::Ignored,
+            LineEvent::Stored(b'e'),
+            LineEvent::Ignored,
+            LineEvent::Ignored,
+            LineEvent::Stored(b't'),
+            LineEvent::Ignored,
+            LineEvent::Ignored,
+        ]
+    );
+    assert_eq!(buffer.line(), Some("get"));
+}
+
+#[test]
+fn longest_line_fits() {
+    let line = "set 2026-10-17 14:03:00";
+    let mut buffer = LineBuffer::<23>::default();
+
+    assert!(feed(&mut buffer, line.as_bytes()).iter().all(|event| *event != LineEvent::Ignored));
+    assert_eq!(buffer.push(b'\r'), LineEvent::Complete);
+    assert_eq!(buffer.line(), Some(line));
+}
+
+#[test]
+fn too_long_line_is_dropped() {
+    let mut buffer = LineBuffer::<8>::default();
+
+    let events = feed(&mut buffer, b"set 2026-10-17 14:03:00");
+    assert_eq!(events[..8], b"set 2026".map(LineEvent::Stored));
+    assert!(events[8..].iter().all(|event| *event == LineEvent::Ignored));
+
+    // Erasing characters does not make the line valid again
+    assert_eq!(buffer.push(0x08), LineEvent::Erased);
+    assert_eq!(buffer.push(b'\r'), LineEvent::Complete);
+    assert_eq!(buffer.line(), None);
+
+    buffer.clear();
+    feed(&mut buffer, b"get");
+    assert_eq!(buffer.line(), Some("get"));
+}
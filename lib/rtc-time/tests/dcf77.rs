@@ -0,0 +1,264 @@
+//! DCF77 decoding of synthetic pulse trains, run on the host using
+//! `cargo test -p rtc-time --target x86_64-unknown-linux-gnu`
+
+use rtc_time::{
+    calendar::DateTime,
+    dcf77::{decode, ConfirmingDecoder, DcfError, DcfTime, DecodedMinute, Dcf77Decoder},
+};
+
+const ZERO_MS: u32 = 100;
+const ONE_MS: u32 = 200;
+
+/// Pulse start and length in milliseconds
+type Pulse = (u32, u32);
+
+fn minute(hours: u8, minutes: u8, summer_time: bool) -> DcfTime {
+    DcfTime { time: DateTime::new(2026, 10, 17, hours, minutes, 0).unwrap(), summer_time }
+}
+
+/// Set `value` in BCD into `width` bits from `first`, returns the parity
+fn set_bcd(bits: &mut u64, first: u8, width: u8, value: u8) -> bool {
+    let bcd = (((value/10) << 4) | (value % 10)) as u64 & ((1 << width) - 1);
+    *bits |= bcd << first;
+    bcd.count_ones() % 2 == 1
+}
+
+/// Bits 0 - 58 transmitted during the minute before `minute`
+fn frame(minute: &DcfTime) -> u64 {
+    let time = &minute.time;
+    let mut bits = 1 << 20;
+    bits |= if minute.summer_time { 1 << 17 } else { 1 << 18 };
+
+    let parity = set_bcd(&mut bits, 21, 7, time.minutes());
+    bits |= (parity as u64) << 28;
+    let parity = set_bcd(&mut bits, 29, 6, time.hours());
+    bits |= (parity as u64) << 35;
+
+    let weekday = if time.weekday() == 0 { 7 } else { time.weekday() };
+    let parity = set_bcd(&mut bits, 36, 6, time.day())
+        ^ set_bcd(&mut bits, 42, 3, weekday)
+        ^ set_bcd(&mut bits, 45, 5, time.month())
+        ^ set_bcd(&mut bits, 50, 8, (time.year() - 2000) as u8);
+    bits |= (parity as u64) << 58;
+
+    bits
+}
+
+/// Pulses of the frames starting at `start_ms`, followed by the first pulse
+/// of the next minute, which completes the last frame
+fn pulses(frames: &[u64], start_ms: u32) -> Vec<Pulse> {
+    let mut pulses = Vec::new();
+    let mut time = start_ms;
+
+    for bits in frames {
+        for second in 0..59 {
+            let length = if (bits >> second) & 1 == 1 { ONE_MS } else { ZERO_MS };
+            pulses.push((time, length));
+            time = time.wrapping_add(1000);
+        }
+
+        // No pulse in the last second of the minute
+        time = time.wrapping_add(1000);
+    }
+
+    pulses.push((time, ZERO_MS));
+    pulses
+}
+
+fn feed(decoder: &mut Dcf77Decoder, pulses: &[Pulse]) -> Vec<Result<DcfTime, DcfError>> {
+    pulses.iter().filter_map(|(start, length)| decoder.pulse(*start, *length)).collect()
+}
+
+#[test]
+fn frame_is_decoded() {
+    let summer = minute(14, 3, true);
+    let winter = minute(23, 59, false);
+
+    assert_eq!(decode(frame(&summer)), Ok(summer));
+    assert_eq!(decode(frame(&winter)), Ok(winter));
+}
+
+#[test]
+fn minute_is_decoded_after_marker() {
+    let first = minute(14, 3, true);
+    let second = minute(14, 4, true);
+    let mut decoder = Dcf77Decoder::default();
+
+    // First minute only synchronizes the decoder to the marker
+    let results = feed(&mut decoder, &pulses(&[frame(&first), frame(&second)], 5000));
+
+    assert_eq!(results, [Ok(second)]);
+    assert!(decoder.is_synchronized());
+    assert_eq!(decoder.bit_count(), 1);
+}
+
+#[test]
+fn millisecond_counter_wraps() {
+    let minutes = [minute(14, 3, true), minute(14, 4, true), minute(14, 5, true)];
+    let frames = minutes.map(|minute| frame(&minute));
+    let mut decoder = Dcf77Decoder::default();
+
+    let results = feed(&mut decoder, &pulses(&frames, u32::MAX - 90_000));
+
+    assert_eq!(results, [Ok(minutes[1]), Ok(minutes[2])]);
+}
+
+#[test]
+fn parity_errors_are_detected() {
+    let time = minute(14, 3, true);
+
+    // Minute, hour and date bits with their parity
+    for bit in [21, 30, 40, 52] {
+        assert_eq!(decode(frame(&time) ^ (1 << bit)), Err(DcfError::InvalidParity), "{}", bit);
+    }
+
+    let mut decoder = Dcf77Decoder::default();
+    let results = feed(&mut decoder, &pulses(&[0, frame(&time) ^ (1 << 22)], 0));
+    assert_eq!(results, [Err(DcfError::InvalidParity)]);
+}
+
+#[test]
+fn invalid_frames_are_rejected() {
+    let bits = frame(&minute(14, 3, true));
+
+    assert_eq!(decode(bits | 1), Err(DcfError::InvalidFrame));
+    assert_eq!(decode(bits & !(1 << 20)), Err(DcfError::InvalidFrame));
+    assert_eq!(decode(bits | (1 << 18)), Err(DcfError::InvalidFrame));
+    assert_eq!(decode(bits & !(1 << 17)), Err(DcfError::InvalidFrame));
+}
+
+#[test]
+fn invalid_bcd_is_rejected() {
+    // Units digit 15 of the minutes with even parity
+    let bits = frame(&minute(14, 0, true)) | (0b1111 << 21);
+    assert_eq!(decode(bits), Err(DcfError::InvalidValue));
+}
+
+#[test]
+fn missing_minute_marker_is_detected() {
+    let time = minute(14, 3, true);
+    let mut pulses = pulses(&[0, frame(&time), frame(&time)], 0);
+
+    // Pulse in the 59th second of the second minute hides the marker
+    let (start, _) = pulses[59 + 58];
+    pulses.insert(59 + 59, (start + 1000, ZERO_MS));
+
+    let mut decoder = Dcf77Decoder::default();
+    let results = feed(&mut decoder, &pulses);
+
+    assert_eq!(results, [Err(DcfError::InvalidTiming)]);
+
+    // Synchronized again by the marker after the last minute
+    assert!(decoder.is_synchronized());
+}
+
+#[test]
+fn early_marker_reports_missing_bits() {
+    let first = minute(14, 3, true);
+    let second = minute(14, 4, true);
+    let mut pulses = pulses(&[0, frame(&first), frame(&second)], 0);
+
+    // Lost pulse looks like a marker in the middle of the minute, the bits
+    // after it are cut by the real marker
+    pulses.remove(59 + 30);
+
+    let mut decoder = Dcf77Decoder::default();
+
+    assert_eq!(
+        feed(&mut decoder, &pulses),
+        [Err(DcfError::MissingBits), Err(DcfError::MissingBits), Ok(second)]
+    );
+}
+
+#[test]
+fn short_glitches_are_ignored() {
+    let time = minute(14, 3, true);
+    let mut pulses = pulses(&[0, frame(&time)], 0);
+
+    for index in (10..pulses.len()).step_by(7).rev() {
+        let (start, _) = pulses[index];
+        pulses.insert(index, (start - 450, 15));
+    }
+
+    let mut decoder = Dcf77Decoder::default();
+    assert_eq!(feed(&mut decoder, &pulses), [Ok(time)]);
+}
+
+#[test]
+fn long_glitch_restarts_search() {
+    let first = minute(14, 3, true);
+    let second = minute(14, 4, true);
+    let third = minute(14, 5, true);
+    let mut pulses = pulses(&[0, frame(&first), frame(&second), frame(&third)], 0);
+
+    // Noise pulse between two seconds of the first minute
+    let (start, _) = pulses[59 + 20];
+    pulses.insert(59 + 20, (start - 500, 60));
+
+    // Carrier missing for too long in the last minute
+    let index = 3*59 + 10 + 1;
+    pulses[index].1 = 500;
+
+    let mut decoder = Dcf77Decoder::default();
+    let results = feed(&mut decoder, &pulses);
+
+    // Search restarts and finds the marker after the first minute
+    assert_eq!(
+        results,
+        [Err(DcfError::InvalidTiming), Ok(second), Err(DcfError::InvalidPulse)]
+    );
+}
+
+#[test]
+fn consecutive_minutes_are_confirmed() {
+    let minutes = [minute(14, 3, true), minute(14, 4, true), minute(14, 5, true)];
+    let frames: Vec<u64> = [0].into_iter().chain(minutes.iter().map(frame)).collect();
+    let mut decoder = ConfirmingDecoder::default();
+
+    let results: Vec<_> = pulses(&frames, 0).iter()
+        .filter_map(|(start, length)| decoder.pulse(*start, *length))
+        .collect();
+
+    assert_eq!(results, [
+        Ok(DecodedMinute { minute: minutes[0], confirmed: false }),
+        Ok(DecodedMinute { minute: minutes[1], confirmed: true }),
+        Ok(DecodedMinute { minute: minutes[2], confirmed: true }),
+    ]);
+}
+
+#[test]
+fn non_consecutive_minutes_are_not_confirmed() {
+    let minutes = [minute(14, 3, true), minute(14, 5, true), minute(14, 6, true)];
+    let frames: Vec<u64> = [0].into_iter().chain(minutes.iter().map(frame)).collect();
+    let mut decoder = ConfirmingDecoder::default();
+
+    let confirmed: Vec<bool> = pulses(&frames, 0).iter()
+        .filter_map(|(start, length)| decoder.pulse(*start, *length))
+        .map(|result| result.unwrap().confirmed)
+        .collect();
+
+    assert_eq!(confirmed, [false, false, true]);
+}
+
+#[test]
+fn error_forgets_previous_minute() {
+    let minutes = [minute(14, 3, true), minute(14, 4, true), minute(14, 5, true)];
+    let frames = [0, frame(&minutes[0]), frame(&minutes[1]) ^ (1 << 22), frame(&minutes[2])];
+    let mut decoder = ConfirmingDecoder::default();
+
+    let results: Vec<_> = pulses(&frames, 0).iter()
+        .filter_map(|(start, length)| decoder.pulse(*start, *length))
+        .collect();
+
+    assert_eq!(results, [
+        Ok(DecodedMinute { minute: minutes[0], confirmed: false }),
+        Err(DcfError::InvalidParity),
+        Ok(DecodedMinute { minute: minutes[2], confirmed: false }),
+    ]);
+}
+
+#[test]
+fn local_time_is_converted_to_utc() {
+    assert_eq!(minute(14, 3, true).to_utc(), DateTime::new(2026, 10, 17, 12, 3, 0));
+    assert_eq!(minute(0, 30, false).to_utc(), DateTime::new(2026, 10, 16, 23, 30, 0));
+}
@@ -0,0 +1,81 @@
+//! FAT time stamp packing, run on the host using
+//! `cargo test -p rtc-time --target x86_64-unknown-linux-gnu`
+
+use rtc_time::{
+    calendar::{CalendarError, DateTime, SECONDS_PER_DAY},
+    fat::{FatTimeError, FatTimestamp, FAT_FIRST_YEAR, FAT_LAST_YEAR},
+};
+
+fn time(year: u16, month: u8, day: u8, hours: u8, minutes: u8, seconds: u8) -> DateTime {
+    DateTime::new(year, month, day, hours, minutes, seconds).unwrap()
+}
+
+#[test]
+fn first_fat_time_is_epoch() {
+    let first = time(FAT_FIRST_YEAR, 1, 1, 0, 0, 0);
+
+    assert_eq!(FatTimestamp::from_date_time(&first), Ok(FatTimestamp::EPOCH));
+    assert_eq!(FatTimestamp::EPOCH.to_date_time(), Ok(first));
+}
+
+#[test]
+fn last_fat_time_uses_all_bits() {
+    let last = time(FAT_LAST_YEAR, 12, 31, 23, 59, 58);
+    let timestamp = FatTimestamp::from_date_time(&last).unwrap();
+
+    assert_eq!(timestamp, FatTimestamp {
+        date: (127 << 9) | (12 << 5) | 31,
+        time: (23 << 11) | (59 << 5) | 29,
+    });
+    assert_eq!(timestamp.to_date_time(), Ok(last));
+}
+
+#[test]
+fn years_outside_fat_range_are_rejected() {
+    for value in [
+        time(1979, 12, 31, 23, 59, 59),
+        time(2108, 1, 1, 0, 0, 0),
+        time(1970, 1, 1, 0, 0, 0),
+        time(9999, 1, 1, 0, 0, 0),
+    ] {
+        assert_eq!(FatTimestamp::from_date_time(&value), Err(FatTimeError::YearOutOfRange));
+    }
+}
+
+#[test]
+fn every_fat_date_round_trips() {
+    let first = time(FAT_FIRST_YEAR, 1, 1, 0, 0, 0).to_unix_seconds();
+    let last = time(FAT_LAST_YEAR, 12, 31, 0, 0, 0).to_unix_seconds();
+
+    for day in 0..=(last - first)/SECONDS_PER_DAY {
+        // Different even time of each day
+        let time_of_day = (day*122) % SECONDS_PER_DAY;
+        let value = DateTime::from_unix_seconds(first + day*SECONDS_PER_DAY + time_of_day).unwrap();
+        let timestamp = FatTimestamp::from_date_time(&value).unwrap();
+
+        assert_eq!(timestamp.to_date_time(), Ok(value));
+    }
+}
+
+#[test]
+fn odd_seconds_are_rounded_down() {
+    let timestamp = FatTimestamp::from_date_time(&time(2026, 10, 17, 14, 3, 59)).unwrap();
+    assert_eq!(timestamp.to_date_time(), Ok(time(2026, 10, 17, 14, 3, 58)));
+}
+
+#[test]
+fn invalid_fields_are_rejected() {
+    let valid = FatTimestamp::from_date_time(&time(2026, 10, 17, 14, 3, 0)).unwrap();
+
+    for (timestamp, error) in [
+        (FatTimestamp { date: valid.date & !(0b1111 << 5), ..valid }, CalendarError::InvalidMonth),
+        (FatTimestamp { date: valid.date | (0b1111 << 5), ..valid }, CalendarError::InvalidMonth),
+        (FatTimestamp { date: valid.date & !0b1_1111, ..valid }, CalendarError::InvalidDay),
+        (FatTimestamp { date: (46 << 9) | (2 << 5) | 29, ..valid }, CalendarError::InvalidDay),
+        (FatTimestamp { time: 24 << 11, ..valid }, CalendarError::InvalidTime),
+        (FatTimestamp { time: 60 << 5, ..valid }, CalendarError::InvalidTime),
+        (FatTimestamp { time: 30, ..valid }, CalendarError::InvalidTime),
+    ] {
+        assert_eq!(timestamp.to_date_time(), Err(error), "{:?}", timestamp);
+    }
+}
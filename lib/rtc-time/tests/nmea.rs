@@ -0,0 +1,206 @@
+//! NMEA sentence parsing on sentences recorded from GPS modules, run
+//! on the host using `cargo test -p rtc-time --target x86_64-unknown-linux-gnu`
+
+use rtc_time::{
+    calendar::{CalendarError, DateTime},
+    nmea::{
+        needs_update, parse_sentence, verify_checksum, GpsTime, NmeaError, SentenceKind,
+        SentenceReader,
+    },
+};
+
+const RMC: &str = "$GNRMC,140300.00,A,4948.87012,N,01424.54316,E,0.012,,171026,,,A*63";
+const GGA: &str = "$GPGGA,140300.00,4948.87012,N,01424.54316,E,1,08,1.01,350.2,M,44.9,M,,*54";
+const ZDA: &str = "$GPZDA,140300.00,17,10,2026,00,00*61";
+const TXT: &str = "$GPTXT,01,01,02,ANTSTATUS=OK*3B";
+/// Receiver knows the time from a satellite, but has no fix yet
+const RMC_VOID: &str = "$GPRMC,140301.00,V,,,,,,,171026,,,N*79";
+/// Receiver after a cold start
+const RMC_EMPTY: &str = "$GPRMC,,V,,,,,,,,,,N*53";
+const ZDA_EMPTY: &str = "$GPZDA,,,,,,*48";
+
+fn time(year: u16, month: u8, day: u8, hours: u8, minutes: u8, seconds: u8) -> DateTime {
+    DateTime::new(year, month, day, hours, minutes, seconds).unwrap()
+}
+
+/// Feed `data` in pieces of `chunk_size` bytes, returns the sentences read
+fn read(reader: &mut SentenceReader, data: &str, chunk_size: usize) -> Vec<String> {
+    let mut sentences = Vec::new();
+
+    for chunk in data.as_bytes().chunks(chunk_size) {
+        for byte in chunk {
+            if let Some(sentence) = reader.feed(*byte) {
+                sentences.push(sentence.to_string());
+            }
+        }
+    }
+
+    sentences
+}
+
+#[test]
+fn rmc_is_parsed() {
+    assert_eq!(
+        parse_sentence(RMC),
+        Ok(GpsTime { kind: SentenceKind::Rmc, time: time(2026, 10, 17, 14, 3, 0) })
+    );
+}
+
+#[test]
+fn reference_rmc_is_parsed() {
+    // Example sentence of the NMEA 0183 documentation, RMC years are 2000 - 2099
+    let sentence = "$GPRMC,123519,A,4807.038,N,01131.000,E,022.4,084.4,230394,003.1,W*6A";
+
+    assert_eq!(
+        parse_sentence(sentence),
+        Ok(GpsTime { kind: SentenceKind::Rmc, time: time(2094, 3, 23, 12, 35, 19) })
+    );
+}
+
+#[test]
+fn zda_is_parsed() {
+    assert_eq!(
+        parse_sentence(ZDA),
+        Ok(GpsTime { kind: SentenceKind::Zda, time: time(2026, 10, 17, 14, 3, 0) })
+    );
+}
+
+#[test]
+fn line_terminator_is_ignored() {
+    assert!(parse_sentence(&format!("{}\r\n", RMC)).is_ok());
+}
+
+#[test]
+fn sentences_without_date_are_unsupported() {
+    // GGA carries the time of the fix, but no date
+    assert_eq!(verify_checksum(GGA).map(|_| ()), Ok(()));
+    assert_eq!(parse_sentence(GGA), Err(NmeaError::Unsupported));
+    assert_eq!(parse_sentence(TXT), Err(NmeaError::Unsupported));
+}
+
+#[test]
+fn bad_checksum_is_rejected() {
+    let damaged = RMC.replace("140300", "140400");
+    let wrong_sum = RMC.replace("*63", "*64");
+
+    assert_eq!(parse_sentence(&damaged), Err(NmeaError::InvalidChecksum));
+    assert_eq!(parse_sentence(&wrong_sum), Err(NmeaError::InvalidChecksum));
+    assert_eq!(parse_sentence(&ZDA.replace("*61", "*6")), Err(NmeaError::InvalidFormat));
+    assert_eq!(parse_sentence(&ZDA.replace("*61", "*6G")), Err(NmeaError::InvalidFormat));
+    assert_eq!(parse_sentence(&ZDA.replace("*61", "")), Err(NmeaError::InvalidFormat));
+    assert_eq!(parse_sentence(&ZDA[1..]), Err(NmeaError::InvalidFormat));
+}
+
+#[test]
+fn void_fix_is_rejected() {
+    assert_eq!(parse_sentence(RMC_VOID), Err(NmeaError::NoFix));
+}
+
+#[test]
+fn missing_time_is_reported() {
+    assert_eq!(parse_sentence(RMC_EMPTY), Err(NmeaError::NoTime));
+    assert_eq!(parse_sentence(ZDA_EMPTY), Err(NmeaError::NoTime));
+}
+
+#[test]
+fn missing_fields_are_rejected() {
+    assert_eq!(parse_sentence("$GPRMC,140300.00,A,4948.87012,N*7F"), Err(NmeaError::InvalidFormat));
+    assert_eq!(parse_sentence("$GPZDA,140300.00,17,10*4B"), Err(NmeaError::InvalidFormat));
+}
+
+#[test]
+fn invalid_fields_are_rejected() {
+    // Five digit time
+    assert_eq!(
+        parse_sentence("$GPRMC,14030.00,A,4948.87012,N,01424.54316,E,0.012,,171026,,,A*4D"),
+        Err(NmeaError::InvalidField)
+    );
+    // Hour 24
+    assert_eq!(
+        parse_sentence("$GPRMC,246000.00,A,4948.87012,N,01424.54316,E,0.012,,171026,,,A*7B"),
+        Err(NmeaError::InvalidDateTime(CalendarError::InvalidTime))
+    );
+    // 31st of November
+    assert_eq!(
+        parse_sentence("$GPRMC,140300.00,A,4948.87012,N,01424.54316,E,0.012,,311126,,,A*78"),
+        Err(NmeaError::InvalidDateTime(CalendarError::InvalidDay))
+    );
+}
+
+/// Sentence `$<data>*<checksum>` with the right checksum
+fn with_checksum(data: &str) -> String {
+    format!("${}*{:02X}", data, data.bytes().fold(0, |sum, byte| sum ^ byte))
+}
+
+#[test]
+fn non_ascii_sentences_are_rejected() {
+    // Multi-byte character in the address
+    assert_eq!(parse_sentence("$Xé12,1*2C"), Err(NmeaError::InvalidFormat));
+
+    // and in the date and time fields
+    for data in [
+        "GPRMC,14é300.00,A,4948.87012,N,01424.54316,E,0.012,,171026,,,A",
+        "GPRMC,140300.00,A,4948.87012,N,01424.54316,E,0.012,,17é026,,,A",
+        "GPZDA,140300.00,1é,10,2026,00,00",
+    ] {
+        let sentence = with_checksum(data);
+        assert_eq!(verify_checksum(&sentence), Ok(data));
+        assert_eq!(parse_sentence(&sentence), Err(NmeaError::InvalidFormat), "{}", sentence);
+    }
+}
+
+#[test]
+fn sentences_split_across_feed_calls() {
+    let data = format!("{}\r\n{}\r\n{}\r\n", RMC, GGA, ZDA);
+
+    for chunk_size in [1, 2, 7, 16, 64, data.len()] {
+        let mut reader = SentenceReader::default();
+        assert_eq!(read(&mut reader, &data, chunk_size), [RMC, GGA, ZDA], "{}", chunk_size);
+    }
+}
+
+#[test]
+fn sentence_is_completed_by_later_feed() {
+    let mut reader = SentenceReader::default();
+    let (start, end) = RMC.split_at(30);
+
+    assert!(read(&mut reader, start, 8).is_empty());
+    assert_eq!(read(&mut reader, &format!("{}\r\n", end), 8), [RMC]);
+}
+
+#[test]
+fn noise_before_sentence_is_skipped() {
+    let mut reader = SentenceReader::default();
+    let data = format!("\r\n\nNMEA garbage\r\n{}\n", ZDA);
+
+    assert_eq!(read(&mut reader, &data, 5), [ZDA]);
+}
+
+#[test]
+fn too_long_line_is_skipped() {
+    let mut reader = SentenceReader::default();
+    let data = format!("${}\r\n{}\r\n", "A".repeat(100), ZDA);
+
+    assert_eq!(read(&mut reader, &data, 3), [ZDA]);
+}
+
+#[test]
+fn reset_drops_partial_sentence() {
+    let mut reader = SentenceReader::default();
+
+    read(&mut reader, &RMC[..20], 4);
+    reader.reset();
+
+    assert_eq!(read(&mut reader, &format!("{}\r\n", ZDA), 4), [ZDA]);
+}
+
+#[test]
+fn update_is_needed_over_threshold() {
+    let gps = time(2026, 10, 17, 14, 3, 0);
+
+    assert!(!needs_update(&gps, &gps, 2));
+    assert!(!needs_update(&time(2026, 10, 17, 14, 3, 2), &gps, 2));
+    assert!(!needs_update(&time(2026, 10, 17, 14, 2, 58), &gps, 2));
+    assert!(needs_update(&time(2026, 10, 17, 14, 3, 3), &gps, 2));
+    assert!(needs_update(&time(2026, 10, 17, 14, 2, 57), &gps, 2));
+}
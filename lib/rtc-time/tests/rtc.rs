@@ -0,0 +1,175 @@
+//! PCF8563 register conversions and flags, run on the host using
+//! `cargo test -p rtc-time --target x86_64-unknown-linux-gnu`
+
+use embedded_hal::blocking::i2c::{Write, WriteRead};
+use rtc_time::{
+    calendar::{CalendarError, DateTime},
+    clock::ClockReading,
+    rtc::{
+        century_flag, from_pcf8563, read_century, read_voltage_low, to_pcf8563, to_reading,
+        write_century, RtcConvertError, PCF8563_ADDRESS,
+    },
+};
+
+/// PCF8563 register file behind the I2C bus
+struct TestRegisters([u8; 16]);
+
+impl Write for TestRegisters {
+    type Error = ();
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+        assert_eq!(address, PCF8563_ADDRESS);
+        let (register, values) = bytes.split_first().ok_or(())?;
+        let start = *register as usize;
+        self.0[start..start + values.len()].copy_from_slice(values);
+        Ok(())
+    }
+}
+
+impl WriteRead for TestRegisters {
+    type Error = ();
+
+    fn write_read(&mut self, address: u8, bytes: &[u8], buffer: &mut [u8]) -> Result<(), ()> {
+        assert_eq!(address, PCF8563_ADDRESS);
+        let start = *bytes.first().ok_or(())? as usize;
+        buffer.copy_from_slice(&self.0[start..start + buffer.len()]);
+        Ok(())
+    }
+}
+
+/// Registers with the week day not set, it is not checked when reading
+fn registers(
+    year: u8,
+    month: u8,
+    day: u8,
+    hours: u8,
+    minutes: u8,
+    seconds: u8,
+) -> pcf8563::DateTime {
+    pcf8563::DateTime { year, month, weekday: 0, day, hours, minutes, seconds }
+}
+
+fn time(year: u16, month: u8, day: u8, hours: u8, minutes: u8, seconds: u8) -> DateTime {
+    DateTime::new(year, month, day, hours, minutes, seconds).unwrap()
+}
+
+#[test]
+fn century_flag_selects_century() {
+    for (registers, century, expected) in [
+        (registers(26, 10, 17, 14, 3, 0), false, time(2026, 10, 17, 14, 3, 0)),
+        (registers(26, 10, 17, 14, 3, 0), true, time(2126, 10, 17, 14, 3, 0)),
+        (registers(0, 1, 1, 0, 0, 0), false, time(2000, 1, 1, 0, 0, 0)),
+        (registers(99, 12, 31, 23, 59, 59), true, time(2199, 12, 31, 23, 59, 59)),
+    ] {
+        assert_eq!(from_pcf8563(&registers, century), Ok(expected));
+    }
+}
+
+#[test]
+fn registers_round_trip() {
+    for value in [
+        time(2000, 1, 1, 0, 0, 0),
+        time(2026, 10, 17, 14, 3, 0),
+        time(2099, 12, 31, 23, 59, 59),
+        time(2100, 1, 1, 0, 0, 0),
+        time(2100, 2, 28, 12, 0, 0),
+        time(2199, 12, 31, 23, 59, 59),
+    ] {
+        let registers = to_pcf8563(&value).unwrap();
+
+        assert!(registers.year < 100);
+        assert_eq!(registers.weekday, value.weekday());
+        assert_eq!(from_pcf8563(&registers, century_flag(&value)), Ok(value));
+    }
+}
+
+#[test]
+fn century_rolls_over_after_2099() {
+    let last = time(2099, 12, 31, 23, 59, 59);
+    let first = last.add_seconds(1).unwrap();
+
+    assert_eq!(first, time(2100, 1, 1, 0, 0, 0));
+    assert!(!century_flag(&last));
+    assert!(century_flag(&first));
+    assert_eq!(to_pcf8563(&last).unwrap().year, 99);
+    assert_eq!(to_pcf8563(&first).unwrap().year, 0);
+
+    // Years wrap from 99 to 00 and the chip toggles the flag
+    assert_eq!(from_pcf8563(&registers(0, 1, 1, 0, 0, 0), true), Ok(first));
+}
+
+#[test]
+fn years_out_of_range_are_rejected() {
+    for year in [1999, 2200, 9999] {
+        assert_eq!(to_pcf8563(&time(year, 1, 1, 0, 0, 0)), Err(RtcConvertError::YearOutOfRange));
+    }
+}
+
+#[test]
+fn invalid_registers_are_rejected() {
+    for (registers, error) in [
+        (registers(100, 1, 1, 0, 0, 0), CalendarError::InvalidYear),
+        (registers(26, 13, 1, 0, 0, 0), CalendarError::InvalidMonth),
+        (registers(26, 2, 29, 0, 0, 0), CalendarError::InvalidDay),
+        (registers(26, 10, 17, 24, 0, 0), CalendarError::InvalidTime),
+    ] {
+        assert_eq!(from_pcf8563(&registers, false), Err(RtcConvertError::InvalidValue(error)));
+    }
+
+    // 2100 is not a leap year
+    assert!(from_pcf8563(&registers(0, 2, 29, 0, 0, 0), false).is_ok());
+    assert_eq!(
+        from_pcf8563(&registers(0, 2, 29, 0, 0, 0), true),
+        Err(RtcConvertError::InvalidValue(CalendarError::InvalidDay))
+    );
+}
+
+#[test]
+fn voltage_low_reading_is_invalid() {
+    let registers = registers(26, 10, 17, 14, 3, 0);
+
+    assert_eq!(
+        to_reading(&registers, false, false),
+        ClockReading { time: time(2026, 10, 17, 14, 3, 0), valid: true }
+    );
+    assert_eq!(
+        to_reading(&registers, false, true),
+        ClockReading { time: time(2026, 10, 17, 14, 3, 0), valid: false }
+    );
+}
+
+#[test]
+fn invalid_registers_reading_is_invalid() {
+    for voltage_low in [false, true] {
+        assert_eq!(
+            to_reading(&registers(26, 13, 17, 14, 3, 0), false, voltage_low),
+            ClockReading { time: DateTime::UNIX_EPOCH, valid: false }
+        );
+    }
+}
+
+#[test]
+fn voltage_low_flag_is_read() {
+    let mut i2c = TestRegisters([0; 16]);
+
+    // Seconds register holds 42 in BCD below the flag
+    i2c.0[0x02] = 0x42;
+    assert_eq!(read_voltage_low(&mut i2c), Ok(false));
+
+    i2c.0[0x02] = 0x80 | 0x42;
+    assert_eq!(read_voltage_low(&mut i2c), Ok(true));
+}
+
+#[test]
+fn century_flag_keeps_month() {
+    let mut i2c = TestRegisters([0; 16]);
+    i2c.0[0x07] = 0x12;
+
+    assert_eq!(read_century(&mut i2c), Ok(false));
+    write_century(&mut i2c, true).unwrap();
+    assert_eq!(i2c.0[0x07], 0x92);
+    assert_eq!(read_century(&mut i2c), Ok(true));
+    write_century(&mut i2c, false).unwrap();
+    assert_eq!(i2c.0[0x07], 0x12);
+    assert_eq!(read_century(&mut i2c), Ok(false));
+}
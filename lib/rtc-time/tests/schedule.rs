@@ -0,0 +1,312 @@
+//! Schedules and the scheduler over simulated time, run on the host using
+//! `cargo test -p rtc-time --target x86_64-unknown-linux-gnu`
+
+use rtc_time::{
+    calendar::DateTime,
+    schedule::{Fired, Schedule, ScheduleError, Scheduler, MAX_MISSED},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Task {
+    Record,
+    Report,
+}
+
+fn time(year: u16, month: u8, day: u8, hours: u8, minutes: u8, seconds: u8) -> DateTime {
+    DateTime::new(year, month, day, hours, minutes, seconds).unwrap()
+}
+
+fn scheduler(tasks: &[(Task, Schedule)]) -> Scheduler<Task, 4> {
+    let mut scheduler = Scheduler::default();
+
+    for (task, schedule) in tasks {
+        scheduler.add(*task, *schedule).unwrap();
+    }
+
+    scheduler
+}
+
+/// All tasks due at `now`
+fn poll(scheduler: &mut Scheduler<Task, 4>, now: &DateTime) -> Vec<Fired<Task>> {
+    std::iter::from_fn(|| scheduler.poll(now)).collect()
+}
+
+/// Poll every `step_seconds` from `start` for `duration_seconds`, returns
+/// the fired tasks
+fn run(
+    scheduler: &mut Scheduler<Task, 4>,
+    start: &DateTime,
+    duration_seconds: i64,
+    step_seconds: i64,
+) -> Vec<Fired<Task>> {
+    (0..=duration_seconds/step_seconds)
+        .flat_map(|step| poll(scheduler, &start.add_seconds(step*step_seconds).unwrap()))
+        .collect()
+}
+
+fn scheduled(fired: &[Fired<Task>]) -> Vec<DateTime> {
+    fired.iter().map(|fired| fired.scheduled).collect()
+}
+
+#[test]
+fn schedules_are_parsed() {
+    assert_eq!(Schedule::parse("*/10 * * * * *"), Ok(Schedule::every_seconds(10)));
+    assert_eq!(Schedule::parse("0 */5 * * * *"), Ok(Schedule::every_minutes(5)));
+    assert_eq!(Schedule::parse("0 15 * * * *"), Ok(Schedule::hourly_at(15)));
+    assert_eq!(Schedule::parse("0 30 2 * * *"), Ok(Schedule::daily_at(2, 30)));
+    assert_eq!(Schedule::parse("  0   0\t0 * * * "), Ok(Schedule::daily_at(0, 0)));
+}
+
+#[test]
+fn invalid_schedules_are_rejected() {
+    for (text, error) in [
+        ("", ScheduleError::FieldCount),
+        ("* * * * *", ScheduleError::FieldCount),
+        ("* * * * * * *", ScheduleError::FieldCount),
+        ("x * * * * *", ScheduleError::InvalidField),
+        ("1-x * * * * *", ScheduleError::InvalidField),
+        ("*/ * * * * *", ScheduleError::InvalidField),
+        (", * * * * *", ScheduleError::InvalidField),
+        ("60 * * * * *", ScheduleError::ValueOutOfRange),
+        ("* 60 * * * *", ScheduleError::ValueOutOfRange),
+        ("* * 24 * * *", ScheduleError::ValueOutOfRange),
+        ("* * * 0 * *", ScheduleError::ValueOutOfRange),
+        ("* * * 32 * *", ScheduleError::ValueOutOfRange),
+        ("* * * * 13 *", ScheduleError::ValueOutOfRange),
+        ("* * * * * 7", ScheduleError::ValueOutOfRange),
+        ("*/0 * * * * *", ScheduleError::ValueOutOfRange),
+        ("30-10 * * * * *", ScheduleError::ValueOutOfRange),
+        ("300 * * * * *", ScheduleError::ValueOutOfRange),
+    ] {
+        assert_eq!(Schedule::parse(text), Err(error), "{:?}", text);
+    }
+}
+
+#[test]
+fn fields_match() {
+    let schedule = Schedule::parse("0,30 5-10/5 8-17 * 1-10 1-5").unwrap();
+
+    // Saturday 17. 10. 2026 and Monday 19. 10. 2026
+    assert!(!schedule.matches(&time(2026, 10, 17, 8, 5, 0)));
+    assert!(schedule.matches(&time(2026, 10, 19, 8, 5, 0)));
+    assert!(schedule.matches(&time(2026, 10, 19, 17, 10, 30)));
+    assert!(!schedule.matches(&time(2026, 10, 19, 8, 5, 1)));
+    assert!(!schedule.matches(&time(2026, 10, 19, 8, 6, 0)));
+    assert!(!schedule.matches(&time(2026, 10, 19, 18, 5, 0)));
+    assert!(schedule.matches(&time(2026, 6, 1, 8, 5, 0)));
+    assert!(!schedule.matches(&time(2026, 11, 2, 8, 5, 0)));
+}
+
+#[test]
+fn day_or_weekday_matches() {
+    // As in cron, Friday 13th is matched by both fields
+    let schedule = Schedule::parse("0 0 0 13 * 5").unwrap();
+
+    assert!(schedule.matches(&time(2026, 10, 13, 0, 0, 0)));
+    assert!(schedule.matches(&time(2026, 10, 16, 0, 0, 0)));
+    assert!(!schedule.matches(&time(2026, 10, 14, 0, 0, 0)));
+
+    let weekdays_only = Schedule::parse("0 0 0 * * 5").unwrap();
+    assert!(!weekdays_only.matches(&time(2026, 10, 13, 0, 0, 0)));
+    assert!(weekdays_only.matches(&time(2026, 10, 16, 0, 0, 0)));
+}
+
+#[test]
+fn next_match_is_found() {
+    let now = time(2026, 10, 17, 14, 3, 7);
+
+    assert_eq!(Schedule::every_seconds(10).next_after(&now), Some(time(2026, 10, 17, 14, 3, 10)));
+    assert_eq!(Schedule::hourly_at(0).next_after(&now), Some(time(2026, 10, 17, 15, 0, 0)));
+    assert_eq!(Schedule::daily_at(0, 0).next_after(&now), Some(time(2026, 10, 18, 0, 0, 0)));
+    assert_eq!(
+        Schedule::parse("0 0 0 29 2 *").unwrap().next_after(&now),
+        Some(time(2028, 2, 29, 0, 0, 0))
+    );
+    assert_eq!(Schedule::parse("0 0 0 31 2 *").unwrap().next_after(&now), None);
+}
+
+#[test]
+fn first_poll_fires_nothing() {
+    let start = time(2026, 10, 17, 14, 3, 0);
+    let mut scheduler = scheduler(&[(Task::Record, Schedule::every_seconds(10))]);
+
+    assert!(poll(&mut scheduler, &start).is_empty());
+    assert_eq!(scheduler.next_time(), Some(time(2026, 10, 17, 14, 3, 10)));
+}
+
+#[test]
+fn each_match_fires_once() {
+    let start = time(2026, 10, 17, 14, 0, 0);
+
+    for step_seconds in [1, 3, 7, 10] {
+        let mut scheduler = scheduler(&[(Task::Record, Schedule::every_seconds(10))]);
+        let fired = run(&mut scheduler, &start, 3600, step_seconds);
+
+        // The last poll is at the last whole step
+        let end = 3600/step_seconds*step_seconds;
+        let expected: Vec<_> = (1..=end/10)
+            .map(|index| start.add_seconds(index*10).unwrap())
+            .collect();
+
+        assert_eq!(scheduled(&fired), expected, "{}", step_seconds);
+    }
+}
+
+#[test]
+fn minute_and_hour_fields_fire_over_day() {
+    let start = time(2026, 10, 17, 0, 0, 0);
+    let mut scheduler = scheduler(&[
+        (Task::Record, Schedule::every_minutes(15)),
+        (Task::Report, Schedule::hourly_at(30)),
+    ]);
+
+    let fired = run(&mut scheduler, &start, 24*3600 - 1, 1);
+    let records = fired.iter().filter(|fired| fired.task == Task::Record).count();
+    let reports: Vec<_> = fired.iter().filter(|fired| fired.task == Task::Report).collect();
+
+    // Start time itself is not matched, the first poll fires nothing
+    assert_eq!(records, 24*4 - 1);
+    assert_eq!(reports.len(), 24);
+    assert!(reports
+        .iter()
+        .all(|fired| (fired.scheduled.minutes(), fired.scheduled.seconds()) == (30, 0)));
+}
+
+#[test]
+fn weekday_field_fires_on_working_days() {
+    // Saturday 17. 10. 2026
+    let start = time(2026, 10, 17, 0, 0, 0);
+    let mut scheduler = scheduler(&[(Task::Report, Schedule::parse("0 0 8 * * 1-5").unwrap())]);
+
+    let fired = run(&mut scheduler, &start, 14*24*3600, 60);
+
+    assert_eq!(fired.len(), 10);
+    assert!(fired.iter().all(|fired| (1..=5).contains(&fired.scheduled.weekday())));
+    assert_eq!(fired[0].scheduled, time(2026, 10, 19, 8, 0, 0));
+}
+
+#[test]
+fn tasks_fire_in_order_of_their_times() {
+    // Tasks due at the same time fire in the order they were added
+    let start = time(2026, 10, 17, 14, 0, 0);
+    let mut scheduler = scheduler(&[
+        (Task::Report, Schedule::hourly_at(1)),
+        (Task::Record, Schedule::every_seconds(30)),
+    ]);
+
+    poll(&mut scheduler, &start);
+    let fired = poll(&mut scheduler, &time(2026, 10, 17, 14, 1, 30));
+
+    assert_eq!(fired.iter().map(|fired| (fired.task, fired.scheduled)).collect::<Vec<_>>(), [
+        (Task::Record, time(2026, 10, 17, 14, 0, 30)),
+        (Task::Report, time(2026, 10, 17, 14, 1, 0)),
+        (Task::Record, time(2026, 10, 17, 14, 1, 0)),
+        (Task::Record, time(2026, 10, 17, 14, 1, 30)),
+    ]);
+}
+
+#[test]
+fn forward_jump_fires_missed_matches() {
+    let start = time(2026, 10, 17, 14, 0, 0);
+    let mut scheduler = scheduler(&[(Task::Record, Schedule::every_minutes(1))]);
+
+    poll(&mut scheduler, &start);
+    let fired = poll(&mut scheduler, &time(2026, 10, 17, 14, 5, 30));
+
+    let expected: Vec<_> = (1..=5).map(|minute| time(2026, 10, 17, 14, minute, 0)).collect();
+    assert_eq!(scheduled(&fired), expected);
+    assert_eq!(scheduler.next_time(), Some(time(2026, 10, 17, 14, 6, 0)));
+}
+
+#[test]
+fn long_forward_jump_fires_limited_matches() {
+    let start = time(2026, 10, 17, 14, 0, 0);
+    let now = time(2026, 10, 18, 14, 0, 5);
+    let mut scheduler = scheduler(&[
+        (Task::Record, Schedule::every_seconds(10)),
+        (Task::Report, Schedule::daily_at(20, 0)),
+    ]);
+
+    poll(&mut scheduler, &start);
+    let fired = poll(&mut scheduler, &now);
+    let records: Vec<_> = fired.iter().filter(|fired| fired.task == Task::Record).collect();
+    let reports: Vec<_> = fired.iter().filter(|fired| fired.task == Task::Report).collect();
+
+    // Oldest missed records fire, the rest is skipped
+    assert_eq!(records.len(), MAX_MISSED as usize);
+    assert_eq!(records[0].scheduled, time(2026, 10, 17, 14, 0, 10));
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].scheduled, time(2026, 10, 17, 20, 0, 0));
+
+    assert!(poll(&mut scheduler, &now).is_empty());
+    assert_eq!(scheduler.next_time(), Some(time(2026, 10, 18, 14, 0, 10)));
+    let next = time(2026, 10, 18, 14, 0, 10);
+    assert_eq!(scheduled(&poll(&mut scheduler, &next)), [next]);
+}
+
+#[test]
+fn daylight_saving_end_does_not_fire_twice() {
+    // Local time goes from 3:00 back to 2:00
+    let start = time(2026, 10, 25, 1, 0, 0);
+    let repeated = time(2026, 10, 25, 2, 0, 0);
+    let mut scheduler = scheduler(&[
+        (Task::Report, Schedule::daily_at(2, 30)),
+        (Task::Record, Schedule::every_minutes(10)),
+    ]);
+
+    let mut fired = run(&mut scheduler, &start, 2*3600 - 1, 1);
+    fired.extend(run(&mut scheduler, &repeated, 3600, 1));
+
+    let reports: Vec<_> = fired.iter().filter(|fired| fired.task == Task::Report).collect();
+    let records = fired.iter().filter(|fired| fired.task == Task::Record).count();
+
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].scheduled, time(2026, 10, 25, 2, 30, 0));
+    // Records continue in the repeated hour, from 2:10 to 3:00
+    assert_eq!(records, 11 + 6);
+    assert_eq!(scheduler.next_time(), Some(time(2026, 10, 25, 3, 10, 0)));
+}
+
+#[test]
+fn backward_jump_does_not_repeat_fired_match() {
+    let start = time(2026, 10, 17, 14, 0, 0);
+    let mut scheduler = scheduler(&[(Task::Record, Schedule::every_seconds(10))]);
+
+    poll(&mut scheduler, &start);
+    assert_eq!(poll(&mut scheduler, &time(2026, 10, 17, 14, 0, 10)).len(), 1);
+
+    // Clock set back by a few seconds, the next match is planned again
+    assert!(poll(&mut scheduler, &time(2026, 10, 17, 14, 0, 5)).is_empty());
+    assert_eq!(scheduler.next_time(), Some(time(2026, 10, 17, 14, 0, 10)));
+}
+
+#[test]
+fn clock_correction_plans_all_tasks_again() {
+    let start = time(2026, 10, 17, 14, 0, 0);
+    let corrected = time(2026, 10, 16, 14, 0, 0);
+    let mut scheduler = scheduler(&[(Task::Report, Schedule::daily_at(20, 0))]);
+
+    poll(&mut scheduler, &start);
+    assert!(poll(&mut scheduler, &corrected).is_empty());
+    assert_eq!(scheduler.next_time(), Some(time(2026, 10, 16, 20, 0, 0)));
+}
+
+#[test]
+fn task_added_later_starts_after_last_poll() {
+    let start = time(2026, 10, 17, 14, 0, 0);
+    let mut scheduler = scheduler(&[]);
+
+    poll(&mut scheduler, &start);
+    scheduler.add(Task::Record, Schedule::every_seconds(10)).unwrap();
+
+    assert_eq!(scheduled(&poll(&mut scheduler, &time(2026, 10, 17, 14, 0, 20))), [
+        time(2026, 10, 17, 14, 0, 10),
+        time(2026, 10, 17, 14, 0, 20),
+    ]);
+}
+
+#[test]
+fn full_scheduler_is_reported() {
+    let mut scheduler = scheduler(&[(Task::Record, Schedule::every_seconds(1)); 4]);
+    assert_eq!(scheduler.add(Task::Report, Schedule::every_seconds(1)), Err(ScheduleError::Full));
+}
@@ -0,0 +1,130 @@
+//! Daylight saving rules and local time conversion, run on the host using
+//! `cargo test -p rtc-time --target x86_64-unknown-linux-gnu`
+
+use rtc_time::{
+    calendar::DateTime,
+    zone::{
+        write_iso8601, DstRule, LocalTime, TimeZone, CENTRAL_EUROPE, EASTERN_EUROPE, US_EASTERN,
+        US_PACIFIC, UTC, WESTERN_EUROPE,
+    },
+};
+
+fn time(year: u16, month: u8, day: u8, hours: u8, minutes: u8, seconds: u8) -> DateTime {
+    DateTime::new(year, month, day, hours, minutes, seconds).unwrap()
+}
+
+fn iso8601(local: &LocalTime) -> String {
+    let mut text = String::new();
+    write_iso8601(&mut text, local).unwrap();
+    text
+}
+
+/// Offset changes from `before` to `after` minutes exactly at the UTC
+/// time `at`
+fn assert_transition(zone: &TimeZone, at: DateTime, before: i16, after: i16) {
+    let second_before = at.add_seconds(-1).unwrap();
+    let second_after = at.add_seconds(1).unwrap();
+
+    assert_eq!(zone.offset_at(&second_before), before, "{} before {:?}", zone.standard_name, at);
+    assert_eq!(zone.offset_at(&at), after, "{} at {:?}", zone.standard_name, at);
+    assert_eq!(zone.offset_at(&second_after), after, "{} after {:?}", zone.standard_name, at);
+}
+
+#[test]
+fn eu_rule_switches_on_last_sundays_at_one_utc() {
+    // All European zones switch at the same instant
+    for zone in [WESTERN_EUROPE, CENTRAL_EUROPE, EASTERN_EUROPE] {
+        let standard = zone.offset_minutes;
+        let daylight = standard + 60;
+
+        assert_transition(&zone, time(2026, 3, 29, 1, 0, 0), standard, daylight);
+        assert_transition(&zone, time(2026, 10, 25, 1, 0, 0), daylight, standard);
+
+        // Last Sunday of October is the last day of the month
+        assert_transition(&zone, time(2027, 3, 28, 1, 0, 0), standard, daylight);
+        assert_transition(&zone, time(2027, 10, 31, 1, 0, 0), daylight, standard);
+    }
+}
+
+#[test]
+fn us_rule_switches_on_sundays_at_two_local_time() {
+    // Second Sunday of March 2:00 standard time, first Sunday of November
+    // 2:00 daylight time
+    assert_transition(&US_EASTERN, time(2026, 3, 8, 7, 0, 0), -300, -240);
+    assert_transition(&US_EASTERN, time(2026, 11, 1, 6, 0, 0), -240, -300);
+    assert_transition(&US_EASTERN, time(2027, 3, 14, 7, 0, 0), -300, -240);
+    assert_transition(&US_EASTERN, time(2027, 11, 7, 6, 0, 0), -240, -300);
+
+    // Each zone switches at its own local time
+    assert_transition(&US_PACIFIC, time(2026, 3, 8, 10, 0, 0), -480, -420);
+    assert_transition(&US_PACIFIC, time(2026, 11, 1, 9, 0, 0), -420, -480);
+}
+
+#[test]
+fn local_time_around_transitions() {
+    let before = CENTRAL_EUROPE.to_local(&time(2026, 3, 29, 0, 59, 59)).unwrap();
+    let after = CENTRAL_EUROPE.to_local(&time(2026, 3, 29, 1, 0, 0)).unwrap();
+
+    assert_eq!((before.time, before.name), (time(2026, 3, 29, 1, 59, 59), "CET"));
+    assert_eq!((after.time, after.name), (time(2026, 3, 29, 3, 0, 0), "CEST"));
+
+    // Local hour from 1:00 to 2:00 is repeated
+    let before = US_EASTERN.to_local(&time(2026, 11, 1, 5, 59, 59)).unwrap();
+    let after = US_EASTERN.to_local(&time(2026, 11, 1, 6, 0, 0)).unwrap();
+
+    assert_eq!((before.time, before.name), (time(2026, 11, 1, 1, 59, 59), "EDT"));
+    assert_eq!((after.time, after.name), (time(2026, 11, 1, 1, 0, 0), "EST"));
+}
+
+#[test]
+fn zone_without_rule_keeps_its_offset() {
+    for utc in [time(2026, 1, 1, 0, 0, 0), time(2026, 7, 1, 12, 0, 0)] {
+        assert!(!UTC.is_dst(&utc));
+        assert_eq!(UTC.to_local(&utc).unwrap().time, utc);
+    }
+}
+
+#[test]
+fn local_time_is_converted_back_to_utc() {
+    for utc in [
+        time(2026, 1, 17, 14, 3, 0),
+        time(2026, 7, 17, 14, 3, 0),
+        time(2026, 3, 29, 1, 0, 0),
+        time(2026, 10, 25, 0, 59, 59),
+    ] {
+        let local = CENTRAL_EUROPE.to_local(&utc).unwrap();
+        assert_eq!(CENTRAL_EUROPE.to_utc(&local.time), Ok(utc), "{:?}", utc);
+    }
+
+    // Repeated hour is taken as daylight time
+    assert_eq!(CENTRAL_EUROPE.to_utc(&time(2026, 10, 25, 2, 30, 0)), Ok(time(2026, 10, 25, 0, 30, 0)));
+    // Skipped hour is taken as standard time
+    assert_eq!(CENTRAL_EUROPE.to_utc(&time(2026, 3, 29, 2, 30, 0)), Ok(time(2026, 3, 29, 1, 30, 0)));
+    assert_eq!(US_EASTERN.to_utc(&time(2026, 7, 4, 12, 0, 0)), Ok(time(2026, 7, 4, 16, 0, 0)));
+}
+
+#[test]
+fn iso8601_includes_the_offset() {
+    let summer = CENTRAL_EUROPE.to_local(&time(2026, 7, 17, 12, 3, 0)).unwrap();
+    let winter = CENTRAL_EUROPE.to_local(&time(2026, 1, 17, 13, 3, 0)).unwrap();
+    assert_eq!(iso8601(&summer), "2026-07-17T14:03:00+02:00");
+    assert_eq!(iso8601(&winter), "2026-01-17T14:03:00+01:00");
+
+    let utc = UTC.to_local(&time(2026, 10, 17, 14, 3, 0)).unwrap();
+    assert_eq!(iso8601(&utc), "2026-10-17T14:03:00+00:00");
+}
+
+#[test]
+fn iso8601_negative_offset() {
+    let daylight = US_EASTERN.to_local(&time(2026, 10, 17, 18, 3, 0)).unwrap();
+    let standard = US_EASTERN.to_local(&time(2026, 12, 1, 0, 30, 5)).unwrap();
+    assert_eq!(iso8601(&daylight), "2026-10-17T14:03:00-04:00");
+    assert_eq!(iso8601(&standard), "2026-11-30T19:30:05-05:00");
+
+    // Minutes of the offset are negative as well
+    let newfoundland = TimeZone {
+        offset_minutes: -210, dst: DstRule::None, standard_name: "NST", daylight_name: "NDT",
+    };
+    let local = newfoundland.to_local(&time(2026, 1, 17, 14, 3, 0)).unwrap();
+    assert_eq!(iso8601(&local), "2026-01-17T10:33:00-03:30");
+}
@@ -0,0 +1,91 @@
+//! Block device backed by a disk image file
+
+use std::{
+    cell::{Cell, RefCell},
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+use embedded_sdmmc::{Block, BlockCount, BlockDevice, BlockIdx};
+
+pub struct FileBlockDevice {
+    file: RefCell<File>,
+    blocks: u32,
+    write_protected: Cell<bool>,
+}
+
+impl FileBlockDevice {
+    /// Create (or truncate) an image file of `blocks` zeroed blocks
+    pub fn create(path: impl AsRef<Path>, blocks: u32) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        file.set_len(blocks as u64*Block::LEN as u64)?;
+        Ok(Self::new(file, blocks))
+    }
+
+    /// Open an existing image, for example copied from a card using `dd`
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let blocks = (file.metadata()?.len()/Block::LEN as u64) as u32;
+        Ok(Self::new(file, blocks))
+    }
+
+    fn new(file: File, blocks: u32) -> Self {
+        Self { file: RefCell::new(file), blocks, write_protected: Cell::new(false) }
+    }
+
+    /// Make writes fail as on a card with a failing (or locked) medium
+    pub fn set_write_protected(&self, protected: bool) {
+        self.write_protected.set(protected);
+    }
+
+    fn seek(&self, start: BlockIdx, count: usize) -> io::Result<()> {
+        let BlockIdx(start) = start;
+
+        if start as u64 + count as u64 > self.blocks as u64 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "Block out of range"));
+        }
+
+        self.file.borrow_mut().seek(SeekFrom::Start(start as u64*Block::LEN as u64))?;
+        Ok(())
+    }
+}
+
+impl BlockDevice for FileBlockDevice {
+    type Error = io::Error;
+
+    fn read(&self, blocks: &mut [Block], start_block_idx: BlockIdx, _reason: &str) -> io::Result<()> {
+        self.seek(start_block_idx, blocks.len())?;
+        let mut file = self.file.borrow_mut();
+
+        for block in blocks.iter_mut() {
+            file.read_exact(&mut block.contents)?;
+        }
+
+        Ok(())
+    }
+
+    fn write(&self, blocks: &[Block], start_block_idx: BlockIdx) -> io::Result<()> {
+        if self.write_protected.get() {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Write protected"));
+        }
+
+        self.seek(start_block_idx, blocks.len())?;
+        let mut file = self.file.borrow_mut();
+
+        for block in blocks.iter() {
+            file.write_all(&block.contents)?;
+        }
+
+        Ok(())
+    }
+
+    fn num_blocks(&self) -> io::Result<BlockCount> {
+        Ok(BlockCount(self.blocks))
+    }
+}
@@ -0,0 +1,466 @@
+//! Creating an SD card image with an MBR partition table and a single
+//! FAT16 or FAT32 partition
+
+use embedded_sdmmc::{Block, BlockCount, BlockDevice, BlockIdx};
+
+/// Partition is aligned to 1 MiB as on cards formatted by most tools
+pub const PARTITION_START: u32 = 2048;
+
+const BYTES_PER_SECTOR: u32 = Block::LEN_U32;
+const FAT_COUNT: u32 = 2;
+const FAT16_RESERVED_SECTORS: u32 = 4;
+const FAT16_ROOT_ENTRIES: u32 = 512;
+const FAT32_RESERVED_SECTORS: u32 = 32;
+const FAT32_ROOT_CLUSTER: u32 = 2;
+const FAT32_INFO_SECTOR: u32 = 1;
+const FAT32_BACKUP_BOOT_SECTOR: u32 = 6;
+const DIR_ENTRY_SIZE: usize = 32;
+
+/// Cluster count limits telling the FAT type apart
+const FAT16_MIN_CLUSTERS: u32 = 4085;
+const FAT32_MIN_CLUSTERS: u32 = 65525;
+const FAT32_MAX_CLUSTERS: u32 = 0x0fff_fff5;
+
+const PARTITION_TYPE_FAT16_LBA: u8 = 0x0e;
+const PARTITION_TYPE_FAT32_LBA: u8 = 0x0c;
+const MEDIA_FIXED_DISK: u8 = 0xf8;
+const ATTRIBUTE_READ_ONLY: u8 = 0x01;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FatType {
+    Fat16,
+    Fat32,
+}
+
+/// Cluster allocation of a volume created by `format`, lets tests check
+/// the FAT after files were written
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FatUsage {
+    /// Clusters in the data region
+    pub clusters: u32,
+    /// Clusters with a free entry in the first FAT
+    pub free_clusters: u32,
+    /// All FAT copies have the same content
+    pub copies_match: bool,
+    /// Free cluster count and next free cluster kept in the FAT32 file
+    /// system information sector, `u32::MAX` when unknown
+    pub info: Option<(u32, u32)>,
+}
+
+#[derive(Debug)]
+pub enum ImageError<E> {
+    /// Device is too small (or too large) for the requested FAT type
+    InvalidSize,
+    /// Device does not contain a volume created by `format`
+    InvalidVolume,
+    FileNotFound,
+    Device(E),
+}
+
+/// Volume layout in sectors relative to the partition start
+struct Layout {
+    fat_type: FatType,
+    total_sectors: u32,
+    sectors_per_cluster: u32,
+    reserved_sectors: u32,
+    fat_sectors: u32,
+    root_dir_sectors: u32,
+}
+
+impl Layout {
+    fn new(fat_type: FatType, total_sectors: u32) -> Option<Self> {
+        let (reserved_sectors, root_dir_sectors, min_clusters, max_clusters) = match fat_type {
+            FatType::Fat16 => (
+                FAT16_RESERVED_SECTORS,
+                FAT16_ROOT_ENTRIES*DIR_ENTRY_SIZE as u32/BYTES_PER_SECTOR,
+                FAT16_MIN_CLUSTERS,
+                FAT32_MIN_CLUSTERS - 1,
+            ),
+            FatType::Fat32 => (
+                FAT32_RESERVED_SECTORS, 0, FAT32_MIN_CLUSTERS, FAT32_MAX_CLUSTERS
+            ),
+        };
+
+        // Smallest cluster giving a valid cluster count for the FAT type
+        [1, 2, 4, 8, 16, 32, 64, 128].iter().find_map(|&sectors_per_cluster| {
+            let mut layout = Self {
+                fat_type,
+                total_sectors,
+                sectors_per_cluster,
+                reserved_sectors,
+                fat_sectors: 1,
+                root_dir_sectors,
+            };
+
+            // FAT size depends on the cluster count and vice versa,
+            // grow the FAT until it covers all clusters
+            loop {
+                let clusters = layout.cluster_count()?;
+                let fat_sectors = ((clusters + 2)*layout.fat_entry_size())
+                    .div_ceil(BYTES_PER_SECTOR);
+
+                if fat_sectors <= layout.fat_sectors {
+                    break;
+                }
+
+                layout.fat_sectors = fat_sectors;
+            }
+
+            let clusters = layout.cluster_count()?;
+            (min_clusters..=max_clusters).contains(&clusters).then_some(layout)
+        })
+    }
+
+    fn fat_entry_size(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat16 => 2,
+            FatType::Fat32 => 4,
+        }
+    }
+
+    fn first_fat_sector(&self) -> u32 {
+        self.reserved_sectors
+    }
+
+    fn root_dir_sector(&self) -> u32 {
+        self.reserved_sectors + FAT_COUNT*self.fat_sectors
+    }
+
+    fn first_data_sector(&self) -> u32 {
+        self.root_dir_sector() + self.root_dir_sectors
+    }
+
+    fn cluster_count(&self) -> Option<u32> {
+        let data_sectors = self.total_sectors.checked_sub(self.first_data_sector())?;
+        Some(data_sectors/self.sectors_per_cluster)
+    }
+}
+
+/// Write the partition table and an empty FAT volume spanning the whole
+/// device after `PARTITION_START`
+pub fn format<D: BlockDevice>(device: &D, fat_type: FatType) -> Result<(), ImageError<D::Error>> {
+    let BlockCount(blocks) = device.num_blocks().map_err(ImageError::Device)?;
+    let total_sectors = blocks.checked_sub(PARTITION_START).ok_or(ImageError::InvalidSize)?;
+    let layout = Layout::new(fat_type, total_sectors).ok_or(ImageError::InvalidSize)?;
+
+    let write = |sector: u32, block: &Block| {
+        device.write(core::slice::from_ref(block), BlockIdx(PARTITION_START + sector))
+            .map_err(ImageError::Device)
+    };
+
+    device.write(&[master_boot_record(&layout)], BlockIdx(0)).map_err(ImageError::Device)?;
+
+    // Reserved sectors, both FATs and the root directory start empty
+    let empty = Block::new();
+
+    for sector in 0..layout.first_data_sector() {
+        write(sector, &empty)?;
+    }
+
+    let boot_sector = boot_sector(&layout);
+    write(0, &boot_sector)?;
+
+    if fat_type == FatType::Fat32 {
+        write(FAT32_INFO_SECTOR, &info_sector(&layout))?;
+        write(FAT32_BACKUP_BOOT_SECTOR, &boot_sector)?;
+
+        // Root directory occupies the first cluster
+        for sector in 0..layout.sectors_per_cluster {
+            write(layout.first_data_sector() + sector, &empty)?;
+        }
+    }
+
+    let first_fat = first_fat_sector(&layout);
+
+    for fat in 0..FAT_COUNT {
+        write(layout.first_fat_sector() + fat*layout.fat_sectors, &first_fat)?;
+    }
+
+    Ok(())
+}
+
+fn master_boot_record(layout: &Layout) -> Block {
+    let mut block = Block::new();
+    let entry = &mut block.contents[446..462];
+
+    // Not bootable, CHS addresses are not used (LBA only)
+    entry[1..4].copy_from_slice(&[0xfe, 0xff, 0xff]);
+    entry[4] = match layout.fat_type {
+        FatType::Fat16 => PARTITION_TYPE_FAT16_LBA,
+        FatType::Fat32 => PARTITION_TYPE_FAT32_LBA,
+    };
+    entry[5..8].copy_from_slice(&[0xfe, 0xff, 0xff]);
+    entry[8..12].copy_from_slice(&PARTITION_START.to_le_bytes());
+    entry[12..16].copy_from_slice(&layout.total_sectors.to_le_bytes());
+
+    set_signature(&mut block);
+    block
+}
+
+fn boot_sector(layout: &Layout) -> Block {
+    let mut block = Block::new();
+    let data = &mut block.contents;
+
+    let jump_offset = match layout.fat_type {
+        FatType::Fat16 => 0x3c,
+        FatType::Fat32 => 0x58,
+    };
+
+    data[0..3].copy_from_slice(&[0xeb, jump_offset, 0x90]);
+    data[3..11].copy_from_slice(b"BLKPILL ");
+    data[11..13].copy_from_slice(&(BYTES_PER_SECTOR as u16).to_le_bytes());
+    data[13] = layout.sectors_per_cluster as u8;
+    data[14..16].copy_from_slice(&(layout.reserved_sectors as u16).to_le_bytes());
+    data[16] = FAT_COUNT as u8;
+    data[21] = MEDIA_FIXED_DISK;
+    // Geometry is not used, values common for LBA addressed disks
+    data[24..26].copy_from_slice(&63u16.to_le_bytes());
+    data[26..28].copy_from_slice(&255u16.to_le_bytes());
+    data[28..32].copy_from_slice(&PARTITION_START.to_le_bytes());
+
+    let extended = match layout.fat_type {
+        FatType::Fat16 => {
+            data[17..19].copy_from_slice(&(FAT16_ROOT_ENTRIES as u16).to_le_bytes());
+
+            match u16::try_from(layout.total_sectors) {
+                Ok(sectors) => data[19..21].copy_from_slice(&sectors.to_le_bytes()),
+                Err(_) => data[32..36].copy_from_slice(&layout.total_sectors.to_le_bytes()),
+            }
+
+            data[22..24].copy_from_slice(&(layout.fat_sectors as u16).to_le_bytes());
+            36
+        },
+        FatType::Fat32 => {
+            data[32..36].copy_from_slice(&layout.total_sectors.to_le_bytes());
+            data[36..40].copy_from_slice(&layout.fat_sectors.to_le_bytes());
+            data[44..48].copy_from_slice(&FAT32_ROOT_CLUSTER.to_le_bytes());
+            data[48..50].copy_from_slice(&(FAT32_INFO_SECTOR as u16).to_le_bytes());
+            data[50..52].copy_from_slice(&(FAT32_BACKUP_BOOT_SECTOR as u16).to_le_bytes());
+            64
+        },
+    };
+
+    let file_system = match layout.fat_type {
+        FatType::Fat16 => b"FAT16   ",
+        FatType::Fat32 => b"FAT32   ",
+    };
+
+    // Drive number, extended boot signature, serial number, label and type
+    data[extended] = 0x80;
+    data[extended + 2] = 0x29;
+    data[extended + 3..extended + 7].copy_from_slice(&0x1234_5678u32.to_le_bytes());
+    data[extended + 7..extended + 18].copy_from_slice(b"NO NAME    ");
+    data[extended + 18..extended + 26].copy_from_slice(file_system);
+
+    set_signature(&mut block);
+    block
+}
+
+/// FAT32 file system information sector of an empty volume, as written
+/// by most tools the free cluster count is known and the next free
+/// cluster follows the root directory
+fn info_sector(layout: &Layout) -> Block {
+    let mut block = Block::new();
+    let data = &mut block.contents;
+    let free_clusters = layout.cluster_count().unwrap_or(1) - 1;
+
+    data[0..4].copy_from_slice(&0x4161_5252u32.to_le_bytes());
+    data[484..488].copy_from_slice(&0x6141_7272u32.to_le_bytes());
+    data[488..492].copy_from_slice(&free_clusters.to_le_bytes());
+    data[492..496].copy_from_slice(&(FAT32_ROOT_CLUSTER + 1).to_le_bytes());
+    data[508..512].copy_from_slice(&0xaa55_0000u32.to_le_bytes());
+    block
+}
+
+/// Media descriptor and end of chain entries for the reserved clusters,
+/// on FAT32 also the end of the root directory chain
+fn first_fat_sector(layout: &Layout) -> Block {
+    let mut block = Block::new();
+    let data = &mut block.contents;
+
+    match layout.fat_type {
+        FatType::Fat16 => {
+            data[0..2].copy_from_slice(&(0xff00 | MEDIA_FIXED_DISK as u16).to_le_bytes());
+            data[2..4].copy_from_slice(&0xffffu16.to_le_bytes());
+        },
+        FatType::Fat32 => {
+            data[0..4].copy_from_slice(&(0x0fff_ff00 | MEDIA_FIXED_DISK as u32).to_le_bytes());
+            data[4..8].copy_from_slice(&0x0fff_ffffu32.to_le_bytes());
+            data[8..12].copy_from_slice(&0x0fff_ffffu32.to_le_bytes());
+        },
+    }
+
+    block
+}
+
+fn set_signature(block: &mut Block) {
+    block.contents[510] = 0x55;
+    block.contents[511] = 0xaa;
+}
+
+/// Set the read only attribute of a file in the root directory of a volume
+/// created by `format` (on FAT32, only the first root directory cluster
+/// is searched), `name` is a short 8.3 name
+pub fn set_read_only<D: BlockDevice>(device: &D, name: &str) -> Result<(), ImageError<D::Error>> {
+    let mut block = Block::new();
+
+    let read = |sector: u32, block: &mut Block| {
+        device.read(core::slice::from_mut(block), BlockIdx(sector), "image")
+            .map_err(ImageError::Device)
+    };
+
+    read(PARTITION_START, &mut block)?;
+    let layout = parse_boot_sector(&block).ok_or(ImageError::InvalidVolume)?;
+
+    let root_sectors = match layout.fat_type {
+        FatType::Fat16 => layout.root_dir_sectors,
+        FatType::Fat32 => layout.sectors_per_cluster,
+    };
+
+    let short_name = short_name(name).ok_or(ImageError::FileNotFound)?;
+
+    for sector in 0..root_sectors {
+        let sector = PARTITION_START + layout.root_dir_sector() + sector;
+        read(sector, &mut block)?;
+
+        let found = block.contents.chunks_exact_mut(DIR_ENTRY_SIZE)
+            .find(|entry| entry[0..11] == short_name);
+
+        if let Some(entry) = found {
+            entry[11] |= ATTRIBUTE_READ_ONLY;
+            return device.write(&[block], BlockIdx(sector)).map_err(ImageError::Device);
+        }
+    }
+
+    Err(ImageError::FileNotFound)
+}
+
+/// Count free clusters and compare the FAT copies of a volume created by
+/// `format`
+pub fn fat_usage<D: BlockDevice>(device: &D) -> Result<FatUsage, ImageError<D::Error>> {
+    let layout = read_layout(device)?;
+    let clusters = layout.cluster_count().ok_or(ImageError::InvalidVolume)?;
+    let entry_size = layout.fat_entry_size() as usize;
+
+    let mut free_clusters = 0;
+    let mut copies_match = true;
+    let mut blocks = [Block::new(), Block::new()];
+
+    for sector in 0..layout.fat_sectors {
+        for (copy, block) in blocks.iter_mut().enumerate() {
+            let sector = layout.first_fat_sector() + copy as u32*layout.fat_sectors + sector;
+            read_block(device, sector, block)?;
+        }
+
+        copies_match &= blocks[0].contents == blocks[1].contents;
+
+        // Entries of the two reserved clusters and past the last cluster
+        // are not counted
+        let first_cluster = sector*BYTES_PER_SECTOR/layout.fat_entry_size();
+
+        free_clusters += blocks[0].contents.chunks_exact(entry_size)
+            .zip(first_cluster..)
+            .filter(|(entry, cluster)| {
+                (2..clusters + 2).contains(cluster) && fat_entry(&layout, entry) == 0
+            })
+            .count() as u32;
+    }
+
+    let info = match layout.fat_type {
+        FatType::Fat16 => None,
+        FatType::Fat32 => {
+            let mut block = Block::new();
+            read_block(device, FAT32_INFO_SECTOR, &mut block)?;
+            let data = &block.contents;
+            let u32_at = |offset: usize| {
+                u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+            };
+
+            Some((u32_at(488), u32_at(492)))
+        },
+    };
+
+    Ok(FatUsage { clusters, free_clusters, copies_match, info })
+}
+
+/// FAT entry of `cluster` in the first FAT of a volume created by `format`,
+/// zero for a free cluster
+pub fn cluster_entry<D: BlockDevice>(
+    device: &D,
+    cluster: u32,
+) -> Result<u32, ImageError<D::Error>> {
+    let layout = read_layout(device)?;
+    let offset = cluster*layout.fat_entry_size();
+
+    let mut block = Block::new();
+    read_block(device, layout.first_fat_sector() + offset/BYTES_PER_SECTOR, &mut block)?;
+
+    let offset = (offset % BYTES_PER_SECTOR) as usize;
+    Ok(fat_entry(&layout, &block.contents[offset..offset + layout.fat_entry_size() as usize]))
+}
+
+fn fat_entry(layout: &Layout, entry: &[u8]) -> u32 {
+    match layout.fat_type {
+        FatType::Fat16 => u16::from_le_bytes([entry[0], entry[1]]) as u32,
+        FatType::Fat32 => {
+            u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) & 0x0fff_ffff
+        },
+    }
+}
+
+fn read_layout<D: BlockDevice>(device: &D) -> Result<Layout, ImageError<D::Error>> {
+    let mut block = Block::new();
+    read_block(device, 0, &mut block)?;
+    parse_boot_sector(&block).ok_or(ImageError::InvalidVolume)
+}
+
+/// Read a sector relative to the partition start
+fn read_block<D: BlockDevice>(
+    device: &D,
+    sector: u32,
+    block: &mut Block,
+) -> Result<(), ImageError<D::Error>> {
+    device.read(core::slice::from_mut(block), BlockIdx(PARTITION_START + sector), "image")
+        .map_err(ImageError::Device)
+}
+
+fn parse_boot_sector(block: &Block) -> Option<Layout> {
+    let data = &block.contents;
+    let u16_at = |offset: usize| u16::from_le_bytes([data[offset], data[offset + 1]]) as u32;
+    let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+
+    if data[510..512] != [0x55, 0xaa] {
+        return None;
+    }
+
+    let root_entries = u16_at(17);
+
+    let (fat_type, fat_sectors) = match u16_at(22) {
+        0 => (FatType::Fat32, u32_at(36)),
+        sectors => (FatType::Fat16, sectors),
+    };
+
+    Some(Layout {
+        fat_type,
+        total_sectors: u32_at(32).max(u16_at(19)),
+        sectors_per_cluster: data[13] as u32,
+        reserved_sectors: u16_at(14),
+        fat_sectors,
+        root_dir_sectors: root_entries*DIR_ENTRY_SIZE as u32/BYTES_PER_SECTOR,
+    })
+}
+
+/// Directory entry form of an 8.3 name, `LOG.TXT` is `LOG     TXT`
+fn short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, extension) = name.split_once('.').unwrap_or((name, ""));
+
+    if base.is_empty() || base.len() > 8 || extension.len() > 3 {
+        return None;
+    }
+
+    let mut result = [b' '; 11];
+    result[..base.len()].copy_from_slice(base.as_bytes());
+    result[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+    result.make_ascii_uppercase();
+    Some(result)
+}
@@ -0,0 +1,7 @@
+//! SD card images for testing the SD card code on the host, formatting
+//! works with any block device, file backed images need the `std` feature
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+pub mod file;
+pub mod image;
@@ -0,0 +1,608 @@
+//! Binary log format
+//!
+//! A file starts with a header describing the records, all numbers are
+//! little endian:
+//!
+//! - magic `SDLB`, format version (u8), flags (u8, bit 0 delta encoding)
+//! - record size (u16) and number of fields (u8)
+//! - for each field its type code (u8), name length (u8) and name of ASCII
+//!   letters, digits and `_`
+//! - CRC-32 of the header before it (u32)
+//!
+//! Fixed size records follow the header:
+//!
+//! - kind (u8, 1 sample, 2 time sync) and flags (u8, `FLAG_` constants)
+//! - sequence number (u32)
+//! - time as Unix seconds (u32), or with delta encoding the seconds since
+//!   the last time sync record (u16)
+//! - field values in the header order
+//! - CRC-32 of the record before it (u32), see `record` for the CRC
+//!
+//! With delta encoding a time sync record carries the Unix time in place
+//! of the time and the first value bytes. It is written before the first
+//! sample of a file (after `RecordEncoder::restart`), when the difference
+//! does not fit and every `SYNC_INTERVAL` samples, so that a damaged sync
+//! record costs the time of a limited number of samples.
+
+use arrayvec::{ArrayString, ArrayVec};
+use core::fmt::{self, Display};
+use crate::record::{Checksum, RecordScan};
+
+pub const MAGIC: [u8; 4] = *b"SDLB";
+pub const VERSION: u8 = 1;
+
+pub const MAX_FIELDS: usize = 16;
+pub const MAX_FIELD_NAME: usize = 15;
+
+/// Longest header, fixed part, fields and CRC
+pub const MAX_HEADER: usize = 9 + MAX_FIELDS*(2 + MAX_FIELD_NAME) + 4;
+
+/// Longest record, all fields four bytes long
+pub const MAX_RECORD: usize = RECORD_PREFIX + 4 + MAX_FIELDS*4 + 4;
+
+/// Samples between time sync records with delta encoding
+pub const SYNC_INTERVAL: u32 = 64;
+
+/// Time was not valid when the sample was taken
+pub const FLAG_TIME_INVALID: u8 = 0x01;
+/// First sample after a time jump
+pub const FLAG_TIME_JUMP: u8 = 0x02;
+
+const HEADER_DELTA: u8 = 0x01;
+const KIND_SAMPLE: u8 = 1;
+const KIND_SYNC: u8 = 2;
+
+/// Kind, flags and sequence number
+const RECORD_PREFIX: usize = 6;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+}
+
+impl FieldType {
+    pub fn size(self) -> usize {
+        match self {
+            FieldType::U8 | FieldType::I8 => 1,
+            FieldType::U16 | FieldType::I16 => 2,
+            FieldType::U32 | FieldType::I32 => 4,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldType::U8 => "u8",
+            FieldType::I8 => "i8",
+            FieldType::U16 => "u16",
+            FieldType::I16 => "i16",
+            FieldType::U32 => "u32",
+            FieldType::I32 => "i32",
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            FieldType::U8 => 1,
+            FieldType::I8 => 2,
+            FieldType::U16 => 3,
+            FieldType::I16 => 4,
+            FieldType::U32 => 5,
+            FieldType::I32 => 6,
+        }
+    }
+
+    fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(FieldType::U8),
+            2 => Some(FieldType::I8),
+            3 => Some(FieldType::U16),
+            4 => Some(FieldType::I16),
+            5 => Some(FieldType::U32),
+            6 => Some(FieldType::I32),
+            _ => None,
+        }
+    }
+
+    /// Store the `value` truncated to the field size
+    fn encode(self, value: i64, output: &mut [u8]) {
+        let bytes = (value as u32).to_le_bytes();
+        output[..self.size()].copy_from_slice(&bytes[..self.size()]);
+    }
+
+    fn decode(self, data: &[u8]) -> i64 {
+        match self {
+            FieldType::U8 => data[0] as i64,
+            FieldType::I8 => data[0] as i8 as i64,
+            FieldType::U16 => u16::from_le_bytes([data[0], data[1]]) as i64,
+            FieldType::I16 => i16::from_le_bytes([data[0], data[1]]) as i64,
+            FieldType::U32 => u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as i64,
+            FieldType::I32 => i32::from_le_bytes([data[0], data[1], data[2], data[3]]) as i64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FormatError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    TooManyFields,
+    /// Empty or too long name, or a name with other characters than ASCII
+    /// letters, digits and `_`
+    InvalidFieldName,
+    UnknownFieldType(u8),
+    /// Record size in the header does not match the fields
+    BadRecordSize,
+    /// Delta encoding needs at least two bytes of values
+    DeltaWithoutValues,
+    Truncated,
+    BadCrc,
+    UnknownKind(u8),
+    /// Delta encoded sample without a preceding time sync record
+    MissingTime,
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::BadMagic => write!(f, "not a binary log"),
+            FormatError::UnsupportedVersion(version) => write!(f, "unsupported version {}", version),
+            FormatError::TooManyFields => write!(f, "too many fields"),
+            FormatError::InvalidFieldName => write!(f, "invalid field name"),
+            FormatError::UnknownFieldType(code) => write!(f, "unknown field type {}", code),
+            FormatError::BadRecordSize => write!(f, "bad record size"),
+            FormatError::DeltaWithoutValues => write!(f, "delta encoding without values"),
+            FormatError::Truncated => write!(f, "truncated"),
+            FormatError::BadCrc => write!(f, "bad CRC"),
+            FormatError::UnknownKind(kind) => write!(f, "unknown record kind {}", kind),
+            FormatError::MissingTime => write!(f, "missing time sync"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: ArrayString<MAX_FIELD_NAME>,
+    pub field_type: FieldType,
+}
+
+/// Fields of the records and the time encoding
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schema {
+    fields: ArrayVec<Field, MAX_FIELDS>,
+    delta: bool,
+}
+
+impl Schema {
+    pub fn new(delta: bool) -> Self {
+        Self { fields: ArrayVec::new(), delta }
+    }
+
+    /// Add a field named by ASCII letters, digits and `_`, so that the name
+    /// can be written to CSV and JSON output as it is
+    pub fn add_field(&mut self, name: &str, field_type: FieldType) -> Result<(), FormatError> {
+        let valid_name = !name.is_empty()
+            && name.bytes().all(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+
+        let name = ArrayString::from(name)
+            .ok()
+            .filter(|_| valid_name)
+            .ok_or(FormatError::InvalidFieldName)?;
+
+        self.fields.try_push(Field { name, field_type }).map_err(|_| FormatError::TooManyFields)
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub fn delta(&self) -> bool {
+        self.delta
+    }
+
+    pub fn record_size(&self) -> usize {
+        RECORD_PREFIX + self.time_size() + self.values_size() + 4
+    }
+
+    pub fn header_size(&self) -> usize {
+        9 + self.fields.iter().map(|field| 2 + field.name.len()).sum::<usize>() + 4
+    }
+
+    /// Write the file header to `output`, returns its length
+    pub fn write_header(
+        &self,
+        output: &mut [u8],
+        checksum: &mut dyn Checksum,
+    ) -> Result<usize, FormatError> {
+        if self.delta && self.values_size() < 2 {
+            return Err(FormatError::DeltaWithoutValues);
+        }
+
+        let size = self.header_size();
+        let output = output.get_mut(..size).ok_or(FormatError::Truncated)?;
+
+        output[..4].copy_from_slice(&MAGIC);
+        output[4] = VERSION;
+        output[5] = if self.delta { HEADER_DELTA } else { 0 };
+        output[6..8].copy_from_slice(&(self.record_size() as u16).to_le_bytes());
+        output[8] = self.fields.len() as u8;
+
+        let mut position = 9;
+
+        for field in &self.fields {
+            output[position] = field.field_type.code();
+            output[position + 1] = field.name.len() as u8;
+            position += 2;
+            output[position..position + field.name.len()].copy_from_slice(field.name.as_bytes());
+            position += field.name.len();
+        }
+
+        let crc = checksum.crc32(&output[..position]);
+        output[position..].copy_from_slice(&crc.to_le_bytes());
+        Ok(size)
+    }
+
+    /// Read the file header at the start of `data`, returns the schema
+    /// and the header length
+    pub fn read_header(
+        data: &[u8],
+        checksum: &mut dyn Checksum,
+    ) -> Result<(Self, usize), FormatError> {
+        // Beginning of the magic is reported as truncated
+        if !MAGIC.starts_with(&data[..data.len().min(MAGIC.len())]) {
+            return Err(FormatError::BadMagic);
+        }
+
+        let fixed = data.get(..9).ok_or(FormatError::Truncated)?;
+
+        if fixed[4] != VERSION {
+            return Err(FormatError::UnsupportedVersion(fixed[4]));
+        }
+
+        let mut schema = Self::new(fixed[5] & HEADER_DELTA != 0);
+        let record_size = u16::from_le_bytes([fixed[6], fixed[7]]) as usize;
+        let mut position = 9;
+
+        for _ in 0..fixed[8] {
+            let field = data.get(position..position + 2).ok_or(FormatError::Truncated)?;
+            let field_type = FieldType::from_code(field[0])
+                .ok_or(FormatError::UnknownFieldType(field[0]))?;
+
+            let name_end = position + 2 + field[1] as usize;
+            let name = data.get(position + 2..name_end).ok_or(FormatError::Truncated)?;
+            let name = core::str::from_utf8(name).map_err(|_| FormatError::InvalidFieldName)?;
+
+            schema.add_field(name, field_type)?;
+            position = name_end;
+        }
+
+        let crc = data.get(position..position + 4).ok_or(FormatError::Truncated)?;
+
+        if u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) != checksum.crc32(&data[..position]) {
+            return Err(FormatError::BadCrc);
+        }
+
+        if record_size != schema.record_size() {
+            return Err(FormatError::BadRecordSize);
+        }
+
+        Ok((schema, position + 4))
+    }
+
+    fn time_size(&self) -> usize {
+        if self.delta { 2 } else { 4 }
+    }
+
+    fn values_size(&self) -> usize {
+        self.fields.iter().map(|field| field.field_type.size()).sum()
+    }
+}
+
+/// Sample with values in the schema field order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub sequence: u32,
+    /// Unix seconds
+    pub time: u32,
+    pub flags: u8,
+    pub values: ArrayVec<i64, MAX_FIELDS>,
+}
+
+/// Encodes samples to records, keeps the time of the last sync record
+pub struct RecordEncoder {
+    schema: Schema,
+    sync_time: Option<u32>,
+    since_sync: u32,
+}
+
+impl RecordEncoder {
+    pub fn new(schema: Schema) -> Self {
+        Self { schema, sync_time: None, since_sync: 0 }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// The next sample starts a file or follows a restart, it is preceded
+    /// by a time sync record with delta encoding
+    pub fn restart(&mut self) {
+        self.sync_time = None;
+    }
+
+    /// Encode the `sample` to `output` (at least two records long),
+    /// returns the length of the records written
+    pub fn encode(
+        &mut self,
+        sample: &Sample,
+        output: &mut [u8],
+        checksum: &mut dyn Checksum,
+    ) -> Result<usize, FormatError> {
+        let size = self.schema.record_size();
+
+        if self.schema.delta && self.schema.values_size() < 2 {
+            return Err(FormatError::DeltaWithoutValues);
+        }
+
+        if !self.schema.delta {
+            let record = output.get_mut(..size).ok_or(FormatError::Truncated)?;
+            self.write_record(record, KIND_SAMPLE, sample, &sample.time.to_le_bytes(), checksum);
+            return Ok(size);
+        }
+
+        let delta = self.sync_time
+            .filter(|_| self.since_sync < SYNC_INTERVAL)
+            .and_then(|sync_time| sample.time.checked_sub(sync_time))
+            .and_then(|delta| u16::try_from(delta).ok());
+
+        let mut length = 0;
+
+        let delta = match delta {
+            Some(delta) => delta,
+            None => {
+                let record = output.get_mut(..size).ok_or(FormatError::Truncated)?;
+                let sync = Sample { values: ArrayVec::new(), ..sample.clone() };
+                self.write_record(record, KIND_SYNC, &sync, &sample.time.to_le_bytes(), checksum);
+                self.sync_time = Some(sample.time);
+                self.since_sync = 0;
+                length += size;
+                0
+            },
+        };
+
+        let record = output.get_mut(length..length + size).ok_or(FormatError::Truncated)?;
+        self.write_record(record, KIND_SAMPLE, sample, &delta.to_le_bytes(), checksum);
+        self.since_sync += 1;
+        Ok(length + size)
+    }
+
+    fn write_record(
+        &self,
+        record: &mut [u8],
+        kind: u8,
+        sample: &Sample,
+        time: &[u8],
+        checksum: &mut dyn Checksum,
+    ) {
+        record.fill(0);
+        record[0] = kind;
+        record[1] = sample.flags;
+        record[2..RECORD_PREFIX].copy_from_slice(&sample.sequence.to_le_bytes());
+
+        // Sync time may continue over the first value bytes
+        record[RECORD_PREFIX..RECORD_PREFIX + time.len()].copy_from_slice(time);
+
+        let mut position = RECORD_PREFIX + self.schema.time_size();
+
+        for (field, &value) in self.schema.fields.iter().zip(sample.values.iter()) {
+            field.field_type.encode(value, &mut record[position..]);
+            position += field.field_type.size();
+        }
+
+        let crc_position = record.len() - 4;
+        let crc = checksum.crc32(&record[..crc_position]);
+        record[crc_position..].copy_from_slice(&crc.to_le_bytes());
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decoded {
+    Sample(Sample),
+    /// Time sync record with its Unix time
+    TimeSync(u32),
+}
+
+/// Decodes records of a file, keeps the time of the last sync record
+pub struct RecordDecoder {
+    schema: Schema,
+    sync_time: Option<u32>,
+}
+
+impl RecordDecoder {
+    pub fn new(schema: Schema) -> Self {
+        Self { schema, sync_time: None }
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Decode one record of the schema record size, a damaged record
+    /// clears the sync time as it may have been a sync record
+    pub fn decode(
+        &mut self,
+        record: &[u8],
+        checksum: &mut dyn Checksum,
+    ) -> Result<Decoded, FormatError> {
+        let size = self.schema.record_size();
+
+        if record.len() < size {
+            return Err(FormatError::Truncated);
+        }
+
+        let crc_position = size - 4;
+        let crc = &record[crc_position..size];
+
+        if u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) != checksum.crc32(&record[..crc_position]) {
+            self.sync_time = None;
+            return Err(FormatError::BadCrc);
+        }
+
+        let flags = record[1];
+        let sequence = u32::from_le_bytes([record[2], record[3], record[4], record[5]]);
+        let time = &record[RECORD_PREFIX..];
+
+        match record[0] {
+            KIND_SYNC if self.schema.delta => {
+                let time = u32::from_le_bytes([time[0], time[1], time[2], time[3]]);
+                self.sync_time = Some(time);
+                Ok(Decoded::TimeSync(time))
+            },
+            KIND_SAMPLE => {
+                let time = match self.schema.delta {
+                    true => {
+                        let delta = u16::from_le_bytes([time[0], time[1]]) as u32;
+                        self.sync_time.ok_or(FormatError::MissingTime)?.wrapping_add(delta)
+                    },
+                    false => u32::from_le_bytes([time[0], time[1], time[2], time[3]]),
+                };
+
+                let mut values = ArrayVec::new();
+                let mut position = RECORD_PREFIX + self.schema.time_size();
+
+                for field in &self.schema.fields {
+                    values.push(field.field_type.decode(&record[position..]));
+                    position += field.field_type.size();
+                }
+
+                Ok(Decoded::Sample(Sample { sequence, time, flags, values }))
+            },
+            kind => Err(FormatError::UnknownKind(kind)),
+        }
+    }
+}
+
+/// Scan of a binary log file, reads the header and checks the records
+/// following it
+pub struct BinaryScan {
+    header: ArrayVec<u8, MAX_HEADER>,
+    header_error: Option<FormatError>,
+    decoder: Option<RecordDecoder>,
+    record: ArrayVec<u8, MAX_RECORD>,
+    size: u32,
+    last_sequence: Option<u32>,
+    valid: u32,
+    invalid: u32,
+}
+
+impl BinaryScan {
+    pub fn new() -> Self {
+        Self {
+            header: ArrayVec::new(),
+            header_error: None,
+            decoder: None,
+            record: ArrayVec::new(),
+            size: 0,
+            last_sequence: None,
+            valid: 0,
+            invalid: 0,
+        }
+    }
+
+    /// Schema of a valid header
+    pub fn schema(&self) -> Option<&Schema> {
+        self.decoder.as_ref().map(|decoder| decoder.schema())
+    }
+
+    pub fn header_error(&self) -> Option<FormatError> {
+        self.header_error
+    }
+
+    /// File size
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn valid_records(&self) -> u32 {
+        self.valid
+    }
+
+    pub fn invalid_records(&self) -> u32 {
+        self.invalid
+    }
+
+    /// Bytes completing a record cut at the end of the file, records
+    /// appended after them are aligned again
+    pub fn padding(&self) -> usize {
+        match (&self.decoder, self.record.len()) {
+            (Some(decoder), length) if length > 0 => decoder.schema().record_size() - length,
+            _ => 0,
+        }
+    }
+
+    fn feed_header(&mut self, byte: u8, checksum: &mut dyn Checksum) {
+        if self.header.try_push(byte).is_err() {
+            self.header_error = Some(FormatError::Truncated);
+            return;
+        }
+
+        match Schema::read_header(&self.header, checksum) {
+            Ok((schema, _)) => self.decoder = Some(RecordDecoder::new(schema)),
+            Err(FormatError::Truncated) => {},
+            Err(error) => self.header_error = Some(error),
+        }
+    }
+}
+
+impl RecordScan for BinaryScan {
+    fn feed(&mut self, data: &[u8], checksum: &mut dyn Checksum) {
+        for &byte in data {
+            self.size += 1;
+
+            let decoder = match self.decoder.as_mut() {
+                Some(decoder) => decoder,
+                None if self.header_error.is_none() => {
+                    self.feed_header(byte, checksum);
+                    continue;
+                },
+                None => continue,
+            };
+
+            self.record.push(byte);
+
+            if self.record.len() == decoder.schema().record_size() {
+                match decoder.decode(&self.record, checksum) {
+                    Ok(Decoded::Sample(sample)) => {
+                        self.last_sequence = Some(sample.sequence);
+                        self.valid += 1;
+                    },
+                    Ok(Decoded::TimeSync(_)) => {},
+                    Err(_) => self.invalid += 1,
+                }
+
+                self.record.clear();
+            }
+        }
+    }
+
+    /// Header cut before its end is reported as truncated
+    fn finish(&mut self, _checksum: &mut dyn Checksum) {
+        if self.decoder.is_none() && self.header_error.is_none() && self.size > 0 {
+            self.header_error = Some(FormatError::Truncated);
+        }
+    }
+
+    fn last_sequence(&self) -> Option<u32> {
+        self.last_sequence
+    }
+}
+
+impl Default for BinaryScan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
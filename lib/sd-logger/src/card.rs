@@ -0,0 +1,191 @@
+//! Card devices, initialization and the SPI clock speed
+//!
+//! The card identification has to run at 400 kHz at most, data transfers
+//! can use a much faster clock. `ClockedCard` initializes the card using
+//! a slow clock, then selects the fastest clock the card reads reliably at
+//! and steps down when transfers fail with CRC errors or timeouts.
+
+use core::{cell::Cell, fmt::Debug};
+use embedded_hal::{spi::FullDuplex, digital::v2::OutputPin};
+use embedded_sdmmc::{Block, BlockCount, BlockDevice, BlockIdx, SdMmcError, SdMmcSpi};
+
+/// Highest clock allowed during card identification
+pub const IDENTIFICATION_HZ: u32 = 400_000;
+
+/// Reads of the first block done to accept a clock speed
+const SPEED_TEST_READS: usize = 4;
+
+/// Block device which has to be initialized before use and can be
+/// released when not used
+pub trait CardDevice: BlockDevice {
+    fn initialize(&mut self) -> Result<(), SdMmcError>;
+    fn release(&mut self);
+}
+
+impl<SPI, CS> CardDevice for SdMmcSpi<SPI, CS>
+where
+    SPI: FullDuplex<u8>,
+    CS: OutputPin,
+    <SPI as FullDuplex<u8>>::Error: Debug
+{
+    fn initialize(&mut self) -> Result<(), SdMmcError> {
+        self.init()
+    }
+
+    fn release(&mut self) {
+        self.deinit()
+    }
+}
+
+/// Clock of the SPI bus the card is connected to, speed levels go from
+/// the fastest (level 0) to the slowest (`levels() - 1`)
+pub trait SpiClock {
+    fn levels(&self) -> u8;
+    /// Clock frequency in Hz at the speed `level`
+    fn frequency(&self, level: u8) -> u32;
+    /// Reconfigure the bus to the speed `level`, called between transfers
+    fn set_level(&self, level: u8);
+}
+
+/// Card switching the SPI clock between the identification and data
+/// transfer speed
+pub struct ClockedCard<D, C> {
+    card: D,
+    clock: C,
+    level: Cell<u8>,
+    /// Fastest level tried at initialization
+    fastest_level: u8,
+    /// Fastest level not exceeding `IDENTIFICATION_HZ` (or the slowest one)
+    identification_level: u8,
+}
+
+impl<D, C> ClockedCard<D, C>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock {
+    pub fn new(card: D, clock: C) -> Self {
+        let slowest = clock.levels().saturating_sub(1);
+
+        let identification_level = (0..slowest)
+            .find(|&level| clock.frequency(level) <= IDENTIFICATION_HZ)
+            .unwrap_or(slowest);
+
+        clock.set_level(identification_level);
+
+        Self {
+            card,
+            clock,
+            level: Cell::new(identification_level),
+            fastest_level: 0,
+            identification_level,
+        }
+    }
+
+    /// Current SPI clock frequency in Hz
+    pub fn frequency(&self) -> u32 {
+        self.clock.frequency(self.level.get())
+    }
+
+    /// Limit the clock selected at the next initialization to `level`
+    /// and slower, for example to compare transfer speeds
+    pub fn set_fastest_level(&mut self, level: u8) {
+        self.fastest_level = level.min(self.identification_level);
+    }
+
+    pub fn card(&self) -> &D {
+        &self.card
+    }
+
+    fn set_level(&self, level: u8) {
+        self.clock.set_level(level);
+        self.level.set(level);
+    }
+
+    /// Fastest level reading the first block repeatedly without errors
+    fn select_level(&self) {
+        let mut blocks = [Block::new()];
+
+        for level in self.fastest_level..self.identification_level {
+            self.set_level(level);
+
+            let reliable = (0..SPEED_TEST_READS)
+                .all(|_| self.card.read(&mut blocks, BlockIdx(0), "speed").is_ok());
+
+            if reliable {
+                return;
+            }
+        }
+
+        self.set_level(self.identification_level);
+    }
+
+    /// Switch to the next slower level after an `error` caused by the clock
+    /// speed, returns false when the transfer should not be repeated
+    fn step_down(&self, error: &SdMmcError) -> bool {
+        let slower = self.level.get() + 1;
+
+        if is_speed_error(error) && slower <= self.identification_level {
+            self.set_level(slower);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl<D, C> BlockDevice for ClockedCard<D, C>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock {
+    type Error = SdMmcError;
+
+    fn read(
+        &self,
+        blocks: &mut [Block],
+        start_block_idx: BlockIdx,
+        reason: &str
+    ) -> Result<(), SdMmcError> {
+        loop {
+            match self.card.read(blocks, start_block_idx, reason) {
+                Err(error) if self.step_down(&error) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    fn write(&self, blocks: &[Block], start_block_idx: BlockIdx) -> Result<(), SdMmcError> {
+        loop {
+            match self.card.write(blocks, start_block_idx) {
+                Err(error) if self.step_down(&error) => continue,
+                result => return result,
+            }
+        }
+    }
+
+    fn num_blocks(&self) -> Result<BlockCount, SdMmcError> {
+        self.card.num_blocks()
+    }
+}
+
+impl<D, C> CardDevice for ClockedCard<D, C>
+where D: CardDevice<Error = SdMmcError>, C: SpiClock {
+    fn initialize(&mut self) -> Result<(), SdMmcError> {
+        self.set_level(self.identification_level);
+        self.card.initialize()?;
+        self.select_level();
+        Ok(())
+    }
+
+    fn release(&mut self) {
+        self.card.release();
+        self.set_level(self.identification_level);
+    }
+}
+
+/// Errors which can be caused by a clock too fast for the card or wiring
+fn is_speed_error(error: &SdMmcError) -> bool {
+    matches!(
+        error,
+        SdMmcError::CrcError(_, _)
+            | SdMmcError::TimeoutReadBuffer
+            | SdMmcError::TimeoutWaitNotBusy
+            | SdMmcError::TimeoutCommand(_)
+            | SdMmcError::TimeoutACommand(_)
+    )
+}
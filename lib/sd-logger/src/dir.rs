@@ -0,0 +1,220 @@
+//! Files in subdirectories given by paths like `LOGS/2026/10/17.LOG`,
+//! directories are separated by `/` and start at the root directory
+
+use embedded_sdmmc::{
+    BlockDevice, Controller, Directory, Error, Mode, ShortFileName, TimeSource, Volume
+};
+use crate::{
+    fat::{entry_cluster, FatVolume}, open_volume, read_chunks_from_file_in_dir, read_from_file_in_dir,
+    write_to_file_in_dir, SdWriteError
+};
+
+pub const SEPARATOR: char = '/';
+
+/// Longest path accepted by the logger session and log rotation
+pub const MAX_PATH: usize = 32;
+
+/// Directory part (empty for the root directory) and file name of `path`
+pub fn split_path(path: &str) -> (&str, &str) {
+    let path = path.trim_start_matches(SEPARATOR);
+    path.rsplit_once(SEPARATOR).unwrap_or(("", path))
+}
+
+/// Open the existing directory at a non-empty `path` starting at
+/// the opened `root` directory
+pub fn open_dir_path<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &Volume,
+    root: &Directory,
+    path: &str,
+) -> Result<Directory, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    walk_path(controller, volume, root, path, false, &mut false)
+}
+
+/// Open the directory at a non-empty `path` like `open_dir_path`, missing
+/// directories are created
+///
+/// The opened `volume` caches the free cluster count and the next free
+/// cluster, which go stale when a directory takes clusters behind it,
+/// so the volume is opened again after creating one (also when a later
+/// directory on the path fails) and reads both from the updated FAT32
+/// FSInfo sector.
+pub fn create_dir_path<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    root: &Directory,
+    path: &str,
+) -> Result<Directory, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    let mut created = false;
+    let result = walk_path(controller, volume, root, path, true, &mut created);
+
+    if created {
+        match open_volume(controller) {
+            Ok(reopened) => *volume = reopened,
+            Err(error) => {
+                if let Ok(dir) = result {
+                    controller.close_dir(volume, dir);
+                }
+
+                return Err(error);
+            },
+        }
+    }
+
+    result
+}
+
+/// Write `file_data` to the file at `path` starting at the opened `root`
+/// directory, missing directories are created, `mode` selects appending
+/// or replacing
+pub fn write_to_path<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    root: &Directory,
+    path: &str,
+    file_data: &[u8],
+    mode: Mode,
+) -> Result<(), SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    match split_path(path) {
+        ("", file_name) => write_to_file_in_dir(controller, root, volume, file_name, file_data, mode),
+        (dir_path, file_name) => {
+            let dir = create_dir_path(controller, volume, root, dir_path)?;
+            let result = write_to_file_in_dir(controller, &dir, volume, file_name, file_data, mode);
+            controller.close_dir(volume, dir);
+            result
+        },
+    }
+}
+
+/// Read the beginning of the file at `path` starting at the opened `root`
+/// directory into `buffer`, returns the number of bytes read
+pub fn read_from_path<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    root: &Directory,
+    path: &str,
+    buffer: &mut [u8],
+) -> Result<usize, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    read_at_path(controller, volume, root, path, 0, buffer)
+}
+
+/// Read the file at `path` starting at the opened `root` directory from
+/// the byte `offset` into `buffer`, returns the number of bytes read,
+/// 0 at or past the end of the file
+pub fn read_at_path<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    root: &Directory,
+    path: &str,
+    offset: u32,
+    buffer: &mut [u8],
+) -> Result<usize, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    match split_path(path) {
+        ("", file_name) => {
+            read_from_file_in_dir(controller, root, volume, file_name, offset, buffer)
+        },
+        (dir_path, file_name) => {
+            let dir = open_dir_path(controller, volume, root, dir_path)?;
+            let result = read_from_file_in_dir(controller, &dir, volume, file_name, offset, buffer);
+            controller.close_dir(volume, dir);
+            result
+        },
+    }
+}
+
+/// Read the whole file at `path` starting at the opened `root` directory
+/// in chunks passed to `consume`, returns the file size
+pub fn read_chunks_from_path<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    root: &Directory,
+    path: &str,
+    consume: &mut dyn FnMut(&[u8]),
+) -> Result<u32, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    match split_path(path) {
+        ("", file_name) => read_chunks_from_file_in_dir(controller, root, volume, file_name, consume),
+        (dir_path, file_name) => {
+            let dir = open_dir_path(controller, volume, root, dir_path)?;
+            let result = read_chunks_from_file_in_dir(controller, &dir, volume, file_name, consume);
+            controller.close_dir(volume, dir);
+            result
+        },
+    }
+}
+
+/// Open the directory at `path`, missing directories are created when
+/// `create` is set and `created` is set when any was
+fn walk_path<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &Volume,
+    root: &Directory,
+    path: &str,
+    create: bool,
+    created: &mut bool,
+) -> Result<Directory, SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    let mut names = path.split(SEPARATOR).filter(|name| !name.is_empty());
+    let first = names.next().ok_or(SdWriteError::InvalidFileName)?;
+
+    let (mut dir, mut cluster) = enter_dir(controller, volume, root, None, first, create, created)?;
+
+    // Only the parent and the entered directory are open at a time
+    for name in names {
+        let next = enter_dir(controller, volume, &dir, Some(cluster), name, create, created);
+        controller.close_dir(volume, dir);
+        (dir, cluster) = next?;
+    }
+
+    Ok(dir)
+}
+
+/// Open the subdirectory `name` of the `parent` directory starting at
+/// the `parent_cluster` (`None` for the root directory), returns it with
+/// its first cluster, `created` is set when it was created
+fn enter_dir<D, T>(
+    controller: &mut Controller<D, T>,
+    volume: &Volume,
+    parent: &Directory,
+    parent_cluster: Option<u32>,
+    name: &str,
+    create: bool,
+    created: &mut bool,
+) -> Result<(Directory, u32), SdWriteError<D::Error>>
+where D: BlockDevice, T: TimeSource {
+    let short_name = ShortFileName::create_from_str(name)
+        .map_err(|_| SdWriteError::InvalidFileName)?;
+
+    let mut found = None;
+
+    controller.iterate_dir(volume, parent, |entry| {
+        if entry.name == short_name {
+            found = Some((entry.entry_block, entry.entry_offset, entry.attributes.is_directory()));
+        }
+    }).map_err(SdWriteError::CannotOpenDir)?;
+
+    let cluster = match found {
+        // Cluster of the entry is private in embedded-sdmmc, it is read
+        // from the entry itself
+        Some((block, offset, true)) => entry_cluster(controller.device(), block, offset)
+            .map_err(SdWriteError::CannotOpenDir)?,
+        Some((_, _, false)) => return Err(SdWriteError::CannotOpenDir(Error::FileAlreadyExists)),
+        None if create => {
+            let cluster = FatVolume::read(controller.device())
+                .and_then(|fat| fat.create_dir(controller.device(), parent_cluster, name))
+                .map_err(SdWriteError::CannotCreateDir)?;
+
+            *created = true;
+            cluster
+        },
+        None => return Err(SdWriteError::CannotOpenDir(Error::FileNotFound)),
+    };
+
+    let dir = controller.open_dir(volume, parent, name).map_err(SdWriteError::CannotOpenDir)?;
+    Ok((dir, cluster))
+}
@@ -0,0 +1,575 @@
+//! Creating directories directly in the FAT volume blocks, embedded-sdmmc
+//! 0.3 opens subdirectories but cannot create them
+//!
+//! A new directory gets a zeroed chain of clusters allocated in all FAT
+//! copies, the `.` and `..` entries and an entry in a free slot of the parent
+//! directory. The parent directory is not extended when it has no free slot.
+//!
+//! The first cluster of an empty file is allocated here as well, and the
+//! FAT sectors of the clusters embedded-sdmmc allocates or frees in the
+//! first FAT only are copied to the other copies, see `open_file_to_write`.
+//!
+//! The clusters are allocated behind any opened embedded-sdmmc `Volume`,
+//! which has to be opened again afterwards (see `dir::create_dir_path`).
+
+use embedded_sdmmc::{Block, BlockDevice, BlockIdx, Error};
+
+const DIR_ENTRY_SIZE: usize = 32;
+const ENTRY_END: u8 = 0x00;
+const ENTRY_DELETED: u8 = 0xe5;
+const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+
+/// New directories have room for at least this number of entries
+const MIN_DIR_ENTRIES: u32 = 64;
+
+/// Entry dates are 1980-01-01, the controller time source is not
+/// accessible from here
+const ENTRY_DATE: u16 = (1 << 5) | 1;
+
+const PARTITION_TYPES: [u8; 5] = [0x04, 0x06, 0x0b, 0x0c, 0x0e];
+const FAT16_MIN_CLUSTERS: u32 = 4085;
+const FAT32_MIN_CLUSTERS: u32 = 65525;
+const INFO_SECTOR_SIGNATURE: u32 = 0x4161_5252;
+/// Free cluster count or next free cluster not known
+const UNKNOWN: u32 = 0xffff_ffff;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum FatType {
+    Fat16,
+    Fat32,
+}
+
+/// Layout of a FAT volume in blocks from the card start
+pub(crate) struct FatVolume {
+    fat_type: FatType,
+    fat_start: u32,
+    fat_sectors: u32,
+    fat_count: u32,
+    /// FAT16 root directory region
+    root_dir_start: u32,
+    root_dir_sectors: u32,
+    /// FAT32 root directory first cluster
+    root_cluster: u32,
+    data_start: u32,
+    sectors_per_cluster: u32,
+    cluster_count: u32,
+    info_sector: Option<u32>,
+}
+
+impl FatVolume {
+    /// Layout of the first FAT volume in the primary partitions, the same
+    /// one `open_volume` opens
+    pub fn read<D: BlockDevice>(device: &D) -> Result<Self, Error<D::Error>> {
+        let mut block = Block::new();
+        read_block(device, 0, &mut block)?;
+
+        if !has_signature(&block) {
+            return Err(Error::FormatError("No partition table"));
+        }
+
+        let partitions: [(u8, u32); 4] = core::array::from_fn(|index| {
+            let entry = &block.contents[446 + 16*index..446 + 16*(index + 1)];
+            (entry[4], u32_at(entry, 8))
+        });
+
+        for (partition_type, start) in partitions {
+            if !PARTITION_TYPES.contains(&partition_type) {
+                continue;
+            }
+
+            read_block(device, start, &mut block)?;
+
+            if let Some(volume) = Self::parse(&block, start) {
+                return Ok(volume);
+            }
+        }
+
+        Err(Error::FormatError("No FAT volume"))
+    }
+
+    fn parse(block: &Block, start: u32) -> Option<Self> {
+        let data = &block.contents;
+
+        if !has_signature(block) || u16_at(data, 11) as usize != Block::LEN || data[13] == 0 {
+            return None;
+        }
+
+        let sectors_per_cluster = data[13] as u32;
+        let reserved_sectors = u16_at(data, 14) as u32;
+        let fat_count = data[16] as u32;
+        let root_dir_sectors = (u16_at(data, 17) as u32*DIR_ENTRY_SIZE as u32)
+            .div_ceil(Block::LEN_U32);
+
+        let total_sectors = match u16_at(data, 19) {
+            0 => u32_at(data, 32),
+            sectors => sectors as u32,
+        };
+
+        let fat_sectors = match u16_at(data, 22) {
+            0 => u32_at(data, 36),
+            sectors => sectors as u32,
+        };
+
+        let root_dir_start = reserved_sectors + fat_count*fat_sectors;
+        let data_start = root_dir_start + root_dir_sectors;
+        let cluster_count = total_sectors.checked_sub(data_start)?/sectors_per_cluster;
+
+        let fat_type = match cluster_count {
+            count if count < FAT16_MIN_CLUSTERS => return None,
+            count if count < FAT32_MIN_CLUSTERS => FatType::Fat16,
+            _ => FatType::Fat32,
+        };
+
+        let info_sector = match (fat_type, u16_at(data, 48)) {
+            (FatType::Fat32, sector) if sector != 0 && sector != 0xffff => {
+                Some(start + sector as u32)
+            },
+            _ => None,
+        };
+
+        Some(Self {
+            fat_type,
+            fat_start: start + reserved_sectors,
+            fat_sectors,
+            fat_count,
+            root_dir_start: start + root_dir_start,
+            root_dir_sectors,
+            root_cluster: u32_at(data, 44),
+            data_start: start + data_start,
+            sectors_per_cluster,
+            cluster_count,
+            info_sector,
+        })
+    }
+
+    /// Create a directory with the 8.3 `name` in the directory starting
+    /// at the `parent` cluster (`None` for the root directory), returns
+    /// the first cluster of the new directory
+    pub fn create_dir<D: BlockDevice>(
+        &self,
+        device: &D,
+        parent: Option<u32>,
+        name: &str,
+    ) -> Result<u32, Error<D::Error>> {
+        let name = short_name(name).ok_or(Error::FormatError("Invalid name"))?;
+        let (entry_sector, entry_offset) = self.free_entry(device, parent)?
+            .ok_or(Error::NotEnoughSpace)?;
+
+        let clusters = (MIN_DIR_ENTRIES*DIR_ENTRY_SIZE as u32).div_ceil(self.cluster_size());
+
+        let mut first = None;
+        let mut previous = None;
+
+        for _ in 0..clusters {
+            let cluster = self.allocate(device, previous)?;
+            self.clear_cluster(device, cluster)?;
+            first.get_or_insert(cluster);
+            previous = Some(cluster);
+        }
+
+        let first = first.ok_or(Error::NotEnoughSpace)?;
+
+        // Parent of a directory in the root directory is cluster zero
+        let mut block = Block::new();
+        block.contents[..DIR_ENTRY_SIZE].copy_from_slice(&dir_entry(b".          ", first));
+        block.contents[DIR_ENTRY_SIZE..2*DIR_ENTRY_SIZE]
+            .copy_from_slice(&dir_entry(b"..         ", parent.unwrap_or(0)));
+        write_block(device, self.cluster_sector(first), &block)?;
+
+        read_block(device, entry_sector, &mut block)?;
+        block.contents[entry_offset..entry_offset + DIR_ENTRY_SIZE]
+            .copy_from_slice(&dir_entry(&name, first));
+        write_block(device, entry_sector, &block)?;
+
+        self.invalidate_info_sector(device)?;
+        Ok(first)
+    }
+
+    /// Allocate the first cluster of the empty file with the directory entry
+    /// at `offset` in the `block`, returns the cluster
+    pub fn allocate_file_cluster<D: BlockDevice>(
+        &self,
+        device: &D,
+        block: BlockIdx,
+        offset: u32,
+    ) -> Result<u32, Error<D::Error>> {
+        let mut data = Block::new();
+        read_block(device, block.0, &mut data)?;
+
+        let entry = data.contents.get_mut(offset as usize..offset as usize + DIR_ENTRY_SIZE)
+            .ok_or(Error::FormatError("Invalid entry offset"))?;
+
+        let cluster = self.allocate(device, None)?;
+        entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+        write_block(device, block.0, &data)?;
+
+        self.invalidate_info_sector(device)?;
+        Ok(cluster)
+    }
+
+    /// Copy the FAT sectors of the chain starting at `cluster` from the first
+    /// FAT to the other copies where they differ, returns the last cluster
+    /// of the chain. The chain is followed in the second FAT where it ends
+    /// in the first one, so a chain truncated in the first FAT only is
+    /// copied as well.
+    pub fn sync_chain<D: BlockDevice>(&self, device: &D, cluster: u32) -> Result<u32, Error<D::Error>> {
+        let mut first = Block::new();
+        let mut second = Block::new();
+        let mut loaded = None;
+        let mut cluster = cluster;
+        let mut last = cluster;
+        let mut in_first = true;
+
+        // Chain length is limited in case the FAT contains a loop
+        for _ in 0..self.cluster_count {
+            let (sector, offset) = self.fat_position(cluster);
+
+            if loaded != Some(sector) {
+                read_block(device, sector, &mut first)?;
+                loaded = Some(sector);
+
+                if self.fat_count > 1 {
+                    read_block(device, sector + self.fat_sectors, &mut second)?;
+
+                    if second.contents != first.contents {
+                        for copy in 1..self.fat_count {
+                            write_block(device, sector + copy*self.fat_sectors, &first)?;
+                        }
+                    }
+                }
+            }
+
+            let next = self.read_entry(&first, offset);
+            in_first &= self.is_cluster(next);
+
+            let next = match in_first {
+                true => {
+                    last = next;
+                    next
+                },
+                false => self.read_entry(&second, offset),
+            };
+
+            if !self.is_cluster(next) {
+                break;
+            }
+
+            cluster = next;
+        }
+
+        Ok(last)
+    }
+
+    /// Mark the entries following the last cluster in its FAT sector as bad
+    /// clusters, embedded-sdmmc 0.3 takes them for free clusters past the end
+    /// of the volume when no cluster is free after its last allocation
+    pub fn reserve_fat_tail<D: BlockDevice>(&self, device: &D) -> Result<(), Error<D::Error>> {
+        let (sector, first_offset) = self.fat_position(self.cluster_count + 2);
+
+        // FAT sectors after the last cluster are not searched
+        if first_offset == 0 {
+            return Ok(());
+        }
+
+        let mut block = Block::new();
+        read_block(device, sector, &mut block)?;
+
+        let mut changed = false;
+
+        for offset in (first_offset..Block::LEN).step_by(self.entry_size() as usize) {
+            if self.read_entry(&block, offset) == 0 {
+                self.set_entry(&mut block, offset, self.bad_cluster());
+                changed = true;
+            }
+        }
+
+        if changed {
+            for copy in 0..self.fat_count {
+                write_block(device, sector + copy*self.fat_sectors, &block)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Bytes in a cluster
+    pub fn cluster_size(&self) -> u32 {
+        self.sectors_per_cluster*Block::LEN_U32
+    }
+
+    /// Sector and offset of the first free entry in a directory
+    fn free_entry<D: BlockDevice>(
+        &self,
+        device: &D,
+        dir: Option<u32>,
+    ) -> Result<Option<(u32, usize)>, Error<D::Error>> {
+        let mut block = Block::new();
+
+        let mut find_in_sector = |sector: u32| -> Result<Option<(u32, usize)>, Error<D::Error>> {
+            read_block(device, sector, &mut block)?;
+
+            let offset = (0..Block::LEN).step_by(DIR_ENTRY_SIZE)
+                .find(|&offset| matches!(block.contents[offset], ENTRY_END | ENTRY_DELETED));
+
+            Ok(offset.map(|offset| (sector, offset)))
+        };
+
+        let start = match (self.fat_type, dir) {
+            (FatType::Fat16, None) => {
+                for sector in self.root_dir_start..self.root_dir_start + self.root_dir_sectors {
+                    if let Some(entry) = find_in_sector(sector)? {
+                        return Ok(Some(entry));
+                    }
+                }
+
+                return Ok(None);
+            },
+            (FatType::Fat32, None) => self.root_cluster,
+            (_, Some(cluster)) => cluster,
+        };
+
+        let mut cluster = start;
+
+        // Chain length is limited in case the FAT contains a loop
+        for _ in 0..self.cluster_count {
+            let first_sector = self.cluster_sector(cluster);
+
+            for sector in first_sector..first_sector + self.sectors_per_cluster {
+                if let Some(entry) = find_in_sector(sector)? {
+                    return Ok(Some(entry));
+                }
+            }
+
+            match self.next_cluster(device, cluster)? {
+                Some(next) => cluster = next,
+                None => break,
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Allocate a free cluster at the end of the chain ending by `previous`
+    fn allocate<D: BlockDevice>(
+        &self,
+        device: &D,
+        previous: Option<u32>,
+    ) -> Result<u32, Error<D::Error>> {
+        let mut block = Block::new();
+        let mut loaded = None;
+
+        for cluster in 2..self.cluster_count + 2 {
+            let (sector, offset) = self.fat_position(cluster);
+
+            if loaded != Some(sector) {
+                read_block(device, sector, &mut block)?;
+                loaded = Some(sector);
+            }
+
+            if self.read_entry(&block, offset) == 0 {
+                self.write_entry(device, cluster, self.end_of_chain())?;
+
+                if let Some(previous) = previous {
+                    self.write_entry(device, previous, cluster)?;
+                }
+
+                return Ok(cluster);
+            }
+        }
+
+        Err(Error::NotEnoughSpace)
+    }
+
+    fn next_cluster<D: BlockDevice>(
+        &self,
+        device: &D,
+        cluster: u32,
+    ) -> Result<Option<u32>, Error<D::Error>> {
+        let mut block = Block::new();
+        let (sector, offset) = self.fat_position(cluster);
+        read_block(device, sector, &mut block)?;
+
+        let next = self.read_entry(&block, offset);
+        Ok(self.is_cluster(next).then_some(next))
+    }
+
+    fn is_cluster(&self, value: u32) -> bool {
+        (2..self.cluster_count + 2).contains(&value)
+    }
+
+    /// Set the FAT entry of `cluster` in all FAT copies
+    fn write_entry<D: BlockDevice>(
+        &self,
+        device: &D,
+        cluster: u32,
+        value: u32,
+    ) -> Result<(), Error<D::Error>> {
+        let mut block = Block::new();
+        let (sector, offset) = self.fat_position(cluster);
+        read_block(device, sector, &mut block)?;
+        self.set_entry(&mut block, offset, value);
+
+        for copy in 0..self.fat_count {
+            write_block(device, sector + copy*self.fat_sectors, &block)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_entry(&self, block: &mut Block, offset: usize, value: u32) {
+        match self.fat_type {
+            FatType::Fat16 => {
+                block.contents[offset..offset + 2].copy_from_slice(&(value as u16).to_le_bytes());
+            },
+            FatType::Fat32 => {
+                // Upper four bits are reserved and kept
+                let value = (u32_at(&block.contents, offset) & 0xf000_0000) | value;
+                block.contents[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            },
+        }
+    }
+
+    fn read_entry(&self, block: &Block, offset: usize) -> u32 {
+        match self.fat_type {
+            FatType::Fat16 => u16_at(&block.contents, offset) as u32,
+            FatType::Fat32 => u32_at(&block.contents, offset) & 0x0fff_ffff,
+        }
+    }
+
+    fn clear_cluster<D: BlockDevice>(&self, device: &D, cluster: u32) -> Result<(), Error<D::Error>> {
+        let block = Block::new();
+        let first_sector = self.cluster_sector(cluster);
+
+        for sector in first_sector..first_sector + self.sectors_per_cluster {
+            write_block(device, sector, &block)?;
+        }
+
+        Ok(())
+    }
+
+    /// FAT32 free cluster count and next free cluster are marked unknown
+    /// instead of being updated, an opened embedded-sdmmc volume keeps its
+    /// own copies and has to be opened again to read them
+    fn invalidate_info_sector<D: BlockDevice>(&self, device: &D) -> Result<(), Error<D::Error>> {
+        let sector = match self.info_sector {
+            Some(sector) => sector,
+            None => return Ok(()),
+        };
+
+        let mut block = Block::new();
+        read_block(device, sector, &mut block)?;
+
+        if u32_at(&block.contents, 0) == INFO_SECTOR_SIGNATURE {
+            block.contents[488..492].copy_from_slice(&UNKNOWN.to_le_bytes());
+            block.contents[492..496].copy_from_slice(&UNKNOWN.to_le_bytes());
+            write_block(device, sector, &block)?;
+        }
+
+        Ok(())
+    }
+
+    fn entry_size(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat16 => 2,
+            FatType::Fat32 => 4,
+        }
+    }
+
+    fn end_of_chain(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat16 => 0xffff,
+            FatType::Fat32 => 0x0fff_ffff,
+        }
+    }
+
+    fn bad_cluster(&self) -> u32 {
+        match self.fat_type {
+            FatType::Fat16 => 0xfff7,
+            FatType::Fat32 => 0x0fff_fff7,
+        }
+    }
+
+    /// Sector of the first FAT and offset in it of the `cluster` entry
+    fn fat_position(&self, cluster: u32) -> (u32, usize) {
+        let offset = cluster*self.entry_size();
+        (self.fat_start + offset/Block::LEN_U32, (offset % Block::LEN_U32) as usize)
+    }
+
+    fn cluster_sector(&self, cluster: u32) -> u32 {
+        self.data_start + (cluster - 2)*self.sectors_per_cluster
+    }
+}
+
+/// First cluster of the directory entry at `offset` in the `block`
+pub(crate) fn entry_cluster<D: BlockDevice>(
+    device: &D,
+    block: BlockIdx,
+    offset: u32,
+) -> Result<u32, Error<D::Error>> {
+    let mut data = Block::new();
+    read_block(device, block.0, &mut data)?;
+
+    let entry = data.contents.get(offset as usize..offset as usize + DIR_ENTRY_SIZE)
+        .ok_or(Error::FormatError("Invalid entry offset"))?;
+
+    Ok((u16_at(entry, 20) as u32) << 16 | u16_at(entry, 26) as u32)
+}
+
+fn dir_entry(name: &[u8; 11], cluster: u32) -> [u8; DIR_ENTRY_SIZE] {
+    let mut entry = [0u8; DIR_ENTRY_SIZE];
+    entry[..11].copy_from_slice(name);
+    entry[11] = ATTRIBUTE_DIRECTORY;
+
+    for offset in [16, 18, 24] {
+        entry[offset..offset + 2].copy_from_slice(&ENTRY_DATE.to_le_bytes());
+    }
+
+    entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    entry
+}
+
+/// Directory entry form of an 8.3 name, `LOGS` is `LOGS       `
+fn short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, extension) = name.split_once('.').unwrap_or((name, ""));
+
+    let valid = |part: &str, max: usize| part.len() <= max && part.bytes().all(|byte| {
+        byte.is_ascii_alphanumeric() || b"_-~!#$%&'()@^{}".contains(&byte)
+    });
+
+    if base.is_empty() || !valid(base, 8) || !valid(extension, 3) {
+        return None;
+    }
+
+    let mut result = [b' '; 11];
+    result[..base.len()].copy_from_slice(base.as_bytes());
+    result[8..8 + extension.len()].copy_from_slice(extension.as_bytes());
+    result.make_ascii_uppercase();
+    Some(result)
+}
+
+fn read_block<D: BlockDevice>(
+    device: &D,
+    sector: u32,
+    block: &mut Block,
+) -> Result<(), Error<D::Error>> {
+    device.read(core::slice::from_mut(block), BlockIdx(sector), "fat")
+        .map_err(Error::DeviceError)
+}
+
+fn write_block<D: BlockDevice>(device: &D, sector: u32, block: &Block) -> Result<(), Error<D::Error>> {
+    device.write(core::slice::from_ref(block), BlockIdx(sector)).map_err(Error::DeviceError)
+}
+
+fn has_signature(block: &Block) -> bool {
+    block.contents[510..512] == [0x55, 0xaa]
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
@@ -0,0 +1,436 @@
+//! Writing and reading files on an SD card, file names are paths like
+//! `LOGS/2026/10/17.LOG` relative to the root directory
+//!
+//! Functions taking a `CardDevice` initialize it before and release
+//! it after the operation, functions taking a controller over any
+//! `BlockDevice` expect the device to be ready (and can be used with
+//! a disk image on the host)
+#![no_std]
+
+pub mod binary;
+pub mod card;
+pub mod dir;
+mod fat;
+pub mod record;
+pub mod rotation;
+pub mod session;
+#[cfg(feature = "stm32f4")]
+pub mod spi_clock;
+
+use core::fmt::{Debug, Display};
+use card::CardDevice;
+use dir::{read_chunks_from_path, read_from_path, write_to_path};
+use fat::{entry_cluster, FatVolume};
+use embedded_sdmmc::{
+    Controller, TimeSource, VolumeIdx, Volume, Mode, Directory, File, BlockDevice,
+    Error, SdMmcError
+};
+
+#[derive(Debug)]
+pub enum SdWriteError<E>
+where E: core::fmt::Debug {
+    CannotConnect(SdMmcError),
+    NoSuitableVolume,
+    CannotReadRootDir(Error<E>),
+    CannotOpenDir(Error<E>),
+    CannotCreateDir(Error<E>),
+    CannotOpenFile(Error<E>),
+    CannotWriteToOpenedFile(Error<E>),
+    CannotReadOpenedFile(Error<E>),
+    /// File name does not fit the 8.3 format
+    InvalidFileName,
+}
+
+impl<T> Display for SdWriteError<T> where T: Debug {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SdWriteError::CannotConnect(ref err)
+                => write!(f, "Conn:{}", device_error_to_str(err)),
+            SdWriteError::NoSuitableVolume
+                => write!(f, "NoVol"),
+            SdWriteError::CannotReadRootDir(ref err)
+                => write!(f, "RootE:{}", controller_error_to_str(err)),
+            SdWriteError::CannotOpenDir(ref err)
+                => write!(f, "DirE:{}", controller_error_to_str(err)),
+            SdWriteError::CannotCreateDir(ref err)
+                => write!(f, "MkDirE:{}", controller_error_to_str(err)),
+            SdWriteError::CannotOpenFile(ref err)
+                => write!(f, "OpenE:{}", controller_error_to_str(err)),
+            SdWriteError::CannotWriteToOpenedFile(ref err)
+                => write!(f, "WrE:{}", controller_error_to_str(err)),
+            SdWriteError::CannotReadOpenedFile(ref err)
+                => write!(f, "RdE:{}", controller_error_to_str(err)),
+            SdWriteError::InvalidFileName
+                => write!(f, "FName"),
+        }
+    }
+}
+
+/// Initialize the card and append the given `file_data` to the file at
+/// the path `file_name` (file and directories are created if not exist),
+/// on the first suitable primary partition (if found)
+pub fn append_to_file<D, T>(
+    controller: &mut Controller<D, T>,
+    file_name: &str,
+    file_data: &str,
+) -> Result<(), SdWriteError<SdMmcError>>
+where D: CardDevice<Error = SdMmcError>, T: TimeSource {
+    match controller.device().initialize() {
+        Ok(_) => {
+            let result = write_to_volume(
+                controller, file_name, file_data.as_bytes(), Mode::ReadWriteCreateOrAppend
+            );
+            controller.device().release();
+            result
+        },
+        Err(error) => Err(SdWriteError::CannotConnect(error)),
+    }
+}
+
+/// Initialize the card and replace content of the file at the path
+/// `file_name` by `file_data` (file and directories are created if not
+/// exist)
+pub fn replace_file<D, T>(
+    controller: &mut Controller<D, T>,
+    file_name: &str,
+    file_data: &str,
+) -> Result<(), SdWriteError<SdMmcError>>
+where D: CardDevice<Error = SdMmcError>, T: TimeSource {
+    match controller.device().initialize() {
+        Ok(_) => {
+            let result = write_to_volume(
+                controller, file_name, file_data.as_bytes(), Mode::ReadWriteCreateOrTruncate
+            );
+            controller.device().release();
+            result
+        },
+        Err(error) => Err(SdWriteError::CannotConnect(error)),
+    }
+}
+
+/// Initialize the card and read the beginning of the file at the path
+/// `file_name` into `buffer`, returns the number of bytes read
+pub fn read_from_file<D, T>(
+    controller: &mut Controller<D, T>,
+    file_name: &str,
+    buffer: &mut [u8],
+) -> Result<usize, SdWriteError<SdMmcError>>
+where D: CardDevice<Error = SdMmcError>, T: TimeSource {
+    match controller.device().initialize() {
+        Ok(_) => {
+            let result = read_from_volume(controller, file_name, buffer);
+            controller.device().release();
+            result
+        },
+        Err(error) => Err(SdWriteError::CannotConnect(error)),
+    }
+}
+
+/// Initialize the card and read the whole file at the path `file_name`
+/// in chunks passed to `consume`, returns the file size
+pub fn read_chunks_from_file<D, T>(
+    controller: &mut Controller<D, T>,
+    file_name: &str,
+    consume: &mut dyn FnMut(&[u8]),
+) -> Result<u32, SdWriteError<SdMmcError>>
+where D: CardDevice<Error = SdMmcError>, T: TimeSource {
+    match controller.device().initialize() {
+        Ok(_) => {
+            let result = read_chunks_from_volume(controller, file_name, consume);
+            controller.device().release();
+            result
+        },
+        Err(error) => Err(SdWriteError::CannotConnect(error)),
+    }
+}
+
+fn device_error_to_str(error: &SdMmcError) -> &'static str {
+    match error {
+        SdMmcError::Transport => "Transport",
+        SdMmcError::CantEnableCRC => "EnableCrc",
+        SdMmcError::TimeoutReadBuffer => "TOReadBuf",
+        SdMmcError::TimeoutWaitNotBusy => "TOWaitNoBusy",
+        SdMmcError::TimeoutCommand(_) => "TOCommand",
+        SdMmcError::TimeoutACommand(_) => "TOACommand",
+        SdMmcError::Cmd58Error => "Cmd58Err",
+        SdMmcError::RegisterReadError => "RegReadErr",
+        SdMmcError::CrcError(_, _) => "Crc",
+        SdMmcError::ReadError => "ReadErr",
+        SdMmcError::WriteError => "WriteErr",
+        SdMmcError::BadState => "BadState",
+        SdMmcError::CardNotFound => "CardNotFound",
+        SdMmcError::GpioError => "GpioErr",
+    }
+}
+
+fn controller_error_to_str<E>(error: &Error<E>)-> &'static str where E: Debug {
+    match error {
+        Error::DeviceError(_) => "DevErr",
+        Error::FormatError(_) => "FormatErr",
+        Error::NoSuchVolume => "NoVol",
+        Error::FilenameError(_) => "FNameErr",
+        Error::TooManyOpenDirs => "ManyOpenDirs",
+        Error::TooManyOpenFiles => "ManyOpenFiles",
+        Error::FileNotFound => "FileNotFound",
+        Error::FileAlreadyOpen => "FAlreadyOpen",
+        Error::DirAlreadyOpen => "DirAlreadyOpen",
+        Error::OpenedDirAsFile => "OpenDirAsFile",
+        Error::Unsupported => "Unsupported",
+        Error::EndOfFile => "EOF",
+        Error::BadCluster => "BadCluster",
+        Error::ConversionError => "ConvertErr",
+        Error::NotEnoughSpace => "NoSpace",
+        Error::AllocationError => "AllocErr",
+        Error::JumpedFree => "JumpedFree",
+        Error::ReadOnly => "ReadOnly",
+        Error::FileAlreadyExists => "FileExists",
+    }
+}
+
+/// Write `file_data` to the file at the path `file_name` on the first
+/// suitable volume, `mode` selects appending or replacing
+pub fn write_to_volume<D, T, E>(
+    controller: &mut Controller<D, T>,
+    file_name: &str,
+    file_data: &[u8],
+    mode: Mode,
+) -> Result<(), SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    let mut volume = open_volume(controller)?;
+
+    match controller.open_root_dir(&volume) {
+        Ok(dir) => {
+            let result = write_to_path(
+                controller, &mut volume, &dir, file_name, file_data, mode
+            );
+            controller.close_dir(&volume, dir);
+            result
+        },
+        Err(error) => Err(SdWriteError::CannotReadRootDir(error)),
+    }
+}
+
+pub(crate) fn write_to_file_in_dir<D, T, E>(
+    controller: &mut Controller<D, T>,
+    directory: &Directory,
+    volume: &mut Volume,
+    file_name: &str,
+    file_data: &[u8],
+    mode: Mode,
+) -> Result<(), SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    let mut file = open_file_to_write(
+        controller, volume, directory, file_name, mode, !file_data.is_empty()
+    )?;
+
+    let result = write_to_opened_file(controller, volume, &mut file, file_data);
+    let _ = controller.close_file(volume, file.file);
+    result
+}
+
+/// File opened by `open_file_to_write`
+pub(crate) struct WriteFile {
+    pub(crate) file: File,
+    fat: FatVolume,
+    /// Last cluster of the file with its FAT entry in all FAT copies
+    last_cluster: u32,
+}
+
+/// Open the file `file_name` in the `directory` for writing, the first
+/// cluster of an empty file is allocated when `allocate` is set
+///
+/// embedded-sdmmc 0.3 keeps the open file slot of a file opened without
+/// a cluster after `close_file` when `write` allocated one, the slot is
+/// taken by closing and opening the file again around the allocation.
+/// Volume is opened again as in `dir::create_dir_path`.
+///
+/// embedded-sdmmc also allocates and frees clusters in the first FAT only,
+/// the chain of a truncated file is copied to the other FATs here and
+/// the clusters added by writes in `write_to_opened_file`.
+/// The FAT entries past the last cluster it would allocate are reserved
+/// as well.
+pub(crate) fn open_file_to_write<D, T, E>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    directory: &Directory,
+    file_name: &str,
+    mode: Mode,
+    allocate: bool,
+) -> Result<WriteFile, SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    let file = controller.open_file_in_dir(volume, directory, file_name, mode)
+        .map_err(SdWriteError::CannotOpenFile)?;
+
+    let opened = FatVolume::read(controller.device()).and_then(|fat| {
+        fat.reserve_fat_tail(controller.device())?;
+
+        let entry = controller.find_directory_entry(volume, directory, file_name)?;
+        let cluster = entry_cluster(controller.device(), entry.entry_block, entry.entry_offset)?;
+
+        if cluster >= 2 && matches!(mode, Mode::ReadWriteTruncate | Mode::ReadWriteCreateOrTruncate) {
+            fat.sync_chain(controller.device(), cluster)?;
+        }
+
+        Ok((fat, entry, cluster))
+    });
+
+    let (fat, entry, cluster) = match opened {
+        Ok(opened) => opened,
+        Err(error) => {
+            let _ = controller.close_file(volume, file);
+            return Err(SdWriteError::CannotOpenFile(error));
+        },
+    };
+
+    if !allocate || cluster >= 2 {
+        return Ok(WriteFile { file, fat, last_cluster: cluster });
+    }
+
+    let _ = controller.close_file(volume, file);
+
+    let cluster = fat.allocate_file_cluster(controller.device(), entry.entry_block, entry.entry_offset)
+        .map_err(SdWriteError::CannotOpenFile)?;
+
+    *volume = open_volume(controller)?;
+
+    let file = controller.open_file_in_dir(volume, directory, file_name, Mode::ReadWriteAppend)
+        .map_err(SdWriteError::CannotOpenFile)?;
+
+    Ok(WriteFile { file, fat, last_cluster: cluster })
+}
+
+/// Write the whole `file_data`, embedded-sdmmc returns the number of bytes
+/// written when the volume gets full, reported as `NotEnoughSpace`
+pub(crate) fn write_to_opened_file<D, T, E>(
+    controller: &mut Controller<D, T>,
+    volume: &mut Volume,
+    file: &mut WriteFile,
+    file_data: &[u8],
+) -> Result<(), SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    // embedded-sdmmc allocates the first cluster of an empty file even
+    // when nothing is written
+    if file_data.is_empty() {
+        return Ok(());
+    }
+
+    let clusters = file.file.length().div_ceil(file.fat.cluster_size());
+    let result = controller.write(volume, &mut file.file, file_data);
+
+    // Clusters the controller added, also before a failed write
+    let extended = result.is_err() || file.file.length().div_ceil(file.fat.cluster_size()) != clusters;
+
+    if extended && file.last_cluster >= 2 {
+        file.last_cluster = file.fat.sync_chain(controller.device(), file.last_cluster)
+            .map_err(SdWriteError::CannotWriteToOpenedFile)?;
+    }
+
+    match result {
+        Ok(written) if written == file_data.len() => Ok(()),
+        Ok(_) => Err(SdWriteError::CannotWriteToOpenedFile(Error::NotEnoughSpace)),
+        Err(error) => Err(SdWriteError::CannotWriteToOpenedFile(error)),
+    }
+}
+
+/// Read the beginning of the file at the path `file_name` on the first
+/// suitable volume into `buffer`, returns the number of bytes read
+pub fn read_from_volume<D, T, E>(
+    controller: &mut Controller<D, T>,
+    file_name: &str,
+    buffer: &mut [u8],
+) -> Result<usize, SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    let mut volume = open_volume(controller)?;
+
+    let dir = controller.open_root_dir(&volume)
+        .map_err(SdWriteError::CannotReadRootDir)?;
+
+    let result = read_from_path(controller, &mut volume, &dir, file_name, buffer);
+    controller.close_dir(&volume, dir);
+    result
+}
+
+pub(crate) fn read_from_file_in_dir<D, T, E>(
+    controller: &mut Controller<D, T>,
+    directory: &Directory,
+    volume: &mut Volume,
+    file_name: &str,
+    offset: u32,
+    buffer: &mut [u8],
+) -> Result<usize, SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    match controller.open_file_in_dir(volume, directory, file_name, Mode::ReadOnly) {
+        Ok(mut file) => {
+            // Nothing is read past the end of the file
+            let result = match file.seek_from_start(offset) {
+                Ok(()) => controller.read(volume, &mut file, buffer)
+                    .map_err(SdWriteError::CannotReadOpenedFile),
+                Err(_) => Ok(0),
+            };
+            let _ = controller.close_file(volume, file);
+            result
+        },
+        Err(error) => Err(SdWriteError::CannotOpenFile(error)),
+    }
+}
+
+/// Read the whole file at the path `file_name` on the first suitable volume
+/// in chunks passed to `consume`, returns the file size
+pub fn read_chunks_from_volume<D, T, E>(
+    controller: &mut Controller<D, T>,
+    file_name: &str,
+    consume: &mut dyn FnMut(&[u8]),
+) -> Result<u32, SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    let mut volume = open_volume(controller)?;
+
+    let dir = controller.open_root_dir(&volume)
+        .map_err(SdWriteError::CannotReadRootDir)?;
+
+    let result = read_chunks_from_path(controller, &mut volume, &dir, file_name, consume);
+    controller.close_dir(&volume, dir);
+    result
+}
+
+pub(crate) fn read_chunks_from_file_in_dir<D, T, E>(
+    controller: &mut Controller<D, T>,
+    directory: &Directory,
+    volume: &mut Volume,
+    file_name: &str,
+    consume: &mut dyn FnMut(&[u8]),
+) -> Result<u32, SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    let mut file = controller.open_file_in_dir(volume, directory, file_name, Mode::ReadOnly)
+        .map_err(SdWriteError::CannotOpenFile)?;
+
+    let mut buffer = [0u8; 512];
+    let mut size = 0;
+
+    let result = loop {
+        match controller.read(volume, &mut file, &mut buffer) {
+            Ok(0) => break Ok(size),
+            Ok(length) => {
+                consume(&buffer[..length]);
+                size += length as u32;
+            },
+            Err(error) => break Err(SdWriteError::CannotReadOpenedFile(error)),
+        }
+    };
+
+    let _ = controller.close_file(volume, file);
+    result
+}
+
+/// First of the four primary partitions containing a supported FAT volume
+pub fn open_volume<D, T, E>(
+    controller: &mut Controller<D, T>,
+) -> Result<Volume, SdWriteError<E>>
+where D: BlockDevice<Error = E>, T: TimeSource, E: Debug {
+    for volume_index in 0..4 {
+        if let Ok(volume) = controller.get_volume(VolumeIdx(volume_index)) {
+            return Ok(volume);
+        }
+    }
+
+    Err(SdWriteError::NoSuitableVolume)
+}
\ No newline at end of file
@@ -0,0 +1,185 @@
+//! Log records protected by a sequence number and a CRC
+//!
+//! A record is a line `<sequence> <content> *<CRC>`, the CRC is eight hex
+//! digits of the CRC-32 of the line before ` *`. The CRC is computed the way
+//! the STM32 CRC unit does it: polynomial 0x04C11DB7, initial value
+//! 0xFFFFFFFF, no final XOR, data fed as little endian 32 bit words with
+//! the last word padded by zeros. Lines cut by a power failure or garbled
+//! on the card fail the check, the recovery scan finds the last valid record
+//! to resume the sequence after a restart.
+
+use arrayvec::ArrayString;
+use core::fmt::{self, Write};
+
+/// Longest record line including the sequence number and the CRC
+pub const MAX_RECORD: usize = 128;
+
+const CRC_SEPARATOR: &str = " *";
+const CRC_POLYNOMIAL: u32 = 0x04c1_1db7;
+const CRC_INITIAL: u32 = 0xffff_ffff;
+
+/// CRC-32 calculation, in software or by a hardware unit
+pub trait Checksum {
+    fn crc32(&mut self, data: &[u8]) -> u32;
+}
+
+/// Scan of a log file for the last valid record, fed with chunks of any
+/// size and finished after the whole file was read
+pub trait RecordScan {
+    fn feed(&mut self, data: &[u8], checksum: &mut dyn Checksum);
+    fn finish(&mut self, checksum: &mut dyn Checksum);
+    /// Sequence number of the last valid record
+    fn last_sequence(&self) -> Option<u32>;
+
+    /// Sequence number for the next record, zero without valid records
+    fn next_sequence(&self) -> u32 {
+        self.last_sequence().map_or(0, |sequence| sequence.wrapping_add(1))
+    }
+}
+
+/// Bitwise CRC-32 giving the same results as the STM32 CRC unit
+pub struct SoftwareCrc;
+
+impl Checksum for SoftwareCrc {
+    fn crc32(&mut self, data: &[u8]) -> u32 {
+        data.chunks(4).fold(CRC_INITIAL, |crc, chunk| {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+
+            (0..32).fold(crc ^ u32::from_le_bytes(word), |crc, _| {
+                match crc & 0x8000_0000 {
+                    0 => crc << 1,
+                    _ => (crc << 1) ^ CRC_POLYNOMIAL,
+                }
+            })
+        })
+    }
+}
+
+/// Write the record line with `sequence` and `content` (without line
+/// breaks), fails when the line is longer than `MAX_RECORD`
+pub fn write_record(
+    output: &mut dyn Write,
+    sequence: u32,
+    content: &str,
+    checksum: &mut dyn Checksum,
+) -> fmt::Result {
+    let mut line = ArrayString::<MAX_RECORD>::new();
+    write!(line, "{} {}", sequence, content)?;
+
+    // Separator, eight digits and the line break follow
+    if line.len() + CRC_SEPARATOR.len() + 9 > MAX_RECORD {
+        return Err(fmt::Error);
+    }
+
+    let crc = checksum.crc32(line.as_bytes());
+    writeln!(output, "{}{}{:08X}", line, CRC_SEPARATOR, crc)
+}
+
+/// Sequence number and content of a valid record `line` (with or without
+/// the line break)
+pub fn parse_record<'a>(line: &'a [u8], checksum: &mut dyn Checksum) -> Option<(u32, &'a str)> {
+    let line = core::str::from_utf8(line).ok()?.trim_end_matches(&['\r', '\n'][..]);
+    let (protected, crc) = line.rsplit_once(CRC_SEPARATOR)?;
+
+    if crc.len() != 8 || u32::from_str_radix(crc, 16).ok()? != checksum.crc32(protected.as_bytes()) {
+        return None;
+    }
+
+    let (sequence, content) = protected.split_once(' ')?;
+    Some((sequence.parse().ok()?, content))
+}
+
+/// Scan of a log file read in chunks of any size, counts valid and invalid
+/// records and keeps the sequence number of the last valid one
+pub struct RecoveryScan {
+    line: [u8; MAX_RECORD],
+    line_length: usize,
+    /// Current line is longer than any record
+    overflow: bool,
+    last_sequence: Option<u32>,
+    valid: u32,
+    invalid: u32,
+    unterminated: bool,
+}
+
+impl RecoveryScan {
+    pub fn new() -> Self {
+        Self {
+            line: [0; MAX_RECORD],
+            line_length: 0,
+            overflow: false,
+            last_sequence: None,
+            valid: 0,
+            invalid: 0,
+            unterminated: false,
+        }
+    }
+
+    pub fn valid_records(&self) -> u32 {
+        self.valid
+    }
+
+    pub fn invalid_records(&self) -> u32 {
+        self.invalid
+    }
+
+    /// File ends by a partial line, the next record has to start
+    /// on a new line
+    pub fn unterminated(&self) -> bool {
+        self.unterminated
+    }
+
+    fn end_line(&mut self, checksum: &mut dyn Checksum) {
+        let line = &self.line[..self.line_length];
+        let blank = line.iter().all(|byte| byte.is_ascii_whitespace());
+
+        match parse_record(line, checksum) {
+            Some((sequence, _)) if !self.overflow => {
+                self.last_sequence = Some(sequence);
+                self.valid += 1;
+            },
+            _ if blank && !self.overflow => {},
+            _ => self.invalid += 1,
+        }
+
+        self.line_length = 0;
+        self.overflow = false;
+    }
+}
+
+impl RecordScan for RecoveryScan {
+    /// Check records in the next chunk of the file
+    fn feed(&mut self, data: &[u8], checksum: &mut dyn Checksum) {
+        for &byte in data {
+            if byte == b'\n' {
+                self.end_line(checksum);
+            } else if self.line_length < MAX_RECORD {
+                self.line[self.line_length] = byte;
+                self.line_length += 1;
+            } else {
+                self.overflow = true;
+            }
+        }
+    }
+
+    /// Check the last line after the whole file was fed, the file does not
+    /// end by a line break after a cut write
+    fn finish(&mut self, checksum: &mut dyn Checksum) {
+        self.unterminated = self.line_length > 0 || self.overflow;
+
+        if self.unterminated {
+            self.end_line(checksum);
+        }
+    }
+
+    fn last_sequence(&self) -> Option<u32> {
+        self.last_sequence
+    }
+}
+
+impl Default for RecoveryScan {
+    fn default() -> Self {
+        Self::new()
+    }
+}
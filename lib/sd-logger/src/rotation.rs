@@ -0,0 +1,364 @@
+//! Log file rotation and retention
+//!
+//! Records go to log files `LOG000.TXT`, `LOG001.TXT`, ... used as a ring
+//! of `max_files` files, optionally in a directory. A new file is started
+//! every day, or after a number of bytes or records.
+//!
+//! Oldest-first deletion of files over the retained count or bytes is done
+//! by truncating them, embedded-sdmmc 0.3 cannot delete files. A truncated
+//! file keeps only its first cluster and the ring keeps the number of
+//! directory entries constant. The state is kept in the index file between
+//! restarts.
+
+use arrayvec::ArrayString;
+use core::fmt::Write;
+use embedded_sdmmc::{Controller, TimeSource};
+use crate::{card::CardDevice, dir::MAX_PATH, session::LoggerSession, SdWriteError};
+
+pub const INDEX_FILE_NAME: &str = "INDEX.TXT";
+
+/// Most files in the ring, file names have three digits
+pub const MAX_FILES: usize = 100;
+
+/// Longest index file content
+pub const INDEX_SIZE: usize = 1280;
+
+pub type FileName = ArrayString<MAX_PATH>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    /// New file when the day changes
+    Daily,
+    /// New file when the record would make the file larger than the limit
+    Bytes(u32),
+    /// New file after the number of records
+    Records(u32),
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RotationPolicy {
+    pub rotation: Rotation,
+    /// Files retained including the active one, 2 to `MAX_FILES`
+    pub max_files: u16,
+    /// Bytes retained in all files, the active file is always kept
+    pub max_bytes: Option<u32>,
+    /// Directory of the log and index files, `None` for the root directory
+    pub dir: Option<&'static str>,
+    /// Log file name extension, for example `TXT`
+    pub extension: &'static str,
+}
+
+/// Rotation state, files are numbered by a sequence number increasing with
+/// each started file, the file name is given by the sequence number modulo
+/// the number of files
+pub struct LogRotation {
+    policy: RotationPolicy,
+    /// Active file, zero before the first file is started
+    sequence: u32,
+    /// Oldest retained file
+    first: u32,
+    /// Files before this one are emptied
+    cleared: u32,
+    /// Active file has to be emptied before the first record
+    fresh: bool,
+    /// Day the active file was started at, if known
+    day: Option<u32>,
+    /// Records in the active file
+    records: u32,
+    /// Size of files indexed by their position in the ring
+    sizes: [u32; MAX_FILES],
+    /// State changed since the index file was written
+    index_outdated: bool,
+}
+
+impl LogRotation {
+    pub fn new(policy: RotationPolicy) -> Self {
+        let max_files = policy.max_files.clamp(2, MAX_FILES as u16);
+
+        Self {
+            policy: RotationPolicy { max_files, ..policy },
+            sequence: 0,
+            first: 1,
+            cleared: 1,
+            fresh: false,
+            day: None,
+            records: 0,
+            sizes: [0; MAX_FILES],
+            index_outdated: false,
+        }
+    }
+
+    /// File the records are appended to
+    pub fn active_file(&self) -> FileName {
+        self.file_name(self.sequence)
+    }
+
+    /// Retained file started `age` files before the active one
+    pub fn retained_file(&self, age: u32) -> Option<FileName> {
+        let sequence = self.sequence.checked_sub(age)?;
+
+        match sequence >= self.first.max(1) {
+            true => Some(self.file_name(sequence)),
+            false => None,
+        }
+    }
+
+    /// Path of the index file
+    pub fn index_file(&self) -> FileName {
+        let mut name = self.dir_prefix();
+        let _ = name.try_push_str(INDEX_FILE_NAME);
+        name
+    }
+
+    /// Number of retained files including the active one
+    pub fn retained_files(&self) -> u32 {
+        (self.sequence + 1).saturating_sub(self.first)
+    }
+
+    /// Bytes in the retained files
+    pub fn retained_bytes(&self) -> u32 {
+        (self.first..=self.sequence).map(|sequence| self.size(sequence)).sum()
+    }
+
+    pub fn index_outdated(&self) -> bool {
+        self.index_outdated
+    }
+
+    /// Account a record of `length` bytes written at `day` (any number
+    /// changing at midnight, `None` for unknown date) and start a new file
+    /// or expire old files when needed, returns true when a new file was
+    /// started
+    pub fn prepare(&mut self, day: Option<u32>, length: usize) -> bool {
+        let length = length as u32;
+
+        let rotate = self.sequence == 0 || match self.policy.rotation {
+            Rotation::Daily => day.is_some() && day != self.day,
+            Rotation::Bytes(max) => {
+                let size = self.size(self.sequence);
+                size > 0 && size.saturating_add(length) > max
+            },
+            Rotation::Records(max) => self.records >= max,
+        };
+
+        if rotate {
+            self.sequence += 1;
+            self.fresh = true;
+            self.day = day;
+            self.records = 0;
+            self.set_size(self.sequence, 0);
+
+            let max_files = self.policy.max_files as u32;
+            self.first = self.first.max((self.sequence + 1).saturating_sub(max_files));
+        }
+
+        self.records += 1;
+        self.set_size(self.sequence, self.size(self.sequence).saturating_add(length));
+
+        if let Some(max_bytes) = self.policy.max_bytes {
+            while self.first < self.sequence && self.retained_bytes() > max_bytes {
+                self.first += 1;
+            }
+        }
+
+        self.index_outdated = true;
+        rotate
+    }
+
+    /// Next file to be emptied before records are appended, the oldest
+    /// expired file first and the started active file last
+    pub fn pending_file(&self) -> Option<FileName> {
+        if self.cleared < self.first {
+            Some(self.file_name(self.cleared))
+        } else if self.fresh {
+            Some(self.active_file())
+        } else {
+            None
+        }
+    }
+
+    /// Account `length` bytes written to the active file besides
+    /// the prepared records, for example a file header
+    pub fn account(&mut self, length: usize) {
+        self.set_size(self.sequence, self.size(self.sequence).saturating_add(length as u32));
+        self.index_outdated = true;
+    }
+
+    /// Mark the file returned by `pending_file` as emptied
+    pub fn file_emptied(&mut self) {
+        if self.cleared < self.first {
+            self.cleared += 1;
+        } else {
+            self.fresh = false;
+        }
+
+        self.index_outdated = true;
+    }
+
+    /// Write the state as `key=value` lines, sizes of the retained files
+    /// are listed from the oldest one
+    pub fn write_index(&self, output: &mut dyn Write) -> core::fmt::Result {
+        write!(output, "active={}\r\nfiles={}\r\n", self.active_file(), self.policy.max_files)?;
+        write!(output, "sequence={}\r\n", self.sequence)?;
+        write!(output, "first={}\r\ncleared={}\r\n", self.first, self.cleared)?;
+        write!(output, "fresh={}\r\nrecords={}\r\n", self.fresh as u8, self.records)?;
+
+        if let Some(day) = self.day {
+            write!(output, "day={}\r\n", day)?;
+        }
+
+        write!(output, "sizes=")?;
+
+        for sequence in self.first..=self.sequence {
+            let separator = if sequence == self.first { "" } else { "," };
+            write!(output, "{}{}", separator, self.size(sequence))?;
+        }
+
+        write!(output, "\r\n")
+    }
+
+    pub fn index_saved(&mut self) {
+        self.index_outdated = false;
+    }
+
+    /// Restore the state from the index file `content`, returns false
+    /// and keeps the state when the content is not valid or was written
+    /// for another number of files
+    pub fn restore(&mut self, content: &[u8]) -> bool {
+        let text = match core::str::from_utf8(content) {
+            Ok(text) => text,
+            Err(_) => return false,
+        };
+
+        let mut restored = Self::new(self.policy);
+        let mut sizes = None;
+        let mut files = None;
+        let mut found = 0;
+
+        for line in text.lines() {
+            let (key, value) = match line.trim().split_once('=') {
+                Some(pair) => pair,
+                None => continue,
+            };
+
+            let number = value.parse::<u32>().ok();
+
+            match (key, number) {
+                ("files", Some(number)) => files = Some(number),
+                ("sequence", Some(number)) => { restored.sequence = number; found += 1; },
+                ("first", Some(number)) => { restored.first = number; found += 1; },
+                ("cleared", Some(number)) => { restored.cleared = number; found += 1; },
+                ("fresh", Some(number)) => restored.fresh = number != 0,
+                ("records", Some(number)) => restored.records = number,
+                ("day", Some(number)) => restored.day = Some(number),
+                ("sizes", _) => sizes = Some(value),
+                _ => {},
+            }
+        }
+
+        let valid = found == 3
+            && files == Some(restored.policy.max_files as u32)
+            && restored.cleared <= restored.first
+            && restored.first <= restored.sequence.max(1)
+            && restored.retained_files() <= restored.policy.max_files as u32;
+
+        if !valid {
+            return false;
+        }
+
+        if let Some(sizes) = sizes {
+            let values = sizes.split(',').filter_map(|size| size.trim().parse::<u32>().ok());
+
+            for (sequence, size) in (restored.first..=restored.sequence).zip(values) {
+                restored.set_size(sequence, size);
+            }
+        }
+
+        *self = restored;
+        true
+    }
+
+    fn file_name(&self, sequence: u32) -> FileName {
+        let mut name = self.dir_prefix();
+        let _ = write!(name, "LOG{:03}.{}", self.position(sequence), self.policy.extension);
+        name
+    }
+
+    fn dir_prefix(&self) -> FileName {
+        let mut name = FileName::new();
+
+        if let Some(dir) = self.policy.dir {
+            let _ = write!(name, "{}/", dir.trim_end_matches('/'));
+        }
+
+        name
+    }
+
+    fn position(&self, sequence: u32) -> usize {
+        (sequence % self.policy.max_files as u32) as usize
+    }
+
+    fn size(&self, sequence: u32) -> u32 {
+        self.sizes[self.position(sequence)]
+    }
+
+    fn set_size(&mut self, sequence: u32, size: u32) {
+        let position = self.position(sequence);
+        self.sizes[position] = size;
+    }
+}
+
+/// Append `record` to the active log file using the `session`, a new file
+/// is started and expired files are emptied first when needed, the index
+/// file is written when a file was started
+pub fn append_record<D, T>(
+    rotation: &mut LogRotation,
+    session: &mut LoggerSession,
+    controller: &mut Controller<D, T>,
+    record: &[u8],
+    day: Option<u32>,
+    now_seconds: u32,
+) -> Result<(), SdWriteError<D::Error>>
+where D: CardDevice, T: TimeSource {
+    start_record(rotation, session, controller, day, record.len())?;
+    session.append_bytes(controller, &rotation.active_file(), record, now_seconds)
+}
+
+/// Prepare the active log file for a record of `length` bytes like
+/// `append_record` without writing it, returns true when a new file was
+/// started
+pub fn start_record<D, T>(
+    rotation: &mut LogRotation,
+    session: &mut LoggerSession,
+    controller: &mut Controller<D, T>,
+    day: Option<u32>,
+    length: usize,
+) -> Result<bool, SdWriteError<D::Error>>
+where D: CardDevice, T: TimeSource {
+    let started = rotation.prepare(day, length);
+
+    while let Some(file_name) = rotation.pending_file() {
+        session.replace_file(controller, &file_name, "")?;
+        rotation.file_emptied();
+    }
+
+    // The started file must not be reused after a restart
+    if started {
+        save_index(rotation, session, controller)?;
+    }
+
+    Ok(started)
+}
+
+/// Replace the index file by the current state
+pub fn save_index<D, T>(
+    rotation: &mut LogRotation,
+    session: &mut LoggerSession,
+    controller: &mut Controller<D, T>,
+) -> Result<(), SdWriteError<D::Error>>
+where D: CardDevice, T: TimeSource {
+    let mut content = ArrayString::<INDEX_SIZE>::new();
+    let _ = rotation.write_index(&mut content);
+    session.replace_file(controller, &rotation.index_file(), &content)?;
+    rotation.index_saved();
+    Ok(())
+}
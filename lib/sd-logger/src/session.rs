@@ -0,0 +1,311 @@
+//! Logging session keeping the card initialized and the log file open,
+//! records are collected in a RAM buffer and written in larger blocks
+
+use embedded_sdmmc::{Controller, Directory, Mode, TimeSource, Volume};
+use crate::{
+    card::CardDevice,
+    dir::{create_dir_path, split_path, write_to_path, MAX_PATH},
+    open_file_to_write, open_volume, write_to_file_in_dir, write_to_opened_file, SdWriteError,
+    WriteFile
+};
+
+/// Buffer size matches the card block size
+pub const BUFFER_SIZE: usize = 512;
+
+/// Card state kept between writes
+struct Mounted {
+    volume: Volume,
+    root: Directory,
+    /// Directory of the log file when not in the root directory
+    file_dir: Option<Directory>,
+    /// Log file at the session `file_name` path
+    file: Option<WriteFile>,
+}
+
+/// Collects data appended to a log file and writes it when the buffer
+/// is full, the oldest buffered data gets older than `max_age_seconds`,
+/// or the data goes to another file. The card is initialized at the first
+/// write and stays initialized, after an error it is released and
+/// initialized again at the next write, buffered data is kept until written.
+pub struct LoggerSession {
+    mounted: Option<Mounted>,
+    file_name: [u8; MAX_PATH],
+    file_name_length: usize,
+    buffer: [u8; BUFFER_SIZE],
+    buffered: usize,
+    /// Time when the oldest buffered data was appended
+    buffered_since: Option<u32>,
+    max_age_seconds: u32,
+}
+
+impl LoggerSession {
+    pub fn new(max_age_seconds: u32) -> Self {
+        Self {
+            mounted: None,
+            file_name: [0; MAX_PATH],
+            file_name_length: 0,
+            buffer: [0; BUFFER_SIZE],
+            buffered: 0,
+            buffered_since: None,
+            max_age_seconds,
+        }
+    }
+
+    /// Number of bytes not yet written to the card
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    pub fn is_mounted(&self) -> bool {
+        self.mounted.is_some()
+    }
+
+    fn file_name(&self) -> &str {
+        core::str::from_utf8(&self.file_name[..self.file_name_length]).unwrap_or("")
+    }
+
+    /// Append `data` to the file at the path `file_name`, `now_seconds` is
+    /// the time from any seconds counter used for the max age check
+    pub fn append<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+        file_name: &str,
+        data: &str,
+        now_seconds: u32,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: CardDevice, T: TimeSource {
+        self.append_bytes(controller, file_name, data.as_bytes(), now_seconds)
+    }
+
+    /// Append binary `data` like `append`
+    pub fn append_bytes<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+        file_name: &str,
+        mut data: &[u8],
+        now_seconds: u32,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: CardDevice, T: TimeSource {
+        if file_name.len() > MAX_PATH {
+            return Err(SdWriteError::InvalidFileName);
+        }
+
+        if file_name != self.file_name() {
+            self.flush(controller)?;
+            self.close_file(controller);
+            self.file_name[..file_name.len()].copy_from_slice(file_name.as_bytes());
+            self.file_name_length = file_name.len();
+        }
+
+        // Records are not split between writes unless larger than the buffer
+        if data.len() > BUFFER_SIZE - self.buffered {
+            self.flush(controller)?;
+        }
+
+        while !data.is_empty() {
+            if self.buffered == BUFFER_SIZE {
+                self.flush(controller)?;
+            }
+
+            let length = data.len().min(BUFFER_SIZE - self.buffered);
+            self.buffer[self.buffered..self.buffered + length].copy_from_slice(&data[..length]);
+            self.buffered += length;
+            self.buffered_since.get_or_insert(now_seconds);
+            data = &data[length..];
+        }
+
+        self.poll(controller, now_seconds)
+    }
+
+    /// Write the buffered data when the oldest is too old, should be called
+    /// periodically
+    pub fn poll<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+        now_seconds: u32,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: CardDevice, T: TimeSource {
+        match self.buffered_since {
+            Some(since) if now_seconds.wrapping_sub(since) >= self.max_age_seconds => {
+                self.flush(controller)
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Write all buffered data
+    pub fn flush<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: CardDevice, T: TimeSource {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+
+        match self.write_buffer(controller) {
+            Ok(()) => {
+                self.buffered = 0;
+                self.buffered_since = None;
+                Ok(())
+            },
+            Err(error) => {
+                self.unmount(controller);
+                Err(error)
+            },
+        }
+    }
+
+    /// Replace the content of the file at the path `file_name`, not
+    /// buffered. When it is the log file, the buffered data is written and
+    /// the log file closed first, a file in the directory of the log file
+    /// is written using the opened directory.
+    pub fn replace_file<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+        file_name: &str,
+        data: &str,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: CardDevice, T: TimeSource {
+        if file_name == self.file_name() {
+            self.flush(controller)?;
+            self.close_file(controller);
+            self.file_name_length = 0;
+        }
+
+        let (dir_path, name) = split_path(file_name);
+        let shared_dir = !dir_path.is_empty() && dir_path == split_path(self.file_name()).0;
+
+        // Other directories on the path may include the opened one
+        if !shared_dir {
+            self.close_file(controller);
+        }
+
+        self.mount(controller)?;
+
+        let mode = Mode::ReadWriteCreateOrTruncate;
+
+        let result = match self.mounted.as_mut() {
+            Some(mounted) => match mounted.file_dir.as_ref() {
+                Some(dir) if shared_dir => write_to_file_in_dir(
+                    controller, dir, &mut mounted.volume, name, data.as_bytes(), mode
+                ),
+                _ => write_to_path(
+                    controller, &mut mounted.volume, &mounted.root, file_name, data.as_bytes(), mode
+                ),
+            },
+            None => Err(SdWriteError::NoSuitableVolume),
+        };
+
+        if result.is_err() {
+            self.unmount(controller);
+        }
+
+        result
+    }
+
+    /// Write buffered data and release the card, for example before
+    /// it is removed
+    pub fn close<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: CardDevice, T: TimeSource {
+        let result = self.flush(controller);
+        self.unmount(controller);
+        result
+    }
+
+    fn write_buffer<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: CardDevice, T: TimeSource {
+        self.mount(controller)?;
+
+        let file_name = core::str::from_utf8(&self.file_name[..self.file_name_length])
+            .map_err(|_| SdWriteError::InvalidFileName)?;
+
+        let mounted = self.mounted.as_mut().ok_or(SdWriteError::NoSuitableVolume)?;
+
+        if mounted.file.is_none() {
+            let (dir_path, name) = split_path(file_name);
+
+            if !dir_path.is_empty() && mounted.file_dir.is_none() {
+                let dir = create_dir_path(
+                    controller, &mut mounted.volume, &mounted.root, dir_path
+                )?;
+                mounted.file_dir = Some(dir);
+            }
+
+            let dir = mounted.file_dir.as_ref().unwrap_or(&mounted.root);
+
+            let file = open_file_to_write(
+                controller, &mut mounted.volume, dir, name, Mode::ReadWriteCreateOrAppend, true
+            )?;
+
+            mounted.file = Some(file);
+        }
+
+        match mounted.file.as_mut() {
+            Some(file) => write_to_opened_file(
+                controller, &mut mounted.volume, file, &self.buffer[..self.buffered]
+            ),
+            None => Err(SdWriteError::NoSuitableVolume),
+        }
+    }
+
+    fn mount<D, T>(
+        &mut self,
+        controller: &mut Controller<D, T>,
+    ) -> Result<(), SdWriteError<D::Error>>
+    where D: CardDevice, T: TimeSource {
+        if self.mounted.is_some() {
+            return Ok(());
+        }
+
+        controller.device().initialize().map_err(SdWriteError::CannotConnect)?;
+
+        let opened = open_volume(controller).and_then(|volume| {
+            match controller.open_root_dir(&volume) {
+                Ok(root) => Ok(Mounted { volume, root, file_dir: None, file: None }),
+                Err(error) => Err(SdWriteError::CannotReadRootDir(error)),
+            }
+        });
+
+        match opened {
+            Ok(mounted) => {
+                self.mounted = Some(mounted);
+                Ok(())
+            },
+            Err(error) => {
+                controller.device().release();
+                Err(error)
+            },
+        }
+    }
+
+    fn close_file<D, T>(&mut self, controller: &mut Controller<D, T>)
+    where D: CardDevice, T: TimeSource {
+        if let Some(mounted) = self.mounted.as_mut() {
+            if let Some(file) = mounted.file.take() {
+                let _ = controller.close_file(&mounted.volume, file.file);
+            }
+
+            if let Some(dir) = mounted.file_dir.take() {
+                controller.close_dir(&mounted.volume, dir);
+            }
+        }
+    }
+
+    fn unmount<D, T>(&mut self, controller: &mut Controller<D, T>)
+    where D: CardDevice, T: TimeSource {
+        self.close_file(controller);
+
+        if let Some(mounted) = self.mounted.take() {
+            controller.close_dir(&mounted.volume, mounted.root);
+        }
+
+        controller.device().release();
+    }
+}
@@ -0,0 +1,38 @@
+//! SPI clock of the STM32F4 SPI1 bus the SD card demos connect the card to
+
+use crate::card::SpiClock;
+use stm32f4xx_hal::{pac, rcc::Clocks};
+
+/// SPI1 clock for the SD card, the baud rate prescaler divides PCLK2
+/// by 2 (level 0) up to 256 (level 7)
+pub struct Spi1Clock {
+    pclk: u32,
+}
+
+impl Spi1Clock {
+    pub fn new(clocks: &Clocks) -> Self {
+        Self { pclk: clocks.pclk2().raw() }
+    }
+}
+
+impl SpiClock for Spi1Clock {
+    fn levels(&self) -> u8 {
+        8
+    }
+
+    fn frequency(&self, level: u8) -> u32 {
+        self.pclk >> (level + 1)
+    }
+
+    fn set_level(&self, level: u8) {
+        // SPI1 is owned by the card driver, only the prescaler is changed
+        // while no transfer is running
+        let spi = unsafe { &*pac::SPI1::ptr() };
+
+        while spi.sr.read().bsy().bit_is_set() { }
+
+        spi.cr1.modify(|_, w| w.spe().clear_bit());
+        spi.cr1.modify(|_, w| w.br().bits(level));
+        spi.cr1.modify(|_, w| w.spe().set_bit());
+    }
+}
@@ -0,0 +1,202 @@
+//! Binary log format, run on the host using
+//! `cargo test -p sd-logger --target x86_64-unknown-linux-gnu`
+
+use arrayvec::ArrayVec;
+use sd_logger::{
+    binary::{
+        BinaryScan, Decoded, FieldType, FormatError, RecordDecoder, RecordEncoder, Sample, Schema,
+        FLAG_TIME_JUMP, MAX_HEADER, MAX_RECORD, SYNC_INTERVAL,
+    },
+    record::{Checksum, RecordScan, SoftwareCrc},
+};
+
+fn schema(delta: bool) -> Schema {
+    let mut schema = Schema::new(delta);
+    schema.add_field("offset", FieldType::I16).unwrap();
+    schema.add_field("uptime", FieldType::U32).unwrap();
+    schema
+}
+
+fn sample(sequence: u32, time: u32, values: &[i64]) -> Sample {
+    Sample { sequence, time, flags: 0, values: values.iter().copied().collect() }
+}
+
+/// Header followed by records of the samples
+fn encode(schema: Schema, samples: &[Sample]) -> Vec<u8> {
+    let mut header = [0u8; MAX_HEADER];
+    let length = schema.write_header(&mut header, &mut SoftwareCrc).unwrap();
+    let mut file = header[..length].to_vec();
+    let mut encoder = RecordEncoder::new(schema);
+
+    for sample in samples {
+        let mut records = [0u8; 2*MAX_RECORD];
+        let length = encoder.encode(sample, &mut records, &mut SoftwareCrc).unwrap();
+        file.extend_from_slice(&records[..length]);
+    }
+
+    file
+}
+
+fn decode(file: &[u8]) -> Vec<Result<Decoded, FormatError>> {
+    let (schema, header_length) = Schema::read_header(file, &mut SoftwareCrc).unwrap();
+    let size = schema.record_size();
+    let mut decoder = RecordDecoder::new(schema);
+
+    file[header_length..]
+        .chunks(size)
+        .map(|record| decoder.decode(record, &mut SoftwareCrc))
+        .collect()
+}
+
+fn samples(decoded: &[Result<Decoded, FormatError>]) -> Vec<Sample> {
+    decoded.iter()
+        .filter_map(|result| match result {
+            Ok(Decoded::Sample(sample)) => Some(sample.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn header_describes_records() {
+    let mut header = [0u8; MAX_HEADER];
+    let length = schema(false).write_header(&mut header, &mut SoftwareCrc).unwrap();
+    assert_eq!(&header[..9], b"SDLB\x01\x00\x14\x00\x02");
+
+    let (schema, read_length) = Schema::read_header(&header, &mut SoftwareCrc).unwrap();
+    assert_eq!(read_length, length);
+    assert_eq!(schema.fields()[1].name.as_str(), "uptime");
+    assert_eq!(schema.record_size(), 20);
+
+    header[11] ^= 1;
+    assert_eq!(Schema::read_header(&header, &mut SoftwareCrc), Err(FormatError::BadCrc));
+    assert_eq!(Schema::read_header(b"LOG", &mut SoftwareCrc), Err(FormatError::BadMagic));
+
+}
+
+#[test]
+fn field_names_are_restricted() {
+    let mut schema = Schema::new(false);
+    assert_eq!(schema.add_field("offset_min2", FieldType::U8), Ok(()));
+
+    // Quotes, backslashes and commas would need escaping in JSON and CSV
+    for name in [
+        "", "no spaces", "quote\"", "back\\slash", "a,b", "x:1", "teplota°", "a_very_long_name"
+    ] {
+        let result = schema.add_field(name, FieldType::U8);
+        assert_eq!(result, Err(FormatError::InvalidFieldName), "{}", name);
+    }
+
+    assert_eq!(schema.fields().len(), 1);
+}
+
+#[test]
+fn header_with_invalid_field_name_is_rejected() {
+    let mut schema = Schema::new(false);
+    schema.add_field("ab", FieldType::U8).unwrap();
+
+    let mut header = [0u8; MAX_HEADER];
+    let length = schema.write_header(&mut header, &mut SoftwareCrc).unwrap();
+
+    // Header of a field named `a"` with a valid CRC
+    header[12] = b'"';
+    let crc = SoftwareCrc.crc32(&header[..length - 4]);
+    header[length - 4..length].copy_from_slice(&crc.to_le_bytes());
+
+    assert_eq!(Schema::read_header(&header, &mut SoftwareCrc), Err(FormatError::InvalidFieldName));
+}
+
+#[test]
+fn samples_round_trip() {
+    let written = [
+        sample(7, 1_792_245_780, &[120, 4_000_000_000]),
+        Sample { flags: FLAG_TIME_JUMP, ..sample(8, 1_792_246_380, &[-60, 5]) },
+    ];
+
+    for delta in [false, true] {
+        let file = encode(schema(delta), &written);
+        let decoded = decode(&file);
+        assert!(decoded.iter().all(|result| result.is_ok()));
+        assert_eq!(samples(&decoded), written);
+    }
+
+    // Delta encoding is shorter after the first sync
+    assert_eq!(schema(true).record_size(), 18);
+}
+
+#[test]
+fn delta_encoding_syncs_time() {
+    let written: Vec<Sample> = (0..SYNC_INTERVAL + 2)
+        .map(|index| sample(index, 1_000_000 + index*600, &[0, index as i64]))
+        .collect();
+
+    let mut file = encode(schema(true), &written);
+    let decoded = decode(&file);
+    let syncs = decoded.iter().filter(|result| matches!(result, Ok(Decoded::TimeSync(_)))).count();
+    assert_eq!(syncs, 2);
+    assert_eq!(samples(&decoded), written);
+
+    // Damaged sync record leaves samples without time until the next one
+    let header_length = schema(true).header_size();
+    file[header_length + 3] ^= 0xff;
+    let decoded = decode(&file);
+    assert_eq!(decoded[0], Err(FormatError::BadCrc));
+    assert_eq!(decoded[1], Err(FormatError::MissingTime));
+    assert_eq!(samples(&decoded).len(), 2);
+
+    // Time going back or too far needs a sync record
+    let written = [sample(0, 5000, &[0, 0]), sample(1, 4000, &[0, 0]), sample(2, 200_000, &[0, 0])];
+    let decoded = decode(&encode(schema(true), &written));
+    assert_eq!(decoded.len(), 6);
+    assert_eq!(samples(&decoded), written);
+}
+
+#[test]
+fn delta_encoding_needs_values() {
+    let mut schema = Schema::new(true);
+    schema.add_field("flag", FieldType::U8).unwrap();
+
+    let mut header = [0u8; MAX_HEADER];
+    assert_eq!(schema.write_header(&mut header, &mut SoftwareCrc), Err(FormatError::DeltaWithoutValues));
+
+    let mut records = [0u8; 2*MAX_RECORD];
+    let values: ArrayVec<i64, 16> = [1].into_iter().collect();
+    let sample = Sample { sequence: 0, time: 0, flags: 0, values };
+    assert_eq!(
+        RecordEncoder::new(schema).encode(&sample, &mut records, &mut SoftwareCrc),
+        Err(FormatError::DeltaWithoutValues)
+    );
+}
+
+#[test]
+fn scan_finds_last_sample_and_cut_record() {
+    let written: Vec<Sample> = (10..20).map(|index| sample(index, index*60, &[0, 0])).collect();
+    let file = encode(schema(true), &written);
+
+    for chunk_size in [1, 5, 512] {
+        let mut scan = BinaryScan::new();
+
+        // Last record cut in the middle
+        for chunk in file[..file.len() - 3].chunks(chunk_size) {
+            scan.feed(chunk, &mut SoftwareCrc);
+        }
+
+        scan.finish(&mut SoftwareCrc);
+        assert_eq!(scan.schema(), Some(&schema(true)));
+        assert_eq!(scan.last_sequence(), Some(18));
+        assert_eq!(scan.next_sequence(), 19);
+        assert_eq!((scan.valid_records(), scan.invalid_records()), (9, 0));
+        assert_eq!(scan.padding(), 3);
+    }
+
+    let mut scan = BinaryScan::new();
+    scan.feed(&file[..10], &mut SoftwareCrc);
+    scan.finish(&mut SoftwareCrc);
+    assert_eq!(scan.header_error(), Some(FormatError::Truncated));
+
+    let mut scan = BinaryScan::new();
+    scan.feed(b"1 2026-10-17T14:03:00+02:00 A *00000000\n", &mut SoftwareCrc);
+    scan.finish(&mut SoftwareCrc);
+    assert_eq!(scan.header_error(), Some(FormatError::BadMagic));
+    assert_eq!(scan.last_sequence(), None);
+}
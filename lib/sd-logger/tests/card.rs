@@ -0,0 +1,149 @@
+//! SPI clock selection of a card, run on the host using
+//! `cargo test -p sd-logger --target x86_64-unknown-linux-gnu`
+
+use std::{cell::Cell, rc::Rc};
+use embedded_sdmmc::{Block, BlockCount, BlockDevice, BlockIdx, SdMmcError};
+use sd_logger::card::{CardDevice, ClockedCard, SpiClock, IDENTIFICATION_HZ};
+
+/// Bus clock dividing 25 MHz by 2 up to 256
+struct TestClock {
+    frequency: Rc<Cell<u32>>,
+}
+
+impl SpiClock for TestClock {
+    fn levels(&self) -> u8 {
+        8
+    }
+
+    fn frequency(&self, level: u8) -> u32 {
+        25_000_000 >> (level + 1)
+    }
+
+    fn set_level(&self, level: u8) {
+        self.frequency.set(self.frequency(level));
+    }
+}
+
+/// Card failing with CRC errors above `max_hz`
+struct TestCard {
+    frequency: Rc<Cell<u32>>,
+    max_hz: Cell<u32>,
+    removed: bool,
+}
+
+impl TestCard {
+    fn transfer(&self) -> Result<(), SdMmcError> {
+        if self.removed {
+            Err(SdMmcError::CardNotFound)
+        } else if self.frequency.get() > self.max_hz.get() {
+            Err(SdMmcError::CrcError(0, 1))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl BlockDevice for TestCard {
+    type Error = SdMmcError;
+
+    fn read(&self, _blocks: &mut [Block], _start: BlockIdx, _reason: &str) -> Result<(), SdMmcError> {
+        self.transfer()
+    }
+
+    fn write(&self, _blocks: &[Block], _start: BlockIdx) -> Result<(), SdMmcError> {
+        self.transfer()
+    }
+
+    fn num_blocks(&self) -> Result<BlockCount, SdMmcError> {
+        Ok(BlockCount(1024))
+    }
+}
+
+impl CardDevice for TestCard {
+    fn initialize(&mut self) -> Result<(), SdMmcError> {
+        if self.frequency.get() > IDENTIFICATION_HZ {
+            return Err(SdMmcError::TimeoutCommand(0));
+        }
+
+        self.transfer()
+    }
+
+    fn release(&mut self) { }
+}
+
+fn card(max_hz: u32) -> ClockedCard<TestCard, TestClock> {
+    let frequency = Rc::new(Cell::new(0));
+
+    ClockedCard::new(
+        TestCard { frequency: frequency.clone(), max_hz: Cell::new(max_hz), removed: false },
+        TestClock { frequency },
+    )
+}
+
+#[test]
+fn card_is_identified_at_slow_clock() {
+    let mut card = card(25_000_000);
+    assert_eq!(card.frequency(), 390_625);
+
+    card.initialize().unwrap();
+    assert_eq!(card.frequency(), 12_500_000);
+
+    card.release();
+    assert_eq!(card.frequency(), 390_625);
+}
+
+#[test]
+fn fastest_reliable_clock_is_selected() {
+    let mut card = card(5_000_000);
+
+    card.initialize().unwrap();
+    assert_eq!(card.frequency(), 3_125_000);
+}
+
+#[test]
+fn clock_can_be_limited() {
+    let mut card = card(25_000_000);
+
+    card.set_fastest_level(2);
+    card.initialize().unwrap();
+    assert_eq!(card.frequency(), 3_125_000);
+
+    card.set_fastest_level(20);
+    card.initialize().unwrap();
+    assert_eq!(card.frequency(), 390_625);
+}
+
+#[test]
+fn clock_steps_down_after_crc_errors() {
+    let mut card = card(25_000_000);
+    card.initialize().unwrap();
+
+    card.card().max_hz.set(1_000_000);
+    card.write(&[Block::new()], BlockIdx(1)).unwrap();
+    assert_eq!(card.frequency(), 781_250);
+}
+
+#[test]
+fn identification_clock_is_the_slowest_fallback() {
+    let mut card = card(25_000_000);
+    card.initialize().unwrap();
+
+    card.card().max_hz.set(100_000);
+    assert!(matches!(
+        card.read(&mut [Block::new()], BlockIdx(1), "test"),
+        Err(SdMmcError::CrcError(_, _))
+    ));
+    assert_eq!(card.frequency(), 390_625);
+}
+
+#[test]
+fn other_errors_keep_the_clock() {
+    let frequency = Rc::new(Cell::new(0));
+    let mut card = ClockedCard::new(
+        TestCard { frequency: frequency.clone(), max_hz: Cell::new(25_000_000), removed: true },
+        TestClock { frequency },
+    );
+
+    assert!(matches!(card.initialize(), Err(SdMmcError::CardNotFound)));
+    assert_eq!(card.frequency(), 390_625);
+}
@@ -0,0 +1,101 @@
+//! Disk images, cards and clock shared by the tests, each test uses only
+//! some of them
+#![allow(dead_code)]
+
+use std::{fmt::Debug, io};
+use embedded_sdmmc::{
+    Block, BlockCount, BlockDevice, BlockIdx, Controller, Mode, SdMmcError, TimeSource, Timestamp
+};
+use sd_image::{file::FileBlockDevice, image::{format, FatType}};
+use sd_logger::{card::CardDevice, read_from_volume, write_to_volume};
+
+/// Size of the buffer used to read a whole file
+const READ_BUFFER_SIZE: usize = 2048;
+
+/// Time stamp of all files, 17. 10. 2026 12:00:00
+pub struct FixedTime;
+
+impl TimeSource for FixedTime {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 56,
+            zero_indexed_month: 9,
+            zero_indexed_day: 17,
+            hours: 12,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+/// Image file acting as a card which can be removed
+pub struct ImageCard {
+    pub image: FileBlockDevice,
+    pub removed: bool,
+    pub initializations: usize,
+}
+
+impl ImageCard {
+    pub fn new(image: FileBlockDevice) -> Self {
+        ImageCard { image, removed: false, initializations: 0 }
+    }
+
+    pub fn set_removed(&mut self, removed: bool) {
+        self.removed = removed;
+        self.image.set_write_protected(removed);
+    }
+}
+
+impl BlockDevice for ImageCard {
+    type Error = io::Error;
+
+    fn read(&self, blocks: &mut [Block], start: BlockIdx, reason: &str) -> io::Result<()> {
+        self.image.read(blocks, start, reason)
+    }
+
+    fn write(&self, blocks: &[Block], start: BlockIdx) -> io::Result<()> {
+        self.image.write(blocks, start)
+    }
+
+    fn num_blocks(&self) -> io::Result<BlockCount> {
+        self.image.num_blocks()
+    }
+}
+
+impl CardDevice for ImageCard {
+    fn initialize(&mut self) -> Result<(), SdMmcError> {
+        if self.removed {
+            return Err(SdMmcError::CardNotFound);
+        }
+
+        self.initializations += 1;
+        Ok(())
+    }
+
+    fn release(&mut self) { }
+}
+
+/// New image file `sd-<name>.img` in the temporary directory, names must
+/// differ between tests running in parallel
+pub fn image(name: &str, blocks: u32, fat_type: Option<FatType>) -> FileBlockDevice {
+    let path = std::env::temp_dir().join(format!("sd-{}.img", name));
+    let device = FileBlockDevice::create(path, blocks).unwrap();
+
+    if let Some(fat_type) = fat_type {
+        format(&device, fat_type).unwrap();
+    }
+
+    device
+}
+
+pub fn read_to_string<D, T>(controller: &mut Controller<D, T>, path: &str) -> String
+where D: BlockDevice, D::Error: Debug, T: TimeSource {
+    let mut buffer = [0u8; READ_BUFFER_SIZE];
+    let size = read_from_volume(controller, path, &mut buffer).unwrap();
+    String::from_utf8(buffer[..size].to_vec()).unwrap()
+}
+
+pub fn append<D, T>(controller: &mut Controller<D, T>, path: &str, data: &str)
+where D: BlockDevice, D::Error: Debug, T: TimeSource {
+    write_to_volume(controller, path, data.as_bytes(), Mode::ReadWriteCreateOrAppend).unwrap();
+}
@@ -0,0 +1,170 @@
+//! Files in subdirectories on disk images, run on the host using
+//! `cargo test -p sd-logger --target x86_64-unknown-linux-gnu`
+
+mod common;
+
+use embedded_sdmmc::{Controller, Error, Mode};
+use sd_image::{file::FileBlockDevice, image::{cluster_entry, fat_usage, FatType}};
+use sd_logger::{
+    dir::{read_at_path, split_path, write_to_path},
+    open_volume,
+    read_from_volume,
+    write_to_volume,
+    SdWriteError,
+};
+use common::{append, read_to_string, FixedTime};
+
+/// 32 MiB card, FAT16 with 512 byte clusters
+const FAT16_CARD_BLOCKS: u32 = 64*1024;
+/// 64 MiB card, the smallest FAT32 volume
+const FAT32_CARD_BLOCKS: u32 = 128*1024;
+
+type TestController = Controller<FileBlockDevice, FixedTime>;
+
+fn image(name: &str, blocks: u32, fat_type: FatType) -> TestController {
+    Controller::new(common::image(&format!("dir-{}", name), blocks, Some(fat_type)), FixedTime)
+}
+
+/// FAT copies are equal, the FAT32 free cluster count is unknown or right
+/// and the next free cluster is unknown or free
+fn assert_fat_consistent(device: &FileBlockDevice) {
+    let usage = fat_usage(device).unwrap();
+    assert!(usage.copies_match);
+
+    if let Some((free_count, next_free)) = usage.info {
+        assert!(free_count == u32::MAX || free_count == usage.free_clusters);
+        assert!(next_free == u32::MAX || cluster_entry(device, next_free).unwrap() == 0);
+    }
+}
+
+#[test]
+fn paths_are_split_at_last_separator() {
+    assert_eq!(split_path("LOG.TXT"), ("", "LOG.TXT"));
+    assert_eq!(split_path("/LOG.TXT"), ("", "LOG.TXT"));
+    assert_eq!(split_path("/LOGS/2026/10/17.LOG"), ("LOGS/2026/10", "17.LOG"));
+}
+
+#[test]
+fn nested_directories_are_created() {
+    for (name, blocks, fat_type) in [
+        ("nested16", FAT16_CARD_BLOCKS, FatType::Fat16),
+        ("nested32", FAT32_CARD_BLOCKS, FatType::Fat32),
+    ] {
+        let mut controller = image(name, blocks, fat_type);
+
+        append(&mut controller, "/LOGS/2026/10/17.LOG", "first\n");
+        append(&mut controller, "/LOGS/2026/10/17.LOG", "second\n");
+
+        // Existing directories are reused
+        append(&mut controller, "LOGS/2026/10/18.LOG", "next day\n");
+        append(&mut controller, "LOGS/2026/11/01.LOG", "next month\n");
+        append(&mut controller, "ROOT.TXT", "root\n");
+
+        assert_eq!(read_to_string(&mut controller, "LOGS/2026/10/17.LOG"), "first\nsecond\n");
+        assert_eq!(read_to_string(&mut controller, "LOGS/2026/10/18.LOG"), "next day\n");
+        assert_eq!(read_to_string(&mut controller, "LOGS/2026/11/01.LOG"), "next month\n");
+        assert_eq!(read_to_string(&mut controller, "ROOT.TXT"), "root\n");
+    }
+}
+
+#[test]
+fn directory_holds_many_files() {
+    let mut controller = image("many", FAT16_CARD_BLOCKS, FatType::Fat16);
+
+    for index in 0..60 {
+        append(&mut controller, &format!("LOGS/{:02}.LOG", index), &format!("{}\n", index));
+    }
+
+    assert_eq!(read_to_string(&mut controller, "LOGS/00.LOG"), "0\n");
+    assert_eq!(read_to_string(&mut controller, "LOGS/59.LOG"), "59\n");
+}
+
+#[test]
+fn missing_directory_is_not_created_by_read() {
+    let mut controller = image("missing", FAT16_CARD_BLOCKS, FatType::Fat16);
+    let mut buffer = [0u8; 16];
+
+    assert!(matches!(
+        read_from_volume(&mut controller, "NONE/LOG.TXT", &mut buffer),
+        Err(SdWriteError::CannotOpenDir(Error::FileNotFound))
+    ));
+
+    append(&mut controller, "LOGS/LOG.TXT", "log\n");
+
+    assert!(matches!(
+        read_from_volume(&mut controller, "LOGS/NONE.TXT", &mut buffer),
+        Err(SdWriteError::CannotOpenFile(Error::FileNotFound))
+    ));
+}
+
+#[test]
+fn file_is_not_used_as_directory() {
+    let mut controller = image("file", FAT16_CARD_BLOCKS, FatType::Fat16);
+    append(&mut controller, "LOGS", "not a directory\n");
+
+    assert!(matches!(
+        write_to_volume(&mut controller, "LOGS/LOG.TXT", b"log\n", Mode::ReadWriteCreateOrAppend),
+        Err(SdWriteError::CannotOpenDir(Error::FileAlreadyExists))
+    ));
+
+    assert!(matches!(
+        write_to_volume(&mut controller, "LONG_NAME/LOG.TXT", b"log\n", Mode::ReadWriteCreateOrAppend),
+        Err(SdWriteError::InvalidFileName)
+    ));
+}
+
+#[test]
+fn file_is_read_at_offsets() {
+    let mut controller = image("offset", FAT16_CARD_BLOCKS, FatType::Fat16);
+
+    // Several 512 byte clusters
+    let text: String = (0..400).map(|index| format!("{:03}\n", index)).collect();
+    append(&mut controller, "LOGS/LOG.TXT", &text);
+
+    let mut volume = open_volume(&mut controller).unwrap();
+    let root = controller.open_root_dir(&volume).unwrap();
+    let mut buffer = [0u8; 8];
+
+    for offset in [0, 1200, 4, 510, 1596] {
+        let size = read_at_path(&mut controller, &mut volume, &root, "LOGS/LOG.TXT", offset, &mut buffer)
+            .unwrap();
+        let expected = &text.as_bytes()[offset as usize..];
+        assert_eq!(&buffer[..size], &expected[..expected.len().min(8)]);
+    }
+
+    for offset in [1600, 5000] {
+        let size = read_at_path(&mut controller, &mut volume, &root, "LOGS/LOG.TXT", offset, &mut buffer)
+            .unwrap();
+        assert_eq!(size, 0);
+    }
+
+    controller.close_dir(&volume, root);
+}
+
+
+#[test]
+fn created_directories_keep_fat_consistent() {
+    let mode = Mode::ReadWriteCreateOrAppend;
+    let data = [b'x'; 1000];
+
+    for (name, blocks, fat_type) in [
+        ("fsinfo16", FAT16_CARD_BLOCKS, FatType::Fat16),
+        ("fsinfo32", FAT32_CARD_BLOCKS, FatType::Fat32),
+    ] {
+        let mut controller = image(name, blocks, fat_type);
+        let mut volume = open_volume(&mut controller).unwrap();
+        let root = controller.open_root_dir(&volume).unwrap();
+
+        // Opened volume counts free clusters when writing the first file,
+        // the directories take clusters behind it
+        write_to_path(&mut controller, &mut volume, &root, "LOG.TXT", &data, mode).unwrap();
+        write_to_path(&mut controller, &mut volume, &root, "LOGS/2026/17.LOG", &data, mode)
+            .unwrap();
+        write_to_path(&mut controller, &mut volume, &root, "LOG.TXT", &data, mode).unwrap();
+        controller.close_dir(&volume, root);
+
+        assert_fat_consistent(controller.device());
+        assert_eq!(read_to_string(&mut controller, "LOGS/2026/17.LOG").len(), data.len());
+        assert_eq!(read_to_string(&mut controller, "LOG.TXT").len(), 2*data.len());
+    }
+}
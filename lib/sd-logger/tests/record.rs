@@ -0,0 +1,117 @@
+//! Record CRC and recovery scan, run on the host using
+//! `cargo test -p sd-logger --target x86_64-unknown-linux-gnu`
+
+mod common;
+
+use embedded_sdmmc::{Controller, Mode};
+use sd_image::image::FatType;
+use sd_logger::{
+    read_chunks_from_volume,
+    record::{parse_record, write_record, Checksum, RecordScan, RecoveryScan, SoftwareCrc},
+    write_to_volume,
+};
+use common::FixedTime;
+
+const CARD_BLOCKS: u32 = 64*1024;
+
+const CONTENT: &str = "2026-10-17T14:03:00+02:00 A";
+
+/// CRC-32/MPEG-2 of a byte stream, the STM32 CRC unit computes it over
+/// 32 bit words read from memory in little endian order
+fn mpeg2_crc(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+
+    for &byte in data {
+        crc ^= (byte as u32) << 24;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04c1_1db7 } else { crc << 1 };
+        }
+    }
+
+    crc
+}
+
+fn records(sequences: std::ops::Range<u32>) -> String {
+    let mut text = String::new();
+
+    for sequence in sequences {
+        write_record(&mut text, sequence, CONTENT, &mut SoftwareCrc).unwrap();
+    }
+
+    text
+}
+
+fn scan(text: &str, chunk_size: usize) -> RecoveryScan {
+    let mut scan = RecoveryScan::new();
+
+    for chunk in text.as_bytes().chunks(chunk_size) {
+        scan.feed(chunk, &mut SoftwareCrc);
+    }
+
+    scan.finish(&mut SoftwareCrc);
+    scan
+}
+
+#[test]
+fn crc_matches_hardware_word_order() {
+    assert_eq!(mpeg2_crc(b"123456789"), 0x0376_e6e7);
+    assert_eq!(SoftwareCrc.crc32(b"12345678"), mpeg2_crc(b"43218765"));
+    assert_eq!(SoftwareCrc.crc32(b"123456789"), mpeg2_crc(b"43218765\x00\x00\x009"));
+}
+
+#[test]
+fn records_are_checked() {
+    let text = records(5..6);
+    assert_eq!(text, "5 2026-10-17T14:03:00+02:00 A *EF8D09A1\n");
+    assert_eq!(parse_record(text.as_bytes(), &mut SoftwareCrc), Some((5, CONTENT)));
+
+    let garbled = text.replace(" A ", " V ");
+    assert_eq!(parse_record(garbled.as_bytes(), &mut SoftwareCrc), None);
+    assert_eq!(parse_record(&text.as_bytes()[..20], &mut SoftwareCrc), None);
+
+    assert!(write_record(&mut String::new(), 1, &"x".repeat(120), &mut SoftwareCrc).is_err());
+}
+
+#[test]
+fn scan_finds_last_valid_record() {
+    let mut text = records(5..8);
+    text.push_str("8 2026-10-17T14:0");
+
+    for chunk_size in [1, 7, 512] {
+        let scan = scan(&text, chunk_size);
+        assert_eq!(scan.last_sequence(), Some(7));
+        assert_eq!(scan.next_sequence(), 8);
+        assert_eq!((scan.valid_records(), scan.invalid_records()), (3, 1));
+        assert!(scan.unterminated());
+    }
+
+    // Records after a garbled one are still found
+    let text = records(0..2).replace("0 2026", "0 2027") + &records(2..3);
+    let scan = scan(&text, 512);
+    assert_eq!((scan.valid_records(), scan.invalid_records()), (2, 1));
+    assert_eq!(scan.next_sequence(), 3);
+    assert!(!scan.unterminated());
+
+    let scan = self::scan("\n\n", 512);
+    assert_eq!((scan.last_sequence(), scan.invalid_records()), (None, 0));
+}
+
+#[test]
+fn scan_reads_file_in_chunks() {
+    let device = common::image("record", CARD_BLOCKS, Some(FatType::Fat16));
+    let mut controller = Controller::new(device, FixedTime);
+
+    let text = records(0..100);
+    write_to_volume(&mut controller, "LOGS/LOG001.TXT", text.as_bytes(), Mode::ReadWriteCreateOrAppend)
+        .unwrap();
+
+    let mut scan = RecoveryScan::new();
+    let size = read_chunks_from_volume(
+        &mut controller, "LOGS/LOG001.TXT", &mut |chunk| scan.feed(chunk, &mut SoftwareCrc)
+    ).unwrap();
+    scan.finish(&mut SoftwareCrc);
+
+    assert_eq!(size as usize, text.len());
+    assert_eq!((scan.last_sequence(), scan.valid_records()), (Some(99), 100));
+}
@@ -0,0 +1,222 @@
+//! Log rotation and retention, run on the host using
+//! `cargo test -p sd-logger --target x86_64-unknown-linux-gnu`
+
+mod common;
+
+use embedded_sdmmc::Controller;
+use sd_image::image::{fat_usage, FatType};
+use sd_logger::{
+    rotation::{append_record, save_index, LogRotation, Rotation, RotationPolicy},
+    session::LoggerSession,
+};
+use common::{read_to_string, FixedTime, ImageCard};
+
+/// 32 MiB card, FAT16 with 512 byte clusters
+const CARD_BLOCKS: u32 = 64*1024;
+
+/// Records of a log file taking two clusters
+const FILE_RECORDS: u32 = 4;
+const RECORD_SIZE: usize = 200;
+
+type TestController = Controller<ImageCard, FixedTime>;
+
+fn card(name: &str) -> TestController {
+    let image = common::image(&format!("rotation-{}", name), CARD_BLOCKS, Some(FatType::Fat16));
+    Controller::new(ImageCard::new(image), FixedTime)
+}
+
+fn policy(rotation: Rotation, max_files: u16, max_bytes: Option<u32>) -> RotationPolicy {
+    RotationPolicy { rotation, max_files, max_bytes, dir: None, extension: "TXT" }
+}
+
+/// Clusters taken by the log and index files after writing `files` full
+/// log files
+fn used_clusters(name: &str, policy: RotationPolicy, files: u32) -> u32 {
+    let mut controller = card(name);
+    let free_clusters = fat_usage(&controller.device().image).unwrap().free_clusters;
+
+    let mut session = LoggerSession::new(0);
+    let mut rotation = LogRotation::new(policy);
+    let mut record = [b'x'; RECORD_SIZE];
+    record[RECORD_SIZE - 1] = b'\n';
+
+    for _ in 0..files*FILE_RECORDS {
+        append_record(&mut rotation, &mut session, &mut controller, &record, None, 0).unwrap();
+    }
+
+    save_index(&mut rotation, &mut session, &mut controller).unwrap();
+    session.close(&mut controller).unwrap();
+
+    // Emptied files are truncated in all FAT copies
+    let usage = fat_usage(&controller.device().image).unwrap();
+    assert!(usage.copies_match);
+
+    free_clusters - usage.free_clusters
+}
+
+/// Names of files emptied before the next record
+fn empty_pending(rotation: &mut LogRotation) -> Vec<String> {
+    let mut files = Vec::new();
+
+    while let Some(file_name) = rotation.pending_file() {
+        files.push(file_name.to_string());
+        rotation.file_emptied();
+    }
+
+    files
+}
+
+#[test]
+fn daily_rotation_starts_file_on_day_change() {
+    let mut rotation = LogRotation::new(policy(Rotation::Daily, 3, None));
+
+    assert!(rotation.prepare(Some(20261017), 10));
+    assert_eq!(empty_pending(&mut rotation), ["LOG001.TXT"]);
+    assert!(!rotation.prepare(Some(20261017), 10));
+
+    // Unknown date keeps the active file
+    assert!(!rotation.prepare(None, 10));
+    assert!(rotation.prepare(Some(20261018), 10));
+    assert_eq!(rotation.active_file().as_str(), "LOG002.TXT");
+}
+
+#[test]
+fn size_and_record_rotation() {
+    let mut by_size = LogRotation::new(policy(Rotation::Bytes(25), 10, None));
+    let started: Vec<bool> = (0..5).map(|_| by_size.prepare(None, 10)).collect();
+    assert_eq!(started, [true, false, true, false, true]);
+
+    let mut by_records = LogRotation::new(policy(Rotation::Records(3), 10, None));
+    let started: Vec<bool> = (0..5).map(|_| by_records.prepare(None, 10)).collect();
+    assert_eq!(started, [true, false, false, true, false]);
+}
+
+#[test]
+fn oldest_files_are_emptied_over_file_count() {
+    let mut rotation = LogRotation::new(policy(Rotation::Records(1), 3, None));
+
+    for _ in 0..3 {
+        rotation.prepare(None, 10);
+        empty_pending(&mut rotation);
+    }
+
+    assert_eq!(rotation.retained_files(), 3);
+
+    // Ring position of the fourth file is the one of the first file
+    rotation.prepare(None, 10);
+    assert_eq!(empty_pending(&mut rotation), ["LOG001.TXT", "LOG001.TXT"]);
+    assert_eq!(rotation.active_file().as_str(), "LOG001.TXT");
+    assert_eq!(rotation.retained_files(), 3);
+}
+
+#[test]
+fn oldest_files_are_emptied_over_bytes() {
+    let mut rotation = LogRotation::new(policy(Rotation::Records(2), 10, Some(50)));
+
+    for _ in 0..5 {
+        rotation.prepare(None, 10);
+        empty_pending(&mut rotation);
+    }
+
+    assert_eq!(rotation.retained_bytes(), 50);
+
+    rotation.prepare(None, 10);
+    assert_eq!(empty_pending(&mut rotation), ["LOG001.TXT"]);
+    assert_eq!(rotation.retained_bytes(), 40);
+
+    // Active file is kept even when over the limit
+    rotation.prepare(None, 100);
+    assert_eq!(empty_pending(&mut rotation), ["LOG002.TXT", "LOG003.TXT", "LOG004.TXT"]);
+    assert_eq!(rotation.retained_files(), 1);
+}
+
+#[test]
+fn index_restores_state() {
+    let policy = policy(Rotation::Daily, 5, Some(1000));
+    let mut rotation = LogRotation::new(policy);
+
+    for day in [1, 1, 2, 3, 3, 3] {
+        rotation.prepare(Some(day), 10);
+        empty_pending(&mut rotation);
+    }
+
+    let mut index = String::new();
+    rotation.write_index(&mut index).unwrap();
+
+    let mut restored = LogRotation::new(policy);
+    assert!(restored.restore(index.as_bytes()));
+    assert_eq!(restored.active_file(), rotation.active_file());
+    assert_eq!(restored.retained_bytes(), 60);
+    assert!(!restored.prepare(Some(3), 10));
+
+    let mut other = LogRotation::new(RotationPolicy { max_files: 6, ..policy });
+    assert!(!other.restore(index.as_bytes()));
+    assert!(!other.restore(b"sequence=1\r\n"));
+}
+
+#[test]
+fn records_are_written_to_rotated_files() {
+    let mut controller = card("files");
+    let mut session = LoggerSession::new(0);
+    let mut rotation = LogRotation::new(policy(Rotation::Records(2), 2, None));
+
+    for record in [b"a\n", b"b\n", b"c\n", b"d\n", b"e\n"] {
+        append_record(&mut rotation, &mut session, &mut controller, record, None, 0).unwrap();
+    }
+
+    save_index(&mut rotation, &mut session, &mut controller).unwrap();
+    session.close(&mut controller).unwrap();
+
+    // Third file reuses the ring position of the first one
+    assert_eq!(read_to_string(&mut controller, "LOG000.TXT"), "c\nd\n");
+    assert_eq!(read_to_string(&mut controller, "LOG001.TXT"), "e\n");
+    assert!(read_to_string(&mut controller, "INDEX.TXT").starts_with("active=LOG001.TXT\r\n"));
+    assert!(!rotation.index_outdated());
+}
+
+#[test]
+fn records_are_written_to_directory() {
+    let mut controller = card("dir");
+    let mut session = LoggerSession::new(0);
+
+    let mut rotation = LogRotation::new(RotationPolicy {
+        dir: Some("LOGS"),
+        ..policy(Rotation::Records(2), 2, None)
+    });
+
+    for record in [b"a\n", b"b\n", b"c\n"] {
+        append_record(&mut rotation, &mut session, &mut controller, record, None, 0).unwrap();
+    }
+
+    save_index(&mut rotation, &mut session, &mut controller).unwrap();
+    session.close(&mut controller).unwrap();
+
+    assert_eq!(rotation.index_file().as_str(), "LOGS/INDEX.TXT");
+    assert_eq!(read_to_string(&mut controller, "LOGS/LOG001.TXT"), "a\nb\n");
+    assert_eq!(read_to_string(&mut controller, "LOGS/LOG000.TXT"), "c\n");
+    assert!(read_to_string(&mut controller, "LOGS/INDEX.TXT").starts_with("active=LOGS/LOG000.TXT\r\n"));
+}
+
+#[test]
+fn file_count_limit_frees_clusters() {
+    let policy = policy(Rotation::Records(FILE_RECORDS), 4, None);
+
+    // Truncated files of the ring are written again, 100 files take
+    // the clusters of the last four and of the index
+    let used = used_clusters("count-8", policy, 8);
+    assert_eq!(used_clusters("count-100", policy, 100), used);
+    assert!(used <= 4*2 + 1);
+}
+
+#[test]
+fn byte_limit_frees_clusters() {
+    let retained_bytes = 2*FILE_RECORDS*RECORD_SIZE as u32;
+    let by_bytes = policy(Rotation::Records(FILE_RECORDS), 10, Some(retained_bytes));
+    let by_count = policy(Rotation::Records(FILE_RECORDS), 10, None);
+
+    // Expired files keep at most their first cluster
+    let used = used_clusters("bytes-20", by_bytes, 20);
+    assert_eq!(used_clusters("bytes-100", by_bytes, 100), used);
+    assert!(used < used_clusters("bytes-count", by_count, 20));
+    assert!(used <= 2*2 + 8 + 1);
+}
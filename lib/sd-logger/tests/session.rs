@@ -0,0 +1,105 @@
+//! Logger session on a disk image, run on the host using
+//! `cargo test -p sd-logger --target x86_64-unknown-linux-gnu`
+
+mod common;
+
+use embedded_sdmmc::Controller;
+use sd_image::image::FatType;
+use sd_logger::session::{LoggerSession, BUFFER_SIZE};
+use common::{read_to_string, FixedTime, ImageCard};
+
+const CARD_BLOCKS: u32 = 64*1024;
+const MAX_AGE_S: u32 = 60;
+
+type TestController = Controller<ImageCard, FixedTime>;
+
+fn card(name: &str) -> TestController {
+    let image = common::image(&format!("session-{}", name), CARD_BLOCKS, Some(FatType::Fat16));
+    Controller::new(ImageCard::new(image), FixedTime)
+}
+
+#[test]
+fn records_are_buffered_until_closed() {
+    let mut controller = card("buffered");
+    let mut session = LoggerSession::new(MAX_AGE_S);
+
+    for second in 0..3 {
+        session.append(&mut controller, "LOG.TXT", "record\n", second).unwrap();
+    }
+
+    assert_eq!(session.buffered(), 21);
+    assert_eq!(controller.device().initializations, 0);
+
+    session.close(&mut controller).unwrap();
+    assert_eq!(read_to_string(&mut controller, "LOG.TXT"), "record\n".repeat(3));
+}
+
+#[test]
+fn old_records_are_written() {
+    let mut controller = card("age");
+    let mut session = LoggerSession::new(MAX_AGE_S);
+
+    session.append(&mut controller, "LOG.TXT", "first\n", 100).unwrap();
+    session.append(&mut controller, "LOG.TXT", "second\n", 130).unwrap();
+    session.poll(&mut controller, 159).unwrap();
+    assert_eq!(session.buffered(), 13);
+
+    session.poll(&mut controller, 160).unwrap();
+    assert_eq!(session.buffered(), 0);
+    assert!(session.is_mounted());
+}
+
+#[test]
+fn full_buffer_is_written_without_splitting_records() {
+    let mut controller = card("full");
+    let mut session = LoggerSession::new(MAX_AGE_S);
+    let record = "0123456789abcdefghijklmnopqrstuvwxyz\n";
+
+    for _ in 0..20 {
+        session.append(&mut controller, "LOG.TXT", record, 0).unwrap();
+    }
+
+    // 13 records fit the buffer, the 14th starts a new one
+    assert_eq!(session.buffered(), 7*record.len());
+    assert_eq!(BUFFER_SIZE/record.len(), 13);
+
+    session.close(&mut controller).unwrap();
+    assert_eq!(read_to_string(&mut controller, "LOG.TXT"), record.repeat(20));
+    assert_eq!(controller.device().initializations, 1);
+}
+
+#[test]
+fn records_go_to_their_files() {
+    let mut controller = card("files");
+    let mut session = LoggerSession::new(MAX_AGE_S);
+
+    session.append(&mut controller, "20261017.LOG", "before midnight\n", 0).unwrap();
+    session.append(&mut controller, "20261018.LOG", "after midnight\n", 1).unwrap();
+    session.replace_file(&mut controller, "LASTTIME.TXT", "last_unix=1\r\n").unwrap();
+    session.close(&mut controller).unwrap();
+
+    assert_eq!(read_to_string(&mut controller, "20261017.LOG"), "before midnight\n");
+    assert_eq!(read_to_string(&mut controller, "20261018.LOG"), "after midnight\n");
+    assert_eq!(read_to_string(&mut controller, "LASTTIME.TXT"), "last_unix=1\r\n");
+}
+
+#[test]
+fn card_is_mounted_again_after_errors() {
+    let mut controller = card("remount");
+    let mut session = LoggerSession::new(0);
+
+    session.append(&mut controller, "LOG.TXT", "first\n", 0).unwrap();
+    assert_eq!(controller.device().initializations, 1);
+
+    controller.device().set_removed(true);
+    assert!(session.append(&mut controller, "LOG.TXT", "second\n", 1).is_err());
+    assert!(!session.is_mounted());
+    assert!(session.poll(&mut controller, 2).is_err());
+
+    controller.device().set_removed(false);
+    session.append(&mut controller, "LOG.TXT", "third\n", 3).unwrap();
+    assert_eq!(controller.device().initializations, 2);
+
+    session.close(&mut controller).unwrap();
+    assert_eq!(read_to_string(&mut controller, "LOG.TXT"), "first\nsecond\nthird\n");
+}
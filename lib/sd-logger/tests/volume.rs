@@ -0,0 +1,122 @@
+//! Logger operations on disk images, run on the host using
+//! `cargo test -p sd-logger --target x86_64-unknown-linux-gnu`
+
+mod common;
+
+use embedded_sdmmc::{Controller, Error, Mode};
+use sd_image::{file::FileBlockDevice, image::{fat_usage, set_read_only, FatType}};
+use sd_logger::{read_from_volume, write_to_volume, SdWriteError};
+use common::{append, read_to_string, FixedTime};
+
+/// 32 MiB card, large enough for FAT16 with 512 byte clusters
+const SMALL_CARD_BLOCKS: u32 = 64*1024;
+/// 64 MiB card, the smallest FAT32 volume has 65525 clusters
+const FAT32_CARD_BLOCKS: u32 = 128*1024;
+/// Smallest FAT16 volume, about 2 MiB of data
+const TINY_CARD_BLOCKS: u32 = 2048 + 4200;
+
+type TestController = Controller<FileBlockDevice, FixedTime>;
+
+fn image(name: &str, blocks: u32, fat_type: Option<FatType>) -> TestController {
+    Controller::new(common::image(&format!("logger-{}", name), blocks, fat_type), FixedTime)
+}
+
+#[test]
+fn append_creates_and_extends_file() {
+    for (name, blocks, fat_type) in [
+        ("append16", SMALL_CARD_BLOCKS, FatType::Fat16),
+        ("append32", FAT32_CARD_BLOCKS, FatType::Fat32),
+    ] {
+        let mut controller = image(name, blocks, Some(fat_type));
+
+        append(&mut controller, "20261018.LOG", "first\n");
+        append(&mut controller, "20261018.LOG", "second\n");
+
+        assert_eq!(read_to_string(&mut controller, "20261018.LOG"), "first\nsecond\n");
+    }
+}
+
+#[test]
+fn truncate_replaces_content() {
+    let mut controller = image("truncate", SMALL_CARD_BLOCKS, Some(FatType::Fat16));
+
+    append(&mut controller, "LASTTIME.TXT", "last_unix=1\r\n");
+    write_to_volume(
+        &mut controller, "LASTTIME.TXT", b"last_unix=2\r\n", Mode::ReadWriteCreateOrTruncate
+    ).unwrap();
+
+    assert_eq!(read_to_string(&mut controller, "LASTTIME.TXT"), "last_unix=2\r\n");
+}
+
+#[test]
+fn missing_file_is_reported() {
+    let mut controller = image("missing", SMALL_CARD_BLOCKS, Some(FatType::Fat16));
+    let mut buffer = [0u8; 16];
+
+    assert!(matches!(
+        read_from_volume(&mut controller, "RTCCAL.TXT", &mut buffer),
+        Err(SdWriteError::CannotOpenFile(Error::FileNotFound))
+    ));
+}
+
+#[test]
+fn unformatted_card_has_no_volume() {
+    let mut controller = image("unformatted", SMALL_CARD_BLOCKS, None);
+
+    assert!(matches!(
+        write_to_volume(&mut controller, "LOG.TXT", b"data", Mode::ReadWriteCreateOrAppend),
+        Err(SdWriteError::NoSuitableVolume)
+    ));
+}
+
+#[test]
+fn read_only_file_is_not_written() {
+    let mut controller = image("readonly", SMALL_CARD_BLOCKS, Some(FatType::Fat16));
+
+    append(&mut controller, "LOG.TXT", "data\n");
+    set_read_only(controller.device(), "LOG.TXT").unwrap();
+
+    assert!(matches!(
+        write_to_volume(&mut controller, "LOG.TXT", b"more\n", Mode::ReadWriteCreateOrAppend),
+        Err(SdWriteError::CannotOpenFile(Error::ReadOnly))
+    ));
+
+    assert_eq!(read_to_string(&mut controller, "LOG.TXT"), "data\n");
+}
+
+#[test]
+fn full_volume_is_reported() {
+    let mut controller = image("full", TINY_CARD_BLOCKS, Some(FatType::Fat16));
+    let chunk = "x".repeat(64*1024);
+
+    let error = (0..100)
+        .find_map(|_| write_to_volume(
+            &mut controller, "LOG.TXT", chunk.as_bytes(), Mode::ReadWriteCreateOrAppend
+        ).err())
+        .unwrap();
+
+    assert!(matches!(error, SdWriteError::CannotWriteToOpenedFile(Error::NotEnoughSpace)));
+
+    // No cluster past the end of the volume is taken
+    let usage = fat_usage(controller.device()).unwrap();
+    assert_eq!(usage.free_clusters, 0);
+    assert!(usage.copies_match);
+}
+
+#[test]
+fn device_errors_are_reported() {
+    let mut controller = image("protected", SMALL_CARD_BLOCKS, Some(FatType::Fat16));
+
+    append(&mut controller, "LOG.TXT", "data\n");
+    controller.device().set_write_protected(true);
+
+    assert!(matches!(
+        write_to_volume(&mut controller, "LOG.TXT", b"more\n", Mode::ReadWriteCreateOrAppend),
+        Err(SdWriteError::CannotWriteToOpenedFile(Error::DeviceError(_)))
+    ));
+
+    assert!(matches!(
+        write_to_volume(&mut controller, "NEW.TXT", b"data\n", Mode::ReadWriteCreateOrAppend),
+        Err(SdWriteError::CannotOpenFile(Error::DeviceError(_)))
+    ));
+}
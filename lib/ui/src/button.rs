@@ -0,0 +1,66 @@
+//! Push button debouncing and short/long press detection
+
+/// Input must be stable for this time before a change is accepted
+pub const DEBOUNCE_MS: u32 = 30;
+/// Button held for at least this time produces a long press
+pub const LONG_PRESS_MS: u32 = 800;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ButtonEvent {
+    /// Button was released before the long press time elapsed
+    ShortPress,
+    /// Button has been held for the long press time, reported once
+    /// while the button is still held
+    LongPress,
+}
+
+/// Button state machine fed by periodic samples of the button input
+#[derive(Default)]
+pub struct Button {
+    /// Debounced button state
+    pressed: bool,
+    /// Raw input state and time of its last change
+    raw_pressed: bool,
+    raw_changed_ms: u32,
+    /// Time when the debounced state changed to pressed
+    pressed_ms: u32,
+    long_press_reported: bool,
+}
+
+impl Button {
+    /// Feed the current input state (`true` when the button is pressed)
+    /// sampled at `now_ms` milliseconds of a free running (wrapping) clock
+    pub fn update(&mut self, is_pressed: bool, now_ms: u32) -> Option<ButtonEvent> {
+        if is_pressed != self.raw_pressed {
+            self.raw_pressed = is_pressed;
+            self.raw_changed_ms = now_ms;
+        }
+
+        let stable = now_ms.wrapping_sub(self.raw_changed_ms) >= DEBOUNCE_MS;
+
+        if stable && self.raw_pressed != self.pressed {
+            self.pressed = self.raw_pressed;
+
+            if self.pressed {
+                self.pressed_ms = now_ms;
+                self.long_press_reported = false;
+            } else if !self.long_press_reported {
+                return Some(ButtonEvent::ShortPress);
+            }
+        }
+
+        let held_ms = now_ms.wrapping_sub(self.pressed_ms);
+
+        if self.pressed && !self.long_press_reported && held_ms >= LONG_PRESS_MS {
+            self.long_press_reported = true;
+            return Some(ButtonEvent::LongPress);
+        }
+
+        None
+    }
+
+    /// Debounced button state
+    pub fn is_pressed(&self) -> bool {
+        self.pressed
+    }
+}
@@ -0,0 +1,125 @@
+//! Hex dump pages of files read piece by piece, each line shows
+//! the offset, the bytes in hex and the same bytes as ASCII
+
+use arrayvec::{ArrayString, ArrayVec};
+use core::fmt::{self, Write};
+use crate::text::{ReadAt, MAX_LINE};
+
+/// Bytes read at once, holds a page
+const READ_SIZE: usize = 256;
+
+/// Line layout fitting `width` characters for a file of `size` bytes,
+/// the offset has as many digits as the last offset needs (at least 4)
+/// and the rest of the line is shared by the hex and ASCII columns
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct HexLayout {
+    digits: usize,
+    bytes_per_line: usize,
+}
+
+impl HexLayout {
+    pub fn new(width: usize, size: u32) -> Self {
+        let last = size.saturating_sub(1);
+        let digits = (32 - last.leading_zeros()).div_ceil(4).max(4) as usize;
+
+        // Offset and two spaces, each byte takes three characters
+        let bytes_per_line = (width.min(MAX_LINE).saturating_sub(digits + 2)/3).max(1);
+
+        Self { digits, bytes_per_line }
+    }
+
+    pub fn bytes_per_line(&self) -> usize {
+        self.bytes_per_line
+    }
+
+    /// Write the line of `bytes` read at `offset`, like
+    /// `0010 4C4F473030 LOG00`
+    pub fn write_line(&self, offset: u32, bytes: &[u8], output: &mut dyn Write) -> fmt::Result {
+        write!(output, "{:0width$X} ", offset, width = self.digits)?;
+
+        for byte in bytes {
+            write!(output, "{:02X}", byte)?;
+        }
+
+        for _ in bytes.len()..self.bytes_per_line {
+            output.write_str("  ")?;
+        }
+
+        output.write_char(' ')?;
+
+        for &byte in bytes {
+            output.write_char(if (0x20..0x7f).contains(&byte) { byte as char } else { '.' })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Hex dump of a file of `size` bytes shown `ROWS` lines at a time
+pub struct HexPager<const ROWS: usize> {
+    layout: HexLayout,
+    size: u32,
+    /// Index of the first shown line
+    top: u32,
+    lines: ArrayVec<ArrayString<MAX_LINE>, ROWS>,
+}
+
+impl<const ROWS: usize> HexPager<ROWS> {
+    pub fn new(width: usize, size: u32) -> Self {
+        Self { layout: HexLayout::new(width, size), size, top: 0, lines: ArrayVec::new() }
+    }
+
+    /// Lines laid out by `layout`
+    pub fn lines(&self) -> &[ArrayString<MAX_LINE>] {
+        &self.lines
+    }
+
+    /// Offset of the first shown line
+    pub fn top(&self) -> u32 {
+        self.top*self.layout.bytes_per_line as u32
+    }
+
+    /// Scroll by `lines` lines, the last page is full when the file fills
+    /// it, returns whether the page has to be laid out again
+    pub fn scroll(&mut self, lines: i32) -> bool {
+        let bytes_per_line = self.layout.bytes_per_line as u32;
+        let line_count = self.size.div_ceil(bytes_per_line);
+        let last_top = line_count.saturating_sub(ROWS as u32);
+
+        let top = match lines < 0 {
+            true => self.top.saturating_sub(lines.unsigned_abs()),
+            false => self.top.saturating_add(lines as u32).min(last_top),
+        };
+
+        let scrolled = top != self.top;
+        self.top = top;
+        scrolled
+    }
+
+    /// Lay out the lines of the page, `read` fills the buffer from
+    /// the offset
+    pub fn layout<E>(
+        &mut self,
+        read: &mut ReadAt<'_, E>,
+    ) -> Result<(), E> {
+        let bytes_per_line = self.layout.bytes_per_line;
+        let mut buffer = [0u8; READ_SIZE];
+        let page_size = (ROWS*bytes_per_line).min(READ_SIZE);
+        let start = self.top();
+        let length = read(start, &mut buffer[..page_size])?;
+
+        self.lines.clear();
+
+        for (index, bytes) in buffer[..length].chunks(bytes_per_line).enumerate() {
+            let mut line = ArrayString::new();
+            let offset = start + (index*bytes_per_line) as u32;
+            let _ = self.layout.write_line(offset, bytes, &mut line);
+
+            if self.lines.try_push(line).is_err() {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
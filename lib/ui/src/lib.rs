@@ -0,0 +1,7 @@
+//! User interface building blocks shared by the demo applications
+#![no_std]
+
+pub mod button;
+pub mod hex;
+pub mod list;
+pub mod text;
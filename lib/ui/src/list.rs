@@ -0,0 +1,161 @@
+//! Scrollable list of text rows with a selected row
+
+use core::ops::Range;
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{PrimitiveStyle, Rectangle},
+    text::{Baseline, Text},
+};
+
+/// Width of the scroll bar drawn when the list does not fit
+pub const SCROLL_BAR_WIDTH: u32 = 3;
+
+/// Thumb of the scroll bar is never shorter than this
+const MIN_THUMB_HEIGHT: u32 = 3;
+
+/// Selection and scroll position of a list of `len` items showing `rows`
+/// items at a time, the items themselves are kept by the caller
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ListView {
+    len: usize,
+    rows: usize,
+    selected: usize,
+    top: usize,
+}
+
+impl ListView {
+    pub fn new(rows: usize) -> Self {
+        Self { len: 0, rows: rows.max(1), selected: 0, top: 0 }
+    }
+
+    /// Set the number of items, the selection is moved to the last item
+    /// when the list got shorter
+    pub fn set_len(&mut self, len: usize) {
+        self.len = len;
+        self.select(self.selected);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Index of the selected item, 0 for an empty list
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// Index of the first visible item
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    /// Indexes of the visible items
+    pub fn visible(&self) -> Range<usize> {
+        self.top..(self.top + self.rows).min(self.len)
+    }
+
+    /// Select the item at `index` (or the last one), the list scrolls
+    /// to keep the selected item visible
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.len.saturating_sub(1));
+
+        if self.selected < self.top {
+            self.top = self.selected;
+        } else if self.selected >= self.top + self.rows {
+            self.top = self.selected + 1 - self.rows;
+        }
+
+        // Scrolled list does not end by empty rows
+        self.top = self.top.min(self.len.saturating_sub(self.rows));
+    }
+
+    /// Move the selection by `delta` items stopping at the first and
+    /// the last item, returns whether the selection changed
+    pub fn move_by(&mut self, delta: isize) -> bool {
+        let previous = self.selected;
+
+        match delta < 0 {
+            true => self.select(self.selected.saturating_sub(delta.unsigned_abs())),
+            false => self.select(self.selected.saturating_add(delta as usize)),
+        }
+
+        self.selected != previous
+    }
+}
+
+/// Draw the visible rows of the `list` into the `area` of a cleared
+/// target, `rows` holds the texts of the visible items
+/// (`ListView::visible`), the selected row is inverted and rows are cut
+/// at the right edge
+pub fn draw_list<D, S>(
+    target: &mut D,
+    area: &Rectangle,
+    font: &MonoFont,
+    list: &ListView,
+    rows: &[S],
+) -> Result<(), D::Error>
+where D: DrawTarget<Color = BinaryColor>, S: AsRef<str> {
+    let row_height = font.character_size.height;
+    let scrolled = list.len() > list.rows();
+
+    let text_width = match scrolled {
+        true => area.size.width.saturating_sub(SCROLL_BAR_WIDTH + 1),
+        false => area.size.width,
+    };
+
+    for (row, (index, text)) in list.visible().zip(rows).enumerate() {
+        let top_left = area.top_left + Point::new(0, (row as u32*row_height) as i32);
+        let row_area = Rectangle::new(top_left, Size::new(text_width, row_height));
+
+        let color = match index == list.selected() {
+            true => {
+                row_area.into_styled(PrimitiveStyle::with_fill(BinaryColor::On)).draw(target)?;
+                BinaryColor::Off
+            },
+            false => BinaryColor::On,
+        };
+
+        let style = MonoTextStyle::new(font, color);
+        Text::with_baseline(text.as_ref(), top_left, style, Baseline::Top)
+            .draw(&mut target.clipped(&row_area))?;
+    }
+
+    if scrolled {
+        draw_scroll_bar(target, area, list)?;
+    }
+
+    Ok(())
+}
+
+/// Thumb at the right edge of the `area` showing the visible part
+fn draw_scroll_bar<D>(
+    target: &mut D,
+    area: &Rectangle,
+    list: &ListView,
+) -> Result<(), D::Error>
+where D: DrawTarget<Color = BinaryColor> {
+    let height = area.size.height;
+    let thumb_height = (height as usize*list.rows()/list.len()) as u32;
+    let thumb_height = thumb_height.clamp(MIN_THUMB_HEIGHT.min(height), height);
+    let scroll_range = list.len() - list.rows();
+    let thumb_top = ((height - thumb_height) as usize*list.top()/scroll_range) as i32;
+
+    let x = area.top_left.x + area.size.width.saturating_sub(SCROLL_BAR_WIDTH) as i32;
+
+    Rectangle::new(
+        Point::new(x, area.top_left.y + thumb_top),
+        Size::new(SCROLL_BAR_WIDTH, thumb_height)
+    )
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target)
+}
@@ -0,0 +1,321 @@
+//! Text pages of files read piece by piece, lines are word wrapped and
+//! characters missing in the ASCII display fonts are replaced
+
+use arrayvec::{ArrayString, ArrayVec};
+use embedded_graphics::{
+    mono_font::{MonoFont, MonoTextStyle},
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::Rectangle,
+    text::{Baseline, Text},
+};
+
+/// Longest line laid out by `wrap_line`
+pub const MAX_LINE: usize = 32;
+
+/// Shown in place of a character outside of printable ASCII or a byte
+/// which is not valid UTF-8
+pub const REPLACEMENT: char = '?';
+
+/// Reads the file from the offset into the buffer, returns the number
+/// of bytes read
+pub type ReadAt<'a, E> = dyn FnMut(u32, &mut [u8]) -> Result<usize, E> + 'a;
+
+/// Bytes read at once, holds the longest line
+const READ_SIZE: usize = 256;
+
+/// Offsets of lines before the top line kept for scrolling back
+const HISTORY: usize = 64;
+
+/// Bytes a line of `width` characters may take including its line end,
+/// a UTF-8 character takes up to 4 bytes
+pub const fn line_bytes(width: usize) -> usize {
+    width*4 + 2
+}
+
+/// Lay out the line at the start of `data` in at most `width` characters,
+/// returns the line and the number of bytes it takes including the line
+/// end
+///
+/// A line is broken after the last space fitting in, a word longer than
+/// the line is broken anywhere. `data` has to hold `line_bytes(width)`
+/// bytes unless it reaches the end of the file.
+pub fn wrap_line(data: &[u8], width: usize) -> (ArrayString<MAX_LINE>, usize) {
+    let width = width.clamp(1, MAX_LINE);
+    let mut line = ArrayString::new();
+    let mut position = 0;
+    // Line length before and data position after the last space
+    let mut last_space: Option<(usize, usize)> = None;
+
+    while position < data.len() {
+        let (character, length) = decode_char(&data[position..]);
+
+        match character {
+            '\n' => return (line, position + length),
+            '\r' => {
+                position += length;
+                continue;
+            },
+            _ => {},
+        }
+
+        if line.len() == width {
+            return match (character, last_space) {
+                // Space at the line end is left out
+                (' ', _) => (line, position + length),
+                (_, Some((line_length, space_end))) => {
+                    line.truncate(line_length);
+                    (line, space_end)
+                },
+                (_, None) => (line, position),
+            };
+        }
+
+        if character == ' ' && !line.is_empty() {
+            last_space = Some((line.len(), position + length));
+        }
+
+        line.push(character);
+        position += length;
+    }
+
+    (line, position)
+}
+
+/// First character of `data` as shown on the display and its length
+/// in bytes, tabs are shown as spaces
+fn decode_char(data: &[u8]) -> (char, usize) {
+    let length = match data[0] {
+        0x00..=0x7f => 1,
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => return (REPLACEMENT, 1),
+    };
+
+    let character = data.get(..length)
+        .and_then(|bytes| core::str::from_utf8(bytes).ok())
+        .and_then(|text| text.chars().next());
+
+    match character {
+        Some('\t') => (' ', 1),
+        Some(character @ (' '..='~' | '\n' | '\r')) => (character, 1),
+        Some(_) => (REPLACEMENT, length),
+        // Invalid or cut sequence, only its first byte is skipped
+        None => (REPLACEMENT, 1),
+    }
+}
+
+/// Text of a file of `size` bytes shown `ROWS` lines of `width` characters
+/// at a time, lines are laid out from the top line offset so only the shown
+/// page is read
+pub struct TextPager<const ROWS: usize> {
+    width: usize,
+    size: u32,
+    /// Offset of the first shown line
+    top: u32,
+    /// Offsets of the shown lines and of the line after the page
+    offsets: ArrayVec<u32, ROWS>,
+    next_page: u32,
+    /// Offsets of the lines before the top line, the oldest ones are
+    /// dropped and found again by reading the file from its start
+    history: ArrayVec<u32, HISTORY>,
+    lines: ArrayVec<ArrayString<MAX_LINE>, ROWS>,
+}
+
+impl<const ROWS: usize> TextPager<ROWS> {
+    pub fn new(width: usize, size: u32) -> Self {
+        Self {
+            width: width.clamp(1, MAX_LINE),
+            size,
+            top: 0,
+            offsets: ArrayVec::new(),
+            next_page: 0,
+            history: ArrayVec::new(),
+            lines: ArrayVec::new(),
+        }
+    }
+
+    /// Lines laid out by `layout`
+    pub fn lines(&self) -> &[ArrayString<MAX_LINE>] {
+        &self.lines
+    }
+
+    /// Offset of the first shown line
+    pub fn top(&self) -> u32 {
+        self.top
+    }
+
+    /// Page shows the end of the file
+    pub fn at_end(&self) -> bool {
+        self.next_page >= self.size
+    }
+
+    /// Scroll forward by a line or a page (`page` set) within the laid out
+    /// page, returns whether the page has to be laid out again
+    pub fn scroll_down(&mut self, page: bool) -> bool {
+        if self.at_end() {
+            return false;
+        }
+
+        match page {
+            true => {
+                for index in 0..self.offsets.len() {
+                    self.remember(self.offsets[index]);
+                }
+
+                self.top = self.next_page;
+            },
+            false => {
+                self.remember(self.top);
+                self.top = self.offsets.get(1).copied().unwrap_or(self.next_page);
+            },
+        }
+
+        true
+    }
+
+    /// Scroll back by a line or a page (`page` set), `read` fills
+    /// the buffer from the offset when older lines have to be found again,
+    /// returns whether the page has to be laid out again
+    pub fn scroll_up<E>(
+        &mut self,
+        page: bool,
+        read: &mut ReadAt<'_, E>,
+    ) -> Result<bool, E> {
+        let lines = if page { ROWS } else { 1 };
+        let top = self.top;
+
+        for _ in 0..lines {
+            if self.top == 0 {
+                break;
+            }
+
+            if self.history.is_empty() {
+                self.find_history(read)?;
+            }
+
+            self.top = self.history.pop().unwrap_or(0);
+        }
+
+        Ok(self.top != top)
+    }
+
+    /// Lay out the lines of the page starting at the top line
+    pub fn layout<E>(
+        &mut self,
+        read: &mut ReadAt<'_, E>,
+    ) -> Result<(), E> {
+        let lines = &mut self.lines;
+        let offsets = &mut self.offsets;
+        lines.clear();
+        offsets.clear();
+
+        self.next_page = layout_lines(self.top, self.size, self.width, read, &mut |offset, line| {
+            if lines.is_full() {
+                return false;
+            }
+
+            lines.push(line);
+            offsets.push(offset);
+            true
+        })?;
+
+        Ok(())
+    }
+
+    fn remember(&mut self, offset: u32) {
+        if self.history.is_full() {
+            self.history.remove(0);
+        }
+
+        self.history.push(offset);
+    }
+
+    /// Lay out the file from its start to find the lines before the top one
+    fn find_history<E>(
+        &mut self,
+        read: &mut ReadAt<'_, E>,
+    ) -> Result<(), E> {
+        let top = self.top;
+        let mut history = ArrayVec::<u32, HISTORY>::new();
+
+        layout_lines(0, self.size, self.width, read, &mut |offset, _| {
+            if offset >= top {
+                return false;
+            }
+
+            if history.is_full() {
+                history.remove(0);
+            }
+
+            history.push(offset);
+            true
+        })?;
+
+        self.history = history;
+        Ok(())
+    }
+}
+
+/// Lay out lines from the `start` offset passing each line with its offset
+/// to `line` until it returns `false` or the file ends, returns the offset
+/// of the first line not taken
+fn layout_lines<E>(
+    start: u32,
+    size: u32,
+    width: usize,
+    read: &mut ReadAt<'_, E>,
+    line: &mut dyn FnMut(u32, ArrayString<MAX_LINE>) -> bool,
+) -> Result<u32, E> {
+    let mut buffer = [0u8; READ_SIZE];
+    let mut buffer_start = start;
+    let mut filled = 0;
+    let mut offset = start;
+
+    while offset < size {
+        let mut position = (offset - buffer_start) as usize;
+
+        // Line may continue after the buffer end
+        if filled - position < line_bytes(width) && buffer_start + (filled as u32) < size {
+            buffer_start = offset;
+            filled = read(offset, &mut buffer)?;
+            position = 0;
+
+            if filled == 0 {
+                break;
+            }
+        }
+
+        let (text, length) = wrap_line(&buffer[position..filled], width);
+
+        if !line(offset, text) {
+            break;
+        }
+
+        offset += length as u32;
+    }
+
+    Ok(offset)
+}
+
+/// Draw the `lines` from the top of the `area` of a cleared target,
+/// lines are cut at the area edges
+pub fn draw_lines<D, S>(
+    target: &mut D,
+    area: &Rectangle,
+    font: &MonoFont,
+    lines: &[S],
+) -> Result<(), D::Error>
+where D: DrawTarget<Color = BinaryColor>, S: AsRef<str> {
+    let style = MonoTextStyle::new(font, BinaryColor::On);
+    let line_height = font.character_size.height as i32;
+    let mut clipped = target.clipped(area);
+
+    for (row, line) in lines.iter().enumerate() {
+        let position = area.top_left + Point::new(0, row as i32*line_height);
+        Text::with_baseline(line.as_ref(), position, style, Baseline::Top).draw(&mut clipped)?;
+    }
+
+    Ok(())
+}
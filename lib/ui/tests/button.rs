@@ -0,0 +1,134 @@
+//! Button debouncing and short/long press detection, run on the host using
+//! `cargo test -p ui --target x86_64-unknown-linux-gnu`
+
+use ui::button::{Button, ButtonEvent, DEBOUNCE_MS, LONG_PRESS_MS};
+
+/// Sample the input every 5 ms from `start_ms` for `duration_ms`, returns
+/// the events with their times
+fn hold(
+    button: &mut Button,
+    is_pressed: bool,
+    start_ms: u32,
+    duration_ms: u32,
+) -> Vec<(ButtonEvent, u32)> {
+    (0..duration_ms).step_by(5)
+        .map(|elapsed| start_ms.wrapping_add(elapsed))
+        .filter_map(|now_ms| button.update(is_pressed, now_ms).map(|event| (event, now_ms)))
+        .collect()
+}
+
+fn events(events: &[(ButtonEvent, u32)]) -> Vec<ButtonEvent> {
+    events.iter().map(|&(event, _)| event).collect()
+}
+
+#[test]
+fn released_button_reports_nothing() {
+    let mut button = Button::default();
+
+    assert!(hold(&mut button, false, 0, 2000).is_empty());
+    assert!(!button.is_pressed());
+}
+
+#[test]
+fn press_is_accepted_after_the_debounce_time() {
+    let mut button = Button::default();
+
+    assert_eq!(button.update(true, 1000), None);
+    assert_eq!(button.update(true, 1000 + DEBOUNCE_MS - 1), None);
+    assert!(!button.is_pressed());
+
+    assert_eq!(button.update(true, 1000 + DEBOUNCE_MS), None);
+    assert!(button.is_pressed());
+}
+
+#[test]
+fn bouncing_input_is_ignored() {
+    let mut button = Button::default();
+
+    // Input changing faster than the debounce time never settles
+    for step in 0..20 {
+        assert_eq!(button.update(step % 2 == 0, 1000 + step*10), None);
+        assert!(!button.is_pressed());
+    }
+}
+
+#[test]
+fn short_press_is_reported_on_release() {
+    let mut button = Button::default();
+
+    assert!(hold(&mut button, true, 1000, 300).is_empty());
+    assert!(button.is_pressed());
+
+    let released = hold(&mut button, false, 1300, 100);
+    assert_eq!(released, [(ButtonEvent::ShortPress, 1300 + DEBOUNCE_MS)]);
+    assert!(!button.is_pressed());
+}
+
+#[test]
+fn bounce_while_held_is_not_a_release() {
+    let mut button = Button::default();
+
+    hold(&mut button, true, 1000, 200);
+    assert!(hold(&mut button, false, 1200, DEBOUNCE_MS - 10).is_empty());
+    assert!(hold(&mut button, true, 1220, 100).is_empty());
+    assert!(button.is_pressed());
+
+    assert_eq!(events(&hold(&mut button, false, 1320, 100)), [ButtonEvent::ShortPress]);
+}
+
+#[test]
+fn long_press_is_reported_once_while_held() {
+    let mut button = Button::default();
+
+    let held = hold(&mut button, true, 1000, 3000);
+    let pressed_ms = 1000 + DEBOUNCE_MS;
+    assert_eq!(held, [(ButtonEvent::LongPress, pressed_ms + LONG_PRESS_MS)]);
+    assert!(button.is_pressed());
+}
+
+#[test]
+fn release_after_long_press_is_not_a_short_press() {
+    let mut button = Button::default();
+
+    hold(&mut button, true, 1000, 1000);
+    assert!(hold(&mut button, false, 2000, 500).is_empty());
+    assert!(!button.is_pressed());
+
+    // Next press starts over
+    assert_eq!(events(&hold(&mut button, true, 2500, 200)), []);
+    assert_eq!(events(&hold(&mut button, false, 2700, 100)), [ButtonEvent::ShortPress]);
+    assert_eq!(events(&hold(&mut button, true, 2800, 1000)), [ButtonEvent::LongPress]);
+}
+
+#[test]
+fn press_shorter_than_the_long_press_time_is_short() {
+    let mut button = Button::default();
+
+    // Release is accepted after the debounce time, just before the long
+    // press time is reached
+    let release_ms = 1000 + LONG_PRESS_MS - 5;
+    assert!(hold(&mut button, true, 1000, release_ms - 1000).is_empty());
+    assert_eq!(events(&hold(&mut button, false, release_ms, 100)), [ButtonEvent::ShortPress]);
+}
+
+#[test]
+fn long_press_while_the_clock_wraps() {
+    let mut button = Button::default();
+    let start_ms = u32::MAX - 400;
+
+    hold(&mut button, false, start_ms - 100, 100);
+    let held = hold(&mut button, true, start_ms, 2000);
+    let long_press_ms = start_ms.wrapping_add(DEBOUNCE_MS + LONG_PRESS_MS);
+    assert_eq!(held, [(ButtonEvent::LongPress, long_press_ms)]);
+}
+
+#[test]
+fn short_press_while_the_clock_wraps() {
+    let mut button = Button::default();
+    let start_ms = u32::MAX - 100;
+
+    hold(&mut button, false, start_ms - 100, 100);
+    assert!(hold(&mut button, true, start_ms, 200).is_empty());
+    assert!(button.is_pressed());
+    assert_eq!(events(&hold(&mut button, false, 100, 100)), [ButtonEvent::ShortPress]);
+}
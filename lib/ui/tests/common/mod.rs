@@ -0,0 +1,13 @@
+//! File contents read by the pagers, shared by the tests
+
+use std::convert::Infallible;
+
+/// Reads `data` like a file on the card, returns fewer bytes at its end
+pub fn read_from(data: &[u8]) -> impl FnMut(u32, &mut [u8]) -> Result<usize, Infallible> + '_ {
+    move |offset, buffer| {
+        let rest = data.get(offset as usize..).unwrap_or_default();
+        let length = rest.len().min(buffer.len());
+        buffer[..length].copy_from_slice(&rest[..length]);
+        Ok(length)
+    }
+}
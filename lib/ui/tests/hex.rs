@@ -0,0 +1,134 @@
+//! Hex dump lines and pages, run on the host using
+//! `cargo test -p ui --target x86_64-unknown-linux-gnu`
+
+mod common;
+
+use common::read_from;
+use ui::hex::{HexLayout, HexPager};
+
+fn line(layout: &HexLayout, offset: u32, bytes: &[u8]) -> String {
+    let mut line = String::new();
+    layout.write_line(offset, bytes, &mut line).unwrap();
+    line
+}
+
+fn shown<const ROWS: usize>(pager: &HexPager<ROWS>) -> Vec<&str> {
+    pager.lines().iter().map(|line| line.as_str()).collect()
+}
+
+#[test]
+fn offset_digits_fit_the_last_offset() {
+    assert_eq!(line(&HexLayout::new(21, 0), 0, b"A"), "0000 41         A");
+    assert_eq!(line(&HexLayout::new(21, 0x10000), 0xfffb, b"A"), "FFFB 41         A");
+    assert_eq!(line(&HexLayout::new(21, 0x10001), 0x10000, b"A"), "10000 41       A");
+    assert_eq!(line(&HexLayout::new(32, u32::MAX), 0x10, b"A").split(' ').next(), Some("00000010"));
+}
+
+#[test]
+fn bytes_per_line_fit_the_width() {
+    assert_eq!(HexLayout::new(21, 100).bytes_per_line(), 5);
+    assert_eq!(HexLayout::new(23, 100).bytes_per_line(), 5);
+    assert_eq!(HexLayout::new(24, 100).bytes_per_line(), 6);
+    assert_eq!(HexLayout::new(21, 0x10001).bytes_per_line(), 4);
+
+    // At least a byte, at most the longest line
+    assert_eq!(HexLayout::new(4, 100).bytes_per_line(), 1);
+    assert_eq!(HexLayout::new(100, 100).bytes_per_line(), 8);
+}
+
+#[test]
+fn full_line() {
+    let layout = HexLayout::new(21, 100);
+    assert_eq!(line(&layout, 0x10, b"LOG00"), "0010 4C4F473030 LOG00");
+}
+
+#[test]
+fn partial_last_line_keeps_the_columns() {
+    let layout = HexLayout::new(21, 100);
+    assert_eq!(line(&layout, 0x5f, b"LO"), "005F 4C4F       LO");
+    assert_eq!(line(&layout, 0x5f, b"L"), "005F 4C         L");
+}
+
+#[test]
+fn bytes_outside_of_printable_ascii_are_dots() {
+    let layout = HexLayout::new(21, 100);
+    assert_eq!(line(&layout, 0, b"\r\n\x00\x7f~"), "0000 0D0A007F7E ....~");
+}
+
+#[test]
+fn page_of_a_short_file() {
+    let data = b"0123456789AB";
+    let mut read = read_from(data);
+    let mut pager = HexPager::<4>::new(21, data.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    assert_eq!(shown(&pager), [
+        "0000 3031323334 01234",
+        "0005 3536373839 56789",
+        "000A 4142       AB",
+    ]);
+
+    assert!(!pager.scroll(1));
+    assert!(!pager.scroll(-1));
+    assert_eq!(pager.top(), 0);
+}
+
+#[test]
+fn paging_to_a_partial_last_line() {
+    let data = b"0123456789ABCDEFGHIJKLM";
+    let mut read = read_from(data);
+    let mut pager = HexPager::<4>::new(21, data.len() as u32);
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager).len(), 4);
+
+    // Last page is full, it starts at the second line
+    assert!(pager.scroll(4));
+    assert_eq!(pager.top(), 5);
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager), [
+        "0005 3536373839 56789",
+        "000A 4142434445 ABCDE",
+        "000F 464748494A FGHIJ",
+        "0014 4B4C4D     KLM",
+    ]);
+
+    assert!(!pager.scroll(1));
+    assert!(!pager.scroll(i32::MAX));
+    assert_eq!(pager.top(), 5);
+}
+
+#[test]
+fn paging_back_stops_at_the_start() {
+    let data = [b'x'; 100];
+    let mut read = read_from(&data);
+    let mut pager = HexPager::<4>::new(21, data.len() as u32);
+
+    assert!(!pager.scroll(-4));
+    assert!(pager.scroll(5));
+    assert_eq!(pager.top(), 25);
+    assert!(pager.scroll(-4));
+    assert_eq!(pager.top(), 5);
+    assert!(pager.scroll(-4));
+    assert_eq!(pager.top(), 0);
+    assert!(!pager.scroll(i32::MIN));
+
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager)[0], "0000 7878787878 xxxxx");
+}
+
+#[test]
+fn partial_last_line_with_long_offsets() {
+    let data: Vec<u8> = (0..0x10003u32).map(|offset| offset as u8).collect();
+    let mut read = read_from(&data);
+    let mut pager = HexPager::<4>::new(32, data.len() as u32);
+
+    assert!(pager.scroll(i32::MAX));
+    assert_eq!(pager.top(), 0xffe8);
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager), [
+        "0FFE8 E8E9EAEBECEDEEEF ........",
+        "0FFF0 F0F1F2F3F4F5F6F7 ........",
+        "0FFF8 F8F9FAFBFCFDFEFF ........",
+        "10000 000102           ...",
+    ]);
+}
@@ -0,0 +1,154 @@
+//! List selection and scrolling, run on the host using
+//! `cargo test -p ui --target x86_64-unknown-linux-gnu`
+
+use ui::list::ListView;
+
+fn list(rows: usize, len: usize) -> ListView {
+    let mut list = ListView::new(rows);
+    list.set_len(len);
+    list
+}
+
+#[test]
+fn empty_list() {
+    let mut list = list(4, 0);
+
+    assert!(list.is_empty());
+    assert_eq!(list.selected(), 0);
+    assert_eq!(list.visible(), 0..0);
+    assert!(!list.move_by(1));
+    assert!(!list.move_by(-1));
+    assert_eq!(list.selected(), 0);
+}
+
+#[test]
+fn list_shows_at_least_a_row() {
+    let list = list(0, 5);
+
+    assert_eq!(list.rows(), 1);
+    assert_eq!(list.visible(), 0..1);
+}
+
+#[test]
+fn short_list_does_not_scroll() {
+    let mut list = list(4, 3);
+
+    assert_eq!(list.visible(), 0..3);
+
+    for index in 0..3 {
+        list.select(index);
+        assert_eq!(list.selected(), index);
+        assert_eq!(list.top(), 0);
+    }
+}
+
+#[test]
+fn selection_is_clamped_to_the_last_item() {
+    let mut list = list(4, 10);
+
+    list.select(25);
+    assert_eq!(list.selected(), 9);
+    assert_eq!(list.visible(), 6..10);
+}
+
+#[test]
+fn moving_down_scrolls_at_the_bottom_row() {
+    let mut list = list(4, 10);
+
+    for index in 1..10 {
+        assert!(list.move_by(1));
+        assert_eq!(list.selected(), index);
+        assert_eq!(list.top(), index.saturating_sub(3));
+        assert!(list.visible().contains(&index));
+    }
+}
+
+#[test]
+fn moving_up_scrolls_at_the_top_row() {
+    let mut list = list(4, 10);
+    list.select(9);
+
+    assert!(list.move_by(-1));
+    assert_eq!(list.visible(), 6..10);
+
+    for index in (0..8).rev() {
+        assert!(list.move_by(-1));
+        assert_eq!(list.selected(), index);
+        assert_eq!(list.top(), index.min(6));
+    }
+}
+
+#[test]
+fn moves_stop_at_the_first_and_last_item() {
+    let mut list = list(4, 10);
+
+    assert!(!list.move_by(-1));
+    assert!(!list.move_by(-3));
+    assert_eq!(list.selected(), 0);
+
+    assert!(list.move_by(100));
+    assert_eq!(list.selected(), 9);
+    assert!(!list.move_by(1));
+    assert!(!list.move_by(isize::MAX));
+    assert_eq!(list.visible(), 6..10);
+
+    assert!(list.move_by(isize::MIN));
+    assert_eq!(list.selected(), 0);
+    assert_eq!(list.visible(), 0..4);
+}
+
+#[test]
+fn page_moves() {
+    let mut list = list(4, 10);
+
+    assert!(list.move_by(4));
+    assert_eq!((list.selected(), list.visible()), (4, 1..5));
+    assert!(list.move_by(4));
+    assert_eq!((list.selected(), list.visible()), (8, 5..9));
+    assert!(list.move_by(4));
+    assert_eq!((list.selected(), list.visible()), (9, 6..10));
+    assert!(list.move_by(-4));
+    assert_eq!((list.selected(), list.visible()), (5, 5..9));
+}
+
+#[test]
+fn shrinking_list_moves_the_selection_to_the_last_item() {
+    let mut list = list(4, 10);
+    list.select(8);
+
+    list.set_len(6);
+    assert_eq!(list.selected(), 5);
+    assert_eq!(list.visible(), 2..6);
+
+    list.set_len(2);
+    assert_eq!(list.selected(), 1);
+    assert_eq!(list.visible(), 0..2);
+
+    list.set_len(0);
+    assert_eq!(list.selected(), 0);
+    assert_eq!(list.visible(), 0..0);
+}
+
+#[test]
+fn shrinking_list_does_not_end_by_empty_rows() {
+    let mut list = list(4, 10);
+    list.select(9);
+    list.select(5);
+    assert_eq!(list.visible(), 5..9);
+
+    // Selection still exists, the list scrolls back to fill the rows
+    list.set_len(7);
+    assert_eq!(list.selected(), 5);
+    assert_eq!(list.visible(), 3..7);
+}
+
+#[test]
+fn growing_list_keeps_the_selection() {
+    let mut list = list(4, 5);
+    list.select(4);
+    assert_eq!(list.visible(), 1..5);
+
+    list.set_len(20);
+    assert_eq!(list.selected(), 4);
+    assert_eq!(list.visible(), 1..5);
+}
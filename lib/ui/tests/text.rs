@@ -0,0 +1,309 @@
+//! Word wrapping and text pages, run on the host using
+//! `cargo test -p ui --target x86_64-unknown-linux-gnu`
+
+mod common;
+
+use common::read_from;
+use ui::text::{wrap_line, TextPager, MAX_LINE};
+
+/// Lay out the whole text, each line has to take some bytes
+fn wrap_all(text: &[u8], width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut position = 0;
+
+    while position < text.len() {
+        let (line, length) = wrap_line(&text[position..], width);
+        assert!(length > 0, "no progress at {}", position);
+        lines.push(line.to_string());
+        position += length;
+    }
+
+    lines
+}
+
+fn shown<const ROWS: usize>(pager: &TextPager<ROWS>) -> Vec<&str> {
+    pager.lines().iter().map(|line| line.as_str()).collect()
+}
+
+/// Lines `line 00` to `line <count - 1>` each taking 8 bytes
+fn numbered(count: usize) -> Vec<u8> {
+    (0..count).flat_map(|index| format!("line {:02}\n", index).into_bytes()).collect()
+}
+
+#[test]
+fn line_ends_at_line_end() {
+    assert_eq!(wrap_line(b"abc\ndef", 10), ("abc".try_into().unwrap(), 4));
+    assert_eq!(wrap_line(b"abc\r\ndef", 10), ("abc".try_into().unwrap(), 5));
+    assert_eq!(wrap_line(b"\nabc", 10), ("".try_into().unwrap(), 1));
+    assert_eq!(wrap_line(b"abc", 10), ("abc".try_into().unwrap(), 3));
+}
+
+#[test]
+fn line_filling_the_width() {
+    // Line end right after the full line belongs to it
+    assert_eq!(wrap_line(b"abcde\nf", 5), ("abcde".try_into().unwrap(), 6));
+    assert_eq!(wrap_line(b"abcde\r\nf", 5), ("abcde".try_into().unwrap(), 7));
+    assert_eq!(wrap_line(b"abcde", 5), ("abcde".try_into().unwrap(), 5));
+    assert_eq!(wrap_all(b"abcde\nfghij\n", 5), ["abcde", "fghij"]);
+}
+
+#[test]
+fn space_at_the_width_is_left_out() {
+    assert_eq!(wrap_line(b"hello world", 5), ("hello".try_into().unwrap(), 6));
+    assert_eq!(wrap_all(b"hello world", 5), ["hello", "world"]);
+}
+
+#[test]
+fn line_is_broken_after_the_last_space() {
+    assert_eq!(wrap_line(b"ab cdef", 5), ("ab".try_into().unwrap(), 3));
+    assert_eq!(wrap_all(b"the quick brown fox", 10), ["the quick", "brown fox"]);
+    assert_eq!(wrap_all(b"the quick brown fox", 9), ["the quick", "brown fox"]);
+    assert_eq!(wrap_all(b"the quick brown fox", 8), ["the", "quick", "brown", "fox"]);
+}
+
+#[test]
+fn word_longer_than_a_line_is_broken_at_the_width() {
+    assert_eq!(wrap_line(b"abcdefghij", 4), ("abcd".try_into().unwrap(), 4));
+    assert_eq!(wrap_all(b"abcdefghij", 4), ["abcd", "efgh", "ij"]);
+    assert_eq!(wrap_all(b"abcdefgh\n", 4), ["abcd", "efgh"]);
+
+    // Long word after a short one starts on its own line
+    assert_eq!(wrap_all(b"a bcdefghij", 4), ["a", "bcde", "fghi", "j"]);
+    assert_eq!(wrap_all(b"abcdef ghi", 4), ["abcd", "ef", "ghi"]);
+}
+
+#[test]
+fn leading_spaces_are_kept() {
+    assert_eq!(wrap_all(b"  ab cd", 4), ["  ab", "cd"]);
+}
+
+#[test]
+fn width_is_clamped() {
+    assert_eq!(wrap_all(b"abc", 0), ["a", "b", "c"]);
+
+    let long = [b'x'; 40];
+    let lines = wrap_all(&long, 100);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].len(), MAX_LINE);
+}
+
+#[test]
+fn characters_missing_in_the_font_are_replaced() {
+    assert_eq!(wrap_line(b"a\tb", 10), ("a b".try_into().unwrap(), 3));
+    assert_eq!(wrap_line("é€😀".as_bytes(), 10), ("???".try_into().unwrap(), 9));
+    assert_eq!(wrap_line(b"a\x00\x7f\xffb", 10), ("a???b".try_into().unwrap(), 5));
+
+    // Cut sequence at the end of the data skips a byte at a time
+    assert_eq!(wrap_line(b"a\xe2\x82", 10), ("a??".try_into().unwrap(), 3));
+}
+
+#[test]
+fn multi_byte_characters_count_as_one() {
+    let text = "ééééé ééé".as_bytes();
+    assert_eq!(wrap_all(text, 5), ["?????", "???"]);
+    assert_eq!(wrap_line(text, 5).1, 11);
+}
+
+#[test]
+fn empty_file() {
+    let mut read = read_from(b"");
+    let mut pager = TextPager::<4>::new(10, 0);
+    pager.layout(&mut read).unwrap();
+
+    assert!(pager.lines().is_empty());
+    assert!(pager.at_end());
+    assert!(!pager.scroll_down(true));
+    assert!(!pager.scroll_up(true, &mut read).unwrap());
+}
+
+#[test]
+fn file_shorter_than_a_page() {
+    let text = b"first\nsecond line wrapped";
+    let mut read = read_from(text);
+    let mut pager = TextPager::<4>::new(10, text.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    assert_eq!(shown(&pager), ["first", "second", "line", "wrapped"]);
+    assert!(pager.at_end());
+    assert!(!pager.scroll_down(false));
+}
+
+#[test]
+fn paging_forward_stops_at_the_end() {
+    let text = numbered(10);
+    let mut read = read_from(&text);
+    let mut pager = TextPager::<4>::new(10, text.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    assert_eq!(shown(&pager), ["line 00", "line 01", "line 02", "line 03"]);
+    assert!(!pager.at_end());
+
+    assert!(pager.scroll_down(true));
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager), ["line 04", "line 05", "line 06", "line 07"]);
+    assert_eq!(pager.top(), 32);
+
+    // Last page shows the rest of the file
+    assert!(pager.scroll_down(true));
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager), ["line 08", "line 09"]);
+    assert!(pager.at_end());
+
+    assert!(!pager.scroll_down(true));
+    assert!(!pager.scroll_down(false));
+    assert_eq!(pager.top(), 64);
+}
+
+#[test]
+fn page_ending_with_the_file_is_the_last_one() {
+    let text = numbered(8);
+    let mut read = read_from(&text);
+    let mut pager = TextPager::<4>::new(10, text.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    assert!(pager.scroll_down(true));
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager), ["line 04", "line 05", "line 06", "line 07"]);
+    assert!(pager.at_end());
+    assert!(!pager.scroll_down(true));
+}
+
+#[test]
+fn paging_back_stops_at_the_start() {
+    let text = numbered(10);
+    let mut read = read_from(&text);
+    let mut pager = TextPager::<4>::new(10, text.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    assert!(!pager.scroll_up(true, &mut read).unwrap());
+    assert!(!pager.scroll_up(false, &mut read).unwrap());
+
+    for _ in 0..2 {
+        pager.scroll_down(true);
+        pager.layout(&mut read).unwrap();
+    }
+
+    assert!(pager.scroll_up(true, &mut read).unwrap());
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager), ["line 04", "line 05", "line 06", "line 07"]);
+
+    assert!(pager.scroll_up(true, &mut read).unwrap());
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager), ["line 00", "line 01", "line 02", "line 03"]);
+
+    assert!(!pager.scroll_up(true, &mut read).unwrap());
+    assert_eq!(pager.top(), 0);
+}
+
+#[test]
+fn paging_back_from_near_the_start_stops_at_the_start() {
+    let text = numbered(10);
+    let mut read = read_from(&text);
+    let mut pager = TextPager::<4>::new(10, text.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    pager.scroll_down(false);
+    pager.layout(&mut read).unwrap();
+    pager.scroll_down(false);
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager)[0], "line 02");
+
+    assert!(pager.scroll_up(true, &mut read).unwrap());
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager)[0], "line 00");
+}
+
+#[test]
+fn scrolling_by_lines_keeps_the_last_page_full() {
+    let text = numbered(10);
+    let mut read = read_from(&text);
+    let mut pager = TextPager::<4>::new(10, text.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    let mut scrolls = 0;
+
+    while pager.scroll_down(false) {
+        pager.layout(&mut read).unwrap();
+        scrolls += 1;
+    }
+
+    assert_eq!(scrolls, 6);
+    assert_eq!(shown(&pager), ["line 06", "line 07", "line 08", "line 09"]);
+
+    while pager.scroll_up(false, &mut read).unwrap() {
+        pager.layout(&mut read).unwrap();
+        scrolls -= 1;
+        assert_eq!(shown(&pager)[0], format!("line {:02}", scrolls));
+    }
+
+    assert_eq!(scrolls, 0);
+}
+
+/// Page to the end of the text and scroll back a line at a time, each top
+/// line has to be the line before the previous one
+fn assert_scrolls_back(text: &[u8], width: usize) {
+    let expected = wrap_all(text, width);
+    let mut read = read_from(text);
+    let mut pager = TextPager::<4>::new(width, text.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    let mut top = 0;
+
+    while pager.scroll_down(true) {
+        pager.layout(&mut read).unwrap();
+        top += 4;
+        assert_eq!(shown(&pager), expected[top..(top + 4).min(expected.len())]);
+    }
+
+    while pager.scroll_up(false, &mut read).unwrap() {
+        pager.layout(&mut read).unwrap();
+        top -= 1;
+        assert_eq!(shown(&pager)[0], expected[top]);
+    }
+
+    assert_eq!(top, 0);
+    assert_eq!(pager.top(), 0);
+}
+
+#[test]
+fn scrolling_back_beyond_the_remembered_lines() {
+    // 64 remembered lines, before them the file is laid out again
+    assert_scrolls_back(&numbered(100), 10);
+}
+
+#[test]
+fn scrolling_back_through_wrapped_lines() {
+    let text = "alpha beta gamma delta\n".repeat(40);
+    assert_scrolls_back(text.as_bytes(), 10);
+}
+
+#[test]
+fn lines_longer_than_a_read() {
+    // Line of 300 characters does not fit in the read buffer
+    let mut text = vec![b'x'; 300];
+    text.extend_from_slice(b"\nend\n");
+    assert_scrolls_back(&text, 10);
+
+    let mut read = read_from(&text);
+    let mut pager = TextPager::<4>::new(10, text.len() as u32);
+
+    while !pager.at_end() {
+        pager.layout(&mut read).unwrap();
+        pager.scroll_down(true);
+    }
+
+    assert_eq!(shown(&pager), ["xxxxxxxxxx", "xxxxxxxxxx", "end"]);
+}
+
+#[test]
+fn file_not_ending_by_a_line_end() {
+    let text = b"one\ntwo\nthree\nfour\nfive";
+    let mut read = read_from(text);
+    let mut pager = TextPager::<4>::new(10, text.len() as u32);
+    pager.layout(&mut read).unwrap();
+
+    assert!(pager.scroll_down(true));
+    pager.layout(&mut read).unwrap();
+    assert_eq!(shown(&pager), ["five"]);
+    assert!(pager.at_end());
+}
@@ -0,0 +1,249 @@
+//! Binary log file tool, converts the records of a file written by
+//! `sd_logger::binary` to CSV or JSON and checks its integrity
+//!
+//! `cargo run -p sd-log --target x86_64-unknown-linux-gnu -- check LOG001.BIN`
+
+use rtc_time::calendar::DateTime;
+use sd_logger::{
+    binary::{Decoded, FormatError, RecordDecoder, Sample, Schema, FLAG_TIME_INVALID, FLAG_TIME_JUMP},
+    record::SoftwareCrc,
+};
+use std::{
+    env, fs,
+    io::{self, Write},
+    process::ExitCode,
+};
+
+const USAGE: &str = "Usage: sd-log <csv|json|check> FILE";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Csv,
+    Json,
+    Check,
+}
+
+/// Record of a file with its offset
+struct Record {
+    offset: usize,
+    result: Result<Decoded, FormatError>,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let command = match args.first().map(String::as_str) {
+        Some("csv") => Command::Csv,
+        Some("json") => Command::Json,
+        Some("check") => Command::Check,
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        },
+    };
+
+    let path = match args.get(1) {
+        Some(path) if args.len() == 2 => path,
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        },
+    };
+
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            return ExitCode::from(2);
+        },
+    };
+
+    let (schema, header_length) = match Schema::read_header(&data, &mut SoftwareCrc) {
+        Ok(header) => header,
+        Err(error) => {
+            eprintln!("{}: header: {}", path, error);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let records = decode(schema.clone(), &data[header_length..], header_length);
+
+    let result = match command {
+        Command::Csv => write_csv(&mut io::stdout().lock(), &schema, &records),
+        Command::Json => write_json(&mut io::stdout().lock(), &schema, &records),
+        Command::Check => {
+            let errors = check(&mut io::stdout().lock(), &schema, &records);
+
+            return match errors {
+                Ok(0) => ExitCode::SUCCESS,
+                Ok(_) => ExitCode::FAILURE,
+                Err(error) => {
+                    eprintln!("{}", error);
+                    ExitCode::from(2)
+                },
+            };
+        },
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::from(2)
+        },
+    }
+}
+
+/// Decode the records following the header, a shorter rest of the file
+/// is a truncated record
+fn decode(schema: Schema, data: &[u8], header_length: usize) -> Vec<Record> {
+    let size = schema.record_size();
+    let mut decoder = RecordDecoder::new(schema);
+
+    data.chunks(size)
+        .enumerate()
+        .map(|(index, record)| Record {
+            offset: header_length + index*size,
+            result: decoder.decode(record, &mut SoftwareCrc),
+        })
+        .collect()
+}
+
+fn samples(records: &[Record]) -> impl Iterator<Item = &Sample> {
+    records.iter().filter_map(|record| match &record.result {
+        Ok(Decoded::Sample(sample)) => Some(sample),
+        _ => None,
+    })
+}
+
+/// UTC time in ISO 8601 format, or the seconds when out of calendar range
+fn format_time(unix_seconds: u32) -> String {
+    match DateTime::from_unix_seconds(unix_seconds as i64) {
+        Ok(time) => format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            time.year(), time.month(), time.day(),
+            time.hours(), time.minutes(), time.seconds()
+        ),
+        Err(_) => unix_seconds.to_string(),
+    }
+}
+
+/// One line per sample, damaged records are left out
+fn write_csv(output: &mut dyn Write, schema: &Schema, records: &[Record]) -> io::Result<()> {
+    write!(output, "sequence,time,time_valid,time_jump")?;
+
+    for field in schema.fields() {
+        write!(output, ",{}", field.name)?;
+    }
+
+    writeln!(output)?;
+
+    for sample in samples(records) {
+        write!(
+            output, "{},{},{},{}",
+            sample.sequence,
+            format_time(sample.time),
+            sample.flags & FLAG_TIME_INVALID == 0,
+            sample.flags & FLAG_TIME_JUMP != 0
+        )?;
+
+        for value in &sample.values {
+            write!(output, ",{}", value)?;
+        }
+
+        writeln!(output)?;
+    }
+
+    Ok(())
+}
+
+/// Array of sample objects, damaged records are left out
+fn write_json(output: &mut dyn Write, schema: &Schema, records: &[Record]) -> io::Result<()> {
+    writeln!(output, "[")?;
+
+    for (index, sample) in samples(records).enumerate() {
+        if index > 0 {
+            writeln!(output, ",")?;
+        }
+
+        write!(
+            output,
+            "  {{\"sequence\": {}, \"time\": \"{}\", \"time_valid\": {}, \"time_jump\": {}",
+            sample.sequence,
+            format_time(sample.time),
+            sample.flags & FLAG_TIME_INVALID == 0,
+            sample.flags & FLAG_TIME_JUMP != 0
+        )?;
+
+        // Field names are ASCII letters, digits and `_` (`Schema::add_field`
+        // rejects others also when reading the header), no escaping needed
+        for (field, value) in schema.fields().iter().zip(&sample.values) {
+            write!(output, ", \"{}\": {}", field.name, value)?;
+        }
+
+        write!(output, "}}")?;
+    }
+
+    writeln!(output, "\n]")
+}
+
+/// Report damaged records and sequence gaps, returns the number of errors
+fn check(output: &mut dyn Write, schema: &Schema, records: &[Record]) -> io::Result<u32> {
+    let mut errors = 0;
+    let mut samples = 0;
+    let mut syncs = 0;
+    let mut last_sequence: Option<u32> = None;
+
+    for record in records {
+        let sample = match &record.result {
+            Ok(Decoded::Sample(sample)) => sample,
+            Ok(Decoded::TimeSync(_)) => {
+                syncs += 1;
+                continue;
+            },
+            Err(error) => {
+                writeln!(output, "{:#x}: {}", record.offset, error)?;
+                errors += 1;
+                continue;
+            },
+        };
+
+        samples += 1;
+
+        match last_sequence {
+            Some(last) if sample.sequence <= last => {
+                writeln!(
+                    output, "{:#x}: sequence {} after {}", record.offset, sample.sequence, last
+                )?;
+                errors += 1;
+            },
+            Some(last) if sample.sequence == last.wrapping_add(2) => {
+                writeln!(output, "{:#x}: record {} missing", record.offset, last.wrapping_add(1))?;
+                errors += 1;
+            },
+            Some(last) if sample.sequence != last.wrapping_add(1) => {
+                writeln!(
+                    output, "{:#x}: records {} to {} missing",
+                    record.offset, last.wrapping_add(1), sample.sequence - 1
+                )?;
+                errors += 1;
+            },
+            _ => {},
+        }
+
+        last_sequence = Some(sample.sequence);
+    }
+
+    writeln!(
+        output,
+        "{} fields, {} byte records{}, {} samples, {} time syncs, {} errors",
+        schema.fields().len(),
+        schema.record_size(),
+        if schema.delta() { " with delta time" } else { "" },
+        samples,
+        syncs,
+        errors
+    )?;
+
+    Ok(errors)
+}